@@ -0,0 +1,43 @@
+// 运行：cd bench && cargo bench
+// 变量展开依赖 shell 运行时，由 zako --bench-internal 测量
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::process::Command;
+use zakosh::parser::{Lexer, Parser};
+
+// 与 zako --bench-internal 相同的命令行
+const LINES: [&str; 4] = [
+    "ls -la /tmp",
+    "cat Cargo.toml | grep -v '#' | sort | uniq -c > /dev/null",
+    "echo $HOME ${USER} \"$PATH\" 2>&1 &",
+    "diff <(sort a.txt) <(sort b.txt) && echo same || echo differ",
+];
+
+fn lex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for (i, line) in LINES.iter().enumerate() {
+        group.bench_function(i.to_string(), |b| {
+            b.iter(|| Lexer::new(black_box(line)).tokens())
+        });
+    }
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (i, line) in LINES.iter().enumerate() {
+        group.bench_function(i.to_string(), |b| {
+            b.iter(|| Parser::new(black_box(line)).parse_command())
+        });
+    }
+    group.finish();
+}
+
+// fork/exec 的基准，executor 在此之上增加的开销见 zako --bench-internal 的 exec /bin/true
+fn exec(c: &mut Criterion) {
+    c.bench_function("exec /bin/true", |b| {
+        b.iter(|| Command::new("/bin/true").status())
+    });
+}
+
+criterion_group!(benches, lex, parse, exec);
+criterion_main!(benches);
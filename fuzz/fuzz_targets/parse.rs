@@ -0,0 +1,18 @@
+// 运行：cargo +nightly fuzz run parse
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zakosh::parser::{Lexer, Parser};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = Lexer::new(input).tokens();
+    // 能解析的输入转回文本后再次解析，结果必须相同
+    if let Ok(node) = Parser::new(input).parse_command() {
+        let line = node.to_string();
+        let reparsed = Parser::new(&line).parse_command();
+        assert_eq!(reparsed.as_ref().ok(), Some(&node), "{:?}", line);
+    }
+});
@@ -0,0 +1,697 @@
+// 界面文字的本地化：按消息键查找当前语言的文字，找不到时使用中文，键本身也找不到时原样返回键
+// 语言由配置的 language 或 LC_ALL/LC_MESSAGES/LANG 决定，添加语言时在 CATALOG 中增加一列
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(Lang::Zh as u8);
+
+// 键、中文、英文；{} 按顺序替换为参数
+const CATALOG: &[(&str, &str, &str)] = &[
+    // 解析器
+    (
+        "parse.expected_command",
+        "缺少命令名",
+        "Expected command name",
+    ),
+    (
+        "parse.expected_filename",
+        "重定向符号后缺少文件名",
+        "Expected filename after redirection operator",
+    ),
+    (
+        "parse.background_and_or",
+        "不支持在后台运行 && 或 || 连接的命令",
+        "Running && or || lists in the background is not supported",
+    ),
+    // 受限模式
+    (
+        "restricted.slash",
+        "{}: 不允许使用带 / 的命令名",
+        "{}: command names containing / are not allowed",
+    ),
+    (
+        "restricted.cd",
+        "cd: 不允许切换目录",
+        "cd: changing directory is not allowed",
+    ),
+    (
+        "restricted.devtcp",
+        "set: 不允许修改 devtcp 选项",
+        "set: changing the devtcp option is not allowed",
+    ),
+    (
+        "restricted.theme",
+        "theme: 不允许安装或删除主题",
+        "theme: installing or removing themes is not allowed",
+    ),
+    (
+        "restricted.trust",
+        "trust: 不允许信任本地配置",
+        "trust: trusting local config is not allowed",
+    ),
+    (
+        "restricted.hash",
+        "hash: 不允许用 -p 指定命令的路径",
+        "hash: specifying a command path with -p is not allowed",
+    ),
+    (
+        "restricted.readonly",
+        "{}: {} 是只读变量",
+        "{}: {} is a read-only variable",
+    ),
+    (
+        "restricted.redirect",
+        "{}: 不允许输出重定向",
+        "{}: output redirection is not allowed",
+    ),
+    // 作业控制
+    (
+        "job.invalid_number",
+        "{}: 无效的作业编号",
+        "{}: invalid job number",
+    ),
+    (
+        "job.expected_spec",
+        "{}: 参数格式错误，应为 %n",
+        "{}: expected %n",
+    ),
+    ("job.not_found", "{}: 没有找到该作业", "{}: no such job"),
+    (
+        "job.picker_failed",
+        "{}: 无法打开作业选择器: {}",
+        "{}: cannot open the job picker: {}",
+    ),
+    // 信号
+    ("signal.unknown", "{}: 未知信号 {}", "{}: unknown signal {}"),
+    (
+        "signal.uncatchable",
+        "{}: 无法捕获 {}",
+        "{}: cannot trap {}",
+    ),
+    // 执行
+    (
+        "exec.too_deep",
+        "{}: 命令嵌套超过 {} 层（max_nesting），可能是递归的别名或函数",
+        "{}: command nesting exceeds {} levels (max_nesting), possibly a recursive alias or function",
+    ),
+    (
+        "help.not_builtin",
+        "help: {} 不是内建命令",
+        "help: {} is not a builtin",
+    ),
+    (
+        "exec.arg_too_long",
+        "{}: 参数过长（{} 字节，单个参数的上限为 {} 字节）",
+        "{}: argument too long ({} bytes, the limit for one argument is {} bytes)",
+    ),
+    (
+        "exec.arg_list_too_long",
+        "{}: 参数列表过长（{} 字节，ARG_MAX 为 {} 字节）",
+        "{}: argument list too long ({} bytes, ARG_MAX is {} bytes)",
+    ),
+    (
+        "exec.max_args",
+        "{}: 展开后有 {} 个参数，超过了 max_args（{}）",
+        "{}: {} arguments after expansion, more than max_args ({})",
+    ),
+    (
+        "exec.function_alone",
+        "{}: 函数只能在前台单独运行，不能用于管道、重定向或后台",
+        "{}: functions can only run alone in the foreground, not in pipelines, redirections or the background",
+    ),
+    (
+        "exec.builtin_unsupported",
+        "{}: 不支持内建命令 {}",
+        "{}: builtin {} is not supported here",
+    ),
+    (
+        "confirm.prompt",
+        "{} 继续执行吗？[y/N] ",
+        "{} Continue? [y/N] ",
+    ),
+    (
+        "confirm.cancelled",
+        "{}: 已取消",
+        "{}: cancelled",
+    ),
+    (
+        "explain.command",
+        "命令 {}:",
+        "Command {}:",
+    ),
+    (
+        "explain.substitution",
+        "（进程替换，执行时替换为文件路径）",
+        " (process substitution, replaced by a file path when run)",
+    ),
+    (
+        "explain.redirect",
+        "  重定向 {}{} {}",
+        "  redirect {}{} {}",
+    ),
+    (
+        "explain.background",
+        "  后台运行",
+        "  runs in the background",
+    ),
+    (
+        "help.changes_state",
+        "（修改 shell 状态）",
+        " (changes shell state)",
+    ),
+    (
+        "help.runs_command",
+        "（运行其他命令）",
+        " (runs another command)",
+    ),
+    (
+        "type.not_found",
+        "{}: 找不到 {}",
+        "{}: {} not found",
+    ),
+    (
+        "type.alias",
+        "{}: 别名 {}",
+        "{}: alias {}",
+    ),
+    (
+        "type.alias_verbose",
+        "{} 是别名: {}",
+        "{} is an alias for {}",
+    ),
+    (
+        "type.builtin",
+        "{}: 内建命令",
+        "{}: builtin",
+    ),
+    (
+        "type.builtin_verbose",
+        "{} 是内建命令",
+        "{} is a shell builtin",
+    ),
+    (
+        "type.function",
+        "{}: 函数 {}",
+        "{}: function {}",
+    ),
+    (
+        "type.function_verbose",
+        "{} 是自动加载的函数: {}",
+        "{} is an autoloaded function: {}",
+    ),
+    (
+        "type.file_verbose",
+        "{} 是 {}",
+        "{} is {}",
+    ),
+    (
+        "type.shadowed",
+        "{}（被前面的遮蔽）",
+        "{} (shadowed)",
+    ),
+    // 内建命令
+    (
+        "usage",
+        "用法: {}",
+        "Usage: {}",
+    ),
+    (
+        "after.usage",
+        "用法: after DURATION cmd ... | after -l | after -r ID（cmd 为外部命令，立即在后台进程中等待 DURATION 后运行）",
+        "Usage: after DURATION cmd ... | after -l | after -r ID (cmd must be an external command; it starts in the background at once and waits DURATION before running)",
+    ),
+    (
+        "after.cancelled",
+        "已取消 {}",
+        "Cancelled {}",
+    ),
+    (
+        "after.out_of_range",
+        "after: 时长超出范围",
+        "after: duration out of range",
+    ),
+    (
+        "after.scheduled",
+        "[a{}] {} (剩余 {}): {}",
+        "[a{}] {} ({} left): {}",
+    ),
+    (
+        "after.not_scheduled",
+        "没有等待中的调度 a{}",
+        "No pending scheduled command a{}",
+    ),
+    (
+        "sleep.out_of_range",
+        "sleep: 时长之和超出范围",
+        "sleep: total duration out of range",
+    ),
+    (
+        "withenv.same_env",
+        "withenv: 环境变量与父进程相同",
+        "withenv: the environment is the same as the parent's",
+    ),
+    (
+        "sandbox.not_found",
+        "sandbox: 找不到配置 {}，在 config.toml 的 [sandbox.{}] 中定义",
+        "sandbox: no profile {}, define it in [sandbox.{}] of config.toml",
+    ),
+    (
+        "par.failed",
+        "par: [{}] 退出码 {}: {}",
+        "par: [{}] exit status {}: {}",
+    ),
+    (
+        "par.aborted",
+        "par: 已中止，剩余 {} 个命令未运行",
+        "par: aborted, {} commands not run",
+    ),
+    (
+        "config.ok",
+        "config: {} 和主题 {} 没有问题",
+        "config: no problems in {} and theme {}",
+    ),
+    (
+        "config.problems",
+        "config: 发现 {} 个问题",
+        "config: {} problems found",
+    ),
+    (
+        "lastout.empty",
+        "lastout: 没有保存的输出，先运行 set -o captureoutput",
+        "lastout: no saved output, run set -o captureoutput first",
+    ),
+    (
+        "isatty.invalid_fd",
+        "isatty: 无效的文件描述符 {}",
+        "isatty: invalid file descriptor {}",
+    ),
+    (
+        "profile.not_found",
+        "profile: 找不到 {}",
+        "profile: {} not found",
+    ),
+    (
+        "exit.invalid_status",
+        "exit: 无效的退出码 {}",
+        "exit: invalid exit status {}",
+    ),
+    (
+        "set.expected_two",
+        "set 命令需要两个参数: 变量名和值",
+        "set requires two arguments: a name and a value",
+    ),
+    (
+        "stderrcolor.invalid",
+        "stderrcolor: 无效参数 {}，应为 on 或 off",
+        "stderrcolor: invalid argument {}, expected on or off",
+    ),
+    (
+        "waitfor.timed_out",
+        "waitfor: 等待 {} 超时",
+        "waitfor: timed out waiting for {}",
+    ),
+    (
+        "waitfor.invalid_port",
+        "waitfor: 无效的端口 {}",
+        "waitfor: invalid port {}",
+    ),
+    (
+        "coproc.closed",
+        "coproc: {} 的输入已经关闭",
+        "coproc: the input of {} is already closed",
+    ),
+    (
+        "coproc.not_found",
+        "coproc: 没有名为 {} 的协同进程",
+        "coproc: no coprocess named {}",
+    ),
+    (
+        "kill.invalid_target",
+        "kill: {}: 参数应为进程号或 %作业",
+        "kill: {}: arguments must be process IDs or %jobs",
+    ),
+    // 计算器
+    (
+        "calc.empty",
+        "表达式为空",
+        "Empty expression",
+    ),
+    (
+        "calc.trailing",
+        "表达式后有多余的内容",
+        "Unexpected input after the expression",
+    ),
+    (
+        "calc.invalid_char",
+        "无效的字符 {}",
+        "Invalid character {}",
+    ),
+    (
+        "calc.int_out_of_range",
+        "整数 {} 超出范围",
+        "Integer {} out of range",
+    ),
+    (
+        "calc.invalid_number",
+        "无效的数字 {}",
+        "Invalid number {}",
+    ),
+    (
+        "calc.too_deep",
+        "嵌套超过 {} 层",
+        "Nested more than {} levels",
+    ),
+    (
+        "calc.missing_close",
+        "缺少 )",
+        "Missing )",
+    ),
+    (
+        "calc.missing_operand",
+        "{} 前缺少数字",
+        "Missing number before {}",
+    ),
+    (
+        "calc.extra_close",
+        "多余的 )",
+        "Unexpected )",
+    ),
+    (
+        "calc.incomplete",
+        "表达式不完整",
+        "Incomplete expression",
+    ),
+    (
+        "calc.divide_by_zero",
+        "除数为 0",
+        "Division by zero",
+    ),
+    (
+        "calc.overflow",
+        "整数运算 {} {} {} 溢出",
+        "Integer overflow in {} {} {}",
+    ),
+    // 重定向
+    (
+        "redirect.invalid_fd",
+        "无效的文件描述符: {}",
+        "Invalid file descriptor: {}",
+    ),
+    (
+        "redirect.devtcp_off",
+        "网络重定向未开启，使用 set -o devtcp 开启",
+        "Network redirection is off, enable it with set -o devtcp",
+    ),
+    (
+        "redirect.invalid_port",
+        "无效的端口: {}",
+        "Invalid port: {}",
+    ),
+    (
+        "redirect.unknown_host",
+        "无法解析主机: {}",
+        "Cannot resolve host: {}",
+    ),
+    // 时长
+    (
+        "duration.invalid",
+        "无效的时长: {}",
+        "Invalid duration: {}",
+    ),
+    (
+        "duration.invalid_unit",
+        "无效的时长单位: {}，支持 ms/s/m/h/d",
+        "Invalid duration unit: {}, expected ms/s/m/h/d",
+    ),
+    // 升级
+    (
+        "update.notice",
+        "zako {} 已发布{}，当前为 {}，运行 zako upgrade --self 升级",
+        "zako {} is out{}, you have {}; run zako upgrade --self to upgrade",
+    ),
+    (
+        "update.summary",
+        "（{}）",
+        " ({})",
+    ),
+    (
+        "update.curl_failed",
+        "curl 失败（{}）: {}",
+        "curl failed ({}): {}",
+    ),
+    (
+        "update.bad_release",
+        "无法解析最新版本的信息",
+        "Cannot parse the latest release information",
+    ),
+    (
+        "update.up_to_date",
+        "zako {} 已经是最新版本",
+        "zako {} is already the latest version",
+    ),
+    (
+        "update.no_asset",
+        "zako {} 没有 {}-{} 的可执行文件",
+        "zako {} has no executable for {}-{}",
+    ),
+    (
+        "update.no_checksums",
+        "zako {} 没有发布 SHA-256 校验和，不会替换当前的可执行文件",
+        "zako {} has no published SHA-256 checksums, not replacing the current executable",
+    ),
+    (
+        "update.checksum_missing",
+        "{} 中没有 {} 的校验和",
+        "{} has no checksum for {}",
+    ),
+    (
+        "update.downloading",
+        "下载 zako {}: {}",
+        "Downloading zako {}: {}",
+    ),
+    (
+        "update.upgraded",
+        "已升级到 zako {}（{}）",
+        "Upgraded to zako {} ({})",
+    ),
+    (
+        "update.checksum_mismatch",
+        "校验和不一致，下载的文件可能已损坏或被篡改（应为 {}，实际为 {}）",
+        "Checksum mismatch, the download may be corrupted or tampered with (expected {}, got {})",
+    ),
+    // rm 安全网
+    (
+        "safety.confirm",
+        "rm: 将要递归删除 {}（{}）",
+        "rm: about to recursively delete {} ({})",
+    ),
+    (
+        "safety.root",
+        "根目录",
+        "the root directory",
+    ),
+    (
+        "safety.home",
+        "家目录",
+        "the home directory",
+    ),
+    (
+        "safety.git_root",
+        "git 仓库的根目录",
+        "the root of a git repository",
+    ),
+    // 资源限制
+    (
+        "limit.invalid_nice",
+        "nice: 无效的值 {}，应为 -20 到 19 之间的整数",
+        "nice: invalid value {}, expected an integer from -20 to 19",
+    ),
+    (
+        "limit.invalid_size",
+        "无效的大小: {}",
+        "Invalid size: {}",
+    ),
+    (
+        "limit.invalid_size_unit",
+        "无效的大小单位: {}，支持 K/M/G/T",
+        "Invalid size unit: {}, expected K/M/G/T",
+    ),
+    (
+        "limit.invalid_count",
+        "无效的数量: {}",
+        "Invalid count: {}",
+    ),
+    // 执行策略
+    (
+        "policy.command_matched",
+        "{}: 命令匹配执行策略 {}",
+        "{}: command matches execution policy {}",
+    ),
+    (
+        "policy.paste_matched",
+        "粘贴的内容匹配策略 {}",
+        "Pasted text matches policy {}",
+    ),
+    // 函数自动加载
+    (
+        "autoload.unexpected_eof",
+        "{}: 输入意外结束",
+        "{}: unexpected end of input",
+    ),
+    // 别名
+    (
+        "alias.invalid_name",
+        "alias: 无效的别名 {}",
+        "alias: invalid alias name {}",
+    ),
+    (
+        "alias.not_found",
+        "{}: 找不到 {}",
+        "{}: {} not found",
+    ),
+    // 命令路径缓存
+    (
+        "hash.not_found",
+        "hash: 找不到 {}",
+        "hash: {} not found",
+    ),
+    (
+        "hash.empty",
+        "hash: 缓存为空",
+        "hash: the cache is empty",
+    ),
+    (
+        "hash.header",
+        "次数\t命令",
+        "hits\tcommand",
+    ),
+    // 循环和菜单
+    (
+        "select.too_many",
+        "select: 菜单项超过 {} 个",
+        "select: more than {} menu items",
+    ),
+    (
+        "for.invalid_name",
+        "{}: 无效的变量名 {}",
+        "{}: invalid variable name {}",
+    ),
+    (
+        "for.zero_step",
+        "步长不能为 0: {}",
+        "Step cannot be 0: {}",
+    ),
+    // 粘贴
+    (
+        "paste.confirm",
+        "{}，按 y 保留粘贴的内容，其他键撤销",
+        "{}, press y to keep the pasted text, any other key to undo",
+    ),
+    (
+        "paste.deny",
+        "{}，按任意键撤销",
+        "{}, press any key to undo",
+    ),
+];
+
+// zh* 为中文，en* 为英文，其他（包括 C 和 POSIX）使用默认的中文
+pub fn parse_lang(name: &str) -> Option<Lang> {
+    let name = name.to_lowercase();
+    if name.starts_with("zh") {
+        Some(Lang::Zh)
+    } else if name.starts_with("en") {
+        Some(Lang::En)
+    } else {
+        None
+    }
+}
+
+// 按 locale 环境变量的优先级检测，第一个设置了的变量决定语言
+pub fn detect() -> Lang {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| parse_lang(&value))
+        .unwrap_or(Lang::Zh)
+}
+
+pub fn set_lang(lang: Lang) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Lang::En,
+        _ => Lang::Zh,
+    }
+}
+
+fn lookup(lang: Lang, key: &str) -> Option<&'static str> {
+    let (_, zh, en) = CATALOG.iter().find(|(k, _, _)| *k == key)?;
+    Some(match lang {
+        Lang::Zh => zh,
+        Lang::En => en,
+    })
+}
+
+// 当前语言的文字
+pub fn t(key: &str) -> String {
+    tf(key, &[])
+}
+
+// 当前语言的文字，依次替换其中的 {}
+pub fn tf(key: &str, args: &[&str]) -> String {
+    format_in(lang(), key, args)
+}
+
+fn format_in(lang: Lang, key: &str, args: &[&str]) -> String {
+    let Some(template) = lookup(lang, key) else {
+        return key.to_string();
+    };
+    let mut args = args.iter();
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        result.push_str(&rest[..pos]);
+        result.push_str(args.next().copied().unwrap_or_default());
+        rest = &rest[pos + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format_in(Lang::Zh, "signal.unknown", &["kill", "FOO"]),
+            "kill: 未知信号 FOO"
+        );
+        assert_eq!(
+            format_in(Lang::En, "signal.unknown", &["kill", "FOO"]),
+            "kill: unknown signal FOO"
+        );
+        assert_eq!(format_in(Lang::En, "no.such.key", &[]), "no.such.key");
+        // 每种语言的占位符数量相同
+        for (key, zh, en) in CATALOG {
+            assert_eq!(
+                zh.matches("{}").count(),
+                en.matches("{}").count(),
+                "{}",
+                key
+            );
+        }
+        assert_eq!(parse_lang("en_US.UTF-8"), Some(Lang::En));
+        assert_eq!(parse_lang("zh_CN.UTF-8"), Some(Lang::Zh));
+        assert_eq!(parse_lang("C"), None);
+    }
+}
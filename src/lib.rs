@@ -0,0 +1,3 @@
+// 不依赖 shell 运行时的部分，单独作为库导出，供 fuzz 和外部测试使用
+pub mod i18n;
+pub mod parser;
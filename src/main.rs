@@ -2,7 +2,7 @@
 extern crate lazy_static;
 
 use crate::shell::Shell;
-use log::debug;
+use log::{debug, error};
 
 use crate::utils::config::Config;
 use crate::utils::log::init_logger;
@@ -11,8 +11,17 @@ mod shell;
 mod utils;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // 加载 .env 等修改环境变量之前保存父进程传入的环境变量，withenv --diff 与它比较
+    shell::save_inherited_env();
     let config = Config::new();
+    if config.accessible {
+        colored::control::set_override(false);
+    }
+    zakosh::i18n::set_lang(config.language.unwrap_or_else(zakosh::i18n::detect));
     init_logger(&config);
+    for problem in &config.problems {
+        error!("{}", problem);
+    }
     debug!("配置加载成功 {}", config.config_dir.display());
 
     let mut shell = Shell::new(&config);
@@ -0,0 +1,222 @@
+use std::fmt;
+
+use super::lexer::{RedirectOp, SubstKind};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Command(Command),
+    Pipeline(Vec<Command>),
+    // cmd1 && cmd2 || cmd3：从左到右执行，根据前一个的退出码决定是否执行下一个，元素为命令或管道
+    AndOr(Box<Node>, Vec<(Connector, Node)>),
+    // cmd1; cmd2 & cmd3：依次执行，退出码为最后一个命令的退出码
+    List(Vec<Node>),
+}
+
+impl Node {
+    // 按出现顺序列出其中的所有简单命令
+    pub fn commands(&self) -> Vec<&Command> {
+        match self {
+            Node::Command(command) => vec![command],
+            Node::Pipeline(pipeline) => pipeline.iter().collect(),
+            Node::AndOr(first, rest) => first
+                .commands()
+                .into_iter()
+                .chain(rest.iter().flat_map(|(_, node)| node.commands()))
+                .collect(),
+            Node::List(nodes) => nodes.iter().flat_map(Node::commands).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    // &&，前一个成功时执行
+    And,
+    // ||，前一个失败时执行
+    Or,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Command {
+    pub program: String,
+    pub arguments: Vec<String>,
+    pub redirections: Vec<Redirection>,
+    // 参数中的进程替换，执行时替换为对应的文件路径
+    pub substitutions: Vec<ProcessSubst>,
+    pub background: bool,
+}
+
+impl Command {
+    // 以第 skip 个参数作为命令名构造内层命令（前缀内建命令使用），进程替换的位置随之调整
+    pub fn inner(&self, skip: usize, len: usize) -> Option<Command> {
+        let program = self.arguments.get(skip)?.clone();
+        let end = (skip + 1).saturating_add(len).min(self.arguments.len());
+        let arguments = self.arguments.get(skip + 1..end)?.to_vec();
+        let substitutions = self
+            .substitutions
+            .iter()
+            .filter(|s| s.index > skip && s.index < end)
+            .map(|s| ProcessSubst {
+                index: s.index - skip - 1,
+                ..s.clone()
+            })
+            .collect();
+        Some(Command {
+            program,
+            arguments,
+            substitutions,
+            ..self.clone()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirection {
+    // 写在符号前的 fd，如 2>file 中的 2
+    pub fd: Option<i32>,
+    pub operator: RedirectOp,
+    // >& 的目标为 fd
+    pub filename: String,
+}
+
+impl Redirection {
+    // 被重定向的 fd：没有写明时 <、<< 和 <<< 为 0，&> 和 &>> 为 1 和 2，其余为 1
+    pub fn fds(&self) -> Vec<i32> {
+        match (self.fd, &self.operator) {
+            (Some(fd), _) => vec![fd],
+            (None, operator) if operator.is_input() => vec![libc::STDIN_FILENO],
+            (None, RedirectOp::OutputAll | RedirectOp::AppendAll) => {
+                vec![libc::STDOUT_FILENO, libc::STDERR_FILENO]
+            }
+            (None, _) => vec![libc::STDOUT_FILENO],
+        }
+    }
+
+    // 写入文件（受限模式下禁止）
+    pub fn writes_file(&self) -> bool {
+        matches!(
+            self.operator,
+            RedirectOp::Output | RedirectOp::Append | RedirectOp::OutputAll | RedirectOp::AppendAll
+        )
+    }
+
+    pub fn redirects_stdout(&self) -> bool {
+        !self.operator.is_input() && self.fds().contains(&libc::STDOUT_FILENO)
+    }
+}
+
+impl fmt::Display for Redirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(fd) = self.fd {
+            write!(f, "{}", fd)?;
+        }
+        match self.operator {
+            RedirectOp::Duplicate => write!(f, ">&{}", quote_word(&self.filename)),
+            // 内容紧跟在这一行之后，只有 here document 在行尾时才能重新解析
+            // 结束符不能与内容中的某一行相同，否则重新解析时内容会提前结束
+            RedirectOp::HereDoc { expand } => {
+                let mut delimiter = "EOF".to_string();
+                while self.filename.split('\n').any(|line| line == delimiter) {
+                    delimiter.push('_');
+                }
+                if expand {
+                    write!(f, "<<{}\n{}{}", delimiter, self.filename, delimiter)
+                } else {
+                    write!(f, "<<'{}'\n{}{}", delimiter, self.filename, delimiter)
+                }
+            }
+            _ => write!(
+                f,
+                "{} {}",
+                self.operator.symbol(),
+                quote_word(&self.filename)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessSubst {
+    // 在 arguments 中的位置
+    pub index: usize,
+    pub kind: SubstKind,
+    pub command: String,
+}
+
+// 转回命令行文本，重新解析后得到相同的 AST
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Command(command) => write!(f, "{}", command),
+            Node::Pipeline(pipeline) => {
+                let commands: Vec<String> = pipeline.iter().map(|c| c.to_string()).collect();
+                write!(f, "{}", commands.join(" | "))
+            }
+            Node::AndOr(first, rest) => {
+                write!(f, "{}", first)?;
+                for (connector, node) in rest {
+                    let operator = match connector {
+                        Connector::And => "&&",
+                        Connector::Or => "||",
+                    };
+                    write!(f, " {} {}", operator, node)?;
+                }
+                Ok(())
+            }
+            Node::List(nodes) => {
+                let nodes: Vec<String> = nodes.iter().map(|n| n.to_string()).collect();
+                write!(f, "{}", nodes.join("; "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", quote_word(&self.program))?;
+        for (i, arg) in self.arguments.iter().enumerate() {
+            match self.substitutions.iter().find(|s| s.index == i) {
+                Some(subst) => write!(f, " {}", subst)?,
+                None => write!(f, " {}", quote_word(arg))?,
+            }
+        }
+        for redirection in &self.redirections {
+            write!(f, " {}", redirection)?;
+        }
+        if self.background {
+            write!(f, " &")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ProcessSubst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self.kind {
+            SubstKind::Input => '<',
+            SubstKind::Output => '>',
+        };
+        write!(f, "{}({})", prefix, self.command)
+    }
+}
+
+// 不需要引号的单词原样输出，其余的放进双引号并转义 \ 和 "
+fn quote_word(word: &str) -> String {
+    let plain = !word.is_empty()
+        && !word.starts_with(['"', '\''])
+        && !word
+            .chars()
+            .any(|c| c.is_whitespace() || ";<>|&".contains(c));
+    if plain {
+        return word.to_string();
+    }
+    let mut quoted = String::from('"');
+    for c in word.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,619 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Word(String),
+    Pipe,
+    Redirect(RedirectOp),
+    // 紧挨着重定向符号的数字，如 2>file 中的 2
+    IoNumber(i32),
+    // <(cmd) 或 >(cmd)，内容为括号内的命令
+    ProcessSubst(SubstKind, String),
+    Background,
+    Semi,
+    // && 和 ||
+    And,
+    Or,
+    EOF,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RedirectOp {
+    Input,     // <
+    Output,    // >
+    Append,    // >>
+    Duplicate, // >&，目标为已有的 fd
+    OutputAll, // &>，同时重定向 stdout 和 stderr
+    AppendAll, // &>>
+    // <<EOF，后面的 Word 为文档内容；结束符带引号时不展开变量
+    HereDoc { expand: bool },
+    HereString, // <<< word，内容加上换行作为标准输入
+}
+
+impl RedirectOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            RedirectOp::Input => "<",
+            RedirectOp::Output => ">",
+            RedirectOp::Append => ">>",
+            RedirectOp::Duplicate => ">&",
+            RedirectOp::OutputAll => "&>",
+            RedirectOp::AppendAll => "&>>",
+            RedirectOp::HereDoc { .. } => "<<",
+            RedirectOp::HereString => "<<<",
+        }
+    }
+
+    // 重定向标准输入的操作符
+    pub fn is_input(&self) -> bool {
+        matches!(
+            self,
+            RedirectOp::Input | RedirectOp::HereDoc { .. } | RedirectOp::HereString
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SubstKind {
+    Input,  // <(cmd)，命令的输出作为文件读取
+    Output, // >(cmd)，写入文件的内容作为命令的输入
+}
+
+#[derive(Clone)]
+pub struct Lexer<'a> {
+    input: Peekable<Chars<'a>>,
+    // here document 的内容，紧跟在 << 之后返回
+    queued: Option<Token>,
+    // 当前行之后属于 here document 的字符数，读到行尾的换行时跳过
+    heredoc_len: usize,
+    // 有 here document 没有找到结束符
+    unterminated: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input: input.chars().peekable(),
+            queued: None,
+            heredoc_len: 0,
+            unterminated: false,
+        }
+    }
+
+    pub fn next_token(&mut self) -> Token {
+        if let Some(token) = self.queued.take() {
+            return token;
+        }
+        self.skip_whitespace();
+
+        match self.peek_char() {
+            None => Token::EOF,
+            Some(c) => match c {
+                '|' => {
+                    self.read_char();
+                    if self.peek_char() == Some('|') {
+                        self.read_char();
+                        Token::Or
+                    } else {
+                        Token::Pipe
+                    }
+                }
+                ';' => {
+                    self.read_char();
+                    Token::Semi
+                }
+                '&' => {
+                    self.read_char();
+                    match self.peek_char() {
+                        Some('&') => {
+                            self.read_char();
+                            Token::And
+                        }
+                        Some('>') => {
+                            self.read_char();
+                            if self.peek_char() == Some('>') {
+                                self.read_char();
+                                Token::Redirect(RedirectOp::AppendAll)
+                            } else {
+                                Token::Redirect(RedirectOp::OutputAll)
+                            }
+                        }
+                        _ => Token::Background,
+                    }
+                }
+                '<' => {
+                    self.read_char();
+                    if self.peek_char() == Some('(') {
+                        self.read_process_subst(SubstKind::Input)
+                    } else if self.peek_char() == Some('<') {
+                        self.read_char();
+                        if self.input.next_if_eq(&'<').is_some() {
+                            Token::Redirect(RedirectOp::HereString)
+                        } else {
+                            self.read_heredoc()
+                        }
+                    } else {
+                        Token::Redirect(RedirectOp::Input)
+                    }
+                }
+                '>' => {
+                    self.read_char();
+                    if self.peek_char() == Some('(') {
+                        self.read_process_subst(SubstKind::Output)
+                    } else if self.peek_char() == Some('>') {
+                        self.read_char();
+                        Token::Redirect(RedirectOp::Append)
+                    } else if self.peek_char() == Some('&') {
+                        self.read_char();
+                        Token::Redirect(RedirectOp::Duplicate)
+                    } else {
+                        Token::Redirect(RedirectOp::Output)
+                    }
+                }
+                '"' => self.read_quoted_string(),
+                '\'' => self.read_quoted_string(),
+                c if c.is_ascii_digit() => {
+                    self.read_io_number().unwrap_or_else(|| self.read_word())
+                }
+                _ => self.read_word(),
+            },
+        }
+    }
+
+    // 读取剩余的所有词法单元（不含 EOF）
+    pub fn tokens(&mut self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        loop {
+            match self.next_token() {
+                Token::EOF => break,
+                token => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        self.input.next()
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.input.peek().copied()
+    }
+
+    // 行尾的反斜杠表示命令在下一行继续
+    fn at_line_continuation(&self) -> bool {
+        let mut ahead = self.input.clone();
+        ahead.next() == Some('\\') && ahead.next() == Some('\n')
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek_char() {
+            if self.at_line_continuation() {
+                self.read_char();
+                self.read_char();
+                continue;
+            }
+            if !c.is_whitespace() {
+                break;
+            }
+            self.read_char();
+            if c == '\n' {
+                for _ in 0..std::mem::take(&mut self.heredoc_len) {
+                    self.read_char();
+                }
+            }
+        }
+    }
+
+    // <<EOF 或 <<-EOF（去掉每行开头的 tab），内容从当前行的下一行开始，到只有结束符的一行为止
+    // 同一行有多个 here document 时内容依次排列；结束符带引号时内容中的变量不展开
+    fn read_heredoc(&mut self) -> Token {
+        let strip_tabs = self.input.next_if_eq(&'-').is_some();
+        while self.input.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        let quoted = matches!(self.peek_char(), Some('"' | '\''));
+        let delimiter = match self.peek_char() {
+            Some('"' | '\'') => self.read_quoted_string(),
+            Some(c) if c != '\n' => self.read_word(),
+            _ => Token::Word(String::new()),
+        };
+        let operator = Token::Redirect(RedirectOp::HereDoc { expand: !quoted });
+        let Token::Word(delimiter) = delimiter else {
+            return operator;
+        };
+        if delimiter.is_empty() {
+            return operator;
+        }
+
+        let mut ahead = self.input.clone();
+        if !ahead.any(|c| c == '\n') {
+            self.unterminated = true;
+        }
+        let mut ahead = ahead.skip(self.heredoc_len).peekable();
+        let mut body = String::new();
+        let mut terminated = false;
+        while !self.unterminated && ahead.peek().is_some() {
+            let line: String = ahead.by_ref().take_while(|c| *c != '\n').collect();
+            self.heredoc_len += line.chars().count() + 1;
+            let line = if strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                &line
+            };
+            if line == delimiter {
+                terminated = true;
+                break;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+        self.unterminated |= !terminated;
+        self.queued = Some(Token::Word(body));
+        operator
+    }
+
+    fn read_word(&mut self) -> Token {
+        let mut word = String::new();
+
+        while let Some(c) = self.peek_char() {
+            if self.at_line_continuation() {
+                self.read_char();
+                self.read_char();
+                continue;
+            }
+            if c.is_whitespace() || ";<>|&".contains(c) {
+                break;
+            }
+            word.push(self.read_char().unwrap_or_default());
+        }
+
+        Token::Word(word)
+    }
+
+    // 单词开头的数字后面紧跟 < 或 > 时是要重定向的 fd，<(cmd) 和 >(cmd) 除外
+    fn read_io_number(&mut self) -> Option<Token> {
+        let mut ahead = self.input.clone();
+        let digits: String = std::iter::from_fn(|| ahead.next_if(char::is_ascii_digit)).collect();
+        if !matches!(ahead.next(), Some('<' | '>')) || ahead.next() == Some('(') {
+            return None;
+        }
+        let fd = digits.parse().ok()?;
+        for _ in 0..digits.len() {
+            self.read_char();
+        }
+        Some(Token::IoNumber(fd))
+    }
+
+    // 读取括号内的命令，支持嵌套括号
+    fn read_process_subst(&mut self, kind: SubstKind) -> Token {
+        self.read_char(); // 跳过 (
+        let mut command = String::new();
+        let mut depth = 1;
+        while let Some(c) = self.read_char() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            command.push(c);
+        }
+        Token::ProcessSubst(kind, command.trim().to_string())
+    }
+
+    fn read_quoted_string(&mut self) -> Token {
+        let quote = self.read_char().unwrap_or_default();
+        let mut string = String::new();
+        let mut escaped = false;
+
+        while let Some(c) = self.read_char() {
+            match (escaped, c) {
+                // 引号内的续行只连接两行，不保留换行
+                (true, '\n') => escaped = false,
+                (true, _) => {
+                    string.push(c);
+                    escaped = false;
+                }
+                (false, '\\') => escaped = true,
+                (false, c) if c == quote => break,
+                (false, c) => string.push(c),
+            }
+        }
+
+        Token::Word(string)
+    }
+}
+
+// 输入是否还没有结束：引号或进程替换的括号没有闭合、以续行的反斜杠、管道符、&& 或 || 结尾
+// readline 据此继续读取下一行，整段输入作为一条历史记录
+pub fn is_incomplete(input: &str) -> bool {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut depth = 0;
+    let mut prev = ' ';
+    let mut trailing_pipe = false;
+    let mut trailing_and = false;
+    let mut amp = false;
+    for c in input.chars() {
+        let literal = escaped;
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+        } else {
+            match c {
+                '"' | '\'' => quote = Some(c),
+                '(' if depth > 0 || prev == '<' || prev == '>' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                _ => {}
+            }
+        }
+        let operator = !literal && quote.is_none();
+        if !c.is_whitespace() {
+            trailing_pipe = c == '|' && operator;
+            trailing_and = c == '&' && operator && amp;
+        }
+        // 紧挨着的前一个字符是作为操作符的单个 &
+        amp = c == '&' && operator && !trailing_and;
+        prev = c;
+    }
+    quote.is_some()
+        || escaped
+        || depth > 0
+        || trailing_pipe
+        || trailing_and
+        || (input.contains("<<") && heredoc_unterminated(input))
+}
+
+// here document 的内容还没有读到结束的分隔符
+fn heredoc_unterminated(input: &str) -> bool {
+    let mut lexer = Lexer::new(input);
+    while lexer.next_token() != Token::EOF {}
+    lexer.unterminated
+}
+
+// 把多行输入（如粘贴的内容）按行分成命令，没有结束的行（引号、行尾的 | 或 \）与下一行合并，跳过空行和注释
+pub fn split_commands(input: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut pending = String::new();
+    for line in input.lines() {
+        if pending.is_empty() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            pending.push_str(line);
+        } else {
+            pending.push('\n');
+            pending.push_str(line);
+        }
+        if !is_incomplete(&pending) {
+            commands.push(std::mem::take(&mut pending));
+        }
+    }
+    if !pending.is_empty() {
+        commands.push(pending);
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_command() {
+        let mut lexer = Lexer::new("ls -l");
+        assert_eq!(lexer.next_token(), Token::Word("ls".to_string()));
+        assert_eq!(lexer.next_token(), Token::Word("-l".to_string()));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_pipe() {
+        let mut lexer = Lexer::new("ls | grep foo");
+        assert_eq!(lexer.next_token(), Token::Word("ls".to_string()));
+        assert_eq!(lexer.next_token(), Token::Pipe);
+        assert_eq!(lexer.next_token(), Token::Word("grep".to_string()));
+        assert_eq!(lexer.next_token(), Token::Word("foo".to_string()));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_redirections() {
+        let mut lexer = Lexer::new("echo hello > output.txt");
+        assert_eq!(lexer.next_token(), Token::Word("echo".to_string()));
+        assert_eq!(lexer.next_token(), Token::Word("hello".to_string()));
+        assert_eq!(lexer.next_token(), Token::Redirect(RedirectOp::Output));
+        assert_eq!(lexer.next_token(), Token::Word("output.txt".to_string()));
+        assert_eq!(lexer.next_token(), Token::EOF);
+
+        let tokens = Lexer::new("make 2>err 2>&1 &>all &>>log 12 3>(x) a2>b >&2").tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("make".to_string()),
+                Token::IoNumber(2),
+                Token::Redirect(RedirectOp::Output),
+                Token::Word("err".to_string()),
+                Token::IoNumber(2),
+                Token::Redirect(RedirectOp::Duplicate),
+                Token::Word("1".to_string()),
+                Token::Redirect(RedirectOp::OutputAll),
+                Token::Word("all".to_string()),
+                Token::Redirect(RedirectOp::AppendAll),
+                Token::Word("log".to_string()),
+                Token::Word("12".to_string()),
+                Token::Word("3".to_string()),
+                Token::ProcessSubst(SubstKind::Output, "x".to_string()),
+                Token::Word("a2".to_string()),
+                Token::Redirect(RedirectOp::Output),
+                Token::Word("b".to_string()),
+                Token::Redirect(RedirectOp::Duplicate),
+                Token::Word("2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quoted_strings() {
+        let mut lexer = Lexer::new(r#"echo "hello world" 'foo bar'"#);
+        assert_eq!(lexer.next_token(), Token::Word("echo".to_string()));
+        assert_eq!(lexer.next_token(), Token::Word("hello world".to_string()));
+        assert_eq!(lexer.next_token(), Token::Word("foo bar".to_string()));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_process_substitution() {
+        let mut lexer = Lexer::new("diff <(ls a) >(sort -u) < in");
+        assert_eq!(lexer.next_token(), Token::Word("diff".to_string()));
+        assert_eq!(
+            lexer.next_token(),
+            Token::ProcessSubst(SubstKind::Input, "ls a".to_string())
+        );
+        assert_eq!(
+            lexer.next_token(),
+            Token::ProcessSubst(SubstKind::Output, "sort -u".to_string())
+        );
+        assert_eq!(lexer.next_token(), Token::Redirect(RedirectOp::Input));
+        assert_eq!(lexer.next_token(), Token::Word("in".to_string()));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let tokens = Lexer::new("echo a\\\nb \\\n c \"x\\\ny\"").tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("echo".to_string()),
+                Token::Word("ab".to_string()),
+                Token::Word("c".to_string()),
+                Token::Word("xy".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(!is_incomplete("ls -l | grep foo"));
+        assert!(is_incomplete("echo \"hello"));
+        assert!(is_incomplete("echo 'it"));
+        assert!(is_incomplete("ls |"));
+        assert!(is_incomplete("ls | \n"));
+        assert!(is_incomplete("echo a \\"));
+        assert!(is_incomplete("diff <(ls a"));
+        assert!(!is_incomplete("diff <(ls (a)) b"));
+        assert!(!is_incomplete("echo \"a|\""));
+        assert!(!is_incomplete("echo \"multi\nline\""));
+        assert!(!is_incomplete("echo a \\|"));
+        assert!(is_incomplete("echo a \\\\|"));
+        assert!(is_incomplete("make &&"));
+        assert!(is_incomplete("make ||\n"));
+        assert!(!is_incomplete("sleep 1 &"));
+        assert!(!is_incomplete("sleep 1 & &"));
+        assert!(!is_incomplete("echo '&&'"));
+        assert!(!is_incomplete("echo \\&&"));
+        assert!(is_incomplete("cat <<EOF"));
+        assert!(is_incomplete("cat <<EOF\na\nEOFX"));
+        assert!(!is_incomplete("cat <<EOF\na\nEOF"));
+        assert!(!is_incomplete("cat <<-EOF\n\tEOF\n"));
+        assert!(is_incomplete("cat <<EOF\n\tEOF\n"));
+        assert!(is_incomplete("cat <<'EOF' <<B\nEOF\n"));
+        assert!(!is_incomplete("echo '<<EOF'"));
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let word = |s: &str| Token::Word(s.to_string());
+        let heredoc = |expand| Token::Redirect(RedirectOp::HereDoc { expand });
+        let tokens = Lexer::new("cat <<EOF | wc\n$a\n  EOF\nEOF\necho b").tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                word("cat"),
+                heredoc(true),
+                word("$a\n  EOF\n"),
+                Token::Pipe,
+                word("wc"),
+                word("echo"),
+                word("b"),
+            ]
+        );
+
+        let tokens = Lexer::new("cat <<<word 0<<< 'a b'").tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                word("cat"),
+                Token::Redirect(RedirectOp::HereString),
+                word("word"),
+                Token::IoNumber(0),
+                Token::Redirect(RedirectOp::HereString),
+                word("a b"),
+            ]
+        );
+        assert!(!is_incomplete("cat <<< EOF"));
+
+        let tokens = Lexer::new("cat <<- 'END' <<B\n\tx\n\tEND\n\ty\nB").tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                word("cat"),
+                heredoc(false),
+                word("x\n"),
+                heredoc(true),
+                word("\ty\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_commands() {
+        assert_eq!(split_commands("echo a"), vec!["echo a"]);
+        assert_eq!(
+            split_commands("cd /tmp\n\n# 注释\n  ls -l |\n  wc -l\necho \"x\ny\""),
+            vec!["cd /tmp", "ls -l |\n  wc -l", "echo \"x\ny\""]
+        );
+        assert_eq!(split_commands("echo a \\\nb"), vec!["echo a \\\nb"]);
+        assert_eq!(split_commands("echo 'open"), vec!["echo 'open"]);
+        assert!(split_commands(" \n").is_empty());
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = Lexer::new("cat a >> b &").tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("cat".to_string()),
+                Token::Word("a".to_string()),
+                Token::Redirect(RedirectOp::Append),
+                Token::Word("b".to_string()),
+                Token::Background,
+            ]
+        );
+        let tokens = Lexer::new("make&&run || a|b").tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("make".to_string()),
+                Token::And,
+                Token::Word("run".to_string()),
+                Token::Or,
+                Token::Word("a".to_string()),
+                Token::Pipe,
+                Token::Word("b".to_string()),
+            ]
+        );
+    }
+}
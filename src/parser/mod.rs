@@ -0,0 +1,7 @@
+pub mod ast;
+pub mod lexer;
+#[allow(clippy::module_inception)]
+mod parser;
+
+pub use lexer::{is_incomplete, split_commands, Lexer};
+pub use parser::Parser;
@@ -0,0 +1,563 @@
+use super::ast::{Command, Connector, Node, ProcessSubst, Redirection};
+use super::lexer::{Lexer, RedirectOp, SubstKind, Token};
+use crate::i18n;
+
+pub struct Parser<'a> {
+    lexer: Lexer<'a>,
+    current_token: Token,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        let mut lexer = Lexer::new(input);
+        let current_token = lexer.next_token();
+        Parser {
+            lexer,
+            current_token,
+        }
+    }
+
+    fn next_token(&mut self) {
+        self.current_token = self.lexer.next_token();
+    }
+
+    // 取出当前的单词并前进到下一个词法单元，单词直接移入 AST 而不复制
+    fn take_word(&mut self) -> String {
+        let token = std::mem::replace(&mut self.current_token, self.lexer.next_token());
+        match token {
+            Token::Word(word) => word,
+            _ => String::new(),
+        }
+    }
+
+    // 以 ; 分隔的命令列表，后台命令的 & 也结束一个命令，结尾的 ; 可以省略
+    pub fn parse_command(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![self.parse_and_or()?];
+        loop {
+            if self.current_token == Token::Semi {
+                self.next_token();
+            }
+            if self.current_token == Token::EOF {
+                break;
+            }
+            nodes.push(self.parse_and_or()?);
+        }
+
+        Ok(if nodes.len() == 1 {
+            nodes.remove(0)
+        } else {
+            Node::List(nodes)
+        })
+    }
+
+    fn parse_and_or(&mut self) -> Result<Node, String> {
+        let first = self.parse_pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.current_token {
+                Token::And => Connector::And,
+                Token::Or => Connector::Or,
+                _ => break,
+            };
+            self.next_token();
+            // && 和 || 后面必须有命令
+            if self.current_token == Token::EOF {
+                return Err(i18n::t("parse.expected_command"));
+            }
+            rest.push((connector, self.parse_pipeline()?));
+        }
+
+        if rest.is_empty() {
+            return Ok(first);
+        }
+        // 整个列表放到后台运行还不支持，结尾的 & 只会落在最后一个命令上，直接报错
+        let node = Node::AndOr(Box::new(first), rest);
+        if node.commands().iter().any(|command| command.background) {
+            return Err(i18n::t("parse.background_and_or"));
+        }
+        Ok(node)
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Node, String> {
+        let mut commands = Vec::new();
+
+        while self.current_token != Token::EOF {
+            let cmd = self.parse_simple_command()?;
+            commands.push(cmd);
+
+            match self.current_token {
+                Token::Pipe => {
+                    self.next_token();
+                    continue;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(if commands.len() == 1 {
+            Node::Command(commands.pop().unwrap_or_default())
+        } else {
+            Node::Pipeline(commands)
+        })
+    }
+
+    fn parse_simple_command(&mut self) -> Result<Command, String> {
+        let mut command = Command {
+            program: String::new(),
+            arguments: Vec::new(),
+            redirections: Vec::new(),
+            substitutions: Vec::new(),
+            background: false,
+        };
+
+        // 解析命令名
+        match &self.current_token {
+            Token::Word(_) => command.program = self.take_word(),
+            _ => return Err(i18n::t("parse.expected_command")),
+        }
+
+        // 解析参数和重定向
+        loop {
+            match &self.current_token {
+                // coproc NAME { cmd; } 中 ; 之后的 } 仍属于 coproc 命令
+                Token::Semi if self.closes_coproc_group(&command) => {
+                    self.next_token();
+                    command.arguments.push(self.take_word());
+                }
+                Token::EOF | Token::Pipe | Token::Semi | Token::And | Token::Or => break,
+                Token::Background => {
+                    command.background = true;
+                    self.next_token();
+                    break;
+                }
+                Token::Redirect(op) => {
+                    let redirection = self.parse_redirection(None, op.clone())?;
+                    command.redirections.push(redirection);
+                }
+                // 词法分析器只在重定向符号前产生 IoNumber
+                Token::IoNumber(fd) => {
+                    let fd = *fd;
+                    self.next_token();
+                    let Token::Redirect(op) = &self.current_token else {
+                        return Err(i18n::t("parse.expected_filename"));
+                    };
+                    let redirection = self.parse_redirection(Some(fd), op.clone())?;
+                    command.redirections.push(redirection);
+                }
+                Token::Word(_) => {
+                    let word = self.take_word();
+                    command.arguments.push(word);
+                }
+                Token::ProcessSubst(kind, inner) => {
+                    // 先保留原文作为参数，执行时替换为路径
+                    let prefix = match kind {
+                        SubstKind::Input => '<',
+                        SubstKind::Output => '>',
+                    };
+                    command.substitutions.push(ProcessSubst {
+                        index: command.arguments.len(),
+                        kind: *kind,
+                        command: inner.clone(),
+                    });
+                    command.arguments.push(format!("{}({})", prefix, inner));
+                    self.next_token();
+                }
+            }
+        }
+
+        Ok(command)
+    }
+
+    // 还没有命令组语法，只有 coproc [NAME] { cmd 后面紧跟 ; } 时把 } 当作结尾，其他命令中的 { 和 } 只是普通单词
+    fn closes_coproc_group(&self, command: &Command) -> bool {
+        command.program == "coproc"
+            && command.arguments.iter().take(2).any(|arg| arg == "{")
+            && command
+                .arguments
+                .last()
+                .is_some_and(|arg| arg != "{" && arg != "}")
+            && self.lexer.clone().next_token() == Token::Word("}".to_string())
+    }
+
+    fn parse_redirection(
+        &mut self,
+        fd: Option<i32>,
+        operator: RedirectOp,
+    ) -> Result<Redirection, String> {
+        self.next_token(); // 跳过重定向操作符
+
+        match &self.current_token {
+            Token::Word(filename) => {
+                let redirection = Redirection {
+                    fd,
+                    operator,
+                    filename: filename.clone(),
+                };
+                self.next_token();
+                Ok(redirection)
+            }
+            _ => Err(i18n::t("parse.expected_filename")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_simple_command() {
+        let mut parser = Parser::new("ls -l");
+        let node = parser.parse_command().unwrap();
+
+        match node {
+            Node::Command(cmd) => {
+                assert_eq!(cmd.program, "ls");
+                assert_eq!(cmd.arguments, vec!["-l"]);
+                assert!(cmd.redirections.is_empty());
+                assert!(!cmd.background);
+            }
+            _ => panic!("Expected simple command"),
+        }
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_pipeline() {
+        let mut parser = Parser::new("ls -l | grep foo");
+        let node = parser.parse_command().unwrap();
+
+        match node {
+            Node::Pipeline(cmds) => {
+                assert_eq!(cmds.len(), 2);
+                assert_eq!(cmds[0].program, "ls");
+                assert_eq!(cmds[0].arguments, vec!["-l"]);
+                assert_eq!(cmds[1].program, "grep");
+                assert_eq!(cmds[1].arguments, vec!["foo"]);
+            }
+            _ => panic!("Expected pipeline"),
+        }
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_redirection() {
+        let mut parser = Parser::new("echo hello > output.txt");
+        let node = parser.parse_command().unwrap();
+
+        match node {
+            Node::Command(cmd) => {
+                assert_eq!(cmd.program, "echo");
+                assert_eq!(cmd.arguments, vec!["hello"]);
+                assert_eq!(cmd.redirections.len(), 1);
+                assert_eq!(cmd.redirections[0].filename, "output.txt");
+                assert!(matches!(cmd.redirections[0].operator, RedirectOp::Output));
+            }
+            _ => panic!("Expected command with redirection"),
+        }
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_fd_redirection() {
+        let node = Parser::new("make 2> err.log >&2 3< in &>> all")
+            .parse_command()
+            .unwrap();
+
+        match &node {
+            Node::Command(cmd) => {
+                let fds: Vec<Vec<i32>> = cmd.redirections.iter().map(|r| r.fds()).collect();
+                assert_eq!(fds, vec![vec![2], vec![1], vec![3], vec![1, 2]]);
+                assert_eq!(cmd.redirections[1].operator, RedirectOp::Duplicate);
+                assert_eq!(cmd.redirections[1].filename, "2");
+                assert!(cmd.redirections[1].redirects_stdout());
+                assert!(!cmd.redirections[0].redirects_stdout());
+                assert!(!cmd.redirections[2].writes_file());
+            }
+            _ => panic!("Expected command with fd redirections"),
+        }
+        assert_eq!(node.to_string(), "make 2> err.log >&2 3< in &>> all");
+        assert!(Parser::new("make 2>").parse_command().is_err());
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_heredoc() {
+        let node = Parser::new("cat <<'EOF' > out\n$HOME\nEOF")
+            .parse_command()
+            .unwrap();
+
+        match &node {
+            Node::Command(cmd) => {
+                assert_eq!(cmd.program, "cat");
+                assert!(cmd.arguments.is_empty());
+                assert_eq!(
+                    cmd.redirections[0].operator,
+                    RedirectOp::HereDoc { expand: false }
+                );
+                assert_eq!(cmd.redirections[0].filename, "$HOME\n");
+                assert_eq!(cmd.redirections[0].fds(), vec![0]);
+                assert!(!cmd.redirections[0].redirects_stdout());
+                assert!(cmd.redirections[1].redirects_stdout());
+            }
+            _ => panic!("Expected command with a here document"),
+        }
+        let node = Parser::new("cat <<EOF\na\nEOF").parse_command().unwrap();
+        assert_eq!(node.to_string(), "cat <<EOF\na\nEOF");
+        let node = Parser::new("cat <<'END'\nEOF\nEOF_\nEND")
+            .parse_command()
+            .unwrap();
+        assert_eq!(node.to_string(), "cat <<'EOF__'\nEOF\nEOF_\nEOF__");
+        assert_eq!(
+            Parser::new(&node.to_string()).parse_command().unwrap(),
+            node
+        );
+
+        let node = Parser::new("tr a-z A-Z <<< \"hello $USER\"")
+            .parse_command()
+            .unwrap();
+        match &node {
+            Node::Command(cmd) => {
+                assert_eq!(cmd.arguments, vec!["a-z", "A-Z"]);
+                assert_eq!(cmd.redirections[0].operator, RedirectOp::HereString);
+                assert_eq!(cmd.redirections[0].filename, "hello $USER");
+                assert_eq!(cmd.redirections[0].fds(), vec![0]);
+            }
+            _ => panic!("Expected command with a here string"),
+        }
+        assert!(Parser::new("cat <<<").parse_command().is_err());
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_background() {
+        let mut parser = Parser::new("sleep 10 &");
+        let node = parser.parse_command().unwrap();
+
+        match node {
+            Node::Command(cmd) => {
+                assert_eq!(cmd.program, "sleep");
+                assert_eq!(cmd.arguments, vec!["10"]);
+                assert!(cmd.background);
+            }
+            _ => panic!("Expected background command"),
+        }
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_process_substitution() {
+        let mut parser = Parser::new("timeout 5 diff <(ls a) >(sort)");
+        let node = parser.parse_command().unwrap();
+
+        match node {
+            Node::Command(cmd) => {
+                assert_eq!(cmd.arguments, vec!["5", "diff", "<(ls a)", ">(sort)"]);
+                assert_eq!(cmd.substitutions.len(), 2);
+                assert_eq!(cmd.substitutions[0].index, 2);
+                assert_eq!(cmd.substitutions[1].kind, SubstKind::Output);
+
+                let inner = cmd.inner(1, usize::MAX).unwrap();
+                assert_eq!(inner.program, "diff");
+                assert_eq!(inner.substitutions[0].index, 0);
+                assert_eq!(inner.substitutions[1].command, "sort");
+            }
+            _ => panic!("Expected command with process substitution"),
+        }
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_and_or() {
+        let node = Parser::new("make && ./run | tee log || echo failed")
+            .parse_command()
+            .unwrap();
+
+        match &node {
+            Node::AndOr(first, rest) => {
+                assert!(matches!(**first, Node::Command(ref cmd) if cmd.program == "make"));
+                assert_eq!(rest.len(), 2);
+                assert_eq!(rest[0].0, Connector::And);
+                assert!(matches!(rest[0].1, Node::Pipeline(ref cmds) if cmds.len() == 2));
+                assert_eq!(rest[1].0, Connector::Or);
+                assert_eq!(node.commands().len(), 4);
+            }
+            _ => panic!("Expected and-or list"),
+        }
+        assert_eq!(node.to_string(), "make && ./run | tee log || echo failed");
+        assert!(Parser::new("a &&").parse_command().is_err());
+        assert!(Parser::new("|| b").parse_command().is_err());
+        assert!(Parser::new("true && echo x &").parse_command().is_err());
+        assert!(Parser::new("sleep 1 & || echo x").parse_command().is_err());
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_list() {
+        let node = Parser::new("cd /tmp; ls && pwd; sleep 1 & echo done;")
+            .parse_command()
+            .unwrap();
+
+        match &node {
+            Node::List(nodes) => {
+                assert_eq!(nodes.len(), 4);
+                assert!(matches!(nodes[0], Node::Command(ref cmd) if cmd.program == "cd"));
+                assert!(matches!(nodes[1], Node::AndOr(_, _)));
+                assert!(matches!(nodes[2], Node::Command(ref cmd) if cmd.background));
+                assert!(matches!(nodes[3], Node::Command(ref cmd) if cmd.program == "echo"));
+            }
+            _ => panic!("Expected command list"),
+        }
+        assert_eq!(node.commands().len(), 5);
+        assert!(matches!(
+            Parser::new("echo a;").parse_command().unwrap(),
+            Node::Command(_)
+        ));
+        assert_eq!(
+            Parser::new("coproc up { tr a-z A-Z; }; echo started")
+                .parse_command()
+                .unwrap()
+                .to_string(),
+            "coproc up { tr a-z A-Z }; echo started"
+        );
+        assert_eq!(
+            Parser::new("coproc { cat; }")
+                .parse_command()
+                .unwrap()
+                .to_string(),
+            "coproc { cat }"
+        );
+        // 其他命令的 { 参数不会吞掉之后单独的 }
+        assert_eq!(
+            Parser::new("echo { a; }")
+                .parse_command()
+                .unwrap()
+                .to_string(),
+            "echo { a; }"
+        );
+        assert_eq!(
+            Parser::new("coproc up { tr a-z A-Z; }; echo { x; }")
+                .parse_command()
+                .unwrap()
+                .commands()
+                .len(),
+            3
+        );
+        assert!(Parser::new("a;; b").parse_command().is_err());
+        assert!(Parser::new("; b").parse_command().is_err());
+    }
+
+    // 随机生成的单词包含引号、转义、操作符、空白和多字节字符
+    const ALPHABET: &[char] = &[
+        'a', 'b', 'z', '-', '.', '/', '$', '~', '(', ')', '"', '\'', '\\', ';', '<', '>', '|', '&',
+        ' ', '\t', '雑', '魚',
+    ];
+
+    const OPERATORS: [RedirectOp; 7] = [
+        RedirectOp::Input,
+        RedirectOp::Output,
+        RedirectOp::Append,
+        RedirectOp::Duplicate,
+        RedirectOp::OutputAll,
+        RedirectOp::AppendAll,
+        RedirectOp::HereString,
+    ];
+
+    fn word(max_len: usize) -> impl Strategy<Value = String> {
+        prop::collection::vec(prop::sample::select(ALPHABET), 0..max_len)
+            .prop_map(|chars| chars.into_iter().collect())
+    }
+
+    // 参数为单词（Ok）或进程替换（Err，<(...) 或 >(...)）
+    fn argument() -> impl Strategy<Value = Result<String, SubstKind>> {
+        prop_oneof![
+            4 => word(6).prop_map(Ok),
+            1 => prop_oneof![Just(SubstKind::Input), Just(SubstKind::Output)].prop_map(Err),
+        ]
+    }
+
+    fn redirection() -> impl Strategy<Value = Redirection> {
+        (
+            prop::sample::select(&OPERATORS[..]),
+            prop::option::weighted(0.3, 0..10),
+            word(6),
+        )
+            // &> 和 &>> 前面不能写 fd
+            .prop_map(|(operator, fd, filename)| Redirection {
+                fd: fd
+                    .filter(|_| !matches!(operator, RedirectOp::OutputAll | RedirectOp::AppendAll)),
+                operator,
+                filename,
+            })
+    }
+
+    fn command() -> impl Strategy<Value = Command> {
+        (
+            word(6),
+            prop::collection::vec(argument(), 0..4),
+            prop::collection::vec(redirection(), 0..3),
+        )
+            .prop_map(|(program, arguments, redirections)| {
+                let mut command = Command {
+                    program,
+                    redirections,
+                    ..Command::default()
+                };
+                for (index, argument) in arguments.into_iter().enumerate() {
+                    match argument {
+                        Ok(word) => command.arguments.push(word),
+                        // 解析器给进程替换保留的参数是原文，需要与 Display 的输出一致
+                        Err(kind) => {
+                            let subst = ProcessSubst {
+                                index,
+                                kind,
+                                command: "ls -l".to_string(),
+                            };
+                            command.arguments.push(subst.to_string());
+                            command.substitutions.push(subst);
+                        }
+                    }
+                }
+                command
+            })
+    }
+
+    // 一个命令或管道，后台运行时 & 在最后一个命令上
+    fn pipeline() -> impl Strategy<Value = Node> {
+        (
+            prop::collection::vec(command(), 1..4),
+            prop::bool::weighted(0.2),
+        )
+            .prop_map(|(mut commands, background)| {
+                if let Some(last) = commands.last_mut() {
+                    last.background = background;
+                }
+                if commands.len() == 1 {
+                    Node::Command(commands.remove(0))
+                } else {
+                    Node::Pipeline(commands)
+                }
+            })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        // 解析 → 转回文本 → 再解析得到相同的 AST
+        #[test]
+        fn test_round_trip_property(node in pipeline()) {
+            let line = node.to_string();
+            let parsed = Parser::new(&line).parse_command();
+            prop_assert_eq!(parsed, Ok(node), "{:?}", line);
+        }
+
+        #[test]
+        fn test_random_input_never_panics(input in prop::collection::vec(prop::sample::select(ALPHABET), 0..40)) {
+            let input: String = input.into_iter().collect();
+            let _ = Lexer::new(&input).tokens();
+            let _ = Parser::new(&input).parse_command();
+        }
+    }
+}
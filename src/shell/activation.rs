@@ -0,0 +1,157 @@
+// 按目录自动激活开发环境（ZAKO_AUTO_ACTIVATE=on）：
+// 进入含有 .venv/venv 虚拟环境的项目时激活它，进入含有 .nvmrc/.node-version 的项目时
+// 把 nvm 安装的对应 node 版本加入 PATH，离开项目目录时恢复
+use log::debug;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::shell::hooks::DirectoryHook;
+use crate::utils::path;
+
+// 把目录加到 PATH 的最前面
+fn prepend_path(dir: &Path) {
+    let path = env::var("PATH").unwrap_or_default();
+    let dir = dir.display().to_string();
+    if path.is_empty() {
+        env::set_var("PATH", dir);
+    } else {
+        env::set_var("PATH", format!("{}:{}", dir, path));
+    }
+}
+
+// 从 PATH 中去掉之前加入的目录
+fn remove_from_path(dir: &Path) {
+    let Ok(path) = env::var("PATH") else {
+        return;
+    };
+    let dir = dir.display().to_string();
+    let rest: Vec<&str> = path.split(':').filter(|entry| *entry != dir).collect();
+    env::set_var("PATH", rest.join(":"));
+}
+
+// 自动激活的 Python 虚拟环境，手动激活的（已有 VIRTUAL_ENV）不会被替换或退出
+#[derive(Default)]
+pub struct VenvActivation {
+    active: Option<PathBuf>,
+}
+
+impl DirectoryHook for VenvActivation {
+    fn on_change(&mut self, dir: &Path) {
+        let found = dir
+            .ancestors()
+            .flat_map(|dir| [dir.join(".venv"), dir.join("venv")])
+            .find(|venv| venv.join("bin/activate").is_file());
+        if found == self.active {
+            return;
+        }
+        if let Some(old) = self.active.take() {
+            debug!("退出虚拟环境 {}", old.display());
+            remove_from_path(&old.join("bin"));
+            env::remove_var("VIRTUAL_ENV");
+        }
+        if env::var_os("VIRTUAL_ENV").is_some() {
+            return;
+        }
+        if let Some(venv) = found {
+            debug!("激活虚拟环境 {}", venv.display());
+            prepend_path(&venv.join("bin"));
+            env::set_var("VIRTUAL_ENV", &venv);
+            env::remove_var("PYTHONHOME");
+            self.active = Some(venv);
+        }
+    }
+}
+
+// .nvmrc 指定的 node 版本，从 $NVM_DIR（默认 ~/.nvm）中查找已安装的版本
+#[derive(Default)]
+pub struct NodeActivation {
+    // 加入 PATH 的 bin 目录
+    active: Option<PathBuf>,
+}
+
+impl NodeActivation {
+    fn find_bin(dir: &Path) -> Option<PathBuf> {
+        let file = path::find_upwards(dir, &[".nvmrc", ".node-version"])?;
+        let wanted = fs::read_to_string(file).ok()?;
+        let nvm_dir = env::var("NVM_DIR").ok().map(PathBuf::from).or_else(|| {
+            env::var("HOME")
+                .ok()
+                .map(|home| Path::new(&home).join(".nvm"))
+        })?;
+        let versions_dir = nvm_dir.join("versions/node");
+        let installed: Vec<String> = fs::read_dir(&versions_dir)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        let version = resolve_node_version(&installed, wanted.lines().next()?.trim())?;
+        Some(versions_dir.join(version).join("bin"))
+    }
+}
+
+impl DirectoryHook for NodeActivation {
+    fn on_change(&mut self, dir: &Path) {
+        let found = Self::find_bin(dir);
+        if found == self.active {
+            return;
+        }
+        if let Some(old) = self.active.take() {
+            debug!("移除 node {}", old.display());
+            remove_from_path(&old);
+        }
+        if let Some(bin) = found {
+            debug!("使用 node {}", bin.display());
+            prepend_path(&bin);
+            self.active = Some(bin);
+        }
+    }
+}
+
+// 在已安装的版本（v18.17.0 这样的目录名）中找到与 wanted 匹配的最高版本
+// wanted 可以是 18、v18.17 或完整的版本号，lts/* 等别名不支持
+fn resolve_node_version(installed: &[String], wanted: &str) -> Option<String> {
+    let wanted = wanted.strip_prefix('v').unwrap_or(wanted);
+    let parse = |version: &str| -> Option<Vec<u32>> {
+        version
+            .strip_prefix('v')
+            .unwrap_or(version)
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect()
+    };
+    let wanted_parts = parse(wanted)?;
+    installed
+        .iter()
+        .filter_map(|name| Some((parse(name)?, name)))
+        .filter(|(parts, _)| parts.starts_with(&wanted_parts))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, name)| name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_node_version() {
+        let installed: Vec<String> = ["v16.20.2", "v18.9.1", "v18.17.0", "v20.5.0", "system"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            resolve_node_version(&installed, "18").as_deref(),
+            Some("v18.17.0")
+        );
+        assert_eq!(
+            resolve_node_version(&installed, "v18.9").as_deref(),
+            Some("v18.9.1")
+        );
+        assert_eq!(
+            resolve_node_version(&installed, "20.5.0").as_deref(),
+            Some("v20.5.0")
+        );
+        assert_eq!(resolve_node_version(&installed, "19"), None);
+        assert_eq!(resolve_node_version(&installed, "lts/*"), None);
+    }
+}
@@ -0,0 +1,275 @@
+// 别名：alias 定义的和当前目录的 .zako.toml 中的命令名别名，以及 zsh 风格的全局别名和后缀别名
+//   alias -g G='| grep'：全局别名，解析之前替换命令行中任意位置未加引号的完整单词，ls G foo 即 ls | grep foo
+//   alias -s md=glow：后缀别名，命令名以 .md 结尾时在前面加上 glow，README.md 即 glow README.md
+// 优先级：先在解析前展开全局别名（只展开一遍）；命令名是普通别名时展开普通别名，否则按后缀别名展开，
+// 展开结果的命令名仍可以是普通别名；最后才是内建命令、函数和 PATH 中的命令
+// 同名的普通别名 .zako.toml 中的覆盖 alias 定义的
+use std::collections::HashMap;
+use std::io;
+use zakosh::i18n;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AliasKind {
+    Command,
+    Global,
+    Suffix,
+}
+
+#[derive(Default)]
+pub struct Aliases {
+    commands: HashMap<String, String>,
+    local: HashMap<String, String>,
+    global: HashMap<String, String>,
+    suffix: HashMap<String, String>,
+}
+
+impl Aliases {
+    pub fn set_local(&mut self, aliases: HashMap<String, String>) {
+        self.local = aliases;
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.local.get(name).or_else(|| self.commands.get(name))
+    }
+
+    // 命令名 text.EXT 对应的后缀别名，EXT 区分大小写，只有后缀的 .md 不算
+    pub fn suffix(&self, program: &str) -> Option<&String> {
+        let (stem, extension) = program.rsplit_once('.')?;
+        let stem = stem.rsplit('/').next().unwrap_or(stem);
+        if stem.is_empty() || extension.contains('/') {
+            return None;
+        }
+        self.suffix.get(extension)
+    }
+
+    fn table(&mut self, kind: AliasKind) -> &mut HashMap<String, String> {
+        match kind {
+            AliasKind::Command => &mut self.commands,
+            AliasKind::Global => &mut self.global,
+            AliasKind::Suffix => &mut self.suffix,
+        }
+    }
+
+    // 别名的名字（后缀别名为扩展名）和内容
+    pub fn entries(&self, kind: AliasKind) -> HashMap<String, String> {
+        self.list(kind)
+            .into_iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    // 按名字排序的别名，普通别名包括 .zako.toml 中的
+    fn list(&self, kind: AliasKind) -> Vec<(&String, &String)> {
+        let mut list: Vec<(&String, &String)> = match kind {
+            AliasKind::Command => {
+                let mut merged: HashMap<&String, &String> = self.commands.iter().collect();
+                merged.extend(self.local.iter());
+                merged.into_iter().collect()
+            }
+            AliasKind::Global => self.global.iter().collect(),
+            AliasKind::Suffix => self.suffix.iter().collect(),
+        };
+        list.sort();
+        list
+    }
+
+    // 替换命令行中作为完整单词出现、没有引号和反斜杠的全局别名，引号内的内容原样保留
+    // alias 和 unalias 开头的行不展开，unalias -g G 不需要给 G 加引号
+    pub fn expand_global(&self, line: &str) -> String {
+        let first = line.split_whitespace().next();
+        if self.global.is_empty() || matches!(first, Some("alias" | "unalias")) {
+            return line.to_string();
+        }
+        let mut result = String::with_capacity(line.len());
+        let mut word = String::new();
+        // 单词中有引号或反斜杠时不展开
+        let mut literal = false;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' | '"' => {
+                    literal = true;
+                    word.push(c);
+                    let mut escaped = false;
+                    for next in chars.by_ref() {
+                        word.push(next);
+                        match (escaped, next) {
+                            (true, _) => escaped = false,
+                            (false, '\\') => escaped = true,
+                            (false, next) if next == c => break,
+                            _ => {}
+                        }
+                    }
+                }
+                '\\' => {
+                    literal = true;
+                    word.push(c);
+                    if let Some(next) = chars.next() {
+                        word.push(next);
+                    }
+                }
+                c if c.is_whitespace() || ";<>|&".contains(c) => {
+                    self.push_word(&mut result, &word, literal);
+                    word.clear();
+                    literal = false;
+                    result.push(c);
+                }
+                c => word.push(c),
+            }
+        }
+        self.push_word(&mut result, &word, literal);
+        result
+    }
+
+    fn push_word(&self, result: &mut String, word: &str, literal: bool) {
+        match self.global.get(word) {
+            Some(value) if !literal => result.push_str(value),
+            _ => result.push_str(word),
+        }
+    }
+}
+
+// alias [-g | -s] [NAME[=VALUE] ...]：没有参数时列出别名，NAME 显示一个别名，NAME=VALUE 定义别名
+pub fn alias(aliases: &mut Aliases, args: &[String]) -> io::Result<()> {
+    let (kind, args) = match args.first().map(String::as_str) {
+        Some("-g") => (Some(AliasKind::Global), &args[1..]),
+        Some("-s") => (Some(AliasKind::Suffix), &args[1..]),
+        _ => (None, args),
+    };
+    if args.is_empty() {
+        let kinds = match kind {
+            Some(kind) => vec![kind],
+            None => vec![AliasKind::Command, AliasKind::Global, AliasKind::Suffix],
+        };
+        for kind in kinds {
+            for (name, value) in aliases.list(kind) {
+                println!("{}", format_alias(kind, name, value));
+            }
+        }
+        return Ok(());
+    }
+    let kind = kind.unwrap_or(AliasKind::Command);
+    let mut missing = Vec::new();
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) if valid_name(name) => {
+                aliases
+                    .table(kind)
+                    .insert(name.to_string(), value.to_string());
+            }
+            Some((name, _)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("alias.invalid_name", &[name]),
+                ))
+            }
+            None => {
+                let value = match kind {
+                    AliasKind::Command => aliases.get(arg),
+                    AliasKind::Global => aliases.global.get(arg),
+                    AliasKind::Suffix => aliases.suffix.get(arg),
+                };
+                match value {
+                    Some(value) => println!("{}", format_alias(kind, arg, value)),
+                    None => missing.push(i18n::tf("alias.not_found", &["alias", arg])),
+                }
+            }
+        }
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, missing.join("\n")))
+    }
+}
+
+// unalias [-g | -s] NAME ...；.zako.toml 中的别名随目录变化，不能删除
+pub fn unalias(aliases: &mut Aliases, args: &[String]) -> io::Result<()> {
+    let (kind, names) = match args.first().map(String::as_str) {
+        Some("-g") => (AliasKind::Global, &args[1..]),
+        Some("-s") => (AliasKind::Suffix, &args[1..]),
+        _ => (AliasKind::Command, args),
+    };
+    if names.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            i18n::tf("usage", &["unalias [-g | -s] NAME ..."]),
+        ));
+    }
+    let missing: Vec<String> = names
+        .iter()
+        .filter(|name| aliases.table(kind).remove(name.as_str()).is_none())
+        .map(|name| i18n::tf("alias.not_found", &["unalias", name]))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, missing.join("\n")))
+    }
+}
+
+// 别名不能为空，也不能含有空白、引号和命令行中的特殊字符
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "'\"\\;<>|&=/".contains(c))
+}
+
+// 与定义时的写法相同，可以直接粘贴回 rc 文件
+fn format_alias(kind: AliasKind, name: &str, value: &str) -> String {
+    let flag = match kind {
+        AliasKind::Command => "",
+        AliasKind::Global => "-g ",
+        AliasKind::Suffix => "-s ",
+    };
+    format!("alias {}{}={}", flag, name, shell_words::quote(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_global_and_suffix_aliases() -> io::Result<()> {
+        let mut aliases = Aliases::default();
+        alias(&mut aliases, &strings(&["-g", "G=| grep", "L=| less"]))?;
+        alias(&mut aliases, &strings(&["-s", "md=glow"]))?;
+        assert_eq!(aliases.expand_global("ls G foo"), "ls | grep foo");
+        assert_eq!(
+            aliases.expand_global("cat x L;echo G"),
+            "cat x | less;echo | grep"
+        );
+        // 引号中、带反斜杠和只是单词一部分时不展开
+        assert_eq!(
+            aliases.expand_global("echo 'G' \"a G\" \\G GG"),
+            "echo 'G' \"a G\" \\G GG"
+        );
+
+        assert_eq!(aliases.suffix("README.md"), Some(&"glow".to_string()));
+        assert_eq!(aliases.suffix("docs/intro.md"), Some(&"glow".to_string()));
+        assert_eq!(aliases.suffix(".md"), None);
+        assert_eq!(aliases.suffix("a.md/b"), None);
+        assert_eq!(aliases.suffix("README.MD"), None);
+
+        // .zako.toml 中的同名别名优先
+        alias(&mut aliases, &strings(&["ll=ls -l"]))?;
+        aliases.set_local(HashMap::from([("ll".to_string(), "ls -la".to_string())]));
+        assert_eq!(aliases.get("ll"), Some(&"ls -la".to_string()));
+        assert_eq!(
+            format_alias(AliasKind::Global, "G", "| grep"),
+            "alias -g G='| grep'"
+        );
+
+        assert_eq!(aliases.expand_global("unalias -g G"), "unalias -g G");
+        unalias(&mut aliases, &strings(&["-g", "G"]))?;
+        assert_eq!(aliases.expand_global("ls G"), "ls G");
+        assert!(unalias(&mut aliases, &strings(&["-s", "txt"])).is_err());
+        assert!(alias(&mut aliases, &strings(&["a b=c"])).is_err());
+        Ok(())
+    }
+}
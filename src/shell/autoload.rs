@@ -0,0 +1,156 @@
+// 自动加载函数（类似 zsh 的 autoload）：fpath 中每个目录下的文件注册为以文件名命名的函数，
+// 启动时只列出文件名，第一次调用时才读取和解析文件，之后使用缓存的命令；前面的目录中的同名文件优先
+// 文件内容是函数体，在当前 shell 中逐条执行，参数为 $1 $2 ...，$argv 为所有参数，$ARGC 为参数个数
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::shell::parser::ast::Node;
+use crate::shell::parser::{self, Parser};
+use zakosh::i18n;
+
+struct Function {
+    path: PathBuf,
+    // 第一次调用时解析
+    body: Option<Rc<Vec<Node>>>,
+}
+
+pub struct Autoload {
+    functions: HashMap<String, Function>,
+}
+
+impl Autoload {
+    pub fn new(fpath: &[PathBuf]) -> Self {
+        let mut functions = HashMap::new();
+        for dir in fpath {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                // 跳过隐藏文件和编辑器的备份文件
+                if name.starts_with('.') || name.ends_with('~') || !path.is_file() {
+                    continue;
+                }
+                functions.entry(name.to_string()).or_insert(Function {
+                    path: path.clone(),
+                    body: None,
+                });
+            }
+        }
+        Self { functions }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.functions.keys().cloned().collect()
+    }
+
+    // 定义函数的文件
+    pub fn path(&self, name: &str) -> Option<&Path> {
+        self.functions
+            .get(name)
+            .map(|function| function.path.as_path())
+    }
+
+    // 函数体，第一次调用时加载；有语法错误时不缓存，修改文件后再次调用即可
+    pub fn load(&mut self, name: &str) -> Option<io::Result<Rc<Vec<Node>>>> {
+        let function = self.functions.get_mut(name)?;
+        if let Some(body) = &function.body {
+            return Some(Ok(body.clone()));
+        }
+        let path = function.path.display();
+        let body = match fs::read_to_string(&function.path) {
+            Ok(text) => parse_body(&text)
+                .map(Rc::new)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}", path, e))),
+            Err(e) => Err(io::Error::new(e.kind(), format!("{}: {}", path, e))),
+        };
+        if let Ok(body) = &body {
+            debug!("加载函数 {}: {}", name, path);
+            function.body = Some(body.clone());
+        }
+        Some(body)
+    }
+}
+
+// 与脚本一样逐行解析，跨多行的命令合并后解析；错误信息以行号开头
+fn parse_body(text: &str) -> Result<Vec<Node>, String> {
+    let mut body = Vec::new();
+    let mut pending = String::new();
+    let mut number = 0;
+    for (index, line) in text.lines().enumerate() {
+        if pending.is_empty() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            number = index;
+            pending.push_str(line);
+        } else {
+            pending.push('\n');
+            pending.push_str(line);
+        }
+        if parser::is_incomplete(&pending) {
+            continue;
+        }
+        let line = std::mem::take(&mut pending);
+        let node = Parser::new(&line)
+            .parse_command()
+            .map_err(|e| format!("{}: {}", number + 1, e))?;
+        body.push(node);
+    }
+    if !pending.is_empty() {
+        return Err(i18n::tf(
+            "autoload.unexpected_eof",
+            &[&(number + 1).to_string()],
+        ));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_autoload() {
+        let dir = std::env::temp_dir().join(format!("zako_fpath_{}", std::process::id()));
+        let first = dir.join("first");
+        let second = dir.join("second");
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(
+            first.join("greet"),
+            "# 注释\necho hello $1\necho 'a\nb' | cat\n",
+        )
+        .unwrap();
+        fs::write(second.join("greet"), "echo shadowed\n").unwrap();
+        fs::write(second.join("broken"), "echo 'open\n").unwrap();
+        fs::write(second.join("greet~"), "").unwrap();
+
+        let mut autoload = Autoload::new(&[first.clone(), second.clone(), dir.join("missing")]);
+        let mut names = autoload.names();
+        names.sort();
+        assert_eq!(names, ["broken", "greet"]);
+        assert_eq!(autoload.path("greet"), Some(first.join("greet").as_path()));
+
+        let body = autoload.load("greet").unwrap().unwrap();
+        assert_eq!(body.len(), 2);
+        assert!(matches!(body[1], Node::Pipeline(_)));
+        // 加载之后使用缓存，不再读取文件
+        fs::remove_file(first.join("greet")).unwrap();
+        assert!(Rc::ptr_eq(&body, &autoload.load("greet").unwrap().unwrap()));
+
+        let error = autoload.load("broken").unwrap().unwrap_err();
+        assert!(error.to_string().contains("broken:1: 输入意外结束"));
+        assert!(autoload.load("missing").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+// zako --bench-internal：测量解析、变量展开和执行外部命令的耗时，打印每次操作的中位数
+// 与 bench/ 下的 criterion 基准测试覆盖相同的路径，变量展开依赖 executor，只在这里测量
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::shell::executor::Executor;
+use crate::shell::parser::{Lexer, Parser};
+
+// 有代表性的命令行：简单命令、管道和重定向、变量、进程替换和 && ||
+pub const LINES: [&str; 4] = [
+    "ls -la /tmp",
+    "cat Cargo.toml | grep -v '#' | sort | uniq -c > /dev/null",
+    "echo $HOME ${USER} \"$PATH\" 2>&1 &",
+    "diff <(sort a.txt) <(sort b.txt) && echo same || echo differ",
+];
+
+const SAMPLES: usize = 21;
+
+struct Bench {
+    name: &'static str,
+    // 每个样本运行的次数，样本的耗时除以次数为一次操作的耗时
+    iterations: u32,
+}
+
+impl Bench {
+    fn run(&self, mut f: impl FnMut()) -> Duration {
+        // 预热一个样本
+        for _ in 0..self.iterations {
+            f();
+        }
+        let mut samples: Vec<Duration> = (0..SAMPLES)
+            .map(|_| {
+                let start = Instant::now();
+                for _ in 0..self.iterations {
+                    f();
+                }
+                start.elapsed() / self.iterations
+            })
+            .collect();
+        samples.sort();
+        samples[SAMPLES / 2]
+    }
+}
+
+pub fn run(executor: &mut Executor) -> i32 {
+    let mut results = Vec::new();
+    let lex = Bench {
+        name: "lex",
+        iterations: 10_000,
+    };
+    results.push((
+        lex.name,
+        lex.run(|| {
+            for line in LINES {
+                black_box(Lexer::new(black_box(line)).tokens());
+            }
+        }),
+    ));
+    let parse = Bench {
+        name: "parse",
+        iterations: 10_000,
+    };
+    results.push((
+        parse.name,
+        parse.run(|| {
+            for line in LINES {
+                let _ = black_box(Parser::new(black_box(line)).parse_command());
+            }
+        }),
+    ));
+    let expand = Bench {
+        name: "expand_variables",
+        iterations: 10_000,
+    };
+    results.push((
+        expand.name,
+        expand.run(|| {
+            black_box(executor.expand_variables(black_box("$HOME/${USER}:$PATH $? $NOPE")));
+        }),
+    ));
+    let exec = Bench {
+        name: "exec /bin/true",
+        iterations: 20,
+    };
+    let mut failed = false;
+    results.push((
+        exec.name,
+        exec.run(|| match Parser::new("/bin/true").parse_command() {
+            Ok(node) => failed |= executor.execute(node).is_err(),
+            Err(_) => failed = true,
+        }),
+    ));
+
+    for (name, median) in results {
+        println!("{:<20} {:>12}", name, format_median(median));
+    }
+    if failed {
+        eprintln!("zako: 无法运行 /bin/true");
+        return 1;
+    }
+    0
+}
+
+// 保留两位小数，format_duration 只精确到毫秒
+fn format_median(median: Duration) -> String {
+    if median >= Duration::from_millis(1) {
+        format!("{:.2}ms", median.as_secs_f64() * 1000.0)
+    } else if median >= Duration::from_micros(1) {
+        format!("{:.2}µs", median.as_nanos() as f64 / 1000.0)
+    } else {
+        format!("{}ns", median.as_nanos())
+    }
+}
@@ -0,0 +1,142 @@
+use std::io;
+use std::rc::Rc;
+
+use super::executor::Executor;
+use crate::shell::parser::ast::Command as ShellCommand;
+
+// 内建命令的特性
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinFlags {
+    // 会启动子进程运行其他命令（如 timeout、par），不能在进程替换中运行
+    pub needs_fork: bool,
+    // 会修改 shell 自身的状态（如 cd、set），只在 shell 进程中运行才有意义
+    pub changes_state: bool,
+    // 替代同名的外部命令（如 sleep），只在前台单独运行时使用内建实现，
+    // 管道、后台作业和前缀命令（timeout、after、par 等）中运行外部命令
+    pub replaces_external: bool,
+}
+
+// 内建命令运行时所在的作业，启动子进程的内建命令通过它加入进程组
+pub struct JobContext<'a> {
+    pub pgid: &'a mut i32,
+    pub fg_pids: &'a mut Vec<i32>,
+}
+
+pub trait Builtin {
+    fn name(&self) -> &'static str;
+
+    // help 显示的用法
+    fn usage(&self) -> &'static str;
+
+    fn flags(&self) -> BuiltinFlags {
+        BuiltinFlags::default()
+    }
+
+    fn run(
+        &self,
+        executor: &mut Executor,
+        command: &ShellCommand,
+        job: &mut JobContext,
+    ) -> io::Result<()>;
+}
+
+pub type BuiltinHandler = fn(&mut Executor, &ShellCommand, &mut JobContext) -> io::Result<()>;
+
+// 由函数实现的内建命令，shell 自带的内建命令都使用它注册
+pub struct FnBuiltin {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub flags: BuiltinFlags,
+    pub handler: BuiltinHandler,
+}
+
+impl Builtin for FnBuiltin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn usage(&self) -> &'static str {
+        self.usage
+    }
+
+    fn flags(&self) -> BuiltinFlags {
+        self.flags
+    }
+
+    fn run(
+        &self,
+        executor: &mut Executor,
+        command: &ShellCommand,
+        job: &mut JobContext,
+    ) -> io::Result<()> {
+        (self.handler)(executor, command, job)
+    }
+}
+
+// 内建命令注册表，按注册顺序列出
+#[derive(Default)]
+pub struct BuiltinRegistry {
+    builtins: Vec<Rc<dyn Builtin>>,
+}
+
+impl BuiltinRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 注册内建命令，同名的内建命令会被替换
+    pub fn register(&mut self, builtin: Rc<dyn Builtin>) {
+        match self
+            .builtins
+            .iter_mut()
+            .find(|b| b.name() == builtin.name())
+        {
+            Some(existing) => *existing = builtin,
+            None => self.builtins.push(builtin),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.builtins.iter().find(|b| b.name() == name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.builtins.iter().any(|b| b.name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<dyn Builtin>> {
+        self.builtins.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop(_: &mut Executor, _: &ShellCommand, _: &mut JobContext) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn builtin(name: &'static str, usage: &'static str) -> Rc<dyn Builtin> {
+        Rc::new(FnBuiltin {
+            name,
+            usage,
+            flags: BuiltinFlags::default(),
+            handler: noop,
+        })
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = BuiltinRegistry::new();
+        registry.register(builtin("cd", "cd [DIR]"));
+        registry.register(builtin("jobs", "jobs"));
+        registry.register(builtin("cd", "cd DIR"));
+
+        assert!(registry.contains("jobs"));
+        assert!(!registry.contains("ls"));
+        let names: Vec<_> = registry.iter().map(|b| b.name()).collect();
+        assert_eq!(names, vec!["cd", "jobs"]);
+        assert_eq!(registry.get("cd").map(|b| b.usage()), Some("cd DIR"));
+    }
+}
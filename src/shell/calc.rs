@@ -0,0 +1,304 @@
+// calc EXPR / = EXPR：计算算术表达式，例如 = 2^20 / 3.5
+// 支持 + - * / % ^（或 **，乘方，右结合）和括号；整数用 i128 精确计算，除不尽时得到浮点数，
+// 超出 i128 时报错而不是悄悄换成有误差的浮点数，需要近似值时可以写成浮点数，例如 2.0^200
+use std::fmt;
+use zakosh::i18n;
+
+// 括号和正负号的最大嵌套层数，避免递归过深栈溢出
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn as_float(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            // 超过 2^53 的浮点数不能精确表示每个整数，用科学计数法显示，避免看起来像精确的整数
+            Number::Float(x) if x.abs() >= 9007199254740992.0 => write!(f, "{:e}", x),
+            Number::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Num(Number),
+    Op(char),
+    Open,
+    Close,
+}
+
+pub fn evaluate(expr: &str) -> Result<Number, String> {
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Err(i18n::t("calc.empty"));
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let value = parser.expr()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(_) => Err(i18n::t("calc.trailing")),
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // 科学计数法：1e3、2.5E-2
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut end = i + 1;
+                    if end < chars.len() && matches!(chars[end], '+' | '-') {
+                        end += 1;
+                    }
+                    if end < chars.len() && chars[end].is_ascii_digit() {
+                        i = end;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push(Token::Num(parse_number(&text)?));
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                tokens.push(Token::Op('^'));
+                i += 2;
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => {
+                tokens.push(Token::Op(c));
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            _ => return Err(i18n::tf("calc.invalid_char", &[&c.to_string()])),
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Result<Number, String> {
+    if text.chars().all(|c| c.is_ascii_digit()) {
+        return text
+            .parse::<i128>()
+            .map(Number::Int)
+            .map_err(|_| i18n::tf("calc.int_out_of_range", &[text]));
+    }
+    text.parse::<f64>()
+        .map(Number::Float)
+        .map_err(|_| i18n::tf("calc.invalid_number", &[text]))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // 当前的嵌套层数，括号也经由 unary 递归，在这里计数即可
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next_op(&mut self, ops: &[char]) -> Option<char> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(&op) => {
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Number, String> {
+        let mut value = self.term()?;
+        while let Some(op) = self.next_op(&['+', '-']) {
+            value = apply(op, value, self.term()?)?;
+        }
+        Ok(value)
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<Number, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.next_op(&['*', '/', '%']) {
+            value = apply(op, value, self.unary()?)?;
+        }
+        Ok(value)
+    }
+
+    // 乘方优先于负号：-2^2 = -4
+    fn unary(&mut self) -> Result<Number, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(i18n::tf("calc.too_deep", &[&MAX_DEPTH.to_string()]));
+        }
+        self.depth += 1;
+        let value = match self.next_op(&['+', '-']) {
+            Some('-') => self.unary().and_then(|n| apply('-', Number::Int(0), n)),
+            Some(_) => self.unary(),
+            None => self.power(),
+        };
+        self.depth -= 1;
+        value
+    }
+
+    // power := primary ('^' unary)?，右结合，指数可以带负号
+    fn power(&mut self) -> Result<Number, String> {
+        let base = self.primary()?;
+        if self.next_op(&['^']).is_some() {
+            return apply('^', base, self.unary()?);
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Number, String> {
+        match self.peek() {
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Token::Open) => {
+                self.pos += 1;
+                let value = self.expr()?;
+                if self.peek() != Some(Token::Close) {
+                    return Err(i18n::t("calc.missing_close"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(Token::Op(op)) => Err(i18n::tf("calc.missing_operand", &[&op.to_string()])),
+            Some(Token::Close) => Err(i18n::t("calc.extra_close")),
+            None => Err(i18n::t("calc.incomplete")),
+        }
+    }
+}
+
+fn apply(op: char, a: Number, b: Number) -> Result<Number, String> {
+    if matches!(op, '/' | '%') && b.as_float() == 0.0 {
+        return Err(i18n::t("calc.divide_by_zero"));
+    }
+    if let (Number::Int(x), Number::Int(y)) = (a, b) {
+        // 除不尽和负指数的结果不是整数，按浮点数计算；其他情况下没有结果说明溢出
+        let fractional =
+            (op == '/' && x.checked_rem(y).is_some_and(|r| r != 0)) || (op == '^' && y < 0);
+        if !fractional {
+            let exact = match op {
+                '+' => x.checked_add(y),
+                '-' => x.checked_sub(y),
+                '*' => x.checked_mul(y),
+                '/' => x.checked_div(y),
+                '%' => x.checked_rem(y),
+                _ => u32::try_from(y).ok().and_then(|y| x.checked_pow(y)),
+            };
+            return exact.map(Number::Int).ok_or_else(|| {
+                i18n::tf(
+                    "calc.overflow",
+                    &[&x.to_string(), &op.to_string(), &y.to_string()],
+                )
+            });
+        }
+    }
+    let (x, y) = (a.as_float(), b.as_float());
+    Ok(Number::Float(match op {
+        '+' => x + y,
+        '-' => x - y,
+        '*' => x * y,
+        '/' => x / y,
+        '%' => x % y,
+        _ => x.powf(y),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str) -> String {
+        evaluate(expr).map_or_else(|e| format!("error: {}", e), |n| n.to_string())
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(eval("1 + 2 * 3"), "7");
+        assert_eq!(eval("(1 + 2) * 3"), "9");
+        assert_eq!(eval("2^20 / 4"), "262144");
+        assert_eq!(eval("2^20 / 3.5"), "299593.14285714284");
+        assert_eq!(eval("7 / 2"), "3.5");
+        assert_eq!(eval("7 % 3"), "1");
+        assert_eq!(eval("2 ** 3 ** 2"), "512");
+        assert_eq!(eval("-2^2"), "-4");
+        assert_eq!(eval("2^-1"), "0.5");
+        assert_eq!(eval("1.5e3 + 1"), "1501");
+        assert_eq!(eval("9223372036854775807 + 1"), "9223372036854775808");
+        assert_eq!(eval("2^100"), "1267650600228229401496703205376");
+        assert_eq!(eval("-7 / 2"), "-3.5");
+        assert_eq!(eval("2.0^200"), "1.6069380442589903e60");
+        assert_eq!(eval("1e20 + 1"), "1e20");
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        assert_eq!(eval("1 / 0"), "error: 除数为 0");
+        assert_eq!(eval("(1 + 2"), "error: 缺少 )");
+        assert_eq!(eval("1 +"), "error: 表达式不完整");
+        assert_eq!(eval("1 2"), "error: 表达式后有多余的内容");
+        assert_eq!(eval("x + 1"), "error: 无效的字符 x");
+        assert_eq!(eval(""), "error: 表达式为空");
+        assert!(
+            eval("2^200").starts_with("error: 整数运算"),
+            "{}",
+            eval("2^200")
+        );
+        assert!(eval("170141183460469231731687303715884105727 + 1").starts_with("error: "));
+        assert_eq!(
+            eval("999999999999999999999999999999999999999999"),
+            "error: 整数 999999999999999999999999999999999999999999 超出范围"
+        );
+        assert!(eval("(-170141183460469231731687303715884105727 - 1) / -1")
+            .starts_with("error: 整数运算"));
+        let nested = format!("{}1{}", "(".repeat(100000), ")".repeat(100000));
+        assert_eq!(eval(&nested), "error: 嵌套超过 256 层");
+        assert_eq!(
+            eval(&format!("{}1", "-".repeat(100000))),
+            "error: 嵌套超过 256 层"
+        );
+        assert_eq!(
+            eval(&format!("{}1{}", "(".repeat(200), ")".repeat(200))),
+            "1"
+        );
+    }
+}
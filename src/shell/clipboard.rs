@@ -0,0 +1,106 @@
+// 复制到系统剪贴板：优先使用 OSC 52 转义序列（通过 ssh 也能复制到本地），
+// 终端不支持时依次尝试 pbcopy、wl-copy、xclip 和 xsel；shell 没有控制终端时不可用
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::utils::config::ClipboardMode;
+use crate::utils::path;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn copy(text: &str, mode: ClipboardMode) -> io::Result<()> {
+    if mode == ClipboardMode::Off {
+        return Err(io::Error::other("剪贴板已关闭（clipboard = off）"));
+    }
+    let mut tty = OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|_| io::Error::other("没有控制终端，无法使用剪贴板"))?;
+    let osc52 = match mode {
+        ClipboardMode::Osc52 => true,
+        ClipboardMode::Command => false,
+        _ => supports_osc52(),
+    };
+    if osc52 {
+        tty.write_all(osc52_sequence(text).as_bytes())?;
+        return tty.flush();
+    }
+    copy_with_command(text)
+}
+
+// 只有 linux 控制台和 dumb 终端确定不支持，其他终端假定支持
+fn supports_osc52() -> bool {
+    !matches!(
+        std::env::var("TERM").unwrap_or_default().as_str(),
+        "" | "dumb" | "linux"
+    )
+}
+
+fn osc52_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn copy_with_command(text: &str) -> io::Result<()> {
+    let has_env = |name: &str| std::env::var_os(name).is_some();
+    let candidates: [(&str, &[&str], bool); 4] = [
+        ("pbcopy", &[], true),
+        ("wl-copy", &[], has_env("WAYLAND_DISPLAY")),
+        ("xclip", &["-selection", "clipboard"], has_env("DISPLAY")),
+        ("xsel", &["--clipboard", "--input"], has_env("DISPLAY")),
+    ];
+    let (program, args) = candidates
+        .iter()
+        .filter(|(_, _, usable)| *usable)
+        .map(|(name, args, _)| (path::find_file_in_path(name, true), *args))
+        .find(|(program, _)| !program.is_empty())
+        .ok_or_else(|| io::Error::other("找不到 pbcopy、wl-copy、xclip 或 xsel"))?;
+    let mut child = Command::new(&program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{} 失败（{}）", program, status)));
+    }
+    Ok(())
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("雑魚".as_bytes()), "6ZuR6a2a");
+        assert_eq!(osc52_sequence("ls -la"), "\x1b]52;c;bHMgLWxh\x07");
+    }
+}
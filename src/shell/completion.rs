@@ -0,0 +1,783 @@
+// Tab 补全：命令名、$变量、文件名，文件名补全支持 ~、~user 和 $VAR / ${VAR} 开头的路径
+// 列目录前先展开前缀，替换时只改最后一段文件名，缓冲区里保留用户输入的 ~alice/ 或 $HOME/
+// fg/bg/kill/wait/disown 之后的 % 补全作业编号，kill - 之后补全信号名，cd 之后只补全目录
+// 候选项按 ZAKO_COMPLETION_MATCH 配置的方式匹配：前缀、忽略大小写、忽略 -/_ 或模糊匹配
+use crate::shell::completion_cache::CompletionCache;
+use crate::shell::signals;
+use crate::utils::config::CompletionMatch;
+use rustyline::completion::Pair;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+// 需要在文件名里转义的字符
+const SPECIAL_CHARS: &str = " \t\"'\\|&;<>()$`!*?[]{}#";
+
+// 补全菜单最多显示的行数
+const MENU_ROWS: usize = 8;
+
+// 接受作业编号的命令
+const JOB_COMMANDS: &[&str] = &["fg", "bg", "kill", "wait", "disown"];
+
+pub struct Completion {
+    matching: CompletionMatch,
+    // 当前的作业编号和命令
+    pub jobs: Vec<(usize, String)>,
+    // 内建命令名和自动加载的函数名
+    pub builtins: Vec<String>,
+    // 普通别名、全局别名和后缀别名（按扩展名），用于检查命令名是否存在和预览展开的内容
+    pub aliases: HashMap<String, String>,
+    pub global_aliases: HashMap<String, String>,
+    pub suffix_aliases: HashMap<String, String>,
+    // 命令的历史使用次数，常用的命令排在前面
+    pub command_counts: HashMap<String, u64>,
+    // shell 变量名，环境变量在补全时读取
+    pub variables: Vec<String>,
+    cache: CompletionCache,
+}
+
+impl Completion {
+    pub fn new(matching: CompletionMatch, cache_dir: PathBuf) -> Self {
+        Self {
+            matching,
+            jobs: Vec::new(),
+            builtins: Vec::new(),
+            aliases: HashMap::new(),
+            global_aliases: HashMap::new(),
+            suffix_aliases: HashMap::new(),
+            command_counts: HashMap::new(),
+            variables: Vec::new(),
+            cache: CompletionCache::new(cache_dir),
+        }
+    }
+
+    // 返回替换的起始位置和候选项
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let start = word_start(&line[..pos]);
+        let word = &line[start..pos];
+
+        let command = command_name(&line[..start]);
+        if let Some(command) = command {
+            if JOB_COMMANDS.contains(&command) && word.starts_with('%') {
+                return (start, self.complete_jobs(&word[1..]));
+            }
+            if command == "kill" && word.starts_with('-') {
+                return (start, self.complete_signals(&word[1..]));
+            }
+        }
+
+        if !word.contains('/') {
+            // ~ali 补全用户名
+            if let Some(prefix) = word.strip_prefix('~') {
+                return (start, self.complete_users(prefix));
+            }
+            if let Some(name) = word.strip_prefix('$') {
+                return (start, self.complete_variables(name));
+            }
+            if command.is_none() {
+                return (start, self.complete_commands(&unescape(word)));
+            }
+        }
+
+        let (dir, prefix) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..]),
+            None => ("", word),
+        };
+        let candidates = match command {
+            Some("cd") => {
+                let cdpath = env::var("CDPATH").ok();
+                self.complete_dirs(dir, &unescape(prefix), cdpath.as_deref())
+            }
+            _ => self.complete_files(dir, &unescape(prefix)),
+        };
+        (start + dir.len(), candidates)
+    }
+
+    fn is_match(&self, candidate: &str, pattern: &str) -> bool {
+        is_match(self.matching, candidate, pattern)
+    }
+
+    fn complete_jobs(&self, prefix: &str) -> Vec<Pair> {
+        self.jobs
+            .iter()
+            .filter(|(index, _)| index.to_string().starts_with(prefix))
+            .map(|(index, command)| Pair {
+                display: format!("%{}  {}", index, command),
+                replacement: format!("%{}", index),
+            })
+            .collect()
+    }
+
+    fn complete_signals(&self, prefix: &str) -> Vec<Pair> {
+        let upper = prefix.to_uppercase();
+        let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+        signals::all_signals()
+            .iter()
+            .filter(|(_, signal)| self.is_match(signal, name))
+            .map(|(_, signal)| Pair {
+                display: signal.to_string(),
+                replacement: format!("-{}", signal),
+            })
+            .collect()
+    }
+
+    // 内建命令、函数和 PATH 中的可执行文件，使用次数多的在前，次数相同的按名字排序
+    fn complete_commands(&self, prefix: &str) -> Vec<Pair> {
+        let mut names: Vec<String> = self
+            .builtins
+            .iter()
+            .filter(|name| self.is_match(name, prefix))
+            .cloned()
+            .collect();
+        names.extend(
+            self.executables()
+                .into_iter()
+                .filter(|name| self.is_match(name, prefix)),
+        );
+        names.sort();
+        names.dedup();
+        names.sort_by_key(|name| {
+            std::cmp::Reverse(self.command_counts.get(name).copied().unwrap_or(0))
+        });
+        names
+            .into_iter()
+            .map(|name| Pair {
+                replacement: escape(&name),
+                display: name,
+            })
+            .collect()
+    }
+
+    // PATH 中的可执行文件名，按 PATH 各目录的修改时间缓存
+    fn executables(&self) -> Vec<String> {
+        let path = env::var("PATH").unwrap_or_default();
+        let dirs: Vec<PathBuf> = path
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        self.cache.get_or_update("executables", &dirs, || {
+            let mut names = Vec::new();
+            for dir in &dirs {
+                let Ok(entries) = fs::read_dir(dir) else {
+                    continue;
+                };
+                names.extend(entries.flatten().filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    let executable = entry
+                        .metadata()
+                        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0);
+                    (executable && !name.contains('\n')).then_some(name)
+                }));
+            }
+            names.sort();
+            names.dedup();
+            names
+        })
+    }
+
+    // 内建命令、函数、别名和 PATH 中的可执行文件的名字
+    pub fn command_names(&self) -> HashSet<String> {
+        let mut names: HashSet<String> = self
+            .builtins
+            .iter()
+            .chain(self.aliases.keys())
+            .cloned()
+            .collect();
+        names.extend(self.executables());
+        names
+    }
+
+    // 命令名 text.EXT 对应的后缀别名，与 Aliases::suffix 的规则相同
+    pub fn suffix_alias(&self, program: &str) -> Option<&String> {
+        let (stem, extension) = program.rsplit_once('.')?;
+        let stem = stem.rsplit('/').next().unwrap_or(stem);
+        if stem.is_empty() {
+            return None;
+        }
+        self.suffix_aliases.get(extension)
+    }
+
+    // 光标所在的单词是别名时展开后的内容：命令名位置的普通别名和后缀别名，任意位置的全局别名
+    pub fn alias_preview(&self, line: &str, pos: usize) -> Option<String> {
+        let start = word_start(&line[..pos]);
+        let end = line[pos..]
+            .find(|c: char| c.is_whitespace() || ";|&<>()".contains(c))
+            .map_or(line.len(), |i| pos + i);
+        let word = &line[start..end];
+        if word.is_empty() {
+            return None;
+        }
+        if command_name(&line[..start]).is_none() {
+            if let Some(alias) = self.aliases.get(word) {
+                return Some(alias.clone());
+            }
+            if let Some(handler) = self.suffix_alias(word) {
+                return Some(format!("{} {}", handler, word));
+            }
+        }
+        self.global_aliases.get(word).cloned()
+    }
+
+    // $NAME 和 ${NAME}，包括 shell 变量和环境变量
+    fn complete_variables(&self, word: &str) -> Vec<Pair> {
+        let (braced, prefix) = match word.strip_prefix('{') {
+            Some(prefix) => (true, prefix),
+            None => (false, word),
+        };
+        let mut names: Vec<String> = env::vars()
+            .map(|(name, _)| name)
+            .chain(self.variables.iter().cloned())
+            .filter(|name| self.is_match(name, prefix))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .map(|name| Pair {
+                replacement: if braced {
+                    format!("${{{}}}", name)
+                } else {
+                    format!("${}", name)
+                },
+                display: name,
+            })
+            .collect()
+    }
+
+    fn complete_files(&self, dir: &str, prefix: &str) -> Vec<Pair> {
+        let Some(expanded) = expand_prefix(dir) else {
+            return Vec::new();
+        };
+        let path = if expanded.is_empty() { "." } else { &expanded };
+        self.list_dir(Path::new(path), prefix, false)
+    }
+
+    // cd 只补全目录，相对路径同时在 cdpath（CDPATH 的值）的各个目录下查找
+    fn complete_dirs(&self, dir: &str, prefix: &str, cdpath: Option<&str>) -> Vec<Pair> {
+        let Some(expanded) = expand_prefix(dir) else {
+            return Vec::new();
+        };
+        let mut bases = vec![if expanded.is_empty() {
+            ".".to_string()
+        } else {
+            expanded.clone()
+        }];
+        let relative = !(expanded.starts_with('/')
+            || expanded.starts_with("./")
+            || expanded.starts_with("../"));
+        if relative && !dir.starts_with('~') && !dir.starts_with('$') {
+            if let Some(cdpath) = cdpath {
+                bases.extend(
+                    cdpath
+                        .split(':')
+                        .filter(|entry| !entry.is_empty())
+                        .map(|entry| {
+                            Path::new(entry)
+                                .join(&expanded)
+                                .to_string_lossy()
+                                .into_owned()
+                        }),
+                );
+            }
+        }
+        let mut candidates: Vec<Pair> = bases
+            .iter()
+            .flat_map(|base| self.list_dir(Path::new(base), prefix, true))
+            .collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates.dedup_by(|a, b| a.display == b.display);
+        candidates
+    }
+
+    // 列出目录下与 prefix 匹配的文件，目录加上 /
+    fn list_dir(&self, path: &Path, prefix: &str, dirs_only: bool) -> Vec<Pair> {
+        let Ok(entries) = fs::read_dir(path) else {
+            return Vec::new();
+        };
+        let mut candidates: Vec<Pair> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                if !self.is_match(&name, prefix)
+                    || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+                // 跟随符号链接判断是否为目录
+                let is_dir = path.join(&name).is_dir();
+                if dirs_only && !is_dir {
+                    return None;
+                }
+                let suffix = if is_dir { "/" } else { "" };
+                Some(Pair {
+                    display: format!("{}{}", name, suffix),
+                    replacement: format!("{}{}", escape(&name), suffix),
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates
+    }
+
+    fn complete_users(&self, prefix: &str) -> Vec<Pair> {
+        let mut candidates: Vec<Pair> = user_names()
+            .into_iter()
+            .filter(|name| self.is_match(name, prefix))
+            .map(|name| Pair {
+                display: format!("~{}", name),
+                replacement: format!("~{}/", name),
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates.dedup_by(|a, b| a.display == b.display);
+        candidates
+    }
+}
+
+// 补全菜单：候选项按列排列在输入行下方，Tab 和方向键依次选中的候选项直接替换到输入行中
+pub struct Menu {
+    // 补全开始时替换位置之前的内容和被替换的内容
+    before: String,
+    original: String,
+    candidates: Vec<Pair>,
+}
+
+impl Menu {
+    pub fn new(line: &str, start: usize, pos: usize, candidates: Vec<Pair>) -> Self {
+        Self {
+            before: line[..start].to_string(),
+            original: line[start..pos].to_string(),
+            candidates,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    // 输入行当前选中的候选项，Some(None) 表示还是原来的内容，输入行已经改变时返回 None
+    pub fn selected(&self, line: &str, pos: usize) -> Option<Option<usize>> {
+        let current = line.get(..pos)?.strip_prefix(self.before.as_str())?;
+        match self
+            .candidates
+            .iter()
+            .position(|c| c.replacement == current)
+        {
+            Some(index) => Some(Some(index)),
+            None => (current == self.original).then_some(None),
+        }
+    }
+
+    // 按终端宽度排成多列，超过 MENU_ROWS 行时只显示选中项附近的行
+    pub fn render(
+        &self,
+        selected: Option<usize>,
+        width: usize,
+        style: impl Fn(String, bool) -> String,
+    ) -> String {
+        let cell = self
+            .candidates
+            .iter()
+            .map(|c| c.display.width())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let columns = (width / cell).max(1);
+        let rows = self.candidates.len().div_ceil(columns);
+        let first = match selected {
+            Some(index) if index % rows >= MENU_ROWS => index % rows + 1 - MENU_ROWS,
+            _ => 0,
+        };
+        let mut menu = String::new();
+        for row in first..rows.min(first + MENU_ROWS) {
+            menu.push('\n');
+            for column in 0..columns {
+                let index = column * rows + row;
+                let Some(candidate) = self.candidates.get(index) else {
+                    break;
+                };
+                let padding = " ".repeat(cell - candidate.display.width());
+                let text = format!("{}{}", candidate.display, padding);
+                menu.push_str(&style(text, selected == Some(index)));
+            }
+        }
+        if rows > MENU_ROWS {
+            let position = selected.map_or(0, |index| index + 1);
+            menu.push_str(&format!("\n({}/{})", position, self.candidates.len()));
+        }
+        menu
+    }
+}
+
+// 光标所在单词的起始位置，反斜杠转义的空白不算分隔符
+fn word_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c.is_whitespace() || ";|&<>()".contains(c) {
+            start = i + c.len_utf8();
+        }
+    }
+    start
+}
+
+// 行首的命令名和它的起始位置；含有引号、$、~ 或通配符等要展开的字符，或者是重定向的 fd（2>err）时返回 None
+pub fn command_word(line: &str) -> Option<(usize, &str)> {
+    let start = line.len() - line.trim_start().len();
+    let rest = &line[start..];
+    let end = rest
+        .find(|c: char| c.is_whitespace() || ";|&<>()".contains(c))
+        .unwrap_or(rest.len());
+    let word = &rest[..end];
+    let plain = word
+        .chars()
+        .all(|c| c.is_alphanumeric() || "-_./+:@,".contains(c));
+    let redirect = rest[end..].starts_with(['<', '>']);
+    (!word.is_empty() && plain && !redirect).then_some((start, word))
+}
+
+// 光标所在的简单命令的命令名，光标在命令名上时返回 None
+fn command_name(before: &str) -> Option<&str> {
+    let simple = before
+        .rfind(|c| ";|&(".contains(c))
+        .map_or(before, |i| &before[i + 1..]);
+    simple.split_whitespace().next()
+}
+
+// 按匹配方式判断候选项是否与输入的内容匹配
+fn is_match(matching: CompletionMatch, candidate: &str, pattern: &str) -> bool {
+    // 忽略大小写，-/_ 不敏感时把 - 当作 _
+    let fold = |s: &str, separators: bool| -> String {
+        s.chars()
+            .map(|c| match c {
+                '-' if separators => '_',
+                _ => c.to_ascii_lowercase(),
+            })
+            .collect()
+    };
+    match matching {
+        CompletionMatch::Prefix => candidate.starts_with(pattern),
+        CompletionMatch::IgnoreCase => fold(candidate, false).starts_with(&fold(pattern, false)),
+        CompletionMatch::IgnoreSeparators => {
+            fold(candidate, true).starts_with(&fold(pattern, true))
+        }
+        CompletionMatch::Fuzzy => {
+            let mut chars = fold(candidate, false)
+                .chars()
+                .collect::<Vec<_>>()
+                .into_iter();
+            fold(pattern, false).chars().all(|p| chars.any(|c| c == p))
+        }
+    }
+}
+
+// 展开目录部分开头的 ~、~user、$VAR 和 ${VAR}，用户或变量不存在时返回 None
+fn expand_prefix(dir: &str) -> Option<String> {
+    if let Some(rest) = dir.strip_prefix('~') {
+        let end = rest.find('/').unwrap_or(rest.len());
+        let home = match &rest[..end] {
+            "" => env::var("HOME").ok()?,
+            user => home_dir(user)?,
+        };
+        return Some(format!("{}{}", home, unescape(&rest[end..])));
+    }
+    if let Some(rest) = dir.strip_prefix('$') {
+        let (name, rest) = match rest.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced.find('}')?;
+                (&braced[..end], &braced[end + 1..])
+            }
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        let value = env::var(name).ok()?;
+        return Some(format!("{}{}", value, unescape(rest)));
+    }
+    Some(unescape(dir))
+}
+
+// 从 passwd 数据库读取用户的主目录
+fn home_dir(user: &str) -> Option<String> {
+    let name = CString::new(user).ok()?;
+    unsafe {
+        let pw = libc::getpwnam(name.as_ptr());
+        if pw.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr((*pw).pw_dir).to_string_lossy().into_owned())
+    }
+}
+
+// 遍历 passwd 数据库中的用户名
+fn user_names() -> Vec<String> {
+    let mut names = Vec::new();
+    unsafe {
+        libc::setpwent();
+        loop {
+            let pw = libc::getpwent();
+            if pw.is_null() {
+                break;
+            }
+            names.push(CStr::from_ptr((*pw).pw_name).to_string_lossy().into_owned());
+        }
+        libc::endpwent();
+    }
+    names
+}
+
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if SPECIAL_CHARS.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(word: &str) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn completion() -> Completion {
+        let cache_dir = env::temp_dir().join(format!("zako_complete_cache_{}", std::process::id()));
+        let mut completion = Completion::new(CompletionMatch::Prefix, cache_dir);
+        completion.jobs = vec![(1, "sleep 100".to_string()), (12, "vim a.txt".to_string())];
+        completion.builtins = vec!["cd".to_string(), "jobs".to_string()];
+        completion.variables = vec!["ZAKO_COMPLETE_LOCAL".to_string()];
+        completion
+    }
+
+    fn replacements(line: &str) -> (usize, Vec<String>) {
+        let (start, pairs) = completion().complete(line, line.len());
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn test_expand_prefix() {
+        let home = env::var("HOME").unwrap();
+        assert_eq!(expand_prefix("~/"), Some(format!("{}/", home)));
+        assert_eq!(expand_prefix("$HOME/a/"), Some(format!("{}/a/", home)));
+        assert_eq!(expand_prefix("${HOME}/"), Some(format!("{}/", home)));
+        assert_eq!(expand_prefix("~root/"), home_dir("root").map(|d| d + "/"));
+        assert_eq!(expand_prefix("~no_such_user_zako/"), None);
+        assert_eq!(expand_prefix("$NO_SUCH_VAR_ZAKO/"), None);
+        assert_eq!(expand_prefix("my\\ dir/"), Some("my dir/".to_string()));
+    }
+
+    #[test]
+    fn test_complete_files() {
+        let dir = env::temp_dir().join(format!("zako_complete_{}", std::process::id()));
+        fs::create_dir_all(dir.join("project dir")).unwrap();
+        fs::write(dir.join("profile.txt"), "").unwrap();
+        fs::write(dir.join(".private"), "").unwrap();
+        env::set_var("ZAKO_COMPLETE_TEST", &dir);
+
+        let line = "ls $ZAKO_COMPLETE_TEST/pro";
+        let (start, candidates) = replacements(line);
+        assert_eq!(start, line.len() - 3);
+        assert_eq!(candidates, vec!["profile.txt", "project\\ dir/"]);
+        let (_, candidates) = replacements("ls ${ZAKO_COMPLETE_TEST}/.");
+        assert_eq!(candidates, vec![".private"]);
+        let (_, candidates) = replacements("ls $ZAKO_COMPLETE_TEST/project\\ d");
+        assert_eq!(candidates, vec!["project\\ dir/"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_complete_dirs() {
+        let dir = env::temp_dir().join(format!("zako_complete_cd_{}", std::process::id()));
+        fs::create_dir_all(dir.join("zako_src/shell")).unwrap();
+        fs::create_dir_all(dir.join("projects/zakosh")).unwrap();
+        fs::write(dir.join("profile.txt"), "").unwrap();
+        let base = dir.to_string_lossy().into_owned();
+        let completion = completion();
+
+        assert_eq!(
+            completion
+                .complete_dirs(&format!("{}/", base), "pro", None)
+                .len(),
+            1
+        );
+        assert_eq!(
+            completion.complete_dirs(&format!("{}/projects/", base), "", None)[0].replacement,
+            "zakosh/"
+        );
+        assert_eq!(
+            completion
+                .complete_files(&format!("{}/", base), "pro")
+                .len(),
+            2
+        );
+        let names: Vec<String> = completion
+            .complete_dirs("", "zako_s", Some(&base))
+            .into_iter()
+            .map(|p| p.replacement)
+            .collect();
+        assert!(names.contains(&"zako_src/".to_string()));
+        assert_eq!(
+            completion.complete_dirs("zako_src/", "", Some(&base))[0].replacement,
+            "shell/"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_complete_jobs_and_signals() {
+        assert_eq!(replacements("fg %"), (3, vec!["%1".into(), "%12".into()]));
+        assert_eq!(replacements("sleep 1; kill %12"), (14, vec!["%12".into()]));
+        assert_eq!(replacements("kill -TE"), (5, vec!["-TERM".into()]));
+        assert_eq!(
+            replacements("kill -sigus"),
+            (5, vec!["-USR1".into(), "-USR2".into()])
+        );
+        assert_eq!(replacements("echo %").1, Vec::<String>::new());
+        assert_eq!(command_name("sudo ls | kill "), Some("kill"));
+        assert_eq!(command_name("ls; "), None);
+    }
+
+    #[test]
+    fn test_complete_commands_and_variables() {
+        let (start, commands) = replacements("jo");
+        assert_eq!(start, 0);
+        assert!(commands.contains(&"jobs".to_string()));
+        assert!(replacements("ls | c").1.contains(&"cd".to_string()));
+        assert_eq!(
+            replacements("echo $ZAKO_COMPLETE_LO"),
+            (5, vec!["$ZAKO_COMPLETE_LOCAL".into()])
+        );
+        assert_eq!(
+            replacements("echo ${ZAKO_COMPLETE_LO"),
+            (5, vec!["${ZAKO_COMPLETE_LOCAL}".into()])
+        );
+
+        // 常用的命令排在前面
+        let mut completion = completion();
+        completion.builtins.push("cargo".to_string());
+        completion.command_counts.insert("cd".to_string(), 3);
+        let names: Vec<String> = completion
+            .complete_commands("c")
+            .into_iter()
+            .map(|pair| pair.display)
+            .collect();
+        assert_eq!(names[0], "cd");
+        assert!(names.iter().position(|name| name == "cargo") > Some(0));
+    }
+
+    #[test]
+    fn test_is_match() {
+        use CompletionMatch::*;
+        assert!(is_match(Prefix, "Makefile", "Make"));
+        assert!(!is_match(Prefix, "Makefile", "make"));
+        assert!(is_match(IgnoreCase, "Makefile", "make"));
+        assert!(!is_match(IgnoreCase, "git_flow", "git-f"));
+        assert!(is_match(IgnoreSeparators, "Git_Flow", "git-f"));
+        assert!(is_match(IgnoreSeparators, "git-flow", "GIT_"));
+        assert!(is_match(Fuzzy, "zakosh", "zsh"));
+        assert!(!is_match(Fuzzy, "zakosh", "hz"));
+    }
+
+    #[test]
+    fn test_menu() {
+        let candidates = ["alpha", "beta", "gamma", "delta", "epsilon"]
+            .iter()
+            .map(|name| Pair {
+                display: name.to_string(),
+                replacement: name.to_string(),
+            })
+            .collect();
+        let menu = Menu::new("ls a", 3, 4, candidates);
+        assert_eq!(menu.selected("ls a", 4), Some(None));
+        assert_eq!(menu.selected("ls gamma", 8), Some(Some(2)));
+        assert_eq!(menu.selected("ls gammax", 9), None);
+        assert_eq!(menu.selected("cat gamma", 9), None);
+
+        // 每列 9 个字符，宽 20 时排成两列三行，按列排列
+        let mark = |text: String, selected: bool| {
+            if selected {
+                format!("[{}]", text.trim_end())
+            } else {
+                text
+            }
+        };
+        assert_eq!(
+            menu.render(Some(1), 20, mark),
+            "\nalpha    delta    \n[beta]epsilon  \ngamma    "
+        );
+    }
+
+    #[test]
+    fn test_word_start() {
+        assert_eq!(word_start("ls ~ro"), 3);
+        assert_eq!(word_start("cat a\\ b"), 4);
+        assert_eq!(word_start("ls|wc"), 3);
+    }
+
+    #[test]
+    fn test_command_word() {
+        assert_eq!(command_word("  gti status"), Some((2, "gti")));
+        assert_eq!(command_word("./run.sh|wc"), Some((0, "./run.sh")));
+        assert_eq!(command_word("$EDITOR a"), None);
+        assert_eq!(command_word("'ls' a"), None);
+        assert_eq!(command_word("2>err make"), None);
+        assert_eq!(command_word("(cd a)"), None);
+        assert_eq!(command_word(""), None);
+
+        let mut completion = completion();
+        completion.aliases = HashMap::from([("ll".to_string(), "ls -l".to_string())]);
+        completion.suffix_aliases = HashMap::from([("md".to_string(), "glow".to_string())]);
+        let names = completion.command_names();
+        assert!(names.contains("ll") && names.contains("cd"));
+        assert!(completion.suffix_alias("docs/README.md").is_some());
+        assert!(completion.suffix_alias("docs/.md").is_none());
+        assert!(completion.suffix_alias("README.txt").is_none());
+    }
+
+    #[test]
+    fn test_alias_preview() {
+        let mut completion = completion();
+        completion.aliases = HashMap::from([("ll".to_string(), "ls -l".to_string())]);
+        completion.global_aliases = HashMap::from([("G".to_string(), "| grep".to_string())]);
+        completion.suffix_aliases = HashMap::from([("md".to_string(), "glow".to_string())]);
+        let preview = |line: &str, pos: usize| completion.alias_preview(line, pos);
+
+        assert_eq!(preview("ll", 2), Some("ls -l".to_string()));
+        assert_eq!(preview("  ll /tmp", 3), Some("ls -l".to_string()));
+        assert_eq!(preview("ll /tmp", 4), None);
+        assert_eq!(preview("echo ll", 7), None);
+        assert_eq!(preview("cd a; ll", 8), Some("ls -l".to_string()));
+        assert_eq!(preview("dmesg G usb", 7), Some("| grep".to_string()));
+        assert_eq!(preview("README.md", 0), Some("glow README.md".to_string()));
+        assert_eq!(preview("cat README.md", 13), None);
+        assert_eq!(preview("", 0), None);
+    }
+}
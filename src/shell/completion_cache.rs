@@ -0,0 +1,128 @@
+// 补全数据的磁盘缓存，保存在 cache_dir 下，新会话第一次按 Tab 时不用重新扫描
+// 文件格式：开头每行记录一个来源文件的修改时间（mtime 秒.纳秒 路径），空行之后每行一个条目
+// 来源列表或任意来源的修改时间变化时重新生成
+use log::{debug, warn};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub struct CompletionCache {
+    dir: PathBuf,
+}
+
+impl CompletionCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    // 读取名为 name 的缓存，失效时调用 build 重新生成并写回
+    pub fn get_or_update(
+        &self,
+        name: &str,
+        sources: &[PathBuf],
+        build: impl FnOnce() -> Vec<String>,
+    ) -> Vec<String> {
+        let path = self.dir.join(name);
+        let header: Vec<String> = sources.iter().map(|source| source_line(source)).collect();
+        if let Some(entries) = read_cache(&path, &header) {
+            return entries;
+        }
+        debug!("补全缓存失效，重新生成: {}", path.display());
+        let entries = build();
+        if let Err(err) = write_cache(&path, &header, &entries) {
+            warn!("无法写入补全缓存: {} {}", path.display(), err);
+        }
+        entries
+    }
+}
+
+// 来源文件不存在时记录为 0，之后创建时缓存同样会失效
+fn source_line(source: &Path) -> String {
+    let mtime = fs::metadata(source)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!(
+        "mtime {}.{:09} {}",
+        mtime.as_secs(),
+        mtime.subsec_nanos(),
+        source.display()
+    )
+}
+
+fn read_cache(path: &Path, header: &[String]) -> Option<Vec<String>> {
+    let content = fs::read_to_string(path).ok()?;
+    let (recorded, entries) = content.split_once("\n\n").unwrap_or((&content, ""));
+    if !recorded.lines().eq(header.iter().map(|line| line.as_str())) {
+        return None;
+    }
+    Some(entries.lines().map(|line| line.to_string()).collect())
+}
+
+// 先写到临时文件再改名，多个会话同时更新时不会读到写了一半的文件
+fn write_cache(path: &Path, header: &[String], entries: &[String]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let mut file = fs::File::create(&tmp)?;
+    for line in header {
+        writeln!(file, "{}", line)?;
+    }
+    writeln!(file)?;
+    for entry in entries {
+        writeln!(file, "{}", entry)?;
+    }
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::env;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_cache_invalidation() {
+        let dir = env::temp_dir().join(format!("zako_cache_{}", std::process::id()));
+        let source = dir.join("bin");
+        fs::create_dir_all(&source).unwrap();
+        let cache = CompletionCache::new(dir.join("cache"));
+        let builds = Cell::new(0);
+        let build = || {
+            builds.set(builds.get() + 1);
+            vec!["ls".to_string(), "cat".to_string()]
+        };
+
+        let sources = [source.clone()];
+        assert_eq!(
+            cache.get_or_update("commands", &sources, build),
+            ["ls", "cat"]
+        );
+        assert_eq!(
+            cache.get_or_update("commands", &sources, build),
+            ["ls", "cat"]
+        );
+        assert_eq!(builds.get(), 1);
+
+        // 来源目录的修改时间变化后重新生成
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::open(&source)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        cache.get_or_update("commands", &sources, build);
+        assert_eq!(builds.get(), 2);
+
+        // 来源列表变化后重新生成
+        cache.get_or_update("commands", &[source, dir.join("other")], build);
+        assert_eq!(builds.get(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
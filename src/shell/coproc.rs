@@ -0,0 +1,119 @@
+// coproc [NAME] { cmd ... } 或 coproc cmd ...：在后台启动命令，它的 stdin/stdout 分别接到 shell 持有的管道上
+// ${NAME[0]} 是读取命令输出的 fd，${NAME[1]} 是写入命令输入的 fd，NAME_PID 是它的 pid；没有名字时为 COPROC
+// 通过 /dev/fd 使用，例如 echo 1+2 > /dev/fd/${calc[1]}; head -n 1 < /dev/fd/${calc[0]}
+// coproc -c [NAME] 关闭写入端，命令读到 EOF 后结束
+use std::os::fd::{AsRawFd, OwnedFd};
+
+use super::for_loop;
+
+pub const DEFAULT_NAME: &str = "COPROC";
+
+pub const USAGE: &str = "用法: coproc [NAME] { cmd ... } | coproc cmd ... | coproc -c [NAME]";
+
+#[derive(Debug, PartialEq)]
+pub struct Spec {
+    pub name: String,
+    // 命令在参数中的范围
+    pub start: usize,
+    pub end: usize,
+}
+
+pub fn parse(args: &[String]) -> Result<Spec, String> {
+    let (name, start) = match args {
+        [name, brace, ..] if brace == "{" && for_loop::is_name(name) => (name.as_str(), 2),
+        [brace, ..] if brace == "{" => (DEFAULT_NAME, 1),
+        _ => (DEFAULT_NAME, 0),
+    };
+    // 结尾的 } 可以省略
+    let end = match args.last() {
+        Some(brace) if start > 0 && brace == "}" => args.len() - 1,
+        _ => args.len(),
+    };
+    if start >= end {
+        return Err(USAGE.to_string());
+    }
+    Ok(Spec {
+        name: name.to_string(),
+        start,
+        end,
+    })
+}
+
+// 正在运行的协同进程，shell 持有的两端都带有 FD_CLOEXEC，只在重定向打开 /dev/fd 时被子进程使用
+pub struct Coproc {
+    read: OwnedFd,
+    write: Option<OwnedFd>,
+}
+
+impl Coproc {
+    pub fn new(read: OwnedFd, write: OwnedFd) -> Self {
+        Self {
+            read,
+            write: Some(write),
+        }
+    }
+
+    // ${NAME[0]} 和 ${NAME[1]}，写入端关闭后为空
+    pub fn fd(&self, index: usize) -> Option<i32> {
+        match index {
+            0 => Some(self.read.as_raw_fd()),
+            1 => self.write.as_ref().map(|fd| fd.as_raw_fd()),
+            _ => None,
+        }
+    }
+
+    // $NAME 展开为两个 fd
+    pub fn fds(&self) -> String {
+        [self.fd(0), self.fd(1)]
+            .iter()
+            .flatten()
+            .map(|fd| fd.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn close_input(&mut self) -> bool {
+        self.write.take().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn spec(name: &str, start: usize, end: usize) -> Result<Spec, String> {
+        Ok(Spec {
+            name: name.to_string(),
+            start,
+            end,
+        })
+    }
+
+    #[test]
+    fn test_parse_coproc() {
+        assert_eq!(parse(&args("calc { bc -l }")), spec("calc", 2, 4));
+        assert_eq!(parse(&args("calc { bc -l")), spec("calc", 2, 4));
+        assert_eq!(parse(&args("{ cat }")), spec(DEFAULT_NAME, 1, 2));
+        assert_eq!(parse(&args("cat -u")), spec(DEFAULT_NAME, 0, 2));
+        assert_eq!(parse(&args("cat }")), spec(DEFAULT_NAME, 0, 2));
+        assert_eq!(parse(&args("calc { }")), Err(USAGE.to_string()));
+        assert_eq!(parse(&[]), Err(USAGE.to_string()));
+    }
+
+    #[test]
+    fn test_coproc_fds() -> std::io::Result<()> {
+        let (read, write) = nix::unistd::pipe()?;
+        let (read_fd, write_fd) = (read.as_raw_fd(), write.as_raw_fd());
+        let mut coproc = Coproc::new(read, write);
+        assert_eq!(coproc.fds(), format!("{} {}", read_fd, write_fd));
+        assert!(coproc.close_input());
+        assert!(!coproc.close_input());
+        assert_eq!(coproc.fd(1), None);
+        assert_eq!(coproc.fds(), read_fd.to_string());
+        Ok(())
+    }
+}
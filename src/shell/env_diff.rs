@@ -0,0 +1,98 @@
+// withenv --diff：命令收到的环境变量与父进程（启动 zako 的 bash 等）传给 zako 的环境变量之间的差异
+// zako 自己的修改都会出现在这里：.env、--import-env、SHLVL/ZAKO_PID、.zako.toml 的 env、venv 激活和 shell 变量
+use once_cell::sync::OnceCell;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+
+static INHERITED: OnceCell<BTreeMap<String, String>> = OnceCell::new();
+
+// 在加载配置之前调用，之后的修改都算作 zako 的修改
+pub fn save_inherited() {
+    let _ = INHERITED.set(current());
+}
+
+fn current() -> BTreeMap<String, String> {
+    env::vars_os()
+        .map(|(k, v)| {
+            (
+                k.to_string_lossy().into_owned(),
+                v.to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change<'a> {
+    Added(&'a str, &'a str),
+    Changed(&'a str, &'a str, &'a str),
+    Removed(&'a str, &'a str),
+}
+
+impl std::fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Change::Added(name, value) => write!(f, "+ {}={}", name, value),
+            Change::Changed(name, old, new) => write!(f, "~ {}: {} -> {}", name, old, new),
+            Change::Removed(name, old) => write!(f, "- {} (原来为 {})", name, old),
+        }
+    }
+}
+
+// 按变量名排序
+pub fn diff<'a>(
+    before: &'a BTreeMap<String, String>,
+    after: &'a HashMap<String, String>,
+) -> Vec<Change<'a>> {
+    let mut changes: Vec<Change> = before
+        .iter()
+        .filter_map(|(name, old)| match after.get(name) {
+            None => Some(Change::Removed(name, old)),
+            Some(new) if new != old => Some(Change::Changed(name, old, new)),
+            Some(_) => None,
+        })
+        .collect();
+    changes.extend(
+        after
+            .iter()
+            .filter(|(name, _)| !before.contains_key(*name))
+            .map(|(name, value)| Change::Added(name, value)),
+    );
+    changes.sort_by_key(|change| match change {
+        Change::Added(name, _) | Change::Changed(name, _, _) | Change::Removed(name, _) => *name,
+    });
+    changes
+}
+
+// 与 zako 启动时继承的环境变量比较
+pub fn describe(envs: &HashMap<String, String>) -> Vec<String> {
+    diff(INHERITED.get_or_init(current), envs)
+        .iter()
+        .map(|change| change.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_diff() {
+        let before: BTreeMap<String, String> = [("PATH", "/bin"), ("SHLVL", "1"), ("OLD", "x")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let after: HashMap<String, String> = [("PATH", "/bin"), ("SHLVL", "2"), ("NEW", "y")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let lines: Vec<String> = diff(&before, &after)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["+ NEW=y", "- OLD (原来为 x)", "~ SHLVL: 1 -> 2"]
+        );
+    }
+}
@@ -1,61 +1,678 @@
+use colored::{Color, Colorize};
 use log::{debug, error, trace};
+use nix::fcntl::OFlag;
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
 #[cfg(unix)]
 use std::process::{self, Command, Stdio};
-use std::{env, io};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use std::{env, fs, io};
 
+use super::aliases::{self, AliasKind, Aliases};
+use super::autoload::Autoload;
+use super::builtins::{BuiltinFlags, BuiltinRegistry, FnBuiltin, JobContext};
+use super::calc;
+use super::clipboard;
+use super::coproc::{self, Coproc};
+use super::env_diff;
+use super::for_loop;
+use super::hooks::{self, ExecPolicy, Verdict};
+use super::identity;
+use super::job_picker::{self, Action};
+use super::limits::ResourceLimits;
+use super::local_config;
+use super::options::ShellOptions;
+use super::path_cache::{self, PathCache};
+use super::progress::{self, ProgressInput};
+use super::readline::{ReadlineError, ReadlineManager};
+use super::redirect;
+use super::sandbox;
+use super::select;
+use super::session_log::{self, SessionLog};
+use super::stty;
+use super::temp_resources::{self, TempResources};
+use super::terminal::TerminalController;
+use super::theme_manager;
+use super::ticker::Ticker;
 use super::variable::Variable;
-use crate::shell::job_manager::JobManager;
-use crate::shell::parser::ast::{Command as ShellCommand, Node};
+use super::waitfor::{self, Outcome};
+use crate::shell::job_manager::{Job, JobManager, JobStatus};
+use crate::shell::parser::ast::{
+    Command as ShellCommand, Connector, Node, ProcessSubst, Redirection,
+};
+use crate::shell::parser::lexer::{RedirectOp, SubstKind};
+use crate::shell::parser::Parser;
 use crate::shell::shell::CommandResult;
-use crate::shell::signals;
-use crate::utils::path;
+use crate::shell::signals::{self, Wake};
+use crate::utils::config::{ClipboardMode, Config, SandboxProfile, DEFAULT_PROFILE};
+use crate::utils::theme::Theme;
+use crate::utils::{duration, path};
+use zakosh::i18n;
+
+// 彩色 stderr 使用的颜色（亮红色）
+const STDERR_COLOR: &[u8] = b"\x1b[91m";
+const COLOR_RESET: &[u8] = b"\x1b[0m";
+// captureoutput 最多保存的输出字节数，超出时保留最后的部分
+const LAST_OUTPUT_LIMIT: usize = 64 * 1024;
 
 pub struct Executor {
     variables: Variable,
     job_manager: JobManager,
+    stderr_color: bool,
+    // logoutput on FILE 开启的会话日志，子进程的输出同时写入它
+    session_log: Option<SessionLog>,
+    output_relays: Vec<JoinHandle<()>>,
+    // coproc 启动的协同进程，按名字保存 shell 持有的管道端
+    coprocs: HashMap<String, Coproc>,
+    options: ShellOptions,
+    // 内建命令自己运行了作业时（如 repeat），用它作为整条命令的结果
+    builtin_result: Option<CommandResult>,
+    // 只对下一个 fork 的子进程生效的设置
+    launch: LaunchOptions,
+    // 进程替换使用的命名管道等临时资源
+    temp_resources: TempResources,
+    // 受限模式（rzako）
+    restricted: bool,
+    // 只解析和展开，不执行（zako -n）
+    noexec: bool,
+    builtins: BuiltinRegistry,
+    // 上一条命令的退出码，$? 展开为它
+    last_status: i32,
+    // 上一条命令的耗时
+    last_duration: Duration,
+    // 上一条命令中管道每个阶段的退出码，$PIPESTATUS 展开为它
+    pipe_status: Vec<i32>,
+    // trap 设置的信号处理命令，空字符串表示忽略该信号
+    traps: HashMap<i32, String>,
+    // shell 自身的 pid，$$ 展开为它，管道中的子进程里同样不变
+    shell_pid: u32,
+    // exit 请求的退出码，设置后不再执行后续命令，由 Shell 完成退出流程
+    exit_request: Option<i32>,
+    // exec 对 shell 自身 fd 的重定向
+    shell_fds: redirect::ShellFds,
+    // 终端属于 shell 还是前台作业，交出和收回终端都通过它
+    terminal: TerminalController,
+    // theme 内建命令安装主题的目录
+    themes_dir: PathBuf,
+    // 当前使用的 profile 和 profile 配置所在的目录
+    profile: Option<String>,
+    profiles_dir: PathBuf,
+    // profile NAME 切换的 profile，保存历史后由 shell 重新启动
+    profile_switch: Option<String>,
+    // 当前目录的 .zako.toml 定义的别名，命令名匹配时替换为别名的内容
+    aliases: Aliases,
+    // trust 内建命令记录受信任的 .zako.toml 的文件
+    trust_file: PathBuf,
+    clipboard: ClipboardMode,
+    // set -o captureoutput 时当前前台作业的标准输出，作业结束后成为 last_output
+    capture: Option<Arc<Mutex<Vec<u8>>>>,
+    last_output: String,
+    // 正在执行的 execute_command 的层数，内建命令运行其他命令时增加，超过 max_nesting 时报错
+    nesting: usize,
+    max_nesting: usize,
+    // 作业选择器使用主题的菜单样式，参数过多的提示使用警告样式
+    theme: Theme,
+    // 交互式 shell（没有指定脚本）在参数过多或执行策略要求时等待确认，max_args 为参数数量的上限
+    interactive: bool,
+    // 登录 shell，$- 中的 l
+    login: bool,
+    max_args: usize,
+    // 启动外部命令之前检查的执行策略，例如 rm 安全网
+    policies: Vec<Box<dyn ExecPolicy>>,
+    // config.toml 中定义的沙箱配置
+    sandbox_profiles: Vec<SandboxProfile>,
+    // 交互式 shell 中前台作业运行超过这个时间后在终端标题上显示运行时间
+    ticker_after: Option<Duration>,
+    // fpath 中的自动加载函数
+    autoload: Autoload,
+    // 外部命令在 PATH 中的位置，hash 内建命令查看和清空
+    path_cache: PathCache,
+}
+
+#[derive(Default)]
+struct LaunchOptions {
+    // 子进程在 exec 前等待的时间（after 内建命令）
+    delay: Option<Duration>,
+    // 子进程在 exec 前设置的资源限制和优先级（limit 内建命令）
+    limits: Option<ResourceLimits>,
+    // 子进程在 exec 前进入的沙箱（sandbox 内建命令）
+    sandbox: Option<SandboxProfile>,
+    // 输出的每行加上前缀（par 内建命令）
+    output_prefix: Option<String>,
+    // 从文件读取输入时显示进度（progress 内建命令）
+    progress: bool,
+    // 只对这个命令设置的环境变量（withenv 内建命令）
+    env: Vec<(String, String)>,
+    // 管道中与相邻命令相连的管道端
+    stdin: Option<OwnedFd>,
+    stdout: Option<OwnedFd>,
+}
+
+impl LaunchOptions {
+    fn in_pipeline(&self) -> bool {
+        self.stdin.is_some() || self.stdout.is_some()
+    }
+}
+
+// Linux 上单个参数（包括结尾的 \0）的上限，为 32 个页
+const MAX_ARG_STRLEN: usize = 32 * 4096;
+
+// execve 复制到新进程栈上的大小：每个参数和环境变量字符串加上结尾的 \0，以及指针数组
+fn exec_size(program: &str, args: &[String], envs: &HashMap<String, String>) -> usize {
+    let strings: usize = std::iter::once(program.len())
+        .chain(args.iter().map(|arg| arg.len()))
+        .chain(envs.iter().map(|(k, v)| k.len() + v.len() + 1))
+        .map(|len| len + 1)
+        .sum();
+    let pointers = (args.len() + envs.len() + 3) * std::mem::size_of::<usize>();
+    strings + pointers
+}
+
+// which/whence 找到的一个匹配
+#[derive(Debug, PartialEq)]
+enum Resolution {
+    Alias(String),
+    Builtin,
+    // 自动加载函数和定义它的文件
+    Function(PathBuf),
+    File(PathBuf),
+}
+
+// 一个进程替换：命令通过 path 访问，替换进程在另一端读写
+struct Substitution {
+    kind: SubstKind,
+    command: String,
+    path: String,
+    // 使用 /dev/fd 时的管道两端，使用命名管道时都为 None
+    main_end: Option<OwnedFd>,
+    sub_end: Option<OwnedFd>,
+}
+
+// 把命令名替换为别名的内容，别名为空或无法解析时返回 None
+fn expand_alias_once(alias: &str, command: &ShellCommand) -> Option<ShellCommand> {
+    let words = shell_words::split(alias)
+        .ok()
+        .filter(|words| !words.is_empty())?;
+    let shift = words.len() - 1;
+    let mut words = words.into_iter();
+    let program = words.next().unwrap_or_default();
+    let arguments = words.chain(command.arguments.iter().cloned()).collect();
+    let substitutions = command
+        .substitutions
+        .iter()
+        .map(|s| ProcessSubst {
+            index: s.index + shift,
+            ..s.clone()
+        })
+        .collect();
+    Some(ShellCommand {
+        program,
+        arguments,
+        substitutions,
+        ..command.clone()
+    })
 }
 
 impl Executor {
-    pub fn new(job_manager: JobManager) -> Self {
+    pub fn new(config: &Config, job_manager: JobManager) -> Self {
         Self {
             variables: Variable::new(),
             job_manager,
+            stderr_color: config.stderr_color,
+            session_log: None,
+            output_relays: Vec::new(),
+            coprocs: HashMap::new(),
+            options: ShellOptions::new(),
+            builtin_result: None,
+            launch: LaunchOptions::default(),
+            temp_resources: TempResources::new(),
+            restricted: config.restricted,
+            noexec: config.noexec,
+            builtins: default_builtins(),
+            last_status: 0,
+            last_duration: Duration::ZERO,
+            pipe_status: vec![0],
+            traps: HashMap::new(),
+            shell_pid: process::id(),
+            exit_request: None,
+            shell_fds: redirect::ShellFds::new(),
+            terminal: TerminalController::new(),
+            themes_dir: config.themes_dir.clone(),
+            profile: config.profile.clone(),
+            profiles_dir: config.profiles_dir(),
+            profile_switch: None,
+            aliases: Aliases::default(),
+            trust_file: config.trust_file(),
+            clipboard: config.clipboard,
+            capture: None,
+            last_output: String::new(),
+            nesting: 0,
+            max_nesting: config.max_nesting,
+            theme: Theme::new(config),
+            interactive: config.script.is_none(),
+            login: config.login,
+            max_args: config.max_args,
+            policies: hooks::exec_policies(config),
+            sandbox_profiles: config.sandbox_profiles.clone(),
+            ticker_after: (config.script.is_none() && config.ticker_after > 0)
+                .then(|| Duration::from_secs(config.ticker_after)),
+            autoload: Autoload::new(&config.fpath),
+            path_cache: PathCache::new(config.cache_dir.join("path_usage")),
+        }
+    }
+
+    // 当前目录的 .zako.toml 中的别名
+    pub fn set_aliases(&mut self, aliases: HashMap<String, String>) {
+        self.aliases.set_local(aliases);
+    }
+
+    // 展开全局别名后解析一行命令
+    pub fn parse(&self, line: &str) -> Result<Node, String> {
+        Parser::new(&self.aliases.expand_global(line)).parse_command()
+    }
+
+    // 别名的第一个词作为命令名，其余的词插在原有参数之前
+    // 展开后的命令名也是别名时继续展开，已经展开过的别名不再展开，alias ls='ls --color' 和互相引用的别名都不会循环
+    // 命令名不是别名时先按后缀别名展开，README.md 成为 glow README.md
+    fn expand_alias(&self, mut command: ShellCommand) -> ShellCommand {
+        if self.aliases.get(&command.program).is_none() {
+            if let Some(handler) = self.aliases.suffix(&command.program) {
+                let alias = format!("{} {}", handler, shell_words::quote(&command.program));
+                match expand_alias_once(&alias, &command) {
+                    Some(expanded) => command = expanded,
+                    None => error!("无效的后缀别名 {}: {}", command.program, handler),
+                }
+            }
+        }
+        let mut expanded: Vec<String> = Vec::new();
+        while let Some(alias) = self.aliases.get(&command.program) {
+            if expanded.contains(&command.program) {
+                if expanded.len() > 1 {
+                    error!("别名循环: {} -> {}", expanded.join(" -> "), command.program);
+                }
+                break;
+            }
+            expanded.push(command.program.clone());
+            command = match expand_alias_once(alias, &command) {
+                Some(command) => command,
+                None => {
+                    error!("无效的别名 {}: {}", command.program, alias);
+                    break;
+                }
+            };
+        }
+        command
+    }
+
+    pub fn take_profile_switch(&mut self) -> Option<String> {
+        self.profile_switch.take()
+    }
+
+    // 用新的参数重新执行 zako 替换当前 shell，不会返回
+    pub fn exec_shell(&mut self, args: &[String]) -> ! {
+        let executable = env::current_exe().unwrap_or_default();
+        // 受限 shell 切换 profile 后仍然受限
+        let mut args = args.to_vec();
+        if self.restricted {
+            args.push("--restricted".to_string());
+        }
+        self.cleanup();
+        identity::before_exec();
+        signals::enable_signals();
+        signals::unblock_child_signals();
+        self.exec_program(&executable.to_string_lossy(), &args);
+    }
+
+    pub fn restricted(&self) -> bool {
+        self.restricted
+    }
+
+    pub fn errexit(&self) -> bool {
+        self.options.errexit
+    }
+
+    pub fn histverify(&self) -> bool {
+        self.options.histverify
+    }
+
+    // 作业编号和命令，供补全使用
+    pub fn jobs(&self) -> Vec<(usize, String)> {
+        self.job_manager
+            .get_jobs()
+            .iter()
+            .map(|job| (job.index, job.command.clone()))
+            .collect()
+    }
+
+    // 运行中和已停止的作业数量，供提示符使用
+    pub fn job_counts(&self) -> (usize, usize) {
+        let jobs = self.job_manager.get_jobs();
+        let stopped = jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Stopped)
+            .count();
+        let running = jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Continued)
+            .count();
+        (running, stopped)
+    }
+
+    pub fn builtin_names(&self) -> Vec<String> {
+        self.builtins
+            .iter()
+            .map(|builtin| builtin.name().to_string())
+            .collect()
+    }
+
+    pub fn function_names(&self) -> Vec<String> {
+        self.autoload.names()
+    }
+
+    pub fn alias_entries(&self, kind: AliasKind) -> HashMap<String, String> {
+        self.aliases.entries(kind)
+    }
+
+    pub fn command_counts(&self) -> HashMap<String, u64> {
+        self.path_cache.usage_counts()
+    }
+
+    pub fn variable_names(&self) -> Vec<String> {
+        self.variables.get_all().keys().cloned().collect()
+    }
+
+    pub fn variables(&self) -> &HashMap<String, String> {
+        self.variables.get_all()
+    }
+
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    pub fn last_duration(&self) -> Duration {
+        self.last_duration
+    }
+
+    // 读取输入前换回终端，exec 重定向的输出不影响 readline
+    pub fn use_terminal(&self) {
+        self.shell_fds.use_terminal();
+    }
+
+    pub fn use_redirections(&self) {
+        self.shell_fds.use_redirections();
+    }
+
+    // exit 请求的退出码，Shell 在每条命令之后检查
+    pub fn exit_requested(&self) -> Option<i32> {
+        self.exit_request
+    }
+
+    // 退出时运行 EXIT trap，只运行一次，$? 为退出码；trap 中的 exit 改变退出码
+    pub fn run_exit_trap(&mut self, status: i32) -> i32 {
+        self.exit_request = None;
+        self.last_status = status;
+        self.run_trap(signals::EXIT);
+        self.traps.remove(&signals::EXIT);
+        self.exit_request.take().unwrap_or(status)
+    }
+
+    // 开启 huponexit 时 shell 退出前向所有作业发送 SIGHUP，暂停的作业再发送 SIGCONT 才能处理 SIGHUP
+    pub fn hangup_jobs(&self) {
+        if !self.options.huponexit {
+            return;
         }
+        for job in self.job_manager.get_jobs() {
+            debug!("向作业 %{} 发送 SIGHUP: {}", job.index, job.command);
+            unsafe { libc::kill(-job.gid, libc::SIGHUP) };
+            if job.status == JobStatus::Stopped {
+                unsafe { libc::kill(-job.gid, libc::SIGCONT) };
+            }
+        }
+    }
+
+    // shell 退出前清理临时资源，回收所有已经结束的子进程
+    pub fn cleanup(&mut self) {
+        self.temp_resources.release_all();
+        self.path_cache.save_usage();
+        self.job_manager.reap_orphans();
     }
 
+    // 执行命令并记录退出码（$?），内建命令出错时退出码为 1
     pub fn execute(&mut self, node: Node) -> io::Result<CommandResult> {
+        let result = self.execute_node(node);
+        self.record_result(&result);
+        // 前台命令被 Ctrl-C 终止时，shell 自己收不到 SIGINT，由这里触发 INT trap
+        if matches!(&result, Ok(result) if result.signal == Some(libc::SIGINT)) {
+            self.run_trap(libc::SIGINT);
+        }
+        result
+    }
+
+    // 更新 $?、$PIPESTATUS 和上一条命令的耗时
+    fn record_result(&mut self, result: &io::Result<CommandResult>) {
+        self.last_status = match &result {
+            Ok(result) => result.status,
+            Err(_) => 1,
+        };
+        self.last_duration = match &result {
+            Ok(result) => result.duration,
+            Err(_) => Duration::ZERO,
+        };
+        // 内建命令等没有子进程的命令只有一个阶段
+        self.pipe_status = match &result {
+            Ok(result) if !result.statuses.is_empty() => result.statuses.clone(),
+            _ => vec![self.last_status],
+        };
+    }
+
+    // 运行所有已到达信号的 trap 命令
+    pub fn run_pending_traps(&mut self) {
+        for sig in signals::take_pending_traps() {
+            self.run_trap(sig);
+        }
+    }
+
+    // 运行信号对应的 trap 命令，不改变 $?
+    pub fn run_trap(&mut self, sig: i32) {
+        let action = match self.traps.get(&sig) {
+            Some(action) if !action.is_empty() => action.clone(),
+            _ => return,
+        };
+        debug!("运行 {} 的 trap: {}", signals::trap_name(sig), action);
+        let status = self.last_status;
+        let result = self
+            .parse(&action)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+            .and_then(|node| self.execute_node(node));
+        if let Err(e) = result {
+            eprintln!("trap: {}", e);
+        }
+        self.last_status = status;
+    }
+
+    fn execute_node(&mut self, node: Node) -> io::Result<CommandResult> {
+        if self.noexec {
+            trace!("noexec: {}", self.explain_node(&node));
+            return Ok(CommandResult::new());
+        }
+        // exit 之后同一行、函数和循环中剩下的命令不再执行
+        if let Some(status) = self.exit_request {
+            return Ok(CommandResult::from_status(status));
+        }
+        match node {
+            Node::Pipeline(pipeline) => self.run_job(|executor, pgid, fg_pids| {
+                executor.execute_pipeline(pipeline, pgid, fg_pids)
+            }),
+            Node::Command(command) => self.run_job(|executor, pgid, fg_pids| {
+                executor.execute_command(command, pgid, fg_pids)
+            }),
+            Node::AndOr(first, rest) => self.execute_and_or(*first, rest),
+            Node::List(nodes) => self.execute_list(nodes),
+        }
+    }
+
+    // 依次执行，Ctrl-C、Ctrl-Z 和 exit 中止后面的命令；设置了 errexit 时遇到失败立即停止
+    fn execute_list(&mut self, nodes: Vec<Node>) -> io::Result<CommandResult> {
+        let mut result = CommandResult::new();
+        for node in nodes {
+            result = self.execute_condition(node)?;
+            let failed = result.status != 0 && !result.condition;
+            if result.stopped
+                || result.signal == Some(libc::SIGINT)
+                || self.exit_request.is_some()
+                || (self.options.errexit && failed)
+            {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    // 每个命令结束后更新 $?，后面的命令可以使用；出错的命令（如找不到命令）相当于以 1 退出
+    // Ctrl-C 或 Ctrl-Z 中止整个列表；除了最后一个，列表中失败的命令不会让 set -e 退出
+    fn execute_and_or(
+        &mut self,
+        first: Node,
+        rest: Vec<(Connector, Node)>,
+    ) -> io::Result<CommandResult> {
+        let count = rest.len();
+        let mut result = self.execute_condition(first)?;
+        result.condition = true;
+        for (i, (connector, node)) in rest.into_iter().enumerate() {
+            if result.stopped || result.signal == Some(libc::SIGINT) || self.exit_request.is_some()
+            {
+                break;
+            }
+            let run = match connector {
+                Connector::And => result.status == 0,
+                Connector::Or => result.status != 0,
+            };
+            if run {
+                result = self.execute_condition(node)?;
+                result.condition = i + 1 < count;
+            }
+        }
+        Ok(result)
+    }
+
+    fn execute_condition(&mut self, node: Node) -> io::Result<CommandResult> {
+        let result = self.execute_node(node).or_else(|e| {
+            eprintln!("{}", e);
+            Ok(CommandResult::from_status(1))
+        });
+        self.record_result(&result);
+        result
+    }
+
+    // 启动一个前台作业并等待其结束，之后收回终端控制权
+    fn run_job<F>(&mut self, launch: F) -> io::Result<CommandResult>
+    where
+        F: FnOnce(&mut Self, &mut i32, &mut Vec<i32>) -> io::Result<()>,
+    {
+        let started = Instant::now();
         let mut pgid: i32 = 0;
         let mut fg_pids: Vec<i32> = Vec::new();
-        let _ = match node {
-            Node::Pipeline(pipeline) => self.execute_pipeline(pipeline, &mut pgid, &mut fg_pids),
-            Node::Command(command) => self.execute_command(command, &mut pgid, &mut fg_pids),
+        launch(self, &mut pgid, &mut fg_pids)?;
+
+        // 等待 fg 命令执行完毕，没有子进程时使用内建命令的结果
+        let mut cmd_result = if fg_pids.is_empty() {
+            self.builtin_result.take().unwrap_or_default()
+        } else {
+            self.wait_foreground(pgid, &fg_pids)
         };
+        // 前台作业结束后清理它的命名管道，后台和被暂停的作业还需要使用
+        if !fg_pids.is_empty() && !cmd_result.stopped {
+            self.temp_resources.release(pgid);
+        }
+        // 被暂停的作业保留计时器，继续运行后仍会超时
+        if !cmd_result.stopped && self.job_manager.finish_timeout(pgid) {
+            // 与 coreutils timeout 一致
+            cmd_result.status = 124;
+            cmd_result.timed_out = true;
+        }
 
-        // 等待 fg 命令执行完毕
-        let cmd_result = self.job_manager.wait_fg_job(pgid, &fg_pids);
+        // 等待输出转发完毕，避免和提示符交错输出
+        // 作业被暂停时管道仍然被子进程持有，只能让转发线程自行结束
+        let relays = std::mem::take(&mut self.output_relays);
+        if !cmd_result.stopped {
+            for relay in relays {
+                let _ = relay.join();
+            }
+        }
+        // 只有运行了外部命令的作业才更新，echo $ZAKO_LAST_OUTPUT 之类的内建命令不会覆盖它
+        if let Some(capture) = self.capture.take() {
+            if let Ok(output) = capture.lock() {
+                self.last_output = String::from_utf8_lossy(&output).into_owned();
+            }
+        }
 
         // 恢复终端控制
-        unsafe {
-            let shell_gid = libc::getpgid(0);
-            signals::give_terminal_to(shell_gid);
-        }
+        self.terminal.reclaim();
 
+        cmd_result.duration = started.elapsed();
         Ok(cmd_result)
     }
 
+    // 等待前台作业结束或被暂停，等待期间由计时线程显示运行时间
+    fn wait_foreground(&mut self, pgid: i32, pids: &[i32]) -> CommandResult {
+        let _ticker = self.ticker_after.map(|after| {
+            let jobs = self.job_manager.get_jobs();
+            let label = jobs.iter().find(|job| job.gid == pgid);
+            Ticker::start(after, label.map_or("", |job| job.command.as_str()))
+        });
+        self.job_manager.wait_fg_job(pgid, pids)
+    }
+
     fn execute_pipeline(
         &mut self,
         pipeline: Vec<ShellCommand>,
         pgid: &mut i32,
         fg_pids: &mut Vec<i32>,
     ) -> io::Result<()> {
-        // 暂时只处理单个命令，后续可以扩展管道功能
-        if let Some(command) = pipeline.first() {
-            self.execute_command(command.clone(), pgid, fg_pids)
-        } else {
-            Ok(())
+        // 整条管道是一个作业，最后一个命令带 & 时整条管道在后台运行
+        let background = pipeline.last().is_some_and(|command| command.background);
+        let label = pipeline
+            .iter()
+            .map(|command| {
+                shell_words::join(std::iter::once(&command.program).chain(&command.arguments))
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let count = pipeline.len();
+        let mut stdin = None;
+        for (i, mut command) in pipeline.into_iter().enumerate() {
+            command.background = background;
+            // progress 在管道中不能作为内建命令运行，在这里去掉前缀，只作用于第一个命令
+            if i == 0 && command.program == "progress" && self.builtins.contains("progress") {
+                command = command.inner(0, usize::MAX).ok_or_else(progress_usage)?;
+                self.launch.progress = true;
+            }
+            self.launch.stdin = stdin.take();
+            if i + 1 < count {
+                let (read_end, write_end) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+                self.launch.stdout = Some(write_end);
+                stdin = Some(read_end);
+            }
+            if let Err(e) = self.execute_command(command, pgid, fg_pids) {
+                // 已经启动的阶段仍然需要等待，出错的阶段相当于立即失败
+                self.launch = LaunchOptions::default();
+                if *pgid == 0 {
+                    return Err(e);
+                }
+                eprintln!("{}", e);
+            }
+        }
+        if *pgid != 0 {
+            self.job_manager.rename_job(*pgid, label);
         }
+        Ok(())
     }
 
     fn execute_command(
@@ -64,28 +681,182 @@ impl Executor {
         pgid: &mut i32,
         fg_pids: &mut Vec<i32>,
     ) -> io::Result<()> {
-        // // 处理内建命令
-        // if let Some(result) = self.handle_builtin(&command) {
-        //     debug!("执行内建命令: {:?}", command);
-        //     return result;
-        // }
+        // 例如 alias r='repeat 2 r'，每一层都会再次展开别名，没有上限时会耗尽栈
+        if self.nesting >= self.max_nesting {
+            return Err(io::Error::other(i18n::tf(
+                "exec.too_deep",
+                &[&command.program, &self.max_nesting.to_string()],
+            )));
+        }
+        self.nesting += 1;
+        let result = self.launch_command(command, pgid, fg_pids);
+        self.nesting -= 1;
+        result
+    }
+
+    fn launch_command(
+        &mut self,
+        command: ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let mut command = self.expand_alias(command);
+        self.check_restricted(&command)?;
+
+        // 处理内建命令，管道中的内建命令在子进程中运行，输出才能进入管道
+        // 替代外部命令的内建命令只在前台单独运行时使用，nesting 大于 1 说明由前缀命令启动
+        let builtin = self.builtins.get(&command.program).filter(|builtin| {
+            let standalone = !self.launch.in_pipeline() && !command.background && self.nesting <= 1;
+            standalone || !builtin.flags().replaces_external
+        });
+        let pipeline_builtin = match builtin {
+            Some(builtin) if !self.launch.in_pipeline() => {
+                debug!("执行内建命令: {:?}", command);
+                return builtin.run(self, &command, &mut JobContext { pgid, fg_pids });
+            }
+            // 需要启动子进程的内建命令交给同名的外部命令
+            Some(builtin) if !builtin.flags().needs_fork => Some(builtin),
+            _ => None,
+        };
+        // 同名时别名和内建命令优先于函数，函数优先于 PATH 中的命令
+        if pipeline_builtin.is_none() && self.autoload.path(&command.program).is_some() {
+            return self.call_function(&command);
+        }
 
         // 执行外部命令
         debug!("执行外部命令: {:?}", command);
-        let program = command.program;
-        let original_args = command.arguments.clone();
-        let args: Vec<String> = command
+        let mut program = command.program;
+        let mut original_args = command.arguments.clone();
+        let mut exec_path = None;
+        let mut args: Vec<String> = command
             .arguments
             .iter()
             .map(|arg| self.expand_variables(arg))
             .collect();
+        if pipeline_builtin.is_none() {
+            // 改写后的命令同样作为作业的名字
+            if self.apply_policies(&mut program, &mut args)? {
+                original_args = args.clone();
+            }
+            self.check_arg_list(&program, &args)?;
+            // 在 shell 进程中通过 PATH 缓存查找，子进程直接执行找到的路径
+            if !program.contains('/') {
+                exec_path = self.path_cache.lookup(&program);
+            }
+        }
+        let substitutions = self.prepare_substitutions(&command.substitutions, &mut args)?;
+
+        let mut launch = std::mem::take(&mut self.launch);
+        // 管道中的内建命令不会 exec，会一直持有转发管道的写端
+        let progress_input = if launch.progress && !command.background && pipeline_builtin.is_none()
+        {
+            self.prepare_progress(&mut command.redirections, &mut launch)?
+        } else {
+            None
+        };
+
+        // 彩色 stderr：子进程的 stderr 接到管道上，由转发线程加上颜色后输出
+        // par 的每个命令 stdout/stderr 都经过管道，按行加上前缀
+        // captureoutput：输出到终端的前台命令的 stdout 经过管道，转发的同时保存下来
+        let capture = (self.options.captureoutput
+            && !command.background
+            && launch.stdout.is_none()
+            && launch.output_prefix.is_none()
+            && !command
+                .redirections
+                .iter()
+                .any(Redirection::redirects_stdout))
+        .then(|| self.capture.get_or_insert_with(Default::default).clone());
+        // logoutput：没有经过管道的 stdout/stderr 同样由转发线程写出，同时写入会话日志
+        let log = self.session_log.clone();
+        let mut stdout_pipe = if launch.output_prefix.is_some() || capture.is_some() {
+            Some(nix::unistd::pipe2(OFlag::O_CLOEXEC)?)
+        } else if log.is_some() && launch.stdout.is_none() {
+            Some(session_log::relay_pipe(libc::STDOUT_FILENO)?)
+        } else {
+            None
+        };
+        let mut stderr_pipe = if self.stderr_color || launch.output_prefix.is_some() {
+            Some(nix::unistd::pipe2(OFlag::O_CLOEXEC)?)
+        } else if log.is_some() {
+            Some(session_log::relay_pipe(libc::STDERR_FILENO)?)
+        } else {
+            None
+        };
+        // 加入已有的进程组（同一个作业的多个进程），或者自己成为组长
+        let job_pgid = *pgid;
 
         match unsafe { nix::unistd::fork() } {
             Ok(nix::unistd::ForkResult::Parent { child }) => {
                 // 父进程
+                let mut relays = Vec::new();
+                if let Some((read_end, write_end)) = stdout_pipe.take() {
+                    drop(write_end);
+                    relays.push(relay_output(
+                        read_end,
+                        libc::STDOUT_FILENO,
+                        launch.output_prefix.clone(),
+                        None,
+                        capture,
+                        log.clone(),
+                    ));
+                }
+                if let Some((read_end, write_end)) = stderr_pipe.take() {
+                    drop(write_end);
+                    let color = self.stderr_color.then_some(STDERR_COLOR);
+                    relays.push(relay_output(
+                        read_end,
+                        libc::STDERR_FILENO,
+                        launch.output_prefix.clone(),
+                        color,
+                        None,
+                        log,
+                    ));
+                }
+                // 后台作业的转发线程随作业结束自行退出
+                if !command.background {
+                    self.output_relays.extend(relays);
+                }
+
                 let child_pid: i32 = child.into();
-                *pgid = child_pid;
-                fg_pids.push(child_pid.clone());
+                let is_leader = job_pgid == 0;
+                if is_leader {
+                    *pgid = child_pid;
+                }
+                // 父子进程都设置进程组，避免给终端或发信号时子进程还没来得及设置
+                unsafe {
+                    libc::setpgid(child_pid, *pgid);
+                }
+                // 替换进程在命令启动后再运行，加入同一个进程组
+                self.spawn_substitutions(substitutions, *pgid);
+                if let Some(input) = progress_input {
+                    // 转发进程不能持有管道的其他端，否则命令退出后写入不会失败
+                    drop(launch.stdin.take());
+                    drop(launch.stdout.take());
+                    self.spawn_progress(input, *pgid);
+                }
+                let job_command = program.clone()
+                    + " "
+                    + original_args
+                        .iter()
+                        .map(|s| s.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                        .as_str();
+
+                // 后台作业不等待，也不交出终端，管道只在启动组长时记录一次作业
+                if command.background {
+                    if is_leader {
+                        let index = self.job_manager.add_bg_job(*pgid, child_pid, job_command);
+                        println!("[{}] {}", index, child_pid);
+                    }
+                    return Ok(());
+                }
+                fg_pids.push(child_pid);
+                // 作业只记录组长进程，组员进程只需要等待
+                if !is_leader {
+                    return Ok(());
+                }
 
                 unsafe {
                     // we need to wait pgid of child set to itself,
@@ -104,225 +875,2046 @@ impl Executor {
                     }
                 }
 
-                signals::give_terminal_to(child_pid);
+                self.terminal.give_to_job(child_pid);
 
-                self.job_manager.add_job(
-                    child_pid,
-                    child_pid,
-                    program.clone()
-                        + " "
-                        + original_args
-                            .iter()
-                            .map(|s| s.as_str())
-                            .collect::<Vec<_>>()
-                            .join(" ")
-                            .as_str(),
-                );
+                self.job_manager.add_job(child_pid, child_pid, job_command);
             }
             Ok(nix::unistd::ForkResult::Child) => {
                 // 子进程
                 // 恢复子 shell 的 block 信号处理
                 signals::enable_signals();
 
-                // 设置子进程的进程组
+                if let Some((_, write_end)) = &stdout_pipe {
+                    let _ = nix::unistd::dup2(write_end.as_raw_fd(), libc::STDOUT_FILENO);
+                }
+                if let Some((_, write_end)) = &stderr_pipe {
+                    let _ = nix::unistd::dup2(write_end.as_raw_fd(), libc::STDERR_FILENO);
+                }
+                if let Some(fd) = &launch.stdin {
+                    let _ = nix::unistd::dup2(fd.as_raw_fd(), libc::STDIN_FILENO);
+                }
+                if let Some(fd) = &launch.stdout {
+                    let _ = nix::unistd::dup2(fd.as_raw_fd(), libc::STDOUT_FILENO);
+                }
+
+                // 设置子进程的进程组，job_pgid 为 0 时自己成为组长
                 let pid = unsafe {
                     let pid = libc::getpid();
-                    libc::setpgid(0, pid);
+                    libc::setpgid(0, job_pgid);
                     pid
                 };
 
-                // 执行内建命令
-                // if cmd.is_builtin() {
-                //     trace!("运行内建命令[{}]: {} {:?}", pid, program, args);
-                //     if let Some(status) = try_run_builtin_in_subprocess(sh, cl, idx_cmd, capture) {
-                //         process::exit(status);
-                //     }
-                // }
-
-                // 执行外部命令
-                let program_path = path::find_file_in_path(program.as_str(), true);
-                trace!("运行外部命令[{}]: {} {:?}", pid, program_path, args);
-                let c_program = CString::new(program_path).expect("CString::new failed");
-                let mut c_args = vec![c_program.clone()]; // 添加程序名作为第一个参数
-                c_args.extend(
-                    args.iter()
-                        .map(|s| CString::new(s.as_str()).expect("CString::new failed")),
-                );
-                let c_envs = self
-                    .variables
-                    .get_all()
-                    .iter()
-                    .map(|(k, v)| {
-                        CString::new(format!("{}={}", k, v)).expect("CString::new failed")
-                    })
-                    .collect::<Vec<_>>();
+                // after 调度的作业先在自己的进程里等待，再执行命令
+                if let Some(delay) = launch.delay {
+                    thread::sleep(delay);
+                }
 
-                match nix::unistd::execve(&c_program, &c_args, &c_envs) {
-                    Ok(_) => {}
-                    Err(e) => match e {
-                        nix::Error::ENOEXEC => {
-                            error!("zako: {}: exec format error (ENOEXEC)", program);
-                        }
-                        nix::Error::ENOENT => {
-                            error!("zako: {}: file does not exist", program);
-                        }
-                        nix::Error::EACCES => {
-                            error!("zako: {}: Permission denied", program);
-                        }
-                        _ => {
-                            error!("zako: {}: {:?}", program, e);
+                // 进程替换的管道需要保留到 exec 之后
+                for substitution in &substitutions {
+                    if let Some(fd) = &substitution.main_end {
+                        let _ = nix::fcntl::fcntl(
+                            fd.as_raw_fd(),
+                            nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::empty()),
+                        );
+                    }
+                }
+
+                // 应用重定向，失败时不执行命令
+                for redirection in &command.redirections {
+                    let target = self.redirection_target(redirection);
+                    if let Err(e) =
+                        redirect::apply_redirection(redirection, &target, self.options.devtcp)
+                    {
+                        eprintln!("zako: {}: {}", target, e);
+                        process::exit(1);
+                    }
+                }
+
+                // 执行内建命令
+                if let Some(builtin) = pipeline_builtin {
+                    trace!("运行内建命令[{}]: {} {:?}", pid, program, args);
+                    let (mut pgid, mut fg_pids) = (0, Vec::new());
+                    let mut job = JobContext {
+                        pgid: &mut pgid,
+                        fg_pids: &mut fg_pids,
+                    };
+                    let command = ShellCommand {
+                        program: program.clone(),
+                        arguments: original_args,
+                        ..command
+                    };
+                    let status = match builtin.run(self, &command, &mut job) {
+                        Ok(()) => self
+                            .exit_request
+                            .or(self.builtin_result.take().map(|result| result.status))
+                            .unwrap_or(0),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            1
                         }
-                    },
+                    };
+                    let _ = io::stdout().flush();
+                    process::exit(status);
                 }
 
-                process::exit(1);
+                if let Some(profile) = &launch.sandbox {
+                    if let Err(e) = sandbox::enter(profile) {
+                        eprintln!("zako: sandbox: {}", e);
+                        process::exit(1);
+                    }
+                }
+
+                // 资源限制在 exec 之前最后设置，命令已经在 shell 进程中查找过，nofile 等限制不会影响查找
+                if let Some(limits) = &launch.limits {
+                    if let Err(e) = limits.apply() {
+                        eprintln!("zako: limit: {}", e);
+                        process::exit(1);
+                    }
+                }
+
+                // 子进程中的 shell 变量只用于生成命令的环境变量，覆盖同名的变量
+                for (name, value) in &launch.env {
+                    self.variables.set(name.clone(), value.clone());
+                }
+
+                trace!("运行外部命令[{}]: {} {:?}", pid, program, args);
+                self.exec_program(exec_path.as_deref().unwrap_or(&program), &args);
             }
             Err(e) => {
                 error!("Fork failed: {}", e);
-                return Err(io::Error::new(io::ErrorKind::Other, "Fork failed"));
+                return Err(io::Error::other("Fork failed"));
             }
         }
 
         Ok(())
     }
 
-    fn expand_variables(&self, input: &str) -> String {
-        let mut result = String::new();
-        let mut chars = input.chars().peekable();
-
-        while let Some(c) = chars.next() {
-            if c == '$' && chars.peek().is_some() {
-                let mut var_name = String::new();
-                while let Some(&next_char) = chars.peek() {
-                    if next_char.is_alphanumeric() || next_char == '_' {
-                        var_name.push(chars.next().unwrap());
-                    } else {
-                        break;
-                    }
-                }
-                if !var_name.is_empty() {
-                    result.push_str(&self.variables.get(var_name));
-                }
-            } else {
-                result.push(c);
-            }
-        }
-        result
+    // 子进程继承 shell 的环境变量（包括 --import-env 导入的），shell 变量覆盖同名的环境变量
+    fn child_env(&self) -> HashMap<String, String> {
+        let mut envs: HashMap<String, String> = env::vars().collect();
+        envs.extend(self.variables.get_all().clone());
+        envs
     }
 
-    // 处理内建命令
-    fn handle_builtin(&mut self, command: &ShellCommand) -> Option<io::Result<()>> {
-        match command.program.as_str() {
-            "zako" => Some(self.builtin_shell()),
-            "cd" => Some(self.builtin_cd(command)),
-            "exit" => Some(self.builtin_exit()),
-            "set" => Some(self.builtin_set(command)),
-            "jobs" => Some(self.builtin_jobs()),
-            "fg" => Some(self.builtin_fg(command)),
-            "bg" => Some(self.builtin_bg(command)),
-            _ => None,
+    // 参数超过 ARG_MAX 时 execve 一定会失败（E2BIG），在 fork 之前报错
+    // 参数数量超过 max_args 时多半是展开出了意料之外的内容，交互模式下要求确认，脚本中报错
+    fn check_arg_list(&self, program: &str, args: &[String]) -> io::Result<()> {
+        let too_long =
+            |message: String| io::Error::new(io::ErrorKind::ArgumentListTooLong, message);
+        let limited = cfg!(target_os = "linux");
+        if let Some(arg) = args
+            .iter()
+            .find(|arg| limited && arg.len() >= MAX_ARG_STRLEN)
+        {
+            return Err(too_long(i18n::tf(
+                "exec.arg_too_long",
+                &[
+                    program,
+                    &arg.len().to_string(),
+                    &(MAX_ARG_STRLEN - 1).to_string(),
+                ],
+            )));
         }
+        let size = exec_size(program, args, &self.child_env());
+        let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+        if arg_max > 0 && size > arg_max as usize {
+            return Err(too_long(i18n::tf(
+                "exec.arg_list_too_long",
+                &[program, &size.to_string(), &arg_max.to_string()],
+            )));
+        }
+        if args.len() <= self.max_args {
+            return Ok(());
+        }
+        let warning = i18n::tf(
+            "exec.max_args",
+            &[program, &args.len().to_string(), &self.max_args.to_string()],
+        );
+        self.confirm(program, warning, io::ErrorKind::ArgumentListTooLong)
     }
 
-    fn builtin_shell(&self) -> io::Result<()> {
-        let executable = env::current_exe().unwrap_or_default();
-        let _ = Command::new(executable)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()?
-            .wait();
-        Ok(())
-    }
-
-    fn builtin_cd(&mut self, command: &ShellCommand) -> io::Result<()> {
-        let path = command.arguments.first().map(|s| s.as_str()).unwrap_or("~");
-        let path = shellexpand::tilde(path);
-        std::env::set_current_dir(path.as_ref())
+    // 交互模式下显示警告并等待确认，回答 y 以外的内容时取消；脚本中直接以 refused 类型的错误拒绝
+    fn confirm(&self, program: &str, warning: String, refused: io::ErrorKind) -> io::Result<()> {
+        if !self.interactive || !io::stdin().is_terminal() {
+            return Err(io::Error::new(refused, warning));
+        }
+        let warning = (self.theme.warning_style)(warning);
+        eprint!("{}", i18n::tf("confirm.prompt", &[&warning]));
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if matches!(answer.trim(), "y" | "Y" | "yes") {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                i18n::tf("confirm.cancelled", &[program]),
+            ))
+        }
     }
 
-    fn builtin_exit(&self) -> io::Result<()> {
-        std::process::exit(0);
+    // 依次经过执行策略，命令被改写时返回 true
+    fn apply_policies(&mut self, program: &mut String, args: &mut Vec<String>) -> io::Result<bool> {
+        let mut rewritten = false;
+        for i in 0..self.policies.len() {
+            match self.policies[i].check(program, args) {
+                Verdict::Allow => {}
+                Verdict::Confirm(warning) => {
+                    self.confirm(program, warning, io::ErrorKind::PermissionDenied)?
+                }
+                Verdict::Deny(reason) => {
+                    return Err(io::Error::new(io::ErrorKind::PermissionDenied, reason))
+                }
+                Verdict::Rewrite(new_program, new_args) => {
+                    debug!("执行策略改写命令: {} {:?}", new_program, new_args);
+                    *program = new_program;
+                    *args = new_args;
+                    rewritten = true;
+                }
+            }
+        }
+        Ok(rewritten)
     }
 
-    fn builtin_set(&mut self, command: &ShellCommand) -> io::Result<()> {
-        if command.arguments.len() != 2 {
+    // 在当前 shell 中逐条执行自动加载函数的函数体，参数在执行期间作为 $1 $2 ...、$argv 和 $ARGC，
+    // 结束后恢复调用之前的值，嵌套调用的函数看不到外层多出来的参数
+    fn call_function(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let name = &command.program;
+        if self.launch.in_pipeline() || command.background || !command.redirections.is_empty() {
             return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "set 命令需要两个参数: 变量名和值",
+                io::ErrorKind::Unsupported,
+                i18n::tf("exec.function_alone", &[name]),
             ));
         }
+        let body = match self.autoload.load(name) {
+            Some(body) => body?,
+            None => return Ok(()),
+        };
+        let args: Vec<String> = command
+            .arguments
+            .iter()
+            .map(|arg| self.expand_variables(arg))
+            .collect();
+
+        let outer_count = self
+            .variables
+            .get_all()
+            .get("ARGC")
+            .and_then(|count| count.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut positional: Vec<(String, Option<String>)> = vec![
+            ("0".to_string(), Some(name.clone())),
+            ("argv".to_string(), Some(args.join(" "))),
+            ("ARGC".to_string(), Some(args.len().to_string())),
+        ];
+        for i in 0..args.len().max(outer_count) {
+            positional.push(((i + 1).to_string(), args.get(i).cloned()));
+        }
+        let saved: Vec<(String, Option<String>)> = positional
+            .into_iter()
+            .map(|(key, value)| {
+                let old = self.variables.remove(&key);
+                if let Some(value) = value {
+                    self.variables.set(key.clone(), value);
+                }
+                (key, old)
+            })
+            .collect();
+
+        trace!("调用函数 {} {:?}", name, args);
+        let mut result = CommandResult::new();
+        for node in body.iter() {
+            result = match self.execute_node(node.clone()) {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("{}: {}", name, e);
+                    CommandResult::from_status(1)
+                }
+            };
+            self.last_status = result.status;
+            let interrupted = result.status == 128 + libc::SIGINT;
+            let failed = result.status != 0 && !result.condition;
+            if result.stopped || interrupted || (self.options.errexit && failed) {
+                break;
+            }
+        }
+
+        for (key, old) in saved {
+            match old {
+                Some(old) => self.variables.set(key, old),
+                None => {
+                    self.variables.remove(&key);
+                }
+            }
+        }
+        self.builtin_result = Some(result);
+        Ok(())
+    }
+
+    // 在子进程中执行外部命令，不会返回
+    fn exec_program(&self, program: &str, args: &[String]) -> ! {
+        // 执行外部命令，带 / 的直接按路径执行
+        let program_path = if program.contains('/') {
+            program.to_string()
+        } else {
+            path::find_file_in_path(program, true)
+        };
+        let c_program = CString::new(program_path).unwrap_or_default();
+        let mut c_args = vec![c_program.clone()]; // 添加程序名作为第一个参数
+        c_args.extend(
+            args.iter()
+                .map(|s| CString::new(s.as_str()).unwrap_or_default()),
+        );
+        let c_envs = self
+            .child_env()
+            .iter()
+            .map(|(k, v)| CString::new(format!("{}={}", k, v)).unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        // 退出码与 bash 保持一致：找不到命令 127，无法执行 126
+        let code = match nix::unistd::execve(&c_program, &c_args, &c_envs) {
+            Ok(_) => 0,
+            Err(e) => match e {
+                nix::Error::ENOEXEC => {
+                    error!("zako: {}: exec format error (ENOEXEC)", program);
+                    126
+                }
+                nix::Error::ENOENT => {
+                    error!("zako: {}: command not found", program);
+                    127
+                }
+                nix::Error::EACCES => {
+                    error!("zako: {}: Permission denied", program);
+                    126
+                }
+                _ => {
+                    error!("zako: {}: {:?}", program, e);
+                    1
+                }
+            },
+        };
+
+        process::exit(code);
+    }
+
+    // 为进程替换准备管道或命名管道，并把对应的参数替换为路径
+    fn prepare_substitutions(
+        &mut self,
+        substs: &[ProcessSubst],
+        args: &mut [String],
+    ) -> io::Result<Vec<Substitution>> {
+        let mut substitutions = Vec::new();
+        for subst in substs {
+            let (path, main_end, sub_end) = if temp_resources::has_dev_fd() {
+                let (read_end, write_end) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+                let (main_end, sub_end) = match subst.kind {
+                    SubstKind::Input => (read_end, write_end),
+                    SubstKind::Output => (write_end, read_end),
+                };
+                let path = format!("/dev/fd/{}", main_end.as_raw_fd());
+                (path, Some(main_end), Some(sub_end))
+            } else {
+                let path = self.temp_resources.create_fifo(subst.kind)?;
+                (path.to_string_lossy().to_string(), None, None)
+            };
+            if let Some(arg) = args.get_mut(subst.index) {
+                *arg = path.clone();
+            }
+            substitutions.push(Substitution {
+                kind: subst.kind,
+                command: subst.command.clone(),
+                path,
+                main_end,
+                sub_end,
+            });
+        }
+        Ok(substitutions)
+    }
+
+    // 启动进程替换的命令，不等待它们结束（与 bash 一致）
+    fn spawn_substitutions(&mut self, substitutions: Vec<Substitution>, pgid: i32) {
+        self.temp_resources.assign(pgid);
+        for substitution in substitutions {
+            match unsafe { nix::unistd::fork() } {
+                Ok(nix::unistd::ForkResult::Parent { child }) => unsafe {
+                    libc::setpgid(child.into(), pgid);
+                },
+                Ok(nix::unistd::ForkResult::Child) => {
+                    signals::enable_signals();
+                    unsafe {
+                        libc::setpgid(0, pgid);
+                    }
+                    self.run_substitution(substitution);
+                }
+                Err(e) => error!("Fork failed: {}", e),
+            }
+        }
+    }
+
+    // 在替换进程中接好标准输入输出并运行命令，不会返回
+    fn run_substitution(&mut self, substitution: Substitution) -> ! {
+        let (fd, flags) = match substitution.kind {
+            SubstKind::Input => (libc::STDOUT_FILENO, OFlag::O_WRONLY),
+            SubstKind::Output => (libc::STDIN_FILENO, OFlag::O_RDONLY),
+        };
+        // 命名管道在命令打开另一端之前会阻塞
+        let end = match &substitution.sub_end {
+            Some(end) => Ok(end.as_raw_fd()),
+            None => nix::fcntl::open(
+                substitution.path.as_str(),
+                flags | OFlag::O_CLOEXEC,
+                nix::sys::stat::Mode::empty(),
+            ),
+        };
+        match end {
+            Ok(end) => {
+                let _ = nix::unistd::dup2(end, fd);
+            }
+            Err(e) => {
+                eprintln!("zako: {}: {}", substitution.path, e);
+                process::exit(1);
+            }
+        }
+
+        let command = match Parser::new(&substitution.command).parse_command() {
+            Ok(node) => node
+                .commands()
+                .first()
+                .map(|c| (*c).clone())
+                .unwrap_or_default(),
+            Err(e) => {
+                eprintln!("zako: {}", e);
+                process::exit(2);
+            }
+        };
+        if let Err(e) = self.check_restricted(&command) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        // 需要启动子进程的内建命令不在替换进程中运行，交给同名的外部命令
+        if let Some(builtin) = self
+            .builtins
+            .get(&command.program)
+            .filter(|b| !b.flags().needs_fork)
+        {
+            let (mut pgid, mut fg_pids) = (0, Vec::new());
+            let mut job = JobContext {
+                pgid: &mut pgid,
+                fg_pids: &mut fg_pids,
+            };
+            let result = builtin.run(self, &command, &mut job);
+            let _ = io::stdout().flush();
+            process::exit(if result.is_ok() { 0 } else { 1 });
+        }
+        let args: Vec<String> = command
+            .arguments
+            .iter()
+            .map(|arg| self.expand_variables(arg))
+            .collect();
+        self.exec_program(&command.program, &args);
+    }
+
+    // 解析一行命令并显示 AST 以及展开后的参数和重定向，不执行
+    pub fn explain(&self, line: &str) -> Result<String, String> {
+        let node = self.parse(line)?;
+        Ok(self.explain_node(&node))
+    }
+
+    fn explain_node(&self, node: &Node) -> String {
+        let commands = node.commands();
+        let mut lines = vec![format!("AST: {:#?}", node)];
+        for (i, command) in commands.iter().enumerate() {
+            if commands.len() > 1 {
+                lines.push(i18n::tf("explain.command", &[&(i + 1).to_string()]));
+            }
+            let argv = std::iter::once(&command.program).chain(&command.arguments);
+            for (j, arg) in argv.enumerate() {
+                let expanded = self.expand_variables(arg);
+                let substituted = command
+                    .substitutions
+                    .iter()
+                    .any(|s| j > 0 && s.index == j - 1);
+                let note = if substituted {
+                    i18n::t("explain.substitution")
+                } else {
+                    String::new()
+                };
+                lines.push(format!("  argv[{}] = {:?}{}", j, expanded, note));
+            }
+            for redirection in &command.redirections {
+                let fd = redirection.fd.map(|fd| fd.to_string()).unwrap_or_default();
+                let target = format!("{:?}", self.redirection_target(redirection));
+                lines.push(i18n::tf(
+                    "explain.redirect",
+                    &[&fd, redirection.operator.symbol(), &target],
+                ));
+            }
+            if command.background {
+                lines.push(i18n::t("explain.background"));
+            }
+        }
+        lines.join("\n")
+    }
+
+    // explain 通常由 shell 直接处理整行输入以保留引号，这里处理 repeat 等前缀命令调用的情况
+    fn builtin_explain(&self, command: &ShellCommand) -> io::Result<()> {
+        let explanation = self
+            .explain(&shell_words::join(&command.arguments))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("explain: {}", e)))?;
+        println!("{}", explanation);
+        Ok(())
+    }
+
+    // 受限模式下禁止 cd、带 / 的命令名、输出重定向、开启网络重定向、安装主题以及信任本地配置
+    fn check_restricted(&self, command: &ShellCommand) -> io::Result<()> {
+        if !self.restricted {
+            return Ok(());
+        }
+        let reason = if command.program.contains('/') {
+            i18n::tf("restricted.slash", &[&command.program])
+        } else if command.program == "cd" {
+            i18n::t("restricted.cd")
+        } else if command.program == "set" && command.arguments.iter().any(|a| a == "devtcp") {
+            i18n::t("restricted.devtcp")
+        } else if command.program == "theme"
+            && command.arguments.first().is_some_and(|a| a != "list")
+        {
+            i18n::t("restricted.theme")
+        } else if command.program == "trust" && command.arguments != ["-l"] {
+            i18n::t("restricted.trust")
+        } else if command.program == "hash" && command.arguments.iter().any(|a| a == "-p") {
+            i18n::t("restricted.hash")
+        } else if let Some(name) = restricted_assignment(command) {
+            i18n::tf("restricted.readonly", &[&command.program, name])
+        } else if command.redirections.iter().any(Redirection::writes_file) {
+            i18n::tf("restricted.redirect", &[&command.program])
+        } else {
+            return Ok(());
+        };
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("zako: restricted: {}", reason),
+        ))
+    }
+
+    // 重定向的目标：文件名展开变量和 ~；here document 的内容只在分隔符不带引号时展开变量
+    fn redirection_target(&self, redirection: &Redirection) -> String {
+        match redirection.operator {
+            RedirectOp::HereDoc { expand: false } => redirection.filename.clone(),
+            RedirectOp::HereDoc { expand: true } => self.expand_variables(&redirection.filename),
+            _ => shellexpand::tilde(&self.expand_variables(&redirection.filename)).into_owned(),
+        }
+    }
+
+    pub fn expand_variables(&self, input: &str) -> String {
+        let mut result = String::new();
+        let mut chars = input.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c == '$' && chars.peek() == Some(&'?') {
+                chars.next();
+                result.push_str(&self.last_status.to_string());
+            } else if c == '$' && chars.peek() == Some(&'$') {
+                chars.next();
+                result.push_str(&self.shell_pid.to_string());
+            } else if c == '$' && chars.peek() == Some(&'-') {
+                chars.next();
+                result.push_str(&self.option_flags());
+            } else if c == '$' && chars.peek() == Some(&'{') {
+                // ${NAME} 和 ${NAME[N]}，数组下标从 0 开始
+                chars.next();
+                let inner: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (name, index) = match inner.strip_suffix(']').and_then(|s| s.split_once('[')) {
+                    Some((name, index)) => (name, index.parse::<usize>().ok()),
+                    None => (inner.as_str(), None),
+                };
+                result.push_str(&self.lookup_variable(name, index));
+            } else if c == '$' && chars.peek().is_some() {
+                let mut var_name = String::new();
+                while let Some(&next_char) = chars.peek() {
+                    if next_char.is_alphanumeric() || next_char == '_' {
+                        var_name.push(next_char);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if !var_name.is_empty() {
+                    result.push_str(&self.lookup_variable(&var_name, None));
+                }
+            } else {
+                result.push(c);
+            }
+        }
+        result
+    }
+
+    // $-：set 开启的短选项，i 交互式，l 登录 shell，n 只检查语法（-n），r 受限模式
+    // rc 文件中用 case $- in *i*) ... 区分交互式 shell 和脚本
+    fn option_flags(&self) -> String {
+        let mut flags = self.options.short_flags();
+        for (flag, on) in [
+            ('i', self.interactive),
+            ('l', self.login),
+            ('n', self.noexec),
+            ('r', self.restricted),
+        ] {
+            if on {
+                flags.push(flag);
+            }
+        }
+        flags
+    }
+
+    // 查找变量，PIPESTATUS 是数组，不带下标时展开为以空格分隔的所有元素
+    // ZAKO_LAST_OUTPUT 是上一条命令的输出，与命令替换一样去掉末尾的换行
+    // ZAKO_PID 是执行展开的进程的 pid，SHLVL 是启动时导出的层级，见 identity
+    fn lookup_variable(&self, name: &str, index: Option<usize>) -> String {
+        match name {
+            "ZAKO_PID" => return process::id().to_string(),
+            "SHLVL" => return env::var(name).unwrap_or_default(),
+            _ => {}
+        }
+        if name == "ZAKO_LAST_OUTPUT" {
+            return self.last_output.trim_end_matches('\n').to_string();
+        }
+        if name == "PIPESTATUS" {
+            return match index {
+                Some(index) => self
+                    .pipe_status
+                    .get(index)
+                    .map(|status| status.to_string())
+                    .unwrap_or_default(),
+                None => self
+                    .pipe_status
+                    .iter()
+                    .map(|status| status.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+        }
+        if let Some(coproc) = self.coprocs.get(name) {
+            return match index {
+                Some(index) => coproc.fd(index).map(|fd| fd.to_string()),
+                None => Some(coproc.fds()),
+            }
+            .unwrap_or_default();
+        }
+        match index {
+            Some(0) | None => self.variables.get(name.to_string()),
+            Some(_) => String::new(),
+        }
+    }
+
+    // after DURATION cmd ... 延迟执行；after -l 列出；after -r ID 取消
+    // 不在 shell 的线程里等待：主循环阻塞在 readline 上，线程到时间后也要等用户回车才能通过执行器运行命令，
+    // 所以作业进程立即启动，在 exec 前自己等待；内建命令要在 shell 进程中运行，因此不支持
+    fn builtin_after(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let usage = || io::Error::new(io::ErrorKind::InvalidInput, i18n::t("after.usage"));
+        let mut args = command.arguments.iter();
+        let first = args.next().ok_or_else(usage)?;
+        match first.as_str() {
+            "-l" => {
+                for scheduled in self.job_manager.get_scheduled() {
+                    println!("{}", scheduled);
+                }
+                Ok(())
+            }
+            "-r" => {
+                let id = args
+                    .next()
+                    .and_then(|id| id.trim_start_matches('a').parse::<usize>().ok())
+                    .ok_or_else(usage)?;
+                let scheduled = self.job_manager.cancel_scheduled(id).map_err(|e| {
+                    io::Error::new(io::ErrorKind::NotFound, format!("after: {}", e))
+                })?;
+                println!("{}", i18n::tf("after.cancelled", &[&scheduled.to_string()]));
+                Ok(())
+            }
+            delay => {
+                let delay = duration::parse_duration(delay)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                let due = SystemTime::now().checked_add(delay).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, i18n::t("after.out_of_range"))
+                })?;
+                let mut inner = command.inner(1, usize::MAX).ok_or_else(usage)?;
+                inner.background = true;
+                if self.runs_builtin(&inner.program) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        i18n::tf("exec.builtin_unsupported", &["after", &inner.program]),
+                    ));
+                }
+                let line = shell_words::join(
+                    std::iter::once(&inner.program).chain(inner.arguments.iter()),
+                );
+
+                // 作业进程立即启动并在 exec 前等待，这样即使 shell 正阻塞在输入或前台作业上也能准时运行
+                self.launch.delay = Some(delay);
+                let result = self.execute_command(inner, pgid, fg_pids);
+                // 提前失败时延迟没有被子进程用掉，不能留给下一个命令
+                self.launch.delay = None;
+                result?;
+                let scheduled = self.job_manager.schedule(*pgid, due, line);
+                println!("{}", scheduled);
+                Ok(())
+            }
+        }
+    }
+
+    // limit [cpu=DURATION] [mem=SIZE] [nice=N] ... cmd ...
+    fn builtin_limit(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let usage = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["limit [cpu=DURATION] [mem|fsize|core|stack=SIZE] [nofile|nproc=N] [nice=N] cmd ..."]),
+            )
+        };
+        let (limits, used) = ResourceLimits::parse(&command.arguments)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("limit: {}", e)))?;
+        if used == 0 {
+            return Err(usage());
+        }
+        let inner = command.inner(used, usize::MAX).ok_or_else(usage)?;
+        if self.runs_builtin(&inner.program) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("exec.builtin_unsupported", &["limit", &inner.program]),
+            ));
+        }
+        self.launch.limits = Some(limits);
+        let result = self.execute_command(inner, pgid, fg_pids);
+        // 别名展开为内建命令时限制没有被子进程用掉，不能留给下一个命令
+        self.launch.limits = None;
+        result
+    }
+
+    // withenv [--diff] [NAME=VALUE ...] cmd ...，只为这个命令设置环境变量
+    // --diff 在运行之前列出命令的环境变量与启动 zako 的父进程之间的差异，排查命令在 zako 和 bash 中表现不同的原因
+    fn builtin_withenv(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let usage = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["withenv [--diff] [NAME=VALUE ...] cmd ..."]),
+            )
+        };
+        let mut show_diff = false;
+        let mut env = Vec::new();
+        let mut skip = 0;
+        for arg in &command.arguments {
+            if arg == "--diff" {
+                show_diff = true;
+            } else if let Some((name, value)) = arg.split_once('=').filter(|(name, _)| {
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }) {
+                env.push((name.to_string(), self.expand_variables(value)));
+            } else {
+                break;
+            }
+            skip += 1;
+        }
+        let inner = command.inner(skip, usize::MAX).ok_or_else(usage)?;
+        if self.runs_builtin(&inner.program) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("exec.builtin_unsupported", &["withenv", &inner.program]),
+            ));
+        }
+        if show_diff {
+            let mut envs = self.child_env();
+            envs.extend(env.iter().cloned());
+            let changes = env_diff::describe(&envs);
+            if changes.is_empty() {
+                eprintln!("{}", i18n::t("withenv.same_env"));
+            }
+            for change in changes {
+                eprintln!("{}", change);
+            }
+        }
+        self.launch.env = env;
+        let result = self.execute_command(inner, pgid, fg_pids);
+        self.launch.env.clear();
+        result
+    }
+
+    // progress cmd ... < FILE，显示命令读取文件的进度；管道中只能用于第一个命令
+    fn builtin_progress(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let inner = command.inner(0, usize::MAX).ok_or_else(progress_usage)?;
+        if self.runs_builtin(&inner.program) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("exec.builtin_unsupported", &["progress", &inner.program]),
+            ));
+        }
+        self.launch.progress = true;
+        let result = self.execute_command(inner, pgid, fg_pids);
+        self.launch.progress = false;
+        result
+    }
+
+    // 命令的最后一个 < 重定向是普通文件且 stderr 是终端时，改为从转发进程的管道读取
+    fn prepare_progress(
+        &self,
+        redirections: &mut Vec<Redirection>,
+        launch: &mut LaunchOptions,
+    ) -> io::Result<Option<ProgressInput>> {
+        if launch.stdin.is_some() || !io::stderr().is_terminal() {
+            return Ok(None);
+        }
+        let Some(index) = redirections
+            .iter()
+            .rposition(|r| r.operator == RedirectOp::Input && r.fds() == [libc::STDIN_FILENO])
+        else {
+            return Ok(None);
+        };
+        let target = self.expand_variables(&redirections[index].filename);
+        let target = shellexpand::tilde(&target).into_owned();
+        let file = File::open(&target)
+            .map_err(|e| io::Error::new(e.kind(), format!("zako: {}: {}", target, e)))?;
+        if !file.metadata()?.is_file() {
+            return Ok(None);
+        }
+        let (read_end, input) = progress::prepare(file)?;
+        redirections.remove(index);
+        launch.stdin = Some(read_end);
+        Ok(Some(input))
+    }
+
+    // 与进程替换一样加入作业的进程组，Ctrl-C 和 Ctrl-Z 同样作用于它
+    fn spawn_progress(&mut self, input: ProgressInput, pgid: i32) {
+        match unsafe { nix::unistd::fork() } {
+            Ok(nix::unistd::ForkResult::Parent { child }) => unsafe {
+                libc::setpgid(child.into(), pgid);
+            },
+            Ok(nix::unistd::ForkResult::Child) => {
+                signals::enable_signals();
+                unsafe {
+                    libc::setpgid(0, pgid);
+                }
+                input.run();
+            }
+            Err(e) => error!("Fork failed: {}", e),
+        }
+    }
+
+    // sandbox [-p NAME] cmd ...，没有指定时使用 default：断开网络，家目录只读；sandbox -l 列出配置
+    fn builtin_sandbox(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let usage = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["sandbox [-p NAME] cmd ... | sandbox -l"]),
+            )
+        };
+        let (name, skip) = match command.arguments.first().map(|s| s.as_str()) {
+            None => return Err(usage()),
+            Some("-l") => {
+                if !self.sandbox_profiles.iter().any(|p| p.name == "default") {
+                    println!("{}", sandbox::describe(&SandboxProfile::new("default")));
+                }
+                for profile in &self.sandbox_profiles {
+                    println!("{}", sandbox::describe(profile));
+                }
+                return Ok(());
+            }
+            Some("-p") => (command.arguments.get(1).ok_or_else(usage)?.as_str(), 2),
+            Some(_) => ("default", 0),
+        };
+        let profile = match self.sandbox_profiles.iter().find(|p| p.name == name) {
+            Some(profile) => profile.clone(),
+            None if name == "default" => SandboxProfile::new(name),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    i18n::tf("sandbox.not_found", &[name, name]),
+                ))
+            }
+        };
+        if !sandbox::SUPPORTED {
+            return Err(sandbox::unsupported());
+        }
+        let inner = command.inner(skip, usize::MAX).ok_or_else(usage)?;
+        if self.runs_builtin(&inner.program) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("exec.builtin_unsupported", &["sandbox", &inner.program]),
+            ));
+        }
+        self.launch.sandbox = Some(profile);
+        let result = self.execute_command(inner, pgid, fg_pids);
+        self.launch.sandbox = None;
+        result
+    }
+
+    // par [-j N] cmd1 ... ::: cmd2 ... ::: cmd3 ...
+    // 并行运行多个命令并等待全部结束，输出按行加上 [n] 前缀
+    // 同一批命令属于同一个进程组，Ctrl-C/Ctrl-Z 对整批生效；退出码为失败的命令数
+    fn builtin_par(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let usage = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["par [-j N] cmd1 ... ::: cmd2 ... ::: ..."]),
+            )
+        };
+        let mut args = command.arguments.as_slice();
+        let mut jobs = usize::MAX;
+        let mut start = 0;
+        if args.first().map(|s| s.as_str()) == Some("-j") {
+            jobs = args
+                .get(1)
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .ok_or_else(usage)?;
+            args = &args[2..];
+            start = 2;
+        }
+
+        let mut commands = Vec::new();
+        for group in args.split(|arg| arg == ":::") {
+            if group.is_empty() {
+                return Err(usage());
+            }
+            let inner = command.inner(start, group.len() - 1).ok_or_else(usage)?;
+            start += group.len() + 1;
+            if self.runs_builtin(&inner.program) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("exec.builtin_unsupported", &["par", &inner.program]),
+                ));
+            }
+            commands.push(inner);
+        }
+        if commands.is_empty() {
+            return Err(usage());
+        }
+
+        let colors = [
+            Color::Cyan,
+            Color::Green,
+            Color::Yellow,
+            Color::Magenta,
+            Color::Blue,
+        ];
+        let mut failed = 0;
+        let mut stopped = false;
+        let total = commands.len();
+        for (batch_index, batch) in commands.chunks(jobs).enumerate() {
+            let offset = batch_index * jobs;
+            let label = format!("par {}", shell_words::join(&command.arguments));
+            let result = self.run_job(|executor, pgid, fg_pids| {
+                for (i, cmd) in batch.iter().enumerate() {
+                    let index = offset + i + 1;
+                    let prefix = format!("[{}] ", index)
+                        .color(colors[(index - 1) % colors.len()])
+                        .to_string();
+                    executor.launch.output_prefix = Some(prefix);
+                    executor.execute_command(cmd.clone(), pgid, fg_pids)?;
+                }
+                executor.job_manager.rename_job(*pgid, label);
+                Ok(())
+            })?;
+
+            for (i, status) in result.statuses.iter().enumerate() {
+                if *status != 0 {
+                    failed += 1;
+                    let cmd = &batch[i];
+                    let line =
+                        shell_words::join(std::iter::once(&cmd.program).chain(&cmd.arguments));
+                    eprintln!(
+                        "{}",
+                        i18n::tf(
+                            "par.failed",
+                            &[&(offset + i + 1).to_string(), &status.to_string(), &line],
+                        )
+                    );
+                }
+            }
+            if result.stopped || result.status == 128 + libc::SIGINT {
+                stopped = result.stopped;
+                if offset + batch.len() < total {
+                    let rest = (total - offset - batch.len()).to_string();
+                    eprintln!("{}", i18n::tf("par.aborted", &[&rest]));
+                }
+                break;
+            }
+        }
+
+        let mut result = CommandResult::new();
+        // 与 GNU parallel 一致，退出码为失败的命令数
+        result.status = failed.min(101);
+        result.stopped = stopped;
+        self.builtin_result = Some(result);
+        Ok(())
+    }
+
+    // repeat N cmd ...，依次运行 N 次；设置了 errexit 时遇到失败立即停止
+    fn builtin_repeat(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let usage = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["repeat N cmd ..."]),
+            )
+        };
+        let mut args = command.arguments.iter();
+        let count = args
+            .next()
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(usage)?;
+        let inner = command.inner(1, usize::MAX).ok_or_else(usage)?;
+
+        let mut result = CommandResult::new();
+        for i in 0..count {
+            trace!("repeat 第 {}/{} 次: {:?}", i + 1, count, inner);
+            let inner = inner.clone();
+            result = self.run_job(|executor, pgid, fg_pids| {
+                executor.execute_command(inner, pgid, fg_pids)
+            })?;
+            // Ctrl-Z 或 Ctrl-C 总是中止循环
+            let interrupted = result.status == 128 + libc::SIGINT;
+            if result.stopped || interrupted || (self.options.errexit && result.status != 0) {
+                break;
+            }
+        }
+        self.builtin_result = Some(result);
+        Ok(())
+    }
+
+    // for NAME in WORD ... do cmd ...，每次循环前把 NAME 设为 shell 变量，循环结束后保留最后一个值
+    fn builtin_for(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let for_loop = for_loop::parse(&command.arguments, |word| self.expand_variables(word))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let inner = command
+            .inner(for_loop.body, usize::MAX)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, for_loop::USAGE))?;
+
+        let mut result = CommandResult::new();
+        for value in for_loop.values() {
+            trace!("for {}={}: {:?}", for_loop.name, value, inner);
+            self.variables.set(for_loop.name.clone(), value);
+            let inner = inner.clone();
+            result = self.run_job(|executor, pgid, fg_pids| {
+                executor.execute_command(inner, pgid, fg_pids)
+            })?;
+            // 与 repeat 相同，Ctrl-Z 或 Ctrl-C 总是中止循环
+            let interrupted = result.status == 128 + libc::SIGINT;
+            if result.stopped || interrupted || (self.options.errexit && result.status != 0) {
+                break;
+            }
+        }
+        self.builtin_result = Some(result);
+        Ok(())
+    }
+
+    fn builtin_select(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+        let select = for_loop::parse_as("select", &command.arguments, |word| {
+            self.expand_variables(word)
+        })
+        .map_err(invalid)?;
+        let inner = command
+            .inner(select.body, usize::MAX)
+            .ok_or_else(|| invalid(select::USAGE.to_string()))?;
+        let items = select::collect(select.values()).map_err(invalid)?;
+
+        let mut result = CommandResult::new();
+        let mut show_menu = true;
+        while !items.is_empty() && self.exit_request.is_none() {
+            if show_menu {
+                eprint!("{}", select::menu(&items));
+            }
+            let prompt = match self.variables.get("PS3".to_string()) {
+                prompt if prompt.is_empty() => select::DEFAULT_PROMPT.to_string(),
+                prompt => prompt,
+            };
+            let reply = match ReadlineManager::read_choice(&prompt) {
+                Ok(reply) => reply,
+                Err(ReadlineError::Eof) => {
+                    eprintln!();
+                    break;
+                }
+                Err(ReadlineError::Interrupted) => {
+                    result = interrupted_result();
+                    break;
+                }
+                Err(err) => return Err(io::Error::other(err)),
+            };
+            show_menu = reply.trim().is_empty();
+            if show_menu {
+                continue;
+            }
+            let value = select::choose(&items, &reply).unwrap_or_default();
+            trace!("select {}={}: {:?}", select.name, value, inner);
+            self.variables.set(select.name.clone(), value.to_string());
+            self.variables.set("REPLY".to_string(), reply);
+            let inner = inner.clone();
+            result = self.run_job(|executor, pgid, fg_pids| {
+                executor.execute_command(inner, pgid, fg_pids)
+            })?;
+            // 与 for 相同，Ctrl-Z 或 Ctrl-C 总是中止循环；命令中的 exit 也会结束
+            let interrupted = result.status == 128 + libc::SIGINT;
+            if result.stopped || interrupted || (self.options.errexit && result.status != 0) {
+                break;
+            }
+        }
+        self.builtin_result = Some(result);
+        Ok(())
+    }
+
+    // timeout [-k DURATION] DURATION cmd ...
+    fn builtin_timeout(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let usage = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["timeout [-k DURATION] DURATION cmd ..."]),
+            )
+        };
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+
+        let mut args = command.arguments.iter();
+        let mut kill_after = Duration::from_secs(5);
+        let mut limit = args.next().ok_or_else(usage)?;
+        let mut skip = 1;
+        if limit == "-k" {
+            kill_after =
+                duration::parse_duration(args.next().ok_or_else(usage)?).map_err(invalid)?;
+            limit = args.next().ok_or_else(usage)?;
+            skip = 3;
+        }
+        let limit = duration::parse_duration(limit).map_err(invalid)?;
+
+        let inner = command.inner(skip, usize::MAX).ok_or_else(usage)?;
+        self.execute_command(inner, pgid, fg_pids)?;
+
+        // 内建命令不会 fork，不需要计时
+        if *pgid != 0 {
+            trace!(
+                "作业 {} 超时时限 {}",
+                pgid,
+                duration::format_duration(limit)
+            );
+            self.job_manager.start_timeout(*pgid, limit, kill_after);
+        }
+        Ok(())
+    }
+
+    // config check：重新读取配置文件和当前主题（包括继承的主题），列出发现的问题
+    fn builtin_config(&self, command: &ShellCommand) -> io::Result<()> {
+        if command.arguments != ["check"] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["config check"]),
+            ));
+        }
+        let mut config = Config::default();
+        config.profile = self.profile.clone();
+        let mut problems = config.load_file();
+        if let Ok(theme) = env::var("ZAKO_THEME") {
+            config.theme = theme;
+        }
+        problems.extend(Theme::check(&config));
+        if problems.is_empty() {
+            let file = config.config_file().display().to_string();
+            println!("{}", i18n::tf("config.ok", &[&file, &config.theme]));
+            return Ok(());
+        }
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            i18n::tf("config.problems", &[&problems.len().to_string()]),
+        ))
+    }
+
+    // help 列出所有内建命令，help NAME 显示单个内建命令的用法
+    fn builtin_help(&self, command: &ShellCommand) -> io::Result<()> {
+        match command.arguments.first() {
+            Some(name) => {
+                let builtin = self.builtins.get(name).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        i18n::tf("help.not_builtin", &[name]),
+                    )
+                })?;
+                println!("{}", builtin.usage());
+            }
+            None => {
+                for builtin in self.builtins.iter() {
+                    let flags = builtin.flags();
+                    let note = if flags.changes_state {
+                        i18n::t("help.changes_state")
+                    } else if flags.needs_fork {
+                        i18n::t("help.runs_command")
+                    } else {
+                        String::new()
+                    };
+                    println!("{:<12} {}{}", builtin.name(), builtin.usage(), note);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 名字可以对应的所有命令，按执行时的优先级：别名、内建命令、PATH 中的可执行文件
+    fn resolve_command(&self, name: &str) -> Vec<Resolution> {
+        if name.contains('/') {
+            let path = PathBuf::from(name);
+            return if path::is_executable(&path) {
+                vec![Resolution::File(path)]
+            } else {
+                Vec::new()
+            };
+        }
+        let mut found = Vec::new();
+        if let Some(alias) = self.aliases.get(name) {
+            found.push(Resolution::Alias(alias.clone()));
+        }
+        if self.builtins.contains(name) {
+            found.push(Resolution::Builtin);
+        }
+        if let Some(path) = self.autoload.path(name) {
+            found.push(Resolution::Function(path.to_path_buf()));
+        }
+        found.extend(
+            path::find_all_in_path(name)
+                .into_iter()
+                .map(Resolution::File),
+        );
+        found
+    }
+
+    // which/whence [-a] [-v] NAME ...：-a 列出所有匹配而不只是实际执行的，-v 说明每个匹配的类型
+    fn builtin_which(&self, command: &ShellCommand) -> io::Result<()> {
+        let mut all = false;
+        let mut verbose = false;
+        let mut names = Vec::new();
+        for arg in &command.arguments {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() && flags.chars().all(|c| "av".contains(c)) => {
+                    all |= flags.contains('a');
+                    verbose |= flags.contains('v');
+                }
+                _ => names.push(arg),
+            }
+        }
+        if names.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf(
+                    "usage",
+                    &[&format!("{} [-a] [-v] NAME ...", command.program)],
+                ),
+            ));
+        }
+        let mut missing = Vec::new();
+        for name in names {
+            let found = self.resolve_command(name);
+            if found.is_empty() {
+                missing.push(i18n::tf("type.not_found", &[&command.program, name]));
+                continue;
+            }
+            let count = if all { found.len() } else { 1 };
+            for (i, resolution) in found.iter().take(count).enumerate() {
+                let line = match (resolution, verbose) {
+                    (Resolution::Alias(alias), false) => i18n::tf("type.alias", &[name, alias]),
+                    (Resolution::Alias(alias), true) => {
+                        i18n::tf("type.alias_verbose", &[name, alias])
+                    }
+                    (Resolution::Builtin, false) => i18n::tf("type.builtin", &[name]),
+                    (Resolution::Builtin, true) => i18n::tf("type.builtin_verbose", &[name]),
+                    (Resolution::Function(path), false) => {
+                        i18n::tf("type.function", &[name, &path.display().to_string()])
+                    }
+                    (Resolution::Function(path), true) => i18n::tf(
+                        "type.function_verbose",
+                        &[name, &path.display().to_string()],
+                    ),
+                    (Resolution::File(path), false) => path.display().to_string(),
+                    (Resolution::File(path), true) => {
+                        i18n::tf("type.file_verbose", &[name, &path.display().to_string()])
+                    }
+                };
+                // 排在第一个之后的匹配不会被执行
+                if verbose && i > 0 {
+                    println!("{}", i18n::tf("type.shadowed", &[&line]));
+                } else {
+                    println!("{}", line);
+                }
+            }
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, missing.join("\n")))
+        }
+    }
+
+    // lastout 原样输出上一条命令保存的输出，可以接到管道中，例如 lastout | clip
+    fn builtin_lastout(&self, command: &ShellCommand) -> io::Result<()> {
+        if !command.arguments.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["lastout"]),
+            ));
+        }
+        if !self.options.captureoutput && self.last_output.is_empty() {
+            return Err(io::Error::other(i18n::t("lastout.empty")));
+        }
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(self.last_output.as_bytes())?;
+        stdout.flush()
+    }
+
+    // isatty [FD]：FD（默认为 0）是终端时退出码为 0，否则为 1，不输出内容
+    fn builtin_isatty(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let fd = match command.arguments.as_slice() {
+            [] => 0,
+            [fd] => self.expand_variables(fd).parse::<i32>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("isatty.invalid_fd", &[fd]),
+                )
+            })?,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("usage", &["isatty [FD]"]),
+                ))
+            }
+        };
+        let tty = unsafe { libc::isatty(fd) } == 1;
+        self.builtin_result = Some(CommandResult::from_status(if tty { 0 } else { 1 }));
+        Ok(())
+    }
+
+    // clip TEXT ... 复制参数，cmd | clip 复制标准输入
+    fn builtin_clip(&self, command: &ShellCommand) -> io::Result<()> {
+        let text = if !command.arguments.is_empty() {
+            command
+                .arguments
+                .iter()
+                .map(|arg| self.expand_variables(arg))
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else if !io::stdin().is_terminal() {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["clip TEXT ... | cmd | clip"]),
+            ));
+        };
+        clipboard::copy(&text, self.clipboard)
+            .map_err(|e| io::Error::new(e.kind(), format!("clip: {}", e)))
+    }
+
+    // profile 列出可用的 profile 并标出当前的，profile NAME 切换到另一个 profile（default 表示不使用）
+    fn builtin_profile(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let active = self.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+        match command.arguments.as_slice() {
+            [] => {
+                let mut names: Vec<String> = fs::read_dir(&self.profiles_dir)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter_map(|entry| {
+                        let path = entry.path();
+                        let name = path.file_stem()?.to_str()?.to_string();
+                        (path.extension()? == "toml" && name != DEFAULT_PROFILE).then_some(name)
+                    })
+                    .collect();
+                names.sort();
+                names.insert(0, DEFAULT_PROFILE.to_string());
+                for name in names {
+                    let mark = if name == active { "*" } else { " " };
+                    println!("{} {}", mark, name);
+                }
+                Ok(())
+            }
+            [name] => {
+                let path = self.profiles_dir.join(format!("{}.toml", name));
+                if name != DEFAULT_PROFILE && !path.is_file() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        i18n::tf("profile.not_found", &[&path.display().to_string()]),
+                    ));
+                }
+                if name != active {
+                    self.profile_switch = Some(name.clone());
+                }
+                Ok(())
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["profile [NAME]"]),
+            )),
+        }
+    }
+
+    fn builtin_shell(&self) -> io::Result<()> {
+        let executable = env::current_exe().unwrap_or_default();
+        let _ = Command::new(executable)
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn()?
+            .wait();
+        Ok(())
+    }
+
+    fn builtin_cd(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let path = command.arguments.first().map(|s| s.as_str()).unwrap_or("~");
+        let path = shellexpand::tilde(path);
+        // 相对路径先在 CDPATH 的各个目录下查找，找到时打印切换后的目录
+        let relative =
+            !(path.starts_with('/') || path.starts_with("./") || path.starts_with("../"));
+        if let (true, Ok(cdpath)) = (relative && path != "." && path != "..", env::var("CDPATH")) {
+            for entry in cdpath.split(':').filter(|entry| !entry.is_empty()) {
+                let target = Path::new(entry).join(path.as_ref());
+                if target.is_dir() {
+                    std::env::set_current_dir(&target)?;
+                    if entry != "." {
+                        println!("{}", target.display());
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        std::env::set_current_dir(path.as_ref())
+    }
+
+    // exit [N]：没有参数时使用上一条命令的退出码
+    fn builtin_exit(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let status = match command.arguments.as_slice() {
+            [] => self.last_status,
+            [code] => {
+                let code = self.expand_variables(code);
+                code.parse::<i32>().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        i18n::tf("exit.invalid_status", &[&code]),
+                    )
+                })? & 0xff
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("usage", &["exit [N]"]),
+                ))
+            }
+        };
+        self.exit_request = Some(status);
+        Ok(())
+    }
+
+    fn builtin_set(&mut self, command: &ShellCommand) -> io::Result<()> {
+        // set -e / set +o errexit / set -o
+        let is_option = |arg: &String| arg.starts_with('-') || arg.starts_with('+');
+        if command.arguments.first().is_some_and(is_option) {
+            let list = self
+                .options
+                .apply_args(&command.arguments)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            if list {
+                for (name, value) in self.options.list() {
+                    println!("{:<16}{}", name, if value { "on" } else { "off" });
+                }
+            }
+            return Ok(());
+        }
+
+        if command.arguments.len() != 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::t("set.expected_two"),
+            ));
+        }
+
+        self.variables
+            .set(command.arguments[0].clone(), command.arguments[1].clone());
+        Ok(())
+    }
 
+    // stderrcolor [on|off]，不带参数时切换
+    fn builtin_stderrcolor(&mut self, command: &ShellCommand) -> io::Result<()> {
+        self.stderr_color = match command.arguments.first().map(|s| s.as_str()) {
+            None => !self.stderr_color,
+            Some("on") => true,
+            Some("off") => false,
+            Some(arg) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("stderrcolor.invalid", &[arg]),
+                ));
+            }
+        };
+        println!(
+            "stderrcolor: {}",
+            if self.stderr_color { "on" } else { "off" }
+        );
+        Ok(())
+    }
+
+    // 前缀命令需要在子进程中运行的命令不能是内建命令，替代外部命令的内建命令此时运行外部命令
+    fn runs_builtin(&self, name: &str) -> bool {
+        self.builtins
+            .get(name)
+            .is_some_and(|builtin| !builtin.flags().replaces_external)
+    }
+
+    // sleep DURATION ...，多个时长相加，支持 1.5s、2m 等单位；在 shell 进程中等待，Ctrl-C 中断时返回 130
+    // Ctrl-Z 时才启动一个子进程等待剩余的时间，它作为被暂停的作业，可以用 fg/bg 继续
+    fn builtin_sleep(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        if command.arguments.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["sleep DURATION ..."]),
+            ));
+        }
+        let mut total = Duration::ZERO;
+        for arg in &command.arguments {
+            let arg = self.expand_variables(arg);
+            let value = duration::parse_duration(&arg).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("sleep: {}", e))
+            })?;
+            total = total.checked_add(value).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, i18n::t("sleep.out_of_range"))
+            })?;
+        }
+        match signals::sleep_interruptible(total) {
+            Wake::Elapsed => {}
+            Wake::Interrupted => self.builtin_result = Some(interrupted_result()),
+            Wake::Suspended(remaining) => {
+                let label = shell_words::join(
+                    std::iter::once(&command.program).chain(command.arguments.iter()),
+                );
+                self.suspend_sleep(remaining, label, pgid, fg_pids);
+            }
+        }
+        Ok(())
+    }
+
+    // 子进程自己成为进程组长并暂停，之后与其他被暂停的前台作业一样由 wait_foreground 报告
+    fn suspend_sleep(
+        &mut self,
+        remaining: Duration,
+        label: String,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) {
+        match unsafe { nix::unistd::fork() } {
+            Ok(nix::unistd::ForkResult::Parent { child }) => {
+                let child_pid: i32 = child.into();
+                unsafe {
+                    libc::setpgid(child_pid, child_pid);
+                }
+                *pgid = child_pid;
+                fg_pids.push(child_pid);
+                self.job_manager.add_job(child_pid, child_pid, label);
+            }
+            Ok(nix::unistd::ForkResult::Child) => {
+                signals::enable_signals();
+                unsafe {
+                    libc::setpgid(0, 0);
+                    libc::raise(libc::SIGTSTP);
+                }
+                thread::sleep(remaining);
+                process::exit(0);
+            }
+            Err(e) => error!("Fork failed: {}", e),
+        }
+    }
+
+    // waitfor --port [HOST:]PORT | --file PATH [--timeout DURATION]
+    fn builtin_waitfor(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let args: Vec<String> = command
+            .arguments
+            .iter()
+            .map(|arg| self.expand_variables(arg))
+            .collect();
+        let wait =
+            waitfor::parse(&args).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.builtin_result = Some(match wait.wait() {
+            Outcome::Ready => CommandResult::from_status(0),
+            Outcome::TimedOut => {
+                eprintln!(
+                    "{}",
+                    i18n::tf("waitfor.timed_out", &[&wait.target.describe()])
+                );
+                CommandResult::from_status(1)
+            }
+            Outcome::Interrupted => interrupted_result(),
+        });
+        Ok(())
+    }
+
+    // calc EXPR ... / = EXPR ...，参数拼接为一个表达式，$var 先展开
+    fn builtin_calc(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let expr = command
+            .arguments
+            .iter()
+            .map(|arg| self.expand_variables(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let value = calc::evaluate(&expr).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: {}", command.program, e),
+            )
+        })?;
+        println!("{}", value);
+        Ok(())
+    }
+
+    fn builtin_coproc(
+        &mut self,
+        command: &ShellCommand,
+        pgid: &mut i32,
+        fg_pids: &mut Vec<i32>,
+    ) -> io::Result<()> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+        if command.arguments.first().map(String::as_str) == Some("-c") {
+            let name = command
+                .arguments
+                .get(1)
+                .map_or(coproc::DEFAULT_NAME, String::as_str);
+            return match self.coprocs.get_mut(name).map(Coproc::close_input) {
+                Some(true) => Ok(()),
+                Some(false) => Err(invalid(i18n::tf("coproc.closed", &[name]))),
+                None => Err(invalid(i18n::tf("coproc.not_found", &[name]))),
+            };
+        }
+        let spec = coproc::parse(&command.arguments).map_err(invalid)?;
+        let mut inner = command
+            .inner(spec.start, spec.end - spec.start - 1)
+            .ok_or_else(|| invalid(coproc::USAGE.to_string()))?;
+        // 内建命令在子进程中运行时不会 exec，会一直持有 shell 一侧的管道端
+        if self.runs_builtin(&inner.program) {
+            return Err(invalid(i18n::tf(
+                "exec.builtin_unsupported",
+                &["coproc", &inner.program],
+            )));
+        }
+        inner.background = true;
+
+        let (stdin_read, stdin_write) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+        let (stdout_read, stdout_write) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+        self.launch.stdin = Some(stdin_read);
+        self.launch.stdout = Some(stdout_write);
+        let result = self.execute_command(inner, pgid, fg_pids);
+        self.launch = LaunchOptions::default();
+        result?;
+
+        trace!("coproc {}: pid {}", spec.name, *pgid);
         self.variables
-            .set(command.arguments[0].clone(), command.arguments[1].clone());
+            .set(format!("{}_PID", spec.name), pgid.to_string());
+        // 同名的旧协同进程的管道端在这里关闭
+        self.coprocs
+            .insert(spec.name, Coproc::new(stdout_read, stdin_write));
+        Ok(())
+    }
+
+    // logoutput on FILE | off，没有参数时显示当前状态
+    fn builtin_logoutput(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let args: Vec<String> = command
+            .arguments
+            .iter()
+            .map(|arg| self.expand_variables(arg))
+            .collect();
+        match args
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [] => {}
+            ["on", file] => {
+                let path = shellexpand::tilde(file).into_owned();
+                let log = SessionLog::open(Path::new(&path))
+                    .map_err(|e| io::Error::new(e.kind(), format!("logoutput: {}: {}", path, e)))?;
+                self.session_log = Some(log);
+            }
+            ["off"] => self.session_log = None,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("usage", &["logoutput [on FILE | off]"]),
+                ))
+            }
+        }
+        match &self.session_log {
+            Some(log) => println!("logoutput: on ({})", log.path().display()),
+            None => println!("logoutput: off"),
+        }
         Ok(())
     }
 
-    fn builtin_jobs(&self) -> io::Result<()> {
+    fn builtin_jobs(&mut self, command: &ShellCommand) -> io::Result<()> {
+        match command.arguments.first().map(|s| s.as_str()) {
+            // jobs -q 列出排队中的命令
+            Some("-q") => {
+                for queued in self.job_manager.get_queue() {
+                    println!("{}", queued);
+                }
+                return Ok(());
+            }
+            Some("--pick") => {
+                self.job_manager.update_bg_jobs();
+                return self.pick_job("jobs");
+            }
+            _ => {}
+        }
         for job in self.job_manager.get_jobs() {
             println!("{}", job);
         }
         Ok(())
     }
 
+    // queue cmd ...，当前作业结束后自动运行 cmd
+    fn builtin_queue(&mut self, command: &ShellCommand) -> io::Result<()> {
+        if command.arguments.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("usage", &["queue cmd ..."]),
+            ));
+        }
+        // 词法分析时已经去掉了引号，重新加上以保留参数边界
+        let line = shell_words::join(&command.arguments);
+        let queued = self.job_manager.queue_command(line);
+        println!("{}", queued);
+        Ok(())
+    }
+
+    // 更新后台作业状态，返回可以运行的排队命令
+    pub fn take_ready_queue(&mut self) -> Vec<String> {
+        self.job_manager.update_bg_jobs();
+        self.job_manager
+            .take_ready_queue()
+            .into_iter()
+            .map(|queued| queued.command)
+            .collect()
+    }
+
     fn builtin_fg(&mut self, command: &ShellCommand) -> io::Result<()> {
-        let index =
-            if let Some(arg) = command.arguments.first() {
-                // 处理 %n 格式
-                if let Some(index_str) = arg.strip_prefix('%') {
-                    Some(index_str.parse::<usize>().map_err(|_| {
-                        io::Error::new(io::ErrorKind::InvalidInput, "无效的作业编号")
-                    })?)
-                } else {
-                    return Err(io::Error::new(
+        let index = if let Some(arg) = command.arguments.first() {
+            // 处理 %n 格式
+            if let Some(index_str) = arg.strip_prefix('%') {
+                Some(index_str.parse::<usize>().map_err(|_| {
+                    io::Error::new(
                         io::ErrorKind::InvalidInput,
-                        "fg: 参数格式错误，应为 %n",
-                    ));
-                }
+                        i18n::tf("job.invalid_number", &["fg"]),
+                    )
+                })?)
             } else {
-                None
-            };
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("job.expected_spec", &["fg"]),
+                ));
+            }
+        } else {
+            None
+        };
 
-        let job = self
-            .job_manager
-            .fg(index)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "fg: 没有找到该作业"))?;
-        println!("{}", job);
+        // 先收集已经结束的作业，不会恢复一个已经退出的作业
+        self.job_manager.update_bg_jobs();
+        // 不带参数且有多个作业时在终端上选择
+        if index.is_none() && self.live_jobs().len() > 1 && io::stdin().is_terminal() {
+            return self.pick_job("fg");
+        }
+        self.foreground_job(index)
+    }
 
-        let pid = job.pid;
+    // 还在运行或暂停的作业
+    fn live_jobs(&self) -> Vec<Job> {
+        self.job_manager
+            .get_jobs()
+            .iter()
+            .filter(|job| matches!(job.status, JobStatus::Continued | JobStatus::Stopped))
+            .cloned()
+            .collect()
+    }
 
-        #[cfg(unix)]
+    // 打开作业选择器，把选中的作业恢复到前台或结束它
+    fn pick_job(&mut self, name: &str) -> io::Result<()> {
+        let jobs = self.live_jobs();
+        if jobs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                i18n::tf("job.not_found", &[name]),
+            ));
+        }
+        let picked = job_picker::pick(&jobs, &self.theme).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                i18n::tf("job.picker_failed", &[name, &e.to_string()]),
+            )
+        })?;
+        match picked {
+            Some((pos, Action::Foreground)) => self.foreground_job(Some(jobs[pos].index)),
+            Some((pos, Action::Kill)) => {
+                let job = &jobs[pos];
+                if unsafe { libc::kill(-job.gid, libc::SIGTERM) } != 0 {
+                    return Err(io::Error::other(format!(
+                        "{}: %{}: {}",
+                        name,
+                        job.index,
+                        io::Error::last_os_error()
+                    )));
+                }
+                // 暂停的作业需要 SIGCONT 才能处理 SIGTERM
+                if job.status == JobStatus::Stopped {
+                    unsafe { libc::kill(-job.gid, libc::SIGCONT) };
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn foreground_job(&mut self, index: Option<usize>) -> io::Result<()> {
+        let job = self.job_manager.fg(index).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, i18n::tf("job.not_found", &["fg"]))
+        })?;
+        println!("{}", job);
+
+        // 将作业设置为前台进程组后发送 SIGCONT 继续执行
+        self.terminal.give_to_job(job.gid);
         unsafe {
-            // 将作业设置为前台进程组
-            libc::tcsetpgrp(0, pid as libc::pid_t);
-            // 发送 SIGCONT 信号继续执行
-            libc::kill(-(pid as libc::pid_t), libc::SIGCONT);
+            libc::kill(-(job.gid as libc::pid_t), libc::SIGCONT);
         }
-        trace!("恢复 {} 为前台进程组", pid);
+        trace!("恢复 {} 为前台进程组", job.gid);
 
+        // 与新启动的前台作业一样等待它结束或再次被暂停，run_job 随后收回终端
+        let mut result = self.wait_foreground(job.gid, &[job.pid]);
+        if !result.stopped {
+            self.temp_resources.release(job.gid);
+            if self.job_manager.finish_timeout(job.gid) {
+                result.status = 124;
+                result.timed_out = true;
+            }
+        }
+        self.builtin_result = Some(result);
         Ok(())
     }
 
-    fn builtin_bg(&mut self, command: &ShellCommand) -> io::Result<()> {
-        let index =
-            if let Some(arg) = command.arguments.first() {
-                // 处理 %n 格式
-                if let Some(index_str) = arg.strip_prefix('%') {
-                    Some(index_str.parse::<usize>().map_err(|_| {
-                        io::Error::new(io::ErrorKind::InvalidInput, "无效的作业编号")
-                    })?)
-                } else {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        "bg: 参数格式错误，应为 %n",
-                    ));
+    // exec cmd ... 用命令替换 shell 进程；只有重定向时（exec > file）持久地重定向 shell 自身的 fd
+    fn builtin_exec(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let Some((program, arguments)) = command.arguments.split_first() else {
+            for redirection in &command.redirections {
+                let target = self.redirection_target(redirection);
+                self.shell_fds
+                    .redirect(redirection, &target, self.options.devtcp)
+                    .map_err(|e| io::Error::new(e.kind(), format!("exec: {}: {}", target, e)))?;
+            }
+            return Ok(());
+        };
+
+        let program = self.expand_variables(program);
+        let args: Vec<String> = arguments
+            .iter()
+            .map(|arg| self.expand_variables(arg))
+            .collect();
+        for redirection in &command.redirections {
+            let target = self.redirection_target(redirection);
+            redirect::apply_redirection(redirection, &target, self.options.devtcp)
+                .map_err(|e| io::Error::new(e.kind(), format!("exec: {}: {}", target, e)))?;
+        }
+        self.cleanup();
+        identity::before_exec();
+        signals::enable_signals();
+        signals::unblock_child_signals();
+        self.exec_program(&program, &args);
+    }
+
+    // kill [-s SIG | -SIG | -NUM] pid|%job ...；kill -l [NUM] 列出信号或把编号转换为名称
+    fn builtin_kill(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let usage = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf(
+                    "usage",
+                    &["kill [-s SIG | -SIG] pid|%job ... | kill -l [NUM]"],
+                ),
+            )
+        };
+        let unknown = |name: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                i18n::tf("signal.unknown", &["kill", name]),
+            )
+        };
+        let args = &command.arguments;
+        let mut sig = libc::SIGTERM;
+        let mut rest = &args[..];
+        match args.first().map(|s| s.as_str()) {
+            None => return Err(usage()),
+            Some("-l") | Some("-L") => return list_signals(&args[1..]),
+            Some("-s") | Some("-n") => {
+                let name = args.get(1).ok_or_else(usage)?;
+                sig = signals::parse_signal(name).ok_or_else(|| unknown(name))?;
+                rest = &args[2..];
+            }
+            Some("--") => rest = &args[1..],
+            Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+                sig = signals::parse_signal(&arg[1..]).ok_or_else(|| unknown(&arg[1..]))?;
+                rest = &args[1..];
+            }
+            _ => {}
+        }
+        if rest.first().map(|s| s.as_str()) == Some("--") {
+            rest = &rest[1..];
+        }
+        if rest.is_empty() {
+            return Err(usage());
+        }
+
+        let mut errors = Vec::new();
+        for target in rest {
+            // 作业说明发给整个进程组，暂停的作业还需要 SIGCONT 才能处理信号
+            let (pid, stopped) = if target.starts_with('%') {
+                match self.job_manager.find_job(target) {
+                    Some(job) => (-job.gid, job.status == JobStatus::Stopped),
+                    None => {
+                        errors.push(i18n::tf("job.not_found", &[&format!("kill: {}", target)]));
+                        continue;
+                    }
                 }
             } else {
-                None
+                match target.parse::<i32>() {
+                    Ok(pid) => (pid, false),
+                    Err(_) => {
+                        errors.push(i18n::tf("kill.invalid_target", &[target]));
+                        continue;
+                    }
+                }
             };
-        let job = self
-            .job_manager
-            .bg(index)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "fg: 没有找到该作业"))?;
+            if unsafe { libc::kill(pid, sig) } != 0 {
+                errors.push(format!("kill: {}: {}", target, io::Error::last_os_error()));
+                continue;
+            }
+            if stopped && sig != libc::SIGCONT {
+                unsafe { libc::kill(pid, libc::SIGCONT) };
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::other(errors.join("\n")))
+        }
+    }
+
+    // trap 'cmd' SIG ...；trap '' SIG 忽略；trap - SIG 恢复；trap 列出；trap -l 列出信号
+    // SIG 为 EXIT 时在 shell 退出时运行
+    fn builtin_trap(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let args = &command.arguments;
+        let (action, names) = match args.split_first() {
+            None => {
+                let mut traps: Vec<_> = self.traps.iter().collect();
+                traps.sort();
+                for (sig, action) in traps {
+                    println!(
+                        "trap -- '{}' {}",
+                        action.replace('\'', r"'\''"),
+                        signals::trap_name(*sig)
+                    );
+                }
+                return Ok(());
+            }
+            Some((flag, rest)) if flag == "-l" => return list_signals(rest),
+            Some((_, [])) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("usage", &["trap 'cmd' SIG ... | trap - SIG ... | trap -l"]),
+                ))
+            }
+            Some((action, names)) => (action, names),
+        };
+
+        for name in names {
+            let sig = signals::parse_trap(name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("signal.unknown", &["trap", name]),
+                )
+            })?;
+            if sig == libc::SIGKILL || sig == libc::SIGSTOP {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("signal.uncatchable", &["trap", &signals::signal_name(sig)]),
+                ));
+            }
+            if action == "-" {
+                if sig != signals::EXIT {
+                    signals::reset_trap(sig)?;
+                }
+                self.traps.remove(&sig);
+            } else {
+                if sig != signals::EXIT {
+                    signals::install_trap(sig, action.is_empty())?;
+                }
+                self.traps.insert(sig, action.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn builtin_bg(&mut self, command: &ShellCommand) -> io::Result<()> {
+        let index = if let Some(arg) = command.arguments.first() {
+            // 处理 %n 格式
+            if let Some(index_str) = arg.strip_prefix('%') {
+                Some(index_str.parse::<usize>().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        i18n::tf("job.invalid_number", &["bg"]),
+                    )
+                })?)
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("job.expected_spec", &["bg"]),
+                ));
+            }
+        } else {
+            None
+        };
+        let job = self.job_manager.bg(index).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, i18n::tf("job.not_found", &["bg"]))
+        })?;
         println!("{}", job);
         let pid = job.pid;
 
@@ -335,3 +2927,650 @@ impl Executor {
         Ok(())
     }
 }
+
+// 读取子进程的输出，加上前缀或颜色后写回 shell 的 stdout/stderr，设置了 capture 时同时保存最后 LAST_OUTPUT_LIMIT 字节
+// 开启了会话日志时写出的内容同时追加到日志
+// 有前缀时按行转发，多个命令的输出不会在行中间交错；否则按块转发，没有换行的提示信息也能立即显示
+fn relay_output(
+    read_end: OwnedFd,
+    fd: i32,
+    prefix: Option<String>,
+    color: Option<&'static [u8]>,
+    capture: Option<Arc<Mutex<Vec<u8>>>>,
+    log: Option<SessionLog>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(File::from(read_end));
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let read = if prefix.is_some() {
+                reader.read_until(b'\n', &mut buf)
+            } else {
+                reader.fill_buf().map(|chunk| {
+                    buf.extend_from_slice(chunk);
+                    chunk.len()
+                })
+            };
+            match read {
+                Ok(0) => break,
+                Ok(n) => {
+                    if prefix.is_none() {
+                        reader.consume(n);
+                    }
+                    let mut parts: Vec<&[u8]> = Vec::new();
+                    if let Some(prefix) = &prefix {
+                        parts.push(prefix.as_bytes());
+                    }
+                    if let Some(color) = color {
+                        parts.extend([color, &buf, COLOR_RESET]);
+                    } else {
+                        parts.push(&buf);
+                    }
+                    write_parts(fd, &parts);
+                    if let Some(log) = &log {
+                        log.write(&parts);
+                    }
+                    if let Some(Ok(mut output)) = capture.as_ref().map(|c| c.lock()) {
+                        output.extend_from_slice(&buf);
+                        let excess = output.len().saturating_sub(LAST_OUTPUT_LIMIT);
+                        output.drain(..excess);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+// 在 shell 进程中等待的内建命令被 Ctrl-C 中断，与被 SIGINT 终止的前台作业相同，会运行 INT trap
+fn interrupted_result() -> CommandResult {
+    let mut result = CommandResult::from_status(128 + libc::SIGINT);
+    result.signal = Some(libc::SIGINT);
+    result
+}
+
+fn progress_usage() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        i18n::tf("usage", &["progress cmd ... < FILE"]),
+    )
+}
+
+// 持有锁一次性写完，避免和其他转发线程交错
+fn write_parts(fd: i32, parts: &[&[u8]]) {
+    let write = |out: &mut dyn Write| {
+        for part in parts {
+            out.write_all(part)?;
+        }
+        out.flush()
+    };
+    let _ = if fd == libc::STDERR_FILENO {
+        write(&mut io::stderr().lock())
+    } else {
+        write(&mut io::stdout().lock())
+    };
+}
+
+// kill -l / trap -l：没有参数时列出所有信号，参数为编号（或 128+编号的退出码）时输出名称，为名称时输出编号
+fn list_signals(args: &[String]) -> io::Result<()> {
+    if args.is_empty() {
+        let names: Vec<_> = signals::all_signals()
+            .iter()
+            .map(|(num, name)| format!("{:>2}) SIG{}", num, name))
+            .collect();
+        for row in names.chunks(5) {
+            println!("{}", row.join("\t"));
+        }
+        return Ok(());
+    }
+    for arg in args {
+        let output = match arg.parse::<i32>() {
+            Ok(num) => {
+                let num = if num > 128 { num - 128 } else { num };
+                signals::all_signals()
+                    .iter()
+                    .find(|(n, _)| *n == num)
+                    .map(|(_, name)| name.to_string())
+            }
+            Err(_) => signals::parse_signal(arg).map(|num| num.to_string()),
+        };
+        match output {
+            Some(output) => println!("{}", output),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("signal.unknown", &["kill", arg]),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+// shell 自带的内建命令
+fn default_builtins() -> BuiltinRegistry {
+    // 启动子进程运行其他命令的内建命令
+    let forks = BuiltinFlags {
+        needs_fork: true,
+        ..Default::default()
+    };
+    let stateful = BuiltinFlags {
+        changes_state: true,
+        ..Default::default()
+    };
+    let replaces_external = BuiltinFlags {
+        replaces_external: true,
+        ..Default::default()
+    };
+    let builtins = [
+        FnBuiltin {
+            name: "zako",
+            usage: "zako",
+            flags: forks,
+            handler: |executor, _, _| executor.builtin_shell(),
+        },
+        FnBuiltin {
+            name: "cd",
+            usage: "cd [DIR]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_cd(command),
+        },
+        FnBuiltin {
+            name: "exit",
+            usage: "exit [N]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_exit(command),
+        },
+        FnBuiltin {
+            name: "set",
+            usage: "set [-e|+e] [-o|+o NAME]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_set(command),
+        },
+        FnBuiltin {
+            name: "jobs",
+            usage: "jobs [-q | --pick]",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_jobs(command),
+        },
+        FnBuiltin {
+            name: "queue",
+            usage: "queue cmd ...",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_queue(command),
+        },
+        FnBuiltin {
+            name: "fg",
+            usage: "fg [N]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_fg(command),
+        },
+        FnBuiltin {
+            name: "bg",
+            usage: "bg [N]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_bg(command),
+        },
+        FnBuiltin {
+            name: "exec",
+            usage: "exec [cmd ...] [< file] [> file]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_exec(command),
+        },
+        FnBuiltin {
+            name: "kill",
+            usage: "kill [-s SIG | -SIG] pid|%job ... | kill -l [NUM]",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_kill(command),
+        },
+        FnBuiltin {
+            name: "trap",
+            usage: "trap 'cmd' SIG ... | trap - SIG ... | trap -l",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_trap(command),
+        },
+        FnBuiltin {
+            name: "stderrcolor",
+            usage: "stderrcolor [on|off]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_stderrcolor(command),
+        },
+        FnBuiltin {
+            name: "logoutput",
+            usage: "logoutput [on FILE | off]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_logoutput(command),
+        },
+        FnBuiltin {
+            name: "stty",
+            usage: "stty [sane|raw|-raw|echo|-echo|size]",
+            flags: stateful,
+            handler: |_, command, _| stty::stty(&command.arguments),
+        },
+        FnBuiltin {
+            name: "theme",
+            usage: "theme list | install URL | update [NAME ...] | remove NAME ...",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| {
+                theme_manager::theme(&executor.themes_dir, &command.arguments)
+            },
+        },
+        FnBuiltin {
+            name: "lastout",
+            usage: "lastout",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_lastout(command),
+        },
+        FnBuiltin {
+            name: "clip",
+            usage: "clip TEXT ... | cmd | clip",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_clip(command),
+        },
+        FnBuiltin {
+            name: "trust",
+            usage: "trust [-r] [DIR] | trust -l",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| {
+                local_config::trust(&executor.trust_file, &command.arguments)
+            },
+        },
+        FnBuiltin {
+            name: "profile",
+            usage: "profile [NAME]",
+            flags: stateful,
+            handler: |executor, command, _| executor.builtin_profile(command),
+        },
+        FnBuiltin {
+            name: "config",
+            usage: "config check",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_config(command),
+        },
+        FnBuiltin {
+            name: "explain",
+            usage: "explain LINE",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_explain(command),
+        },
+        FnBuiltin {
+            name: "help",
+            usage: "help [NAME]",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_help(command),
+        },
+        FnBuiltin {
+            name: "alias",
+            usage: "alias [-g | -s] [NAME[=VALUE] ...]",
+            flags: stateful,
+            handler: |executor, command, _| {
+                aliases::alias(&mut executor.aliases, &command.arguments)
+            },
+        },
+        FnBuiltin {
+            name: "unalias",
+            usage: "unalias [-g | -s] NAME ...",
+            flags: stateful,
+            handler: |executor, command, _| {
+                aliases::unalias(&mut executor.aliases, &command.arguments)
+            },
+        },
+        FnBuiltin {
+            name: "hash",
+            usage: "hash [-r] [-p PATH NAME] [-d NAME ...] [-t NAME ...] [NAME ...]",
+            flags: stateful,
+            handler: |executor, command, _| {
+                path_cache::hash(&mut executor.path_cache, &command.arguments)
+            },
+        },
+        FnBuiltin {
+            name: "isatty",
+            usage: "isatty [FD]",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_isatty(command),
+        },
+        FnBuiltin {
+            name: "sleep",
+            usage: "sleep DURATION ...",
+            flags: replaces_external,
+            handler: |executor, command, job| {
+                executor.builtin_sleep(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "waitfor",
+            usage: "waitfor --port [HOST:]PORT | --file PATH [--timeout DURATION]",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_waitfor(command),
+        },
+        FnBuiltin {
+            name: "calc",
+            usage: "calc EXPR ...",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_calc(command),
+        },
+        FnBuiltin {
+            name: "=",
+            usage: "= EXPR ...",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_calc(command),
+        },
+        FnBuiltin {
+            name: "which",
+            usage: "which [-a] [-v] NAME ...",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_which(command),
+        },
+        FnBuiltin {
+            name: "whence",
+            usage: "whence [-a] [-v] NAME ...",
+            flags: BuiltinFlags::default(),
+            handler: |executor, command, _| executor.builtin_which(command),
+        },
+        FnBuiltin {
+            name: "timeout",
+            usage: "timeout [-k DURATION] DURATION cmd ...",
+            flags: forks,
+            handler: |executor, command, job| {
+                executor.builtin_timeout(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "repeat",
+            usage: "repeat N cmd ...",
+            flags: forks,
+            handler: |executor, command, _| executor.builtin_repeat(command),
+        },
+        FnBuiltin {
+            name: "for",
+            usage: "for NAME in WORD ... do cmd ...",
+            flags: forks,
+            handler: |executor, command, _| executor.builtin_for(command),
+        },
+        FnBuiltin {
+            name: "select",
+            usage: "select NAME in WORD ... do cmd ...",
+            flags: forks,
+            handler: |executor, command, _| executor.builtin_select(command),
+        },
+        FnBuiltin {
+            name: "after",
+            usage: "after DURATION cmd ... | after -l | after -r ID",
+            flags: forks,
+            handler: |executor, command, job| {
+                executor.builtin_after(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "limit",
+            usage: "limit [cpu=DURATION] [mem=SIZE] [nice=N] ... cmd ...",
+            flags: forks,
+            handler: |executor, command, job| {
+                executor.builtin_limit(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "coproc",
+            usage: "coproc [NAME] { cmd ... } | coproc cmd ... | coproc -c [NAME]",
+            flags: BuiltinFlags {
+                needs_fork: true,
+                changes_state: true,
+                ..Default::default()
+            },
+            handler: |executor, command, job| {
+                executor.builtin_coproc(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "withenv",
+            usage: "withenv [--diff] [NAME=VALUE ...] cmd ...",
+            flags: forks,
+            handler: |executor, command, job| {
+                executor.builtin_withenv(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "progress",
+            usage: "progress cmd ... < FILE",
+            flags: forks,
+            handler: |executor, command, job| {
+                executor.builtin_progress(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "sandbox",
+            usage: "sandbox [-p NAME] cmd ... | sandbox -l",
+            flags: forks,
+            handler: |executor, command, job| {
+                executor.builtin_sandbox(command, job.pgid, job.fg_pids)
+            },
+        },
+        FnBuiltin {
+            name: "par",
+            usage: "par [-j N] cmd1 ... ::: cmd2 ... ::: ...",
+            flags: forks,
+            handler: |executor, command, _| executor.builtin_par(command),
+        },
+    ];
+
+    let mut registry = BuiltinRegistry::new();
+    for builtin in builtins {
+        registry.register(Rc::new(builtin));
+    }
+    registry
+}
+
+// 受限模式下只读的变量：它们决定运行哪些程序、加载哪些库，修改后会传给子进程
+fn is_restricted_variable(name: &str) -> bool {
+    matches!(
+        name,
+        "PATH" | "ENV" | "SHELL" | "BASH_ENV" | "ZAKO_RESTRICTED"
+    ) || name.starts_with("LD_")
+}
+
+// set NAME VALUE、withenv NAME=VALUE、for/select NAME 要修改的只读变量
+fn restricted_assignment(command: &ShellCommand) -> Option<&str> {
+    match command.program.as_str() {
+        "set" if command.arguments.len() == 2 => Some(command.arguments[0].as_str()),
+        "for" | "select" => command.arguments.first().map(|name| name.as_str()),
+        "withenv" => command
+            .arguments
+            .iter()
+            .take_while(|arg| *arg == "--diff" || arg.contains('='))
+            .filter_map(|arg| arg.split_once('=').map(|(name, _)| name))
+            .find(|name| is_restricted_variable(name)),
+        _ => None,
+    }
+    .filter(|name| is_restricted_variable(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn executor(aliases: &[(&str, &str)]) -> Executor {
+        // 作为脚本运行，参数过多时报错而不是等待确认
+        let config = Config {
+            max_nesting: 8,
+            max_args: 8,
+            script: Some(PathBuf::from("test.zako")),
+            ..Config::default()
+        };
+        let mut executor = Executor::new(&config, JobManager::new());
+        executor.set_aliases(
+            aliases
+                .iter()
+                .map(|(name, alias)| (name.to_string(), alias.to_string()))
+                .collect(),
+        );
+        executor
+    }
+
+    fn expand(executor: &Executor, line: &str) -> Vec<String> {
+        let command = ShellCommand {
+            program: line.to_string(),
+            ..ShellCommand::default()
+        };
+        let command = executor.expand_alias(command);
+        std::iter::once(command.program)
+            .chain(command.arguments)
+            .collect()
+    }
+
+    #[test]
+    fn test_alias_expansion_stops_at_cycles() {
+        let executor = executor(&[
+            ("ls", "ls --color"),
+            ("ll", "ls -l"),
+            ("a", "b 1"),
+            ("b", "a 2"),
+        ]);
+        assert_eq!(expand(&executor, "ls"), ["ls", "--color"]);
+        assert_eq!(expand(&executor, "ll"), ["ls", "--color", "-l"]);
+        assert_eq!(expand(&executor, "a"), ["a", "2", "1"]);
+    }
+
+    #[test]
+    fn test_resolve_command() {
+        let executor = executor(&[("cd", "cd -P"), ("sh", "sh -e")]);
+        assert_eq!(
+            executor.resolve_command("cd"),
+            [Resolution::Alias("cd -P".to_string()), Resolution::Builtin]
+        );
+        // 别名排在 PATH 中的 sh 之前
+        let found = executor.resolve_command("sh");
+        assert_eq!(found[0], Resolution::Alias("sh -e".to_string()));
+        assert!(
+            matches!(found.get(1), Some(Resolution::File(_))),
+            "{:?}",
+            found
+        );
+        assert!(executor.resolve_command("no-such-command-zako").is_empty());
+        assert_eq!(
+            executor.resolve_command("/bin/sh"),
+            [Resolution::File(PathBuf::from("/bin/sh"))]
+        );
+    }
+
+    #[test]
+    fn test_arg_list_limits() {
+        let executor = executor(&[]);
+        let args: Vec<String> = (0..8).map(|i| i.to_string()).collect();
+        assert!(executor.check_arg_list("echo", &args).is_ok());
+        let args: Vec<String> = (0..9).map(|i| i.to_string()).collect();
+        let err = executor.check_arg_list("echo", &args).err();
+        assert_eq!(
+            err.map(|e| e.kind()),
+            Some(io::ErrorKind::ArgumentListTooLong)
+        );
+        let envs = HashMap::from([("A".to_string(), "1".to_string())]);
+        let pointer = std::mem::size_of::<usize>();
+        assert_eq!(
+            exec_size("ls", &["-l".to_string()], &envs),
+            3 + 3 + 4 + 5 * pointer
+        );
+        if cfg!(target_os = "linux") {
+            let huge = vec!["x".repeat(MAX_ARG_STRLEN)];
+            assert!(executor.check_arg_list("echo", &huge).is_err());
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() -> Result<(), String> {
+        // 每次 repeat 运行 r 时都会再展开为 repeat 2 r
+        let mut executor = executor(&[("r", "repeat 2 r")]);
+        let node = Parser::new("r")
+            .parse_command()
+            .map_err(|e| e.to_string())?;
+        let err = match executor.execute(node) {
+            Ok(_) => return Err("没有超过嵌套层数".to_string()),
+            Err(e) => e.to_string(),
+        };
+        assert!(err.contains('8'), "{}", err);
+        assert_eq!(executor.nesting, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restricted_mode() -> Result<(), String> {
+        let mut executor = executor(&[]);
+        executor.restricted = true;
+        let check = |line: &str| -> Result<bool, String> {
+            let node = Parser::new(line)
+                .parse_command()
+                .map_err(|e| e.to_string())?;
+            let command = node.commands().first().map(|c| (*c).clone());
+            let command = command.ok_or_else(|| format!("{}: 没有命令", line))?;
+            Ok(executor.check_restricted(&command).is_ok())
+        };
+        for line in [
+            "/bin/echo x",
+            "hash -p /bin/echo ls",
+            "set PATH /tmp",
+            "set LD_PRELOAD /tmp/x.so",
+            "set BASH_ENV /tmp/x",
+            "withenv LD_PRELOAD=/tmp/x.so ls",
+            "withenv --diff A=1 PATH=/tmp ls",
+            "for SHELL in /bin/sh do echo",
+            "set ZAKO_RESTRICTED 0",
+            "withenv ZAKO_RESTRICTED= zako",
+        ] {
+            assert!(!check(line)?, "{}", line);
+        }
+        for line in [
+            "hash",
+            "hash -r",
+            "set A 1",
+            "withenv A=1 ls",
+            "withenv A=1 ls PATH=/tmp",
+            "for x in 1 2 do echo",
+        ] {
+            assert!(check(line)?, "{}", line);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sleep_overflow() -> Result<(), String> {
+        let mut executor = executor(&[]);
+        let node = Parser::new("sleep 10000000000000000000s 10000000000000000000s")
+            .parse_command()
+            .map_err(|e| e.to_string())?;
+        match executor.execute(node) {
+            Ok(_) => Err("时长之和溢出时没有报错".to_string()),
+            Err(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_after_failure_clears_delay() -> Result<(), String> {
+        let mut executor = executor(&[]);
+        executor.restricted = true;
+        let node = Parser::new("after 1h /bin/echo x")
+            .parse_command()
+            .map_err(|e| e.to_string())?;
+        if executor.execute(node).is_ok() {
+            return Err("受限模式下 after 运行了带 / 的命令".to_string());
+        }
+        assert_eq!(executor.launch.delay, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_special_parameters() {
+        let mut executor = executor(&[]);
+        assert_eq!(executor.expand_variables("[$-]"), "[]");
+        executor.options.errexit = true;
+        executor.interactive = true;
+        assert_eq!(executor.expand_variables("$-"), "ei");
+        assert_eq!(
+            executor.expand_variables("$$"),
+            std::process::id().to_string()
+        );
+    }
+}
@@ -0,0 +1,273 @@
+// for NAME in WORD ... do cmd ...：依次把 NAME 设为每个值并运行命令，与 repeat 一样是前缀命令
+// WORD 可以是范围 1..10（含两端，可以递减）、带步长的 1..10..2，或者通配符 *.txt、src/*/mod.rs
+// 范围按需生成，不需要 seq；通配符没有匹配时保持原样，与 bash 相同
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use zakosh::i18n;
+
+#[derive(Debug, PartialEq)]
+pub struct ForLoop {
+    pub name: String,
+    pub items: Vec<Item>,
+    // 命令在参数中的位置
+    pub body: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Word(String),
+    Range { start: i64, end: i64, step: i64 },
+}
+
+pub const USAGE: &str = "用法: for NAME in WORD ... do cmd ...";
+
+// args 为 for 之后的参数，WORD 用 expand 展开变量后再展开范围和通配符，命令部分每次循环时再展开
+pub fn parse(args: &[String], expand: impl Fn(&str) -> String) -> Result<ForLoop, String> {
+    parse_as("for", args, expand)
+}
+
+// 与 for 语法相同的其他命令（select），错误信息使用它自己的名字
+pub fn parse_as(
+    keyword: &str,
+    args: &[String],
+    expand: impl Fn(&str) -> String,
+) -> Result<ForLoop, String> {
+    let usage = || USAGE.replacen("for", keyword, 1);
+    let name = match args.first() {
+        Some(name) if is_name(name) => name.clone(),
+        Some(name) => return Err(i18n::tf("for.invalid_name", &[keyword, name])),
+        None => return Err(usage()),
+    };
+    if args.get(1).map(|s| s.as_str()) != Some("in") {
+        return Err(usage());
+    }
+    let body = args
+        .iter()
+        .position(|arg| arg == "do")
+        .filter(|&index| index + 1 < args.len())
+        .ok_or_else(usage)?;
+    let mut items = Vec::new();
+    for word in &args[2..body] {
+        let word = expand(word);
+        match parse_range(&word).map_err(|e| format!("{}: {}", keyword, e))? {
+            Some(range) => items.push(range),
+            None => items.extend(glob(&word).into_iter().map(Item::Word)),
+        }
+    }
+    Ok(ForLoop {
+        name,
+        items,
+        body: body + 1,
+    })
+}
+
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// START..END 或 START..END..STEP，步长的符号不影响方向
+fn parse_range(word: &str) -> Result<Option<Item>, String> {
+    let parts: Vec<&str> = word.split("..").collect();
+    if !(2..=3).contains(&parts.len()) {
+        return Ok(None);
+    }
+    let numbers: Vec<i64> = match parts.iter().map(|part| part.parse::<i64>()).collect() {
+        Ok(numbers) => numbers,
+        Err(_) => return Ok(None),
+    };
+    let step = numbers.get(2).map_or(1, |step| step.saturating_abs());
+    if step == 0 {
+        return Err(i18n::tf("for.zero_step", &[word]));
+    }
+    Ok(Some(Item::Range {
+        start: numbers[0],
+        end: numbers[1],
+        step,
+    }))
+}
+
+impl ForLoop {
+    pub fn values(&self) -> impl Iterator<Item = String> + '_ {
+        self.items
+            .iter()
+            .flat_map(|item| -> Box<dyn Iterator<Item = String>> {
+                match item {
+                    Item::Word(word) => Box::new(std::iter::once(word.clone())),
+                    Item::Range { start, end, step } => {
+                        let (start, end, step) = (*start, *end, *step);
+                        let mut next = Some(start);
+                        Box::new(std::iter::from_fn(move || {
+                            let current = next?;
+                            next = if start <= end {
+                                current.checked_add(step).filter(|n| *n <= end)
+                            } else {
+                                current.checked_sub(step).filter(|n| *n >= end)
+                            };
+                            Some(current.to_string())
+                        }))
+                    }
+                }
+            })
+    }
+}
+
+// 展开含有 * ? [...] 的单词，按路径排序；以 . 开头的文件只有模式也以 . 开头时才匹配
+pub fn glob(word: &str) -> Vec<String> {
+    if !word.contains(['*', '?', '[']) {
+        return vec![word.to_string()];
+    }
+    let pattern = shellexpand::tilde(word).into_owned();
+    let mut paths = vec![if pattern.starts_with('/') {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let join = |dir: &str, name: &str| match dir {
+            "" => name.to_string(),
+            dir if dir.ends_with('/') => format!("{}{}", dir, name),
+            dir => format!("{}/{}", dir, name),
+        };
+        if !component.contains(['*', '?', '[']) {
+            paths = paths.iter().map(|dir| join(dir, component)).collect();
+            continue;
+        }
+        let Some(matcher) = wildcard_regex(component) else {
+            return vec![word.to_string()];
+        };
+        let mut matched = Vec::new();
+        for dir in &paths {
+            let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+                continue;
+            };
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+                .filter(|name| matcher.is_match(name))
+                .collect();
+            names.sort();
+            matched.extend(names.iter().map(|name| join(dir, name)));
+        }
+        paths = matched;
+    }
+    paths.retain(|path| Path::new(path).exists());
+    if paths.is_empty() {
+        return vec![word.to_string()];
+    }
+    paths
+}
+
+// * 匹配任意字符，? 匹配一个字符，[abc]、[a-z]、[!a] 匹配字符集合
+fn wildcard_regex(pattern: &str) -> Option<Regex> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let mut class = String::new();
+                if matches!(chars.peek(), Some('!') | Some('^')) {
+                    chars.next();
+                    class.push('^');
+                }
+                loop {
+                    match chars.next()? {
+                        ']' if !class.is_empty() && class != "^" => break,
+                        '\\' => class.push_str("\\\\"),
+                        c @ ('[' | ']' | '&' | '~') => {
+                            class.push('\\');
+                            class.push(c);
+                        }
+                        c => class.push(c),
+                    }
+                }
+                regex.push_str(&format!("[{}]", class));
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn values(line: &str) -> Result<Vec<String>, String> {
+        parse(&args(line), |word| word.replace("$n", "3"))
+            .map(|for_loop| for_loop.values().collect())
+    }
+
+    #[test]
+    fn test_parse_for_loop() {
+        let for_loop = parse(&args("i in a b do echo $i"), str::to_string).ok();
+        assert_eq!(
+            for_loop,
+            Some(ForLoop {
+                name: "i".to_string(),
+                items: vec![Item::Word("a".to_string()), Item::Word("b".to_string())],
+                body: 5,
+            })
+        );
+        assert_eq!(values("i 1..5 do echo"), Err(USAGE.to_string()));
+        assert_eq!(values("i in 1..5"), Err(USAGE.to_string()));
+        assert_eq!(values("i in 1..5 do"), Err(USAGE.to_string()));
+        assert_eq!(
+            values("1x in a do echo"),
+            Err("for: 无效的变量名 1x".to_string())
+        );
+        assert_eq!(
+            values("i in 1..5..0 do echo"),
+            Err("for: 步长不能为 0: 1..5..0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        let values = |line| values(line).unwrap_or_default().join(" ");
+        assert_eq!(values("i in 1..5 do echo"), "1 2 3 4 5");
+        assert_eq!(values("i in 1..10..3 do echo"), "1 4 7 10");
+        assert_eq!(values("i in 5..1..2 do echo"), "5 3 1");
+        assert_eq!(values("i in -1..1 x do echo"), "-1 0 1 x");
+        assert_eq!(values("i in 1..a 3.. do echo"), "1..a 3..");
+        assert_eq!(values("i in 7..7 do echo"), "7");
+        assert_eq!(values("i in 1..$n do echo"), "1 2 3");
+    }
+
+    #[test]
+    fn test_glob() -> std::io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("zako_glob_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub"))?;
+        for name in ["b.txt", "a.txt", "c.md", ".hidden.txt", "sub/d.txt"] {
+            fs::write(dir.join(name), "")?;
+        }
+        let base = dir.display().to_string();
+        let names = |pattern: &str| -> Vec<String> {
+            glob(&format!("{}/{}", base, pattern))
+                .iter()
+                .map(|path| path.trim_start_matches(&base).to_string())
+                .collect()
+        };
+        assert_eq!(names("*.txt"), vec!["/a.txt", "/b.txt"]);
+        assert_eq!(names("[ab].*"), vec!["/a.txt", "/b.txt"]);
+        assert_eq!(names("[!a]?txt"), vec!["/b.txt"]);
+        assert_eq!(names(".*.txt"), vec!["/.hidden.txt"]);
+        assert_eq!(names("*/*.txt"), vec!["/sub/d.txt"]);
+        assert_eq!(names("*.rs"), vec!["/*.rs"]);
+        assert_eq!(glob("plain"), vec!["plain"]);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
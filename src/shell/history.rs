@@ -0,0 +1,413 @@
+// 历史扩展：!! 上一条命令、!N 第 N 条、!-N 倒数第 N 条、!str 最近一条以 str 开头的命令
+// 事件后可以跟 :N、:^、:$、:*、:N-M、:N* 选取其中的单词，!$、!^、!* 是 !!:$ 等的简写
+// 单引号内和反斜杠后的 ! 不扩展，后面跟空白、= 或 ( 的 ! 保持原样
+// 之后还可以跟 :s/old/new/（替换第一处）和 :gs/old/new/（替换全部），new 中的 & 表示 old，分隔符可以是任意字符
+// 行首的 ^old^new^ 是 !!:s^old^new^ 的简写
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Last,
+    Absolute(usize),
+    Relative(usize),
+    Prefix(String),
+}
+
+// 单词下标，Last 为最后一个单词
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WordIndex {
+    At(usize),
+    Last,
+}
+
+// 选取的单词范围（含两端）
+type Words = (WordIndex, WordIndex);
+
+#[derive(Debug, PartialEq)]
+struct Substitution {
+    old: String,
+    new: String,
+    global: bool,
+}
+
+impl Substitution {
+    // old 为空或者没有出现时替换失败
+    fn apply(&self, text: &str) -> Option<String> {
+        if self.old.is_empty() || !text.contains(&self.old) {
+            return None;
+        }
+        Some(if self.global {
+            text.replace(&self.old, &self.new)
+        } else {
+            text.replacen(&self.old, &self.new, 1)
+        })
+    }
+}
+
+// 扩展一行输入中的历史引用，没有需要扩展的内容时返回 None
+pub fn expand(line: &str, history: &[String]) -> Result<Option<String>, String> {
+    if let Some(rest) = line.strip_prefix('^') {
+        return quick_substitute(rest, history).map(Some);
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut result = String::new();
+    let mut expanded = false;
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if !in_single && i + 1 < chars.len() => {
+                result.push(c);
+                result.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '!' if !in_single => {
+                if let Some((event, words, len)) = parse_reference(&chars[i + 1..]) {
+                    let text: String = chars[i..=i + len].iter().collect();
+                    let entry = find_event(&event, history)
+                        .ok_or_else(|| format!("{}: event not found", text))?;
+                    let mut selected = match words {
+                        Some((start, end)) => select_words(entry, start, end)
+                            .ok_or_else(|| format!("{}: bad word specifier", text))?,
+                        None => entry.clone(),
+                    };
+                    let (substitutions, modifiers_len) = parse_modifiers(&chars[i + 1 + len..]);
+                    for substitution in &substitutions {
+                        selected = substitution.apply(&selected).ok_or_else(|| {
+                            let text: String =
+                                chars[i..i + 1 + len + modifiers_len].iter().collect();
+                            format!("{}: substitution failed", text)
+                        })?;
+                    }
+                    result.push_str(&selected);
+                    expanded = true;
+                    i += 1 + len + modifiers_len;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        result.push(c);
+        i += 1;
+    }
+    Ok(expanded.then_some(result))
+}
+
+// ^old^new^rest：把上一条命令中的第一处 old 替换为 new，结尾的 ^ 之后的内容追加到命令后面
+fn quick_substitute(rest: &str, history: &[String]) -> Result<String, String> {
+    let chars: Vec<char> = rest.chars().collect();
+    let (substitution, len) = parse_substitution(&chars, '^', false);
+    let entry = history.last().ok_or("!!: event not found")?;
+    let replaced = substitution
+        .apply(entry)
+        .ok_or_else(|| format!("^{}: substitution failed", rest))?;
+    let remainder: String = chars[len..].iter().collect();
+    Ok(replaced + &remainder)
+}
+
+// 解析连续的 :s 和 :gs 修饰符，返回替换和占用的字符数
+fn parse_modifiers(rest: &[char]) -> (Vec<Substitution>, usize) {
+    let mut substitutions = Vec::new();
+    let mut len = 0;
+    loop {
+        let (global, skip) = match &rest[len..] {
+            [':', 's', ..] => (false, 2),
+            [':', 'g', 's', ..] => (true, 3),
+            _ => break,
+        };
+        match rest.get(len + skip) {
+            Some(&delimiter) if !delimiter.is_whitespace() => {
+                let (substitution, used) =
+                    parse_substitution(&rest[len + skip + 1..], delimiter, global);
+                substitutions.push(substitution);
+                len += skip + 1 + used;
+            }
+            _ => break,
+        }
+    }
+    (substitutions, len)
+}
+
+// 解析分隔符之后的 old<d>new<d>，到行尾时可以省略后面的分隔符
+// \<d> 表示分隔符本身，new 中的 & 表示 old，\& 表示 & 本身
+fn parse_substitution(rest: &[char], delimiter: char, global: bool) -> (Substitution, usize) {
+    let mut i = 0;
+    let mut read_part = |is_new: bool, old: &str| {
+        let mut part = String::new();
+        while i < rest.len() {
+            let c = rest[i];
+            i += 1;
+            match c {
+                '\\' if matches!(rest.get(i), Some(&next) if next == delimiter || next == '&') => {
+                    part.push(rest[i]);
+                    i += 1;
+                }
+                '&' if is_new => part.push_str(old),
+                c if c == delimiter => break,
+                c => part.push(c),
+            }
+        }
+        part
+    };
+    let old = read_part(false, "");
+    let new = read_part(true, &old);
+    (Substitution { old, new, global }, i)
+}
+
+// 解析 ! 之后的事件和单词选择，返回占用的字符数
+fn parse_reference(rest: &[char]) -> Option<(Event, Option<Words>, usize)> {
+    match rest.first()? {
+        '$' | '^' | '*' => {
+            let (start, end, len) = parse_designator(rest)?;
+            return Some((Event::Last, Some((start, end)), len));
+        }
+        ':' => {
+            let (start, end, len) = parse_designator(&rest[1..])?;
+            return Some((Event::Last, Some((start, end)), len + 1));
+        }
+        _ => {}
+    }
+    let (event, len) = parse_event(rest)?;
+    if rest.get(len) == Some(&':') {
+        if let Some((start, end, designator_len)) = parse_designator(&rest[len + 1..]) {
+            return Some((event, Some((start, end)), len + 1 + designator_len));
+        }
+    }
+    Some((event, None, len))
+}
+
+// 解析单词选择：^ $ * N N-M N-$ N*
+fn parse_designator(rest: &[char]) -> Option<(WordIndex, WordIndex, usize)> {
+    let number = |chars: &[char]| {
+        let len = chars.iter().take_while(|c| c.is_ascii_digit()).count();
+        let value: String = chars[..len].iter().collect();
+        value.parse::<usize>().ok().map(|n| (n, len))
+    };
+    match rest.first()? {
+        '^' => Some((WordIndex::At(1), WordIndex::At(1), 1)),
+        '$' => Some((WordIndex::Last, WordIndex::Last, 1)),
+        '*' => Some((WordIndex::At(1), WordIndex::Last, 1)),
+        _ => {
+            let (start, len) = number(rest)?;
+            match rest.get(len) {
+                Some('*') => Some((WordIndex::At(start), WordIndex::Last, len + 1)),
+                Some('-') => match rest.get(len + 1) {
+                    Some('$') => Some((WordIndex::At(start), WordIndex::Last, len + 2)),
+                    _ => {
+                        let (end, end_len) = number(&rest[len + 1..])?;
+                        Some((WordIndex::At(start), WordIndex::At(end), len + 1 + end_len))
+                    }
+                },
+                _ => Some((WordIndex::At(start), WordIndex::At(start), len)),
+            }
+        }
+    }
+}
+
+// 按空白拆分历史记录中的单词，引号内的空白不拆分，保留引号
+pub fn split_words(entry: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for c in entry.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+        } else if c == '"' || c == '\'' {
+            quote = Some(c);
+        } else if c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// 选取单词，* 在只有命令名时为空，其他下标越界时返回 None
+fn select_words(entry: &str, start: WordIndex, end: WordIndex) -> Option<String> {
+    let words = split_words(entry);
+    let last = words.len().checked_sub(1)?;
+    let resolve = |index| match index {
+        WordIndex::At(n) => n,
+        WordIndex::Last => last,
+    };
+    let (start, end) = (resolve(start), resolve(end));
+    if start > last && start == 1 && end == last {
+        return Some(String::new());
+    }
+    Some(words.get(start..=end)?.join(" "))
+}
+
+// 解析 ! 之后的事件，返回事件和占用的字符数
+fn parse_event(rest: &[char]) -> Option<(Event, usize)> {
+    let first = *rest.first()?;
+    if first == '!' {
+        return Some((Event::Last, 1));
+    }
+    if first.is_whitespace() || first == '=' || first == '(' {
+        return None;
+    }
+    let digits = |chars: &[char]| chars.iter().take_while(|c| c.is_ascii_digit()).count();
+    if first == '-' {
+        let len = digits(&rest[1..]);
+        let number: String = rest[1..=len].iter().collect();
+        return number.parse().ok().map(|n| (Event::Relative(n), len + 1));
+    }
+    if first.is_ascii_digit() {
+        let len = digits(rest);
+        let number: String = rest[..len].iter().collect();
+        return number.parse().ok().map(|n| (Event::Absolute(n), len));
+    }
+    let len = rest
+        .iter()
+        .take_while(|c| !c.is_whitespace() && !";|&<>\"':".contains(**c))
+        .count();
+    // 和 bash 一样，! 之后紧跟引号或 ;|&<> 时不是历史引用，避免空前缀匹配到上一条命令
+    if len == 0 {
+        return None;
+    }
+    let prefix: String = rest[..len].iter().collect();
+    Some((Event::Prefix(prefix), len))
+}
+
+fn find_event<'a>(event: &Event, history: &'a [String]) -> Option<&'a String> {
+    match event {
+        Event::Last => history.last(),
+        Event::Absolute(n) => history.get(n.checked_sub(1)?),
+        Event::Relative(n) => history.get(history.len().checked_sub(*n)?),
+        Event::Prefix(prefix) => history.iter().rev().find(|entry| entry.starts_with(prefix)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        ["ls -l", "echo hello", "git status"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_events() {
+        let history = history();
+        let expand = |line| expand(line, &history);
+        assert_eq!(expand("!!"), Ok(Some("git status".to_string())));
+        assert_eq!(expand("sudo !!"), Ok(Some("sudo git status".to_string())));
+        assert_eq!(expand("!1 /tmp"), Ok(Some("ls -l /tmp".to_string())));
+        assert_eq!(expand("!-2"), Ok(Some("echo hello".to_string())));
+        assert_eq!(expand("!ec"), Ok(Some("echo hello".to_string())));
+        assert_eq!(expand("!nope"), Err("!nope: event not found".to_string()));
+        assert_eq!(expand("!9"), Err("!9: event not found".to_string()));
+    }
+
+    #[test]
+    fn test_expand_words() {
+        let history = vec!["cp a.txt \"my file\" /tmp".to_string(), "ls".to_string()];
+        let expand = |line| expand(line, &history);
+        assert_eq!(expand("echo !$"), Ok(Some("echo ls".to_string())));
+        assert_eq!(expand("echo !*"), Ok(Some("echo ".to_string())));
+        assert_eq!(expand("vim !-2:1"), Ok(Some("vim a.txt".to_string())));
+        assert_eq!(
+            expand("echo !cp:*"),
+            Ok(Some("echo a.txt \"my file\" /tmp".to_string()))
+        );
+        assert_eq!(
+            expand("echo !cp:2-$"),
+            Ok(Some("echo \"my file\" /tmp".to_string()))
+        );
+        assert_eq!(expand("echo !cp:^"), Ok(Some("echo a.txt".to_string())));
+        assert_eq!(expand("echo !cp:0"), Ok(Some("echo cp".to_string())));
+        assert_eq!(
+            expand("echo !cp:5"),
+            Err("!cp:5: bad word specifier".to_string())
+        );
+        assert_eq!(
+            split_words("git commit -m 'a b'"),
+            vec!["git", "commit", "-m", "'a b'"]
+        );
+    }
+
+    #[test]
+    fn test_expand_substitutions() {
+        let history = vec![
+            "git push origin main".to_string(),
+            "cat a.txt a.txt".to_string(),
+        ];
+        let expand = |line| expand(line, &history);
+        assert_eq!(
+            expand("^a.txt^b.txt"),
+            Ok(Some("cat b.txt a.txt".to_string()))
+        );
+        assert_eq!(
+            expand("^cat^less^ | head"),
+            Ok(Some("less a.txt a.txt | head".to_string()))
+        );
+        assert_eq!(
+            expand("!!:gs/a.txt/b.txt/"),
+            Ok(Some("cat b.txt b.txt".to_string()))
+        );
+        assert_eq!(
+            expand("!git:s/main/dev/ -f"),
+            Ok(Some("git push origin dev -f".to_string()))
+        );
+        assert_eq!(
+            expand("!git:s|origin|& upstream|"),
+            Ok(Some("git push origin upstream main".to_string()))
+        );
+        assert_eq!(expand("echo !$:s/a/b/"), Ok(Some("echo b.txt".to_string())));
+        assert_eq!(
+            expand("!!:s/a.txt/x/:gs/a/y"),
+            Ok(Some("cyt x y.txt".to_string()))
+        );
+        assert_eq!(
+            expand("^nope^x"),
+            Err("^nope^x: substitution failed".to_string())
+        );
+        assert_eq!(
+            expand("!!:s/nope/x/ ok"),
+            Err("!!:s/nope/x/: substitution failed".to_string())
+        );
+        assert_eq!(expand("^a^b"), Ok(Some("cbt a.txt a.txt".to_string())));
+        assert_eq!(
+            super::expand("^a^b", &[]),
+            Err("!!: event not found".to_string())
+        );
+    }
+
+    #[test]
+    fn test_expand_literal() {
+        let history = history();
+        let expand = |line| expand(line, &history);
+        assert_eq!(expand("echo hi"), Ok(None));
+        assert_eq!(expand("echo 'wow!!'"), Ok(None));
+        assert_eq!(expand("echo \\!!"), Ok(None));
+        assert_eq!(expand("echo ! x"), Ok(None));
+        assert_eq!(expand("test a != b"), Ok(None));
+        assert_eq!(expand("git commit -m \"fix!\""), Ok(None));
+        assert_eq!(expand("echo a!|b"), Ok(None));
+        assert_eq!(expand("echo a!;ls"), Ok(None));
+        assert_eq!(
+            expand("echo \"!!\""),
+            Ok(Some("echo \"git status\"".to_string()))
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// 当前目录变化时运行的钩子（类似 zsh 的 chpwd），每次显示提示符前检查
+// 目录只会被 cd 等内建命令改变，所以在命令执行完之后检查一次就够了
+// 命令钩子（类似 zsh 的 preexec/precmd）在执行输入的命令之前和显示提示符之前运行
+// 执行策略在外部命令展开完参数、启动之前检查每一个命令，可以放行、要求确认、拒绝或改写命令
+use log::debug;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::shell::activation::{NodeActivation, VenvActivation};
+use crate::shell::policy::PolicyRules;
+use crate::shell::safety_net::RmSafetyNet;
+use crate::shell::tmux::TmuxIntegration;
+use crate::utils::config::Config;
+
+pub trait DirectoryHook {
+    fn on_change(&mut self, dir: &Path);
+}
+
+pub trait CommandHook {
+    // 执行输入的一行命令之前
+    fn preexec(&mut self, line: &str);
+    // 显示提示符之前，status 为上一条命令的退出码
+    fn precmd(&mut self, status: i32);
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Allow,
+    // 交互模式下显示提示并等待确认，脚本中拒绝执行
+    Confirm(String),
+    // 拒绝执行，交互模式下也不询问
+    Deny(String),
+    // 改为执行另一个命令和参数
+    Rewrite(String, Vec<String>),
+}
+
+pub trait ExecPolicy {
+    fn check(&mut self, program: &str, args: &[String]) -> Verdict;
+}
+
+pub fn exec_policies(config: &Config) -> Vec<Box<dyn ExecPolicy>> {
+    let mut policies: Vec<Box<dyn ExecPolicy>> = Vec::new();
+    if let Some(rules) = PolicyRules::new(config) {
+        policies.push(Box::new(rules));
+    }
+    if let Some(net) = RmSafetyNet::new(config) {
+        policies.push(Box::new(net));
+    }
+    policies
+}
+
+pub struct Hooks {
+    last_dir: Option<PathBuf>,
+    directory: Vec<Box<dyn DirectoryHook>>,
+    command: Vec<Box<dyn CommandHook>>,
+}
+
+impl Hooks {
+    pub fn new(config: &Config) -> Self {
+        let mut directory: Vec<Box<dyn DirectoryHook>> = Vec::new();
+        if config.auto_activate {
+            directory.push(Box::new(VenvActivation::default()));
+            directory.push(Box::new(NodeActivation::default()));
+        }
+        let mut command: Vec<Box<dyn CommandHook>> = Vec::new();
+        if let Some(tmux) = TmuxIntegration::new(config) {
+            command.push(Box::new(tmux));
+        }
+        Self {
+            last_dir: None,
+            directory,
+            command,
+        }
+    }
+
+    pub fn preexec(&mut self, line: &str) {
+        for hook in &mut self.command {
+            hook.preexec(line);
+        }
+    }
+
+    pub fn precmd(&mut self, status: i32) {
+        for hook in &mut self.command {
+            hook.precmd(status);
+        }
+    }
+
+    // 第一次调用时当作进入了启动时的目录
+    pub fn check_directory(&mut self) {
+        let Ok(dir) = env::current_dir() else {
+            return;
+        };
+        if self.last_dir.as_ref() == Some(&dir) {
+            return;
+        }
+        debug!("当前目录变为 {}", dir.display());
+        for hook in &mut self.directory {
+            hook.on_change(&dir);
+        }
+        self.last_dir = Some(dir);
+    }
+}
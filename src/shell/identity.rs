@@ -0,0 +1,50 @@
+// shell 的身份：启动时导出，嵌套的 shell、tmux 和 ssh 据此识别 zako
+//   SHLVL：每嵌套一层 shell 加 1
+//   ZAKO_PID：zako 进程的 pid，子进程据此找到所在的 shell；在 shell 中展开为执行展开的进程的 pid，
+//     进程替换等在子进程中展开时与 $$ 不同，$$ 始终是 shell 自身的 pid
+//   SHELL：登录 shell 把它设为 zako 自身，tmux 新开的窗口、编辑器中的终端同样运行 zako
+use log::debug;
+use std::env;
+
+pub fn export(login: bool) {
+    let level = next_shlvl(env::var("SHLVL").ok().as_deref());
+    env::set_var("SHLVL", level.to_string());
+    env::set_var("ZAKO_PID", std::process::id().to_string());
+    if login {
+        if let Ok(exe) = env::current_exe() {
+            debug!("登录 shell，SHELL 设为 {}", exe.display());
+            env::set_var("SHELL", exe);
+        }
+    }
+}
+
+// exec 用新的程序替换 shell 进程，层级不变，新启动的 shell 会再加 1
+pub fn before_exec() {
+    if let Some(level) = env::var("SHLVL")
+        .ok()
+        .and_then(|level| level.trim().parse::<u32>().ok())
+    {
+        env::set_var("SHLVL", level.saturating_sub(1).to_string());
+    }
+}
+
+// 与 bash 相同，没有设置或无效时从 1 开始
+fn next_shlvl(current: Option<&str>) -> u32 {
+    current
+        .and_then(|level| level.trim().parse::<u32>().ok())
+        .map_or(1, |level| level.saturating_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_shlvl() {
+        assert_eq!(next_shlvl(None), 1);
+        assert_eq!(next_shlvl(Some("1")), 2);
+        assert_eq!(next_shlvl(Some(" 3 ")), 4);
+        assert_eq!(next_shlvl(Some("-1")), 1);
+        assert_eq!(next_shlvl(Some("abc")), 1);
+    }
+}
@@ -0,0 +1,87 @@
+// zako --import-env（或 import_env = true）：启动时运行一次用户的登录 shell，
+// 把它在 .zprofile/.zshrc/.bash_profile 等文件中导出的环境变量（nvm、pyenv、cargo 的 PATH 等）导入 zako
+use log::{debug, error};
+use std::env;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// 属于登录 shell 自己的会话状态，不导入
+const SKIPPED_VARS: [&str; 6] = ["PWD", "OLDPWD", "SHLVL", "_", "PS1", "ZAKO_RESTRICTED"];
+// rc 文件可能向 stdout 打印内容，环境变量从这个标记之后开始
+const MARKER: &str = "\0__ZAKO_ENV__\0";
+
+pub fn import_login_env() {
+    let Ok(shell) = env::var("SHELL") else {
+        error!("无法导入环境变量: 没有设置 SHELL");
+        return;
+    };
+    // $SHELL 就是 zako 时没有可以导入的
+    let name = Path::new(&shell)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if name.ends_with("zako") {
+        debug!("SHELL 是 {}，跳过导入环境变量", shell);
+        return;
+    }
+    // -i 才会读取 .zshrc/.bashrc，标准输入接到 /dev/null 避免等待输入
+    let output = Command::new(&shell)
+        .args(["-l", "-i", "-c", "printf '\\0__ZAKO_ENV__\\0'; env -0"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            error!("无法导入环境变量: {} 退出码 {}", shell, output.status);
+            return;
+        }
+        Err(e) => {
+            error!("无法导入环境变量: 无法运行 {}: {}", shell, e);
+            return;
+        }
+    };
+    let vars = parse_env(&String::from_utf8_lossy(&output.stdout));
+    debug!("从 {} 导入 {} 个环境变量", shell, vars.len());
+    for (name, value) in vars {
+        env::set_var(name, value);
+    }
+}
+
+// 标记之后是 env -0 的输出：以 NUL 分隔的 NAME=VALUE，值中可以有换行
+fn parse_env(output: &str) -> Vec<(String, String)> {
+    let Some((_, vars)) = output.rsplit_once(MARKER) else {
+        return Vec::new();
+    };
+    vars.split('\0')
+        .filter_map(|entry| {
+            let (name, value) = entry.split_once('=')?;
+            let valid = !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            (valid && !SKIPPED_VARS.contains(&name)).then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env() {
+        let output =
+            "welcome!\n\0__ZAKO_ENV__\0PATH=/home/me/.cargo/bin:/usr/bin\0SHLVL=2\0MOTD=a\nb\0";
+        assert_eq!(
+            parse_env(output),
+            vec![
+                (
+                    "PATH".to_string(),
+                    "/home/me/.cargo/bin:/usr/bin".to_string()
+                ),
+                ("MOTD".to_string(), "a\nb".to_string()),
+            ]
+        );
+        assert!(parse_env("PATH=/usr/bin\0").is_empty());
+    }
+}
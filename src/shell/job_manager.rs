@@ -1,17 +1,88 @@
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local};
 
 use crate::shell::shell::CommandResult;
+use crate::utils::duration;
+use zakosh::i18n;
 
 use super::signals;
 use log::debug;
 use log::error;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum JobStatus {
     Done,
-    Killed,
+    // 被信号终止，附带信号编号和是否产生了 core dump
+    Killed(i32, bool),
     Continued,
     Stopped,
+    TimedOut,
+}
+
+// queue 内建命令排队的命令，在 gid 对应的作业结束后运行
+#[derive(Debug, Clone)]
+pub struct QueuedCommand {
+    pub id: usize,
+    pub gid: i32,
+    pub job_index: usize,
+    pub command: String,
+}
+
+impl fmt::Display for QueuedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.gid == 0 {
+            write!(f, "[q{}] {}", self.id, self.command)
+        } else {
+            write!(
+                f,
+                "[q{}] after %{}: {}",
+                self.id, self.job_index, self.command
+            )
+        }
+    }
+}
+
+// after 内建命令调度的命令，作业进程在 due 之前处于等待状态
+#[derive(Debug, Clone)]
+pub struct ScheduledCommand {
+    pub id: usize,
+    pub gid: i32,
+    pub due: SystemTime,
+    pub command: String,
+}
+
+impl fmt::Display for ScheduledCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let remaining = self
+            .due
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        let due: DateTime<Local> = self.due.into();
+        let text = i18n::tf(
+            "after.scheduled",
+            &[
+                &self.id.to_string(),
+                &due.format("%H:%M:%S").to_string(),
+                &duration::format_duration(remaining),
+                &self.command,
+            ],
+        );
+        write!(f, "{}", text)
+    }
+}
+
+// timeout 内建命令的计时器，超时后先发 SIGTERM，再过 kill_after 发 SIGKILL
+struct JobTimeout {
+    timed_out: Arc<AtomicBool>,
+    cancel: mpsc::Sender<()>,
+    handle: JoinHandle<()>,
 }
 
 #[derive(Debug, Clone)]
@@ -44,10 +115,14 @@ impl Job {
 impl fmt::Display for Job {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let status = match self.status {
-            JobStatus::Done => "done",
-            JobStatus::Killed => "killed",
-            JobStatus::Continued => "continued",
-            JobStatus::Stopped => "stopped",
+            JobStatus::Done => "done".to_string(),
+            JobStatus::Killed(sig, false) => format!("killed ({})", signals::signal_name(sig)),
+            JobStatus::Killed(sig, true) => {
+                format!("killed ({}, core dumped)", signals::signal_name(sig))
+            }
+            JobStatus::Continued => "continued".to_string(),
+            JobStatus::Stopped => "stopped".to_string(),
+            JobStatus::TimedOut => "timed out".to_string(),
         };
         let mark = if self.is_current {
             "+"
@@ -64,14 +139,127 @@ impl fmt::Display for Job {
     }
 }
 
-#[derive(Clone)]
 pub struct JobManager {
     jobs: Vec<Job>,
+    timeouts: HashMap<i32, JobTimeout>,
+    queue: Vec<QueuedCommand>,
+    next_queue_id: usize,
+    scheduled: Vec<ScheduledCommand>,
+    next_schedule_id: usize,
 }
 
 impl JobManager {
     pub fn new() -> Self {
-        Self { jobs: Vec::new() }
+        Self {
+            jobs: Vec::new(),
+            timeouts: HashMap::new(),
+            queue: Vec::new(),
+            next_queue_id: 1,
+            scheduled: Vec::new(),
+            next_schedule_id: 1,
+        }
+    }
+
+    // 记录 after 调度的作业
+    pub fn schedule(&mut self, gid: i32, due: SystemTime, command: String) -> ScheduledCommand {
+        let scheduled = ScheduledCommand {
+            id: self.next_schedule_id,
+            gid,
+            due,
+            command,
+        };
+        self.next_schedule_id += 1;
+        self.scheduled.push(scheduled.clone());
+        scheduled
+    }
+
+    // 还没到时间的调度命令
+    pub fn get_scheduled(&mut self) -> &Vec<ScheduledCommand> {
+        let now = SystemTime::now();
+        let jobs = &self.jobs;
+        self.scheduled
+            .retain(|s| s.due > now && jobs.iter().any(|job| job.gid == s.gid));
+        &self.scheduled
+    }
+
+    // 取消还在等待中的调度命令，结束其作业进程
+    pub fn cancel_scheduled(&mut self, id: usize) -> Result<ScheduledCommand, String> {
+        let pos = self
+            .get_scheduled()
+            .iter()
+            .position(|s| s.id == id)
+            .ok_or_else(|| i18n::tf("after.not_scheduled", &[&id.to_string()]))?;
+        let scheduled = self.scheduled.remove(pos);
+        unsafe {
+            libc::kill(-scheduled.gid, libc::SIGKILL);
+        }
+        Ok(scheduled)
+    }
+
+    // 把命令排在当前作业之后，没有作业时立即可以运行
+    pub fn queue_command(&mut self, command: String) -> QueuedCommand {
+        let (gid, job_index) = self
+            .jobs
+            .iter()
+            .find(|job| job.is_current)
+            .map(|job| (job.gid, job.index))
+            .unwrap_or((0, 0));
+        let queued = QueuedCommand {
+            id: self.next_queue_id,
+            gid,
+            job_index,
+            command,
+        };
+        self.next_queue_id += 1;
+        self.queue.push(queued.clone());
+        queued
+    }
+
+    pub fn get_queue(&self) -> &Vec<QueuedCommand> {
+        &self.queue
+    }
+
+    // 取出所等待的作业已经结束的排队命令
+    pub fn take_ready_queue(&mut self) -> Vec<QueuedCommand> {
+        let jobs = &self.jobs;
+        let (ready, pending) = std::mem::take(&mut self.queue)
+            .into_iter()
+            .partition(|queued| !jobs.iter().any(|job| job.gid == queued.gid));
+        self.queue = pending;
+        ready
+    }
+
+    // 收集 SIGCHLD 通知的子进程状态，更新后台作业
+    pub fn update_bg_jobs(&mut self) {
+        signals::reap_children();
+        let jobs: Vec<(i32, i32)> = self.jobs.iter().map(|job| (job.gid, job.pid)).collect();
+        for (gid, pid) in jobs {
+            if signals::pop_reap_map(pid).is_some() {
+                debug!("后台进程 exited: {}", pid);
+                self.mark_job_as_done(gid, pid, JobStatus::Done);
+            } else if let Some((sig, core_dumped)) = signals::killed_map_pop(pid) {
+                debug!("后台进程 signaled: {}", pid);
+                self.mark_job_as_done(gid, pid, JobStatus::Killed(sig, core_dumped));
+            } else if signals::pop_stopped_map(pid) {
+                debug!("后台进程 stopped: {}", pid);
+                self.mark_job_stopped(gid, pid, true);
+            } else if signals::pop_cont_map(pid) {
+                debug!("后台进程 continued: {}", pid);
+                if let Some(job) = self.jobs.iter_mut().find(|job| job.pid == pid) {
+                    job.status = JobStatus::Continued;
+                }
+            }
+        }
+        self.reap_orphans();
+    }
+
+    // 回收不属于任何作业的子进程，每次显示提示符前和 shell 退出时运行，避免留下僵尸进程和状态记录
+    pub fn reap_orphans(&mut self) {
+        let tracked: Vec<i32> = self.jobs.iter().map(|job| job.pid).collect();
+        let reaped = signals::reap_orphans(&tracked);
+        if reaped > 0 {
+            debug!("回收 {} 个不属于作业的子进程", reaped);
+        }
     }
 
     pub fn get_jobs(&self) -> &Vec<Job> {
@@ -86,6 +274,19 @@ impl JobManager {
         index
     }
 
+    // 按作业说明查找作业：%N、%+/%%/% 当前作业、%- 上一个作业、%name 以 name 开头的作业
+    pub fn find_job(&self, spec: &str) -> Option<&Job> {
+        let spec = spec.strip_prefix('%')?;
+        match spec {
+            "" | "+" | "%" => self.jobs.iter().find(|job| job.is_current),
+            "-" => self.jobs.iter().find(|job| job.is_previous),
+            _ => match spec.parse::<usize>() {
+                Ok(index) => self.jobs.iter().find(|job| job.index == index),
+                Err(_) => self.jobs.iter().find(|job| job.command.starts_with(spec)),
+            },
+        }
+    }
+
     pub fn add_job(&mut self, gid: i32, pid: i32, command: String) {
         let index = self.find_available_index();
         // 将当前任务变为上一个任务
@@ -104,6 +305,24 @@ impl JobManager {
         self.update_marks(index);
     }
 
+    // 修改作业显示的命令，用于由多个进程组成的作业
+    pub fn rename_job(&mut self, gid: i32, command: String) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.gid == gid) {
+            job.command = command;
+        }
+    }
+
+    // 添加后台作业，返回作业编号
+    pub fn add_bg_job(&mut self, gid: i32, pid: i32, command: String) -> usize {
+        self.add_job(gid, pid, command);
+        let job = self.jobs.iter_mut().find(|job| job.pid == pid);
+        job.map(|job| {
+            job.is_bg = true;
+            job.index
+        })
+        .unwrap_or_default()
+    }
+
     pub fn remove_job(&mut self, _gid: i32, pid: i32) -> Option<Job> {
         if let Some(pos) = self.jobs.iter().position(|job| job.pid == pid) {
             let was_current = self.jobs[pos].is_current;
@@ -134,6 +353,8 @@ impl JobManager {
 
         let job_index = self.jobs[pos].index;
         self.jobs[pos].status = JobStatus::Continued;
+        // 回到前台后结束时不再报告
+        self.jobs[pos].is_bg = false;
 
         self.update_marks(job_index);
         Some(self.jobs[pos].clone())
@@ -162,28 +383,80 @@ impl JobManager {
         }
     }
 
+    // 为进程组 gid 启动超时计时器
+    pub fn start_timeout(&mut self, gid: i32, duration: Duration, kill_after: Duration) {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let flag = timed_out.clone();
+        let handle = thread::spawn(move || {
+            // 收到取消或发送端被丢弃，说明作业已经结束
+            if cancelled.recv_timeout(duration) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            flag.store(true, Ordering::SeqCst);
+            unsafe {
+                libc::kill(-gid, libc::SIGTERM);
+                // 被暂停的作业要先继续才能处理 SIGTERM
+                libc::kill(-gid, libc::SIGCONT);
+            }
+            if cancelled.recv_timeout(kill_after) == Err(RecvTimeoutError::Timeout) {
+                unsafe {
+                    libc::kill(-gid, libc::SIGKILL);
+                }
+            }
+        });
+        self.timeouts.insert(
+            gid,
+            JobTimeout {
+                timed_out,
+                cancel,
+                handle,
+            },
+        );
+    }
+
+    // 作业结束后停止计时器，返回是否超时
+    pub fn finish_timeout(&mut self, gid: i32) -> bool {
+        match self.timeouts.remove(&gid) {
+            Some(timeout) => {
+                let _ = timeout.cancel.send(());
+                let _ = timeout.handle.join();
+                timeout.timed_out.load(Ordering::SeqCst)
+            }
+            None => false,
+        }
+    }
+
+    fn is_timed_out(&self, gid: i32) -> bool {
+        self.timeouts
+            .get(&gid)
+            .map(|timeout| timeout.timed_out.load(Ordering::SeqCst))
+            .unwrap_or(false)
+    }
+
     fn mark_job_as_done(&mut self, gid: i32, pid: i32, status: JobStatus) {
+        let timed_out = self.is_timed_out(gid);
         if let Some(mut job) = self.remove_job(gid, pid) {
-            job.status = status;
-            if job.is_bg {
-                println!("");
+            job.status = if timed_out {
+                JobStatus::TimedOut
+            } else {
+                status
+            };
+            if job.is_bg || timed_out {
+                println!();
                 println!("{}", &job);
             }
         }
     }
 
     fn mark_job_stopped(&mut self, _gid: i32, pid: i32, report: bool) {
-        let job = self.jobs.iter_mut().find(|job| job.pid == pid);
-        match job {
-            Some(job) => {
-                job.status = JobStatus::Stopped;
-                job.is_bg = true;
-                if report {
-                    println!("");
-                    println!("{}", &job);
-                }
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.pid == pid) {
+            job.status = JobStatus::Stopped;
+            job.is_bg = true;
+            if report {
+                println!();
+                println!("{}", &job);
             }
-            None => {}
         }
     }
 
@@ -191,10 +464,10 @@ impl JobManager {
         let mut cmd_result = CommandResult::new();
         let mut count_waited = 0;
         let count_child = pids.len();
-        if count_child == 0 {
+        let Some(pid_last) = pids.last() else {
             return cmd_result;
-        }
-        let pid_last = pids.last().unwrap();
+        };
+        cmd_result.statuses = vec![0; count_child];
 
         loop {
             let ws = signals::waitpidx(-1, true);
@@ -207,7 +480,7 @@ impl JobManager {
                 }
 
                 error!("jobc unexpected waitpid error: {}", err);
-                cmd_result = CommandResult::from_status(gid, err as i32);
+                cmd_result = CommandResult::from_status(err as i32);
                 break;
             }
 
@@ -231,6 +504,7 @@ impl JobManager {
                     // for stop signal of fg job (current job)
                     // i.e. Ctrl-Z is pressed on the fg job
                     self.mark_job_stopped(gid, pid, true);
+                    cmd_result.stopped = true;
                 } else {
                     // for stop signal of bg jobs
                     signals::insert_stopped_map(pid);
@@ -245,15 +519,27 @@ impl JobManager {
             } else if ws.is_signaled() {
                 debug!("前台进程 signaled: {}", pid);
                 if is_a_fg_child {
-                    self.mark_job_as_done(gid, pid, JobStatus::Killed);
+                    self.mark_job_as_done(
+                        gid,
+                        pid,
+                        JobStatus::Killed(ws.get_signal(), ws.core_dumped()),
+                    );
                 } else {
-                    signals::killed_map_insert(pid, ws.get_signal());
+                    signals::killed_map_insert(pid, ws.get_signal(), ws.core_dumped());
+                }
+            }
+
+            if is_a_fg_child && (ws.is_exited() || ws.is_signaled()) {
+                if let Some(pos) = pids.iter().position(|p| *p == pid) {
+                    cmd_result.statuses[pos] = ws.get_status();
                 }
             }
 
             if is_a_fg_child && pid == *pid_last {
                 let status = ws.get_status();
                 cmd_result.status = status;
+                cmd_result.signal = ws.is_signaled().then(|| ws.get_signal());
+                cmd_result.core_dumped = ws.core_dumped();
             }
 
             if count_waited >= count_child {
@@ -0,0 +1,178 @@
+// 作业选择器：有多个作业时不带参数的 fg 和 jobs --pick 在终端上列出作业，
+// ↑/↓（或 j/k、Ctrl-P/Ctrl-N）选择，Enter 恢复到前台，x 结束作业，q/Esc/Ctrl-C 取消
+use nix::sys::termios::{self, SetArg, Termios};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+
+use crate::shell::job_manager::Job;
+use crate::utils::theme::Theme;
+
+const HINT: &str = "↑/↓ 选择  Enter 前台  x 结束  q 取消";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Foreground,
+    Kill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Kill,
+    Cancel,
+    Other,
+}
+
+// 一次 read 读到的字节，方向键的转义序列总是一起到达，单独的 Esc 为取消
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        let key = match byte {
+            0x1b => match rest {
+                [b'[' | b'O', b'A', tail @ ..] => {
+                    rest = tail;
+                    Key::Up
+                }
+                [b'[' | b'O', b'B', tail @ ..] => {
+                    rest = tail;
+                    Key::Down
+                }
+                [] => Key::Cancel,
+                // 其他转义序列整个忽略
+                _ => {
+                    rest = &[];
+                    Key::Other
+                }
+            },
+            b'k' | 0x10 => Key::Up,
+            b'j' | 0x0e => Key::Down,
+            b'\r' | b'\n' => Key::Enter,
+            b'x' => Key::Kill,
+            b'q' | 0x03 | 0x04 => Key::Cancel,
+            _ => Key::Other,
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+struct Picker {
+    selected: usize,
+    len: usize,
+}
+
+impl Picker {
+    // 返回 Some 时选择结束，Some(None) 表示取消；上下移动到头时回到另一端
+    fn handle(&mut self, key: Key) -> Option<Option<(usize, Action)>> {
+        match key {
+            Key::Up => self.selected = (self.selected + self.len - 1) % self.len,
+            Key::Down => self.selected = (self.selected + 1) % self.len,
+            Key::Enter => return Some(Some((self.selected, Action::Foreground))),
+            Key::Kill => return Some(Some((self.selected, Action::Kill))),
+            Key::Cancel => return Some(None),
+            Key::Other => {}
+        }
+        None
+    }
+}
+
+// 读取按键期间把终端设为原始模式，结束时恢复
+struct RawTerminal {
+    tty: File,
+    saved: Termios,
+}
+
+impl RawTerminal {
+    fn open() -> io::Result<Self> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let saved = termios::tcgetattr(&tty)?;
+        let mut raw = saved.clone();
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(&tty, SetArg::TCSADRAIN, &raw)?;
+        Ok(Self { tty, saved })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = termios::tcsetattr(&self.tty, SetArg::TCSADRAIN, &self.saved);
+    }
+}
+
+// 选中的作业在 jobs 中的位置和要执行的操作，取消时返回 None
+pub fn pick(jobs: &[Job], theme: &Theme) -> io::Result<Option<(usize, Action)>> {
+    if jobs.is_empty() {
+        return Ok(None);
+    }
+    let mut terminal = RawTerminal::open()?;
+    let mut picker = Picker {
+        selected: jobs.iter().position(|job| job.is_current).unwrap_or(0),
+        len: jobs.len(),
+    };
+    // 原始模式下没有换行转换，每行以 \r\n 结束
+    let lines = jobs.len() + 1;
+    let mut buf = [0u8; 16];
+    let result = loop {
+        let mut screen = String::new();
+        for (i, job) in jobs.iter().enumerate() {
+            let line = if i == picker.selected {
+                (theme.menu_selected_style)(format!("> {}", job))
+            } else {
+                (theme.menu_style)(format!("  {}", job))
+            };
+            screen.push_str(&format!("\x1b[2K{}\r\n", line));
+        }
+        screen.push_str(&format!("\x1b[2K{}", HINT));
+        terminal.tty.write_all(screen.as_bytes())?;
+        terminal.tty.flush()?;
+
+        let n = terminal.tty.read(&mut buf)?;
+        let outcome = if n == 0 {
+            Some(None)
+        } else {
+            parse_keys(&buf[..n])
+                .into_iter()
+                .find_map(|key| picker.handle(key))
+        };
+        // 回到列表的第一行，重新绘制或清除
+        write!(terminal.tty, "\r\x1b[{}A", lines - 1)?;
+        if let Some(outcome) = outcome {
+            break outcome;
+        }
+    };
+    write!(terminal.tty, "\x1b[J")?;
+    terminal.tty.flush()?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_and_navigation() {
+        assert_eq!(
+            parse_keys(b"\x1b[A\x1bOBjx"),
+            [Key::Up, Key::Down, Key::Down, Key::Kill]
+        );
+        assert_eq!(parse_keys(b"\x1b"), [Key::Cancel]);
+        assert_eq!(parse_keys(b"\x1b[5~q"), [Key::Other]);
+        assert_eq!(parse_keys(b"\r"), [Key::Enter]);
+
+        let mut picker = Picker {
+            selected: 0,
+            len: 3,
+        };
+        assert_eq!(picker.handle(Key::Up), None);
+        assert_eq!(picker.selected, 2);
+        assert_eq!(picker.handle(Key::Down), None);
+        assert_eq!(picker.selected, 0);
+        assert_eq!(picker.handle(Key::Other), None);
+        assert_eq!(picker.handle(Key::Kill), Some(Some((0, Action::Kill))));
+        assert_eq!(picker.handle(Key::Cancel), Some(None));
+    }
+}
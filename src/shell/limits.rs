@@ -0,0 +1,139 @@
+// limit cpu=60s mem=1G nice=10 cmd ...：在子进程 exec 之前设置资源限制和优先级
+// 限制同时设为软限制和硬限制，命令自己无法再提高；超过 shell 当前的硬限制时使用硬限制
+use nix::sys::resource::{self, Resource};
+use std::io;
+
+use crate::utils::duration;
+use zakosh::i18n;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ResourceLimits {
+    limits: Vec<(Resource, u64)>,
+    // 绝对的 nice 值，-20 到 19，降低 nice 值需要权限
+    nice: Option<i32>,
+}
+
+impl ResourceLimits {
+    // 解析开头的 KEY=VALUE，返回限制和用掉的参数个数
+    pub fn parse(args: &[String]) -> Result<(Self, usize), String> {
+        let mut limits = Self::default();
+        let mut used = 0;
+        for arg in args {
+            let Some((key, value)) = arg.split_once('=') else {
+                break;
+            };
+            let invalid = |e: String| format!("{}: {}", key, e);
+            match key {
+                "cpu" => {
+                    let seconds = duration::parse_duration(value).map_err(invalid)?;
+                    // 不足 1 秒的时长向上取整，0 秒会让命令立即收到 SIGXCPU
+                    let seconds = seconds.as_secs() + u64::from(seconds.subsec_nanos() > 0);
+                    limits.limits.push((Resource::RLIMIT_CPU, seconds.max(1)));
+                }
+                "mem" => limits
+                    .limits
+                    .push((Resource::RLIMIT_AS, parse_size(value).map_err(invalid)?)),
+                "fsize" => limits
+                    .limits
+                    .push((Resource::RLIMIT_FSIZE, parse_size(value).map_err(invalid)?)),
+                "core" => limits
+                    .limits
+                    .push((Resource::RLIMIT_CORE, parse_size(value).map_err(invalid)?)),
+                "stack" => limits
+                    .limits
+                    .push((Resource::RLIMIT_STACK, parse_size(value).map_err(invalid)?)),
+                "nofile" => limits.limits.push((
+                    Resource::RLIMIT_NOFILE,
+                    parse_count(value).map_err(invalid)?,
+                )),
+                "nproc" => limits
+                    .limits
+                    .push((Resource::RLIMIT_NPROC, parse_count(value).map_err(invalid)?)),
+                "nice" => match value.parse::<i32>() {
+                    Ok(nice) if (-20..=19).contains(&nice) => limits.nice = Some(nice),
+                    _ => return Err(i18n::tf("limit.invalid_nice", &[value])),
+                },
+                // 不认识的 KEY=VALUE 当作命令，例如环境变量赋值
+                _ => break,
+            }
+            used += 1;
+        }
+        Ok((limits, used))
+    }
+
+    // 在子进程中 exec 之前调用
+    pub fn apply(&self) -> io::Result<()> {
+        for &(resource, value) in &self.limits {
+            let (_, hard) = resource::getrlimit(resource)?;
+            let soft = value.min(hard);
+            // CPU 时间达到软限制时收到 SIGXCPU，硬限制多留 1 秒，否则直接被 SIGKILL 杀死
+            let hard = if resource == Resource::RLIMIT_CPU {
+                value.saturating_add(1).min(hard)
+            } else {
+                soft
+            };
+            resource::setrlimit(resource, soft, hard)?;
+        }
+        if let Some(nice) = self.nice {
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+// 字节数，可以带 K/M/G/T 单位（1024 进制），例如 512M、1.5G
+fn parse_size(value: &str) -> Result<u64, String> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| i18n::tf("limit.invalid_size", &[value]))?;
+    let scale: u64 = match unit.to_ascii_uppercase().trim_end_matches(['B', 'I']) {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(i18n::tf("limit.invalid_size_unit", &[unit])),
+    };
+    Ok((number * scale as f64) as u64)
+}
+
+fn parse_count(value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_| i18n::tf("limit.invalid_count", &[value]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_limits() -> Result<(), String> {
+        let (limits, used) =
+            ResourceLimits::parse(&strings(&["cpu=1.5", "mem=1G", "nice=10", "make", "-j"]))?;
+        assert_eq!(used, 3);
+        assert_eq!(
+            limits.limits,
+            [(Resource::RLIMIT_CPU, 2), (Resource::RLIMIT_AS, 1 << 30)]
+        );
+        assert_eq!(limits.nice, Some(10));
+
+        let (_, used) = ResourceLimits::parse(&strings(&["FOO=1", "make"]))?;
+        assert_eq!(used, 0);
+        assert!(ResourceLimits::parse(&strings(&["mem=1X"])).is_err());
+        assert!(ResourceLimits::parse(&strings(&["nice=20"])).is_err());
+        assert_eq!(parse_size("512MiB"), Ok(512 << 20));
+        assert_eq!(parse_size("64k"), Ok(64 << 10));
+        Ok(())
+    }
+}
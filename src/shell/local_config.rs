@@ -0,0 +1,278 @@
+// 项目目录下的 .zako.toml：进入受信任的目录（或它的子目录）时加载，离开时卸载
+//   [aliases] 别名，[env] 环境变量，[prompt] 和 [styles] 与主题文件相同，覆盖当前主题
+// 只加载用 trust 信任过的文件，信任记录的是文件内容的 SHA-256，文件修改后需要重新信任；
+// 检查过的内容原样用于加载和主题，不会再次读取文件
+use log::{debug, error};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::utils::digest::sha256_hex;
+use crate::utils::path;
+use crate::utils::toml::{self, Problem};
+
+pub const LOCAL_CONFIG_FILE: &str = ".zako.toml";
+
+const LOCAL_SCHEMA: [(&str, &[&str]); 4] = [
+    ("aliases", &[]),
+    ("env", &[]),
+    ("prompt", &["segments", "right"]),
+    ("styles", &[]),
+];
+
+// 已加载的本地配置，env 记录设置前的值，卸载时恢复
+struct Loaded {
+    path: PathBuf,
+    // 通过信任检查的内容
+    text: String,
+    aliases: HashMap<String, String>,
+    env: Vec<(String, Option<OsString>)>,
+}
+
+pub struct LocalConfig {
+    trust_file: PathBuf,
+    active: Option<Loaded>,
+    // 上次检查时找到的文件、它的内容和信任列表的内容，都没变时不用重新加载
+    signature: Option<(Option<PathBuf>, String, String)>,
+}
+
+impl LocalConfig {
+    pub fn new(trust_file: PathBuf) -> Self {
+        Self {
+            trust_file,
+            active: None,
+            signature: None,
+        }
+    }
+
+    // 当前生效的 .zako.toml
+    pub fn path(&self) -> Option<&Path> {
+        self.active.as_ref().map(|loaded| loaded.path.as_path())
+    }
+
+    // 当前生效的 .zako.toml 和通过信任检查时读到的内容
+    pub fn source(&self) -> Option<(&Path, &str)> {
+        self.active
+            .as_ref()
+            .map(|loaded| (loaded.path.as_path(), loaded.text.as_str()))
+    }
+
+    pub fn aliases(&self) -> HashMap<String, String> {
+        self.active
+            .as_ref()
+            .map(|loaded| loaded.aliases.clone())
+            .unwrap_or_default()
+    }
+
+    // 每次显示提示符前检查，生效的文件变化时返回 true
+    pub fn update(&mut self, dir: &Path) -> bool {
+        let found = path::find_upwards(dir, &[LOCAL_CONFIG_FILE]);
+        let text = match &found {
+            Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+                error!("无法读取 {}: {}", path.display(), e);
+                String::new()
+            }),
+            None => String::new(),
+        };
+        let trusted = fs::read_to_string(&self.trust_file).unwrap_or_default();
+        let signature = (found, text, trusted);
+        if self.signature.as_ref() == Some(&signature) {
+            return false;
+        }
+        let (found, text, _) = self.signature.insert(signature).clone();
+
+        let before = self.path().map(Path::to_path_buf);
+        self.unload();
+        if let Some(path) = found {
+            if is_trusted(&self.trust_file, &path, &text) {
+                self.load(path, &text);
+            } else {
+                eprintln!("zako: {} 没有被信任，运行 trust 后加载", path.display());
+            }
+        }
+        // 文件内容变化时即使路径相同也要重新加载主题
+        before.is_some() || self.active.is_some()
+    }
+
+    fn load(&mut self, path: PathBuf, text: &str) {
+        debug!("加载本地配置 {}", path.display());
+        let document = toml::parse(text);
+        let mut problems: Vec<Problem> = document
+            .errors
+            .iter()
+            .map(|(line, message)| Problem::new(&path, *line, message.clone()))
+            .collect();
+        problems.extend(toml::check_keys(&path, &document, &LOCAL_SCHEMA));
+        let mut aliases = HashMap::new();
+        let mut saved = Vec::new();
+        for entry in document.section("aliases").chain(document.section("env")) {
+            let Some(value) = entry.value.as_str() else {
+                let message = format!("应为字符串，而不是{}", entry.value.type_name());
+                problems.push(Problem::new(&path, entry.line, message));
+                continue;
+            };
+            if entry.section == "aliases" {
+                aliases.insert(entry.key.clone(), value.to_string());
+            } else {
+                saved.push((entry.key.clone(), env::var_os(&entry.key)));
+                env::set_var(&entry.key, shellexpand::tilde(value).as_ref());
+            }
+        }
+        for problem in problems {
+            error!("{}", problem);
+        }
+        self.active = Some(Loaded {
+            path,
+            text: text.to_string(),
+            aliases,
+            env: saved,
+        });
+    }
+
+    // 按设置的相反顺序恢复环境变量，同一个变量设置多次时恢复到最早的值
+    fn unload(&mut self) {
+        let Some(loaded) = self.active.take() else {
+            return;
+        };
+        debug!("卸载本地配置 {}", loaded.path.display());
+        for (name, value) in loaded.env.into_iter().rev() {
+            match value {
+                Some(value) => env::set_var(&name, value),
+                None => env::remove_var(&name),
+            }
+        }
+    }
+}
+
+// 文件内容的摘要，与路径一起记录在信任列表中
+fn digest(text: &str) -> String {
+    sha256_hex(text.as_bytes())
+}
+
+// 信任列表每行为 摘要 路径
+fn read_trusted(trust_file: &Path) -> Vec<(String, PathBuf)> {
+    fs::read_to_string(trust_file)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (digest, path) = line.split_once(' ')?;
+            Some((digest.to_string(), PathBuf::from(path)))
+        })
+        .collect()
+}
+
+fn write_trusted(trust_file: &Path, entries: &[(String, PathBuf)]) -> io::Result<()> {
+    if let Some(parent) = trust_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text: String = entries
+        .iter()
+        .map(|(digest, path)| format!("{} {}\n", digest, path.display()))
+        .collect();
+    fs::write(trust_file, text)
+}
+
+fn is_trusted(trust_file: &Path, path: &Path, text: &str) -> bool {
+    let digest = digest(text);
+    read_trusted(trust_file)
+        .iter()
+        .any(|(d, p)| *d == digest && p == path)
+}
+
+// trust [DIR] 信任目录（默认当前目录）下的 .zako.toml；trust -r [DIR] 取消信任；trust -l 列出
+pub fn trust(trust_file: &Path, args: &[String]) -> io::Result<()> {
+    let (revoke, rest) = match args.first().map(|s| s.as_str()) {
+        Some("-l") if args.len() == 1 => {
+            for (_, path) in read_trusted(trust_file) {
+                println!("{}", path.display());
+            }
+            return Ok(());
+        }
+        Some("-r") => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let dir = match rest {
+        [] => env::current_dir()?,
+        [dir] => PathBuf::from(shellexpand::tilde(dir).as_ref()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "用法: trust [-r] [DIR] | trust -l",
+            ))
+        }
+    };
+    let path = fs::canonicalize(&dir)
+        .map_err(|e| io::Error::new(e.kind(), format!("trust: {}: {}", dir.display(), e)))?
+        .join(LOCAL_CONFIG_FILE);
+    let mut entries = read_trusted(trust_file);
+    entries.retain(|(_, p)| *p != path);
+    if !revoke {
+        let text = fs::read_to_string(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("trust: {}: {}", path.display(), e)))?;
+        entries.push((digest(&text), path));
+    }
+    write_trusted(trust_file, &entries)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_and_unload() {
+        let dir = env::temp_dir().join(format!("zako_local_config_{}", std::process::id()));
+        let project = dir.join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        let project = fs::canonicalize(&project).unwrap();
+        let trust_file = dir.join("trusted");
+        fs::write(
+            project.join(LOCAL_CONFIG_FILE),
+            "[aliases]\nb = \"cargo build\"\n[env]\nZAKO_LOCAL_TEST = \"on\"\n",
+        )
+        .unwrap();
+
+        // 没有信任时不加载
+        let mut local = LocalConfig::new(trust_file.clone());
+        assert!(!local.update(&project.join("src")));
+        assert!(local.path().is_none());
+
+        let dir_arg = project.display().to_string();
+        trust(&trust_file, std::slice::from_ref(&dir_arg)).unwrap();
+        assert!(local.update(&project.join("src")));
+        assert_eq!(local.aliases().get("b").unwrap(), "cargo build");
+        assert_eq!(env::var("ZAKO_LOCAL_TEST").unwrap(), "on");
+        assert!(!local.update(&project));
+        // 信任列表记录内容的 SHA-256，主题使用检查过的内容
+        let (path, text) = local.source().unwrap();
+        assert_eq!(path, project.join(LOCAL_CONFIG_FILE));
+        let trusted = read_trusted(&trust_file);
+        assert_eq!(trusted[0].0, sha256_hex(text.as_bytes()));
+        assert_eq!(trusted[0].0.len(), 64);
+
+        // 离开项目目录时恢复环境变量
+        assert!(local.update(&dir));
+        assert!(local.aliases().is_empty());
+        assert!(env::var_os("ZAKO_LOCAL_TEST").is_none());
+
+        // 修改后需要重新信任
+        fs::write(
+            project.join(LOCAL_CONFIG_FILE),
+            "[env]\nZAKO_LOCAL_TEST = \"changed\"\n",
+        )
+        .unwrap();
+        local.update(&project);
+        assert!(local.path().is_none());
+        trust(&trust_file, std::slice::from_ref(&dir_arg)).unwrap();
+        local.update(&project);
+        assert_eq!(env::var("ZAKO_LOCAL_TEST").unwrap(), "changed");
+        trust(&trust_file, &["-r".to_string(), dir_arg]).unwrap();
+        local.update(&project);
+        assert!(local.path().is_none());
+        assert!(env::var_os("ZAKO_LOCAL_TEST").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,49 @@
+mod activation;
+mod aliases;
+mod autoload;
+mod bench;
+mod builtins;
+mod calc;
+mod clipboard;
+mod completion;
+mod completion_cache;
+mod coproc;
+mod env_diff;
 mod executor;
+mod for_loop;
+mod history;
+mod hooks;
+mod identity;
+mod import_env;
 mod job_manager;
-mod parser;
+mod job_picker;
+mod limits;
+mod local_config;
+mod options;
+mod path_cache;
+mod path_usage;
+mod policy;
+mod progress;
 mod readline;
+mod redirect;
+mod safety_net;
+mod sandbox;
+mod select;
+mod session_log;
+#[allow(clippy::module_inception)]
 mod shell;
 mod signals;
+mod stty;
+mod temp_resources;
+mod terminal;
+mod theme_manager;
+mod ticker;
+mod tmux;
+mod update;
 mod variable;
+mod waitfor;
 
+use zakosh::parser;
+
+pub use env_diff::save_inherited as save_inherited_env;
 pub use shell::Shell;
@@ -0,0 +1,138 @@
+// shell 选项，通过 set -o/+o name 或 set -x/+x 短选项修改
+#[derive(Debug, Clone, Default)]
+pub struct ShellOptions {
+    // 命令失败时停止执行（set -e）
+    pub errexit: bool,
+    // 允许重定向到 /dev/tcp/host/port 与 /dev/udp/host/port
+    pub devtcp: bool,
+    // 历史扩展后把结果放回编辑器确认，而不是立即执行
+    pub histverify: bool,
+    // 保存上一条前台命令的标准输出，$ZAKO_LAST_OUTPUT 和 lastout 读取（子进程的 stdout 不再是终端）
+    pub captureoutput: bool,
+    // shell 退出（包括收到 SIGTERM）时向所有作业发送 SIGHUP
+    pub huponexit: bool,
+}
+
+// 选项名与对应的短选项
+const OPTIONS: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    ("devtcp", None),
+    ("histverify", None),
+    ("captureoutput", None),
+    ("huponexit", None),
+];
+
+impl ShellOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "errexit" => Some(&mut self.errexit),
+            "devtcp" => Some(&mut self.devtcp),
+            "histverify" => Some(&mut self.histverify),
+            "captureoutput" => Some(&mut self.captureoutput),
+            "huponexit" => Some(&mut self.huponexit),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "errexit" => Some(self.errexit),
+            "devtcp" => Some(self.devtcp),
+            "histverify" => Some(self.histverify),
+            "captureoutput" => Some(self.captureoutput),
+            "huponexit" => Some(self.huponexit),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: bool) -> Result<(), String> {
+        let field = self
+            .field_mut(name)
+            .ok_or_else(|| format!("set: 未知选项 {}", name))?;
+        *field = value;
+        Ok(())
+    }
+
+    pub fn name_of_flag(flag: char) -> Option<&'static str> {
+        OPTIONS
+            .iter()
+            .find(|(_, short)| *short == Some(flag))
+            .map(|(name, _)| *name)
+    }
+
+    // 已开启的短选项，$- 展开为它们加上 shell 自身的状态
+    pub fn short_flags(&self) -> String {
+        OPTIONS
+            .iter()
+            .filter(|(name, _)| self.get(name).unwrap_or_default())
+            .filter_map(|(_, short)| *short)
+            .collect()
+    }
+
+    // 按 set -o 的格式列出所有选项
+    pub fn list(&self) -> Vec<(&'static str, bool)> {
+        OPTIONS
+            .iter()
+            .map(|(name, _)| (*name, self.get(name).unwrap_or_default()))
+            .collect()
+    }
+
+    // 解析 set 的选项参数：-e +e -o name +o name，返回是否需要列出选项
+    pub fn apply_args(&mut self, args: &[String]) -> Result<bool, String> {
+        let mut list = false;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let value = match arg.chars().next() {
+                Some('-') => true,
+                Some('+') => false,
+                _ => return Err(format!("set: 无效参数 {}", arg)),
+            };
+            let flags = &arg[1..];
+            if flags == "o" {
+                match iter.next() {
+                    Some(name) => self.set(name, value)?,
+                    None => list = true,
+                }
+                continue;
+            }
+            if flags.is_empty() {
+                return Err(format!("set: 无效参数 {}", arg));
+            }
+            for flag in flags.chars() {
+                let name =
+                    Self::name_of_flag(flag).ok_or_else(|| format!("set: 未知选项 -{}", flag))?;
+                self.set(name, value)?;
+            }
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_apply_args() {
+        let mut options = ShellOptions::new();
+        assert!(!options.apply_args(&args(&["-e"])).unwrap());
+        assert!(options.errexit);
+        options.apply_args(&args(&["+o", "errexit"])).unwrap();
+        assert!(!options.errexit);
+        assert!(options.apply_args(&args(&["-o"])).unwrap());
+        assert!(options.apply_args(&args(&["-o", "nosuch"])).is_err());
+        assert!(options.apply_args(&args(&["-z"])).is_err());
+        assert_eq!(options.short_flags(), "");
+        options.apply_args(&args(&["-e", "-o", "devtcp"])).unwrap();
+        assert_eq!(options.short_flags(), "e");
+    }
+}
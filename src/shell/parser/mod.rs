@@ -1,5 +0,0 @@
-pub mod ast;
-pub mod lexer;
-mod parser;
-
-pub use parser::Parser;
@@ -0,0 +1,225 @@
+// PATH 缓存（与 bash 的 hash 表一样）：外部命令第一次运行时在 PATH 中查找，之后直接使用记录的路径
+// 缓存的文件被删除时重新查找，PATH 改变时清空（hash -p 固定的除外）；
+// 在 PATH 中靠前的目录新装了同名命令时缓存仍指向旧的路径，用 hash -r 清空
+// 缓存中没有的命令先检查它上次所在的目录，见 path_usage
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::shell::path_usage::PathUsage;
+use crate::utils::path;
+use zakosh::i18n;
+
+struct Entry {
+    path: PathBuf,
+    hits: usize,
+    // hash -p 指定的路径，不检查文件是否存在，PATH 改变时也保留
+    pinned: bool,
+}
+
+#[derive(Default)]
+pub struct PathCache {
+    entries: HashMap<String, Entry>,
+    // 缓存条目时的 PATH
+    path_var: String,
+    usage: PathUsage,
+}
+
+impl PathCache {
+    pub fn new(usage_file: PathBuf) -> Self {
+        Self {
+            usage: PathUsage::load(usage_file),
+            ..Self::default()
+        }
+    }
+
+    // 查找命令并记录一次使用，找不到时返回 None，交给 exec 报错
+    pub fn lookup(&mut self, name: &str) -> Option<String> {
+        self.check_path_var();
+        if let Some(entry) = self.entries.get_mut(name) {
+            if entry.pinned || path::is_executable(&entry.path) {
+                entry.hits += 1;
+                // 固定的路径通常不在 PATH 中，不影响查找的顺序
+                if !entry.pinned {
+                    self.usage.record(name, &entry.path);
+                }
+                return Some(entry.path.to_string_lossy().into_owned());
+            }
+            self.entries.remove(name);
+        }
+        // PATH 中的相对目录随当前目录变化，不缓存
+        let found = self
+            .usage
+            .find_in_path(name)
+            .filter(|found| found.is_absolute())?;
+        self.usage.record(name, &found);
+        self.entries.insert(
+            name.to_string(),
+            Entry {
+                path: found.clone(),
+                hits: 1,
+                pinned: false,
+            },
+        );
+        Some(found.to_string_lossy().into_owned())
+    }
+
+    // 命令的历史使用次数，补全时常用的命令排在前面
+    pub fn usage_counts(&self) -> HashMap<String, u64> {
+        self.usage.counts()
+    }
+
+    pub fn save_usage(&mut self) {
+        self.usage.save();
+    }
+
+    pub fn get(&mut self, name: &str) -> Option<&Path> {
+        self.check_path_var();
+        self.entries.get(name).map(|entry| entry.path.as_path())
+    }
+
+    pub fn pin(&mut self, name: &str, path: PathBuf) {
+        self.entries.insert(
+            name.to_string(),
+            Entry {
+                path,
+                hits: 0,
+                pinned: true,
+            },
+        );
+    }
+
+    pub fn forget(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn check_path_var(&mut self) {
+        let path_var = env::var("PATH").unwrap_or_default();
+        if path_var != self.path_var {
+            self.entries.retain(|_, entry| entry.pinned);
+            self.path_var = path_var;
+        }
+    }
+}
+
+// hash [-r] [-p PATH NAME] [-d NAME ...] [-t NAME ...] [NAME ...]
+//   没有参数时列出缓存的命令和使用次数；-r 清空；-p 把 NAME 固定为 PATH；-d 删除条目；
+//   -t 显示缓存的路径；NAME 在 PATH 中查找并加入缓存
+pub fn hash(cache: &mut PathCache, args: &[String]) -> io::Result<()> {
+    let usage = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            i18n::tf(
+                "usage",
+                &["hash [-r] [-p PATH NAME] [-d NAME ...] [-t NAME ...] [NAME ...]"],
+            ),
+        )
+    };
+    let not_found = |names: Vec<String>| -> io::Result<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<String> = names
+            .iter()
+            .map(|name| i18n::tf("hash.not_found", &[name]))
+            .collect();
+        Err(io::Error::new(io::ErrorKind::NotFound, lines.join("\n")))
+    };
+    match args.first().map(String::as_str) {
+        None => {
+            cache.check_path_var();
+            if cache.entries.is_empty() {
+                println!("{}", i18n::t("hash.empty"));
+                return Ok(());
+            }
+            let mut entries: Vec<(&String, &Entry)> = cache.entries.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            println!("{}", i18n::t("hash.header"));
+            for (_, entry) in entries {
+                println!("{:4}\t{}", entry.hits, entry.path.display());
+            }
+            Ok(())
+        }
+        Some("-r") if args.len() == 1 => {
+            cache.clear();
+            Ok(())
+        }
+        Some("-p") => match &args[1..] {
+            [path, name] => {
+                cache.pin(name, PathBuf::from(path));
+                Ok(())
+            }
+            _ => Err(usage()),
+        },
+        Some("-d") if args.len() > 1 => not_found(
+            args[1..]
+                .iter()
+                .filter(|name| !cache.forget(name))
+                .cloned()
+                .collect(),
+        ),
+        Some("-t") if args.len() > 1 => {
+            let mut missing = Vec::new();
+            for name in &args[1..] {
+                match cache.get(name) {
+                    Some(path) => println!("{}", path.display()),
+                    None => missing.push(name.clone()),
+                }
+            }
+            not_found(missing)
+        }
+        Some(flag) if flag.starts_with('-') => Err(usage()),
+        Some(_) => not_found(
+            args.iter()
+                .filter(|name| cache.lookup(name).is_none())
+                .cloned()
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_cache() {
+        let mut cache = PathCache::default();
+        let Some(sh) = cache.lookup("sh") else {
+            return;
+        };
+        assert_eq!(cache.lookup("sh"), Some(sh.clone()));
+        assert_eq!(cache.entries["sh"].hits, 2);
+        assert_eq!(cache.lookup("zako-no-such-command"), None);
+        assert!(!cache.entries.contains_key("zako-no-such-command"));
+
+        // 固定的路径不检查是否存在，清空 PATH 缓存后仍然保留
+        cache.pin("tool", PathBuf::from("/opt/zako/tool"));
+        assert_eq!(cache.lookup("tool").as_deref(), Some("/opt/zako/tool"));
+        cache.path_var.push_str(":changed");
+        cache.check_path_var();
+        assert!(cache.entries.contains_key("tool"));
+        assert!(!cache.entries.contains_key("sh"));
+
+        // 缓存的文件不存在时重新查找
+        cache.entries.insert(
+            "sh".to_string(),
+            Entry {
+                path: PathBuf::from("/zako/missing/sh"),
+                hits: 5,
+                pinned: false,
+            },
+        );
+        assert_eq!(cache.lookup("sh"), Some(sh));
+        assert_eq!(cache.entries["sh"].hits, 1);
+
+        assert!(cache.forget("tool"));
+        cache.clear();
+        assert!(cache.entries.is_empty());
+    }
+}
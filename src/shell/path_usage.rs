@@ -0,0 +1,196 @@
+// 命令的使用频率，保存在 cache_dir/path_usage 中，每行为 次数\t命令\t所在目录
+// 查找命令时直接检查上次运行时它所在的目录，只要这个目录还在 PATH 中并且命令还在，就不用逐个扫描很长的 PATH；
+// 与 hash 缓存一样，PATH 中更靠前的目录新装了同名命令时仍使用记录的目录；命令补全也把常用的命令排在前面
+// 退出时把本次会话的次数累加到文件中，多个会话同时退出时各自的次数都会保留
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::utils::path;
+
+#[derive(Default)]
+pub struct PathUsage {
+    file: Option<PathBuf>,
+    // 命令 -> (次数, 所在目录)
+    commands: HashMap<String, (u64, PathBuf)>,
+    // 本次会话新增的次数，保存时累加到文件中的次数上
+    added: HashMap<String, (u64, PathBuf)>,
+}
+
+impl PathUsage {
+    pub fn load(file: PathBuf) -> Self {
+        let commands = fs::read_to_string(&file)
+            .map(|content| parse(&content))
+            .unwrap_or_default();
+        Self {
+            file: Some(file),
+            commands,
+            added: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, name: &str, path: &Path) {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        for table in [&mut self.commands, &mut self.added] {
+            let entry = table
+                .entry(name.to_string())
+                .or_insert((0, dir.to_path_buf()));
+            entry.0 += 1;
+            entry.1 = dir.to_path_buf();
+        }
+    }
+
+    pub fn counts(&self) -> HashMap<String, u64> {
+        self.commands
+            .iter()
+            .map(|(name, (count, _))| (name.clone(), *count))
+            .collect()
+    }
+
+    // 记录的目录不再在 PATH 中或命令已被删除时，按 PATH 顺序查找第一个匹配
+    pub fn find_in_path(&self, name: &str) -> Option<PathBuf> {
+        self.find_in(name, &env::var("PATH").unwrap_or_default())
+    }
+
+    fn find_in(&self, name: &str, path_var: &str) -> Option<PathBuf> {
+        let dirs: Vec<&Path> = path_var
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(Path::new)
+            .collect();
+        if let Some((_, dir)) = self.commands.get(name) {
+            if dirs.contains(&dir.as_path()) {
+                let candidate = dir.join(name);
+                if path::is_executable(&candidate) {
+                    return Some(candidate);
+                }
+            }
+        }
+        dirs.iter()
+            .map(|dir| dir.join(name))
+            .find(|candidate| path::is_executable(candidate))
+    }
+
+    // 退出时调用，没有新的记录时不写文件
+    pub fn save(&mut self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if self.added.is_empty() {
+            return;
+        }
+        let mut merged = fs::read_to_string(file)
+            .map(|content| parse(&content))
+            .unwrap_or_default();
+        for (name, (count, dir)) in self.added.drain() {
+            let entry = merged.entry(name).or_insert((0, dir.clone()));
+            entry.0 += count;
+            entry.1 = dir;
+        }
+        match write_usage(file, &merged) {
+            Ok(()) => debug!("保存命令使用次数: {}", file.display()),
+            Err(e) => warn!("无法保存命令使用次数: {} {}", file.display(), e),
+        }
+        self.commands = merged;
+    }
+}
+
+fn parse(content: &str) -> HashMap<String, (u64, PathBuf)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let count = fields.next()?.parse::<u64>().ok()?;
+            let name = fields.next()?;
+            let dir = fields.next()?;
+            Some((name.to_string(), (count, PathBuf::from(dir))))
+        })
+        .collect()
+}
+
+// 先写到临时文件再改名，与补全缓存一样
+fn write_usage(file: &Path, commands: &HashMap<String, (u64, PathBuf)>) -> io::Result<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut sorted: Vec<_> = commands.iter().collect();
+    sorted.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(b.0)));
+    let tmp = file.with_extension(format!("tmp{}", std::process::id()));
+    let mut out = fs::File::create(&tmp)?;
+    for (name, (count, dir)) in sorted {
+        writeln!(out, "{}\t{}\t{}", count, name, dir.display())?;
+    }
+    drop(out);
+    fs::rename(&tmp, file)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_usage() {
+        let dir = env::temp_dir().join(format!("zako_usage_{}", std::process::id()));
+        let file = dir.join("path_usage");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&file, "3\tpython\t/shims\nbad line\n1\tls\t/usr/bin\n").unwrap();
+
+        let mut usage = PathUsage::load(file.clone());
+        assert_eq!(usage.counts()["python"], 3);
+        // 其他会话在此期间写入的次数不会丢失
+        usage.record("python", Path::new("/shims/python"));
+        fs::write(&file, "5\tpython\t/shims\n2\tgit\t/usr/bin\n").unwrap();
+        usage.save();
+        let saved = PathUsage::load(file).counts();
+        assert_eq!(saved["python"], 6);
+        assert_eq!(saved["git"], 2);
+        assert!(!saved.contains_key("ls"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_in_recorded_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("zako_usage_find_{}", std::process::id()));
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        for bin in [&first, &second] {
+            fs::create_dir_all(bin).unwrap();
+            let file = bin.join("zako-tool");
+            fs::write(&file, "").unwrap();
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let path_var = format!("{}:{}", first.display(), second.display());
+
+        let mut usage = PathUsage::default();
+        assert_eq!(
+            usage.find_in("zako-tool", &path_var),
+            Some(first.join("zako-tool"))
+        );
+        // 记录的目录优先，即使 PATH 中更靠前的目录也有同名命令
+        usage.record("zako-tool", &second.join("zako-tool"));
+        assert_eq!(
+            usage.find_in("zako-tool", &path_var),
+            Some(second.join("zako-tool"))
+        );
+        // 记录的目录不在 PATH 中或命令被删除时按 PATH 顺序查找
+        let only_first = first.display().to_string();
+        assert_eq!(
+            usage.find_in("zako-tool", &only_first),
+            Some(first.join("zako-tool"))
+        );
+        fs::remove_file(second.join("zako-tool")).unwrap();
+        assert_eq!(
+            usage.find_in("zako-tool", &path_var),
+            Some(first.join("zako-tool"))
+        );
+        assert_eq!(usage.find_in("zako-missing", &path_var), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
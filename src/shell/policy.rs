@@ -0,0 +1,171 @@
+// 配置中的执行策略规则（[policy.NAME] 段）：展开别名、变量和参数之后，
+// 命令名和参数以空格连接成一行与每条规则的 match 匹配，例如 match = 'kubectl delete.*--all'
+// 匹配到 deny 规则时拒绝执行，否则匹配到的第一条 confirm 规则要求确认；allow 规则不起作用
+// on = "paste" 的规则用同样的方式检查粘贴的内容，见 readline 的 PasteGuard
+use crate::shell::hooks::{ExecPolicy, Verdict};
+use crate::utils::config::{Config, PolicyAction, PolicyRule, PolicyTarget};
+use zakosh::i18n;
+
+pub struct PolicyRules {
+    rules: Vec<PolicyRule>,
+}
+
+impl PolicyRules {
+    pub fn new(config: &Config) -> Option<Self> {
+        let rules = active_rules(config, PolicyTarget::Exec);
+        (!rules.is_empty()).then_some(Self { rules })
+    }
+}
+
+impl ExecPolicy for PolicyRules {
+    fn check(&mut self, program: &str, args: &[String]) -> Verdict {
+        let line = std::iter::once(program)
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let Some(rule) = matching_rule(&self.rules, &line) else {
+            return Verdict::Allow;
+        };
+        let message = rule
+            .message
+            .as_ref()
+            .map(|message| format!("{}: {}", program, message))
+            .unwrap_or_else(|| i18n::tf("policy.command_matched", &[program, &rule.name]));
+        verdict(rule, message)
+    }
+}
+
+// 检查粘贴到输入行的内容
+pub struct PasteRules {
+    rules: Vec<PolicyRule>,
+}
+
+impl PasteRules {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            rules: active_rules(config, PolicyTarget::Paste),
+        }
+    }
+
+    pub fn check(&self, text: &str) -> Verdict {
+        let Some(rule) = matching_rule(&self.rules, text) else {
+            return Verdict::Allow;
+        };
+        let message = rule
+            .message
+            .clone()
+            .unwrap_or_else(|| i18n::tf("policy.paste_matched", &[&rule.name]));
+        verdict(rule, message)
+    }
+}
+
+fn active_rules(config: &Config, on: PolicyTarget) -> Vec<PolicyRule> {
+    config
+        .policy_rules
+        .iter()
+        .filter(|rule| rule.on == on && rule.action != PolicyAction::Allow)
+        .cloned()
+        .collect()
+}
+
+// 匹配的规则中 deny 优先，否则为第一条
+fn matching_rule<'a>(rules: &'a [PolicyRule], text: &str) -> Option<&'a PolicyRule> {
+    let mut matched = rules.iter().filter(|rule| rule.pattern.is_match(text));
+    let first = matched.next()?;
+    Some(
+        std::iter::once(first)
+            .chain(matched)
+            .find(|rule| rule.action == PolicyAction::Deny)
+            .unwrap_or(first),
+    )
+}
+
+fn verdict(rule: &PolicyRule, message: String) -> Verdict {
+    match rule.action {
+        PolicyAction::Confirm => Verdict::Confirm(message),
+        PolicyAction::Deny => Verdict::Deny(message),
+        PolicyAction::Allow => Verdict::Allow,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn rule(name: &str, pattern: &str, action: PolicyAction) -> Option<PolicyRule> {
+        Some(PolicyRule {
+            name: name.to_string(),
+            pattern: Regex::new(pattern).ok()?,
+            action,
+            message: None,
+            on: PolicyTarget::Exec,
+        })
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_policy_rules() -> Result<(), String> {
+        let rules = [
+            rule("kube", "^kubectl delete.*--all", PolicyAction::Confirm),
+            rule("prod", "--context[= ]prod", PolicyAction::Deny),
+        ];
+        let mut policy = PolicyRules {
+            rules: rules
+                .into_iter()
+                .collect::<Option<_>>()
+                .ok_or("无效的正则")?,
+        };
+        assert_eq!(
+            policy.check("kubectl", &strings(&["delete", "pods", "--all"])),
+            Verdict::Confirm("kubectl: 命令匹配执行策略 kube".to_string())
+        );
+        // 同时匹配时 deny 优先
+        assert!(matches!(
+            policy.check(
+                "kubectl",
+                &strings(&["delete", "--all", "--context", "prod"])
+            ),
+            Verdict::Deny(_)
+        ));
+        assert_eq!(
+            policy.check("kubectl", &strings(&["get", "pods"])),
+            Verdict::Allow
+        );
+
+        policy.rules[0].message = Some("会删除所有资源".to_string());
+        assert_eq!(
+            policy.check("kubectl", &strings(&["delete", "--all"])),
+            Verdict::Confirm("kubectl: 会删除所有资源".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_paste_rules() {
+        let mut config = Config::default();
+        let paste = PasteRules::new(&config);
+        let confirm = |text: &str| matches!(paste.check(text), Verdict::Confirm(_));
+        assert!(confirm("echo a\necho b"));
+        assert!(confirm("sudo apt install x"));
+        assert!(confirm("rm -rf build"));
+        assert!(confirm("rm -v -fr build"));
+        assert!(confirm("rm -Rf build"));
+        assert!(confirm("curl -fsSL https://x.sh | sh"));
+        assert!(confirm("wget -qO- x | sudo bash"));
+        assert!(!confirm("rm -r build"));
+        assert!(!confirm("curl https://x | jq ."));
+        assert!(!confirm("pseudo ls"));
+        assert!(PolicyRules::new(&config).is_none());
+
+        for rule in &mut config.policy_rules {
+            if rule.name == "paste_sudo" {
+                rule.action = PolicyAction::Allow;
+            }
+        }
+        assert_eq!(PasteRules::new(&config).check("sudo ls"), Verdict::Allow);
+    }
+}
@@ -0,0 +1,159 @@
+// progress cmd ... < FILE：命令（或管道的第一个命令）从普通文件读取输入时，改为从管道读取，
+// 由加入同一作业的转发进程读取文件写入管道，同时在 stderr 上显示进度，例如 progress grep error < huge.log | wc -l
+// 与进程替换一样，转发进程在命令启动后创建，shell 随即关闭写端，之后启动的管道命令不会持有写端
+// 命令提前退出（head 等）时写入失败，转发进程随之退出；结束时清除进度行
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::OwnedFd;
+use std::time::{Duration, Instant};
+
+use nix::fcntl::OFlag;
+
+// 进度条的宽度和刷新间隔
+const BAR_WIDTH: usize = 24;
+const REFRESH: Duration = Duration::from_millis(200);
+
+pub struct ProgressInput {
+    file: File,
+    total: u64,
+    write_end: OwnedFd,
+}
+
+// 返回作为命令标准输入的管道读端，和转发进程使用的文件与写端
+pub fn prepare(file: File) -> io::Result<(OwnedFd, ProgressInput)> {
+    let total = file.metadata()?.len();
+    let (read_end, write_end) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+    Ok((
+        read_end,
+        ProgressInput {
+            file,
+            total,
+            write_end,
+        },
+    ))
+}
+
+impl ProgressInput {
+    // 在转发进程中运行，不会返回
+    pub fn run(self) -> ! {
+        let pipe = File::from(self.write_end);
+        let status = match copy(self.file, pipe, self.total, draw) {
+            Ok(_) => 0,
+            Err(_) => 1,
+        };
+        std::process::exit(status);
+    }
+}
+
+// 复制全部内容，超过刷新间隔后开始显示进度，很快就能读完的文件不显示
+fn copy(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    total: u64,
+    mut show: impl FnMut(&str),
+) -> io::Result<u64> {
+    let mut buf = vec![0; 64 * 1024];
+    let started = Instant::now();
+    let mut last_drawn = started;
+    let mut drawn = false;
+    let mut done = 0;
+    let result = loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break Ok(done),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e),
+        };
+        if let Err(e) = writer.write_all(&buf[..n]) {
+            break Err(e);
+        }
+        done += n as u64;
+        if last_drawn.elapsed() >= REFRESH {
+            show(&render(done, total, started.elapsed()));
+            last_drawn = Instant::now();
+            drawn = true;
+        }
+    };
+    if drawn {
+        show("");
+    }
+    result
+}
+
+fn draw(line: &str) {
+    let mut stderr = io::stderr().lock();
+    let _ = write!(stderr, "\r\x1b[K{}", line);
+    let _ = stderr.flush();
+}
+
+// [#########---------------]  37% 1.0 GiB / 2.7 GiB  85.3 MiB/s
+fn render(done: u64, total: u64, elapsed: Duration) -> String {
+    let ratio = if total == 0 {
+        1.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    };
+    let filled = (ratio * BAR_WIDTH as f64) as usize;
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        format!("  {}/s", format_bytes((done as f64 / seconds) as u64))
+    } else {
+        String::new()
+    };
+    format!(
+        "[{}{}] {:3}% {} / {}{}",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        (ratio * 100.0) as u32,
+        format_bytes(done),
+        format_bytes(total),
+        rate
+    )
+}
+
+// 1024 进制，与 limit 的大小单位一致
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_progress() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 << 29), "1.5 GiB");
+        assert_eq!(
+            render(1 << 20, 4 << 20, Duration::from_secs(2)),
+            "[######------------------]  25% 1.0 MiB / 4.0 MiB  512.0 KiB/s"
+        );
+        assert!(render(0, 0, Duration::ZERO).starts_with("[########################] 100%"));
+    }
+
+    #[test]
+    fn test_copy_input() -> io::Result<()> {
+        let content: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let mut copied = Vec::new();
+        let mut lines = Vec::new();
+        let done = copy(content.as_slice(), &mut copied, 200_000, |line| {
+            lines.push(line.to_string())
+        })?;
+        assert_eq!(done, 200_000);
+        assert_eq!(copied, content);
+        // 立即完成时不显示进度
+        assert!(lines.is_empty());
+        Ok(())
+    }
+}
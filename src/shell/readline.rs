@@ -1,20 +1,655 @@
-use crate::utils::config::Config;
+use crate::shell::clipboard;
+use crate::shell::completion::{self, Completion, Menu};
+use crate::shell::history;
+use crate::shell::hooks::Verdict;
+use crate::shell::parser;
+use crate::shell::policy::PasteRules;
+use crate::shell::signals;
+use crate::utils::config::{ClipboardMode, Config};
+use crate::utils::prompt;
+use crate::utils::theme::Theme;
 use log::{debug, error, warn};
+use rustyline::completion::{Completer, Pair};
 pub use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::{Hint, Hinter};
 use rustyline::history::FileHistory;
-use rustyline::Editor;
-use rustyline::{CompletionType, Config as RLConfig, EditMode};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{CharSearch, Cmd, CompletionType, Config as RLConfig, EditMode, Movement};
+use rustyline::{
+    ConditionalEventHandler, Event, EventContext, EventHandler, InputMode, KeyCode, KeyEvent,
+    Modifiers, RepeatCount,
+};
+use rustyline::{Editor, Helper};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use zakosh::i18n;
+
+// 输入没有结束时（未闭合的引号、行尾的 | 或 \）按回车换行继续编辑，整段作为一条历史记录
+pub struct ZakoHelper {
+    completion: Completion,
+    // 补全菜单显示在提示（hint）的位置，选中项由输入行当前的内容决定
+    menu: RefCell<Option<Menu>>,
+    selected: Cell<Option<usize>>,
+    menu_state: Arc<MenuState>,
+    theme: Theme,
+    // 显示在行尾的右侧提示符，不参与 rustyline 的布局计算
+    right_prompt: RefCell<String>,
+    // 去掉颜色后交给 rustyline 计算布局的提示符，以及显示时使用的带颜色的原文
+    prompt: RefCell<(String, String)>,
+    // 高亮时检查命令名用的已知命令，每次读取输入时重新生成
+    commands: RefCell<Option<HashSet<String>>>,
+    // 上次显示的输入行是否标记了命令名
+    underlined: Cell<bool>,
+    paste: Arc<Mutex<PasteGuard>>,
+    paste_rules: PasteRules,
+}
+
+impl ZakoHelper {
+    fn render_menu(&self, colored: bool) -> Option<String> {
+        let menu = self.menu.borrow();
+        let menu = menu.as_ref()?;
+        // 留出最后一列，避免正好占满一行时终端自动换行
+        let width = prompt::terminal_width().saturating_sub(1);
+        Some(menu.render(self.selected.get(), width, |text, selected| {
+            match (colored, selected) {
+                (false, _) => text,
+                (true, false) => (self.theme.menu_style)(text),
+                (true, true) => (self.theme.menu_selected_style)(text),
+            }
+        }))
+    }
+
+    // 不是内建命令、函数、别名，也不在 PATH 中的命令名；带 / 的检查文件是否存在
+    fn is_unknown_command(&self, word: &str) -> bool {
+        if self.completion.suffix_alias(word).is_some() {
+            return false;
+        }
+        if word.contains('/') {
+            return !Path::new(word).exists();
+        }
+        let mut commands = self.commands.borrow_mut();
+        !commands
+            .get_or_insert_with(|| self.completion.command_names())
+            .contains(word)
+    }
+
+    // 刚粘贴的内容匹配 on = "paste" 的策略时，在按键决定保留还是撤销之前一直显示原因
+    fn paste_warning(&self, line: &str) -> Option<String> {
+        let mut guard = self.paste.lock().ok()?;
+        if let Some((before, pos)) = guard.start.take() {
+            let end = pos + line.len().saturating_sub(before.len());
+            let verdict = line
+                .get(pos..end)
+                .map_or(Verdict::Allow, |pasted| self.paste_rules.check(pasted));
+            // 等待确认时又粘贴了一次：撤销时换回第一次粘贴前的输入，第二次粘贴没有问题时仍按第一次的结果
+            guard.pending = match guard.pending.take() {
+                Some(previous) => Some(PendingPaste {
+                    verdict: match verdict {
+                        Verdict::Allow => previous.verdict,
+                        verdict => verdict,
+                    },
+                    before: previous.before,
+                    after: line.to_string(),
+                    single: false,
+                }),
+                None => (verdict != Verdict::Allow).then(|| PendingPaste {
+                    verdict,
+                    before,
+                    after: line.to_string(),
+                    single: true,
+                }),
+            };
+        }
+        match &guard.pending.as_ref()?.verdict {
+            Verdict::Confirm(message) => {
+                Some(format!("\n{}", i18n::tf("paste.confirm", &[message])))
+            }
+            Verdict::Deny(message) => Some(format!("\n{}", i18n::tf("paste.deny", &[message]))),
+            _ => None,
+        }
+    }
+
+    // 要标记的命令名；光标停在命令名末尾时可能还没输入完，不标记
+    fn unknown_command<'l>(&self, line: &'l str, pos: usize) -> Option<(usize, &'l str)> {
+        let (start, word) = completion::command_word(line)?;
+        (pos != start + word.len() && self.is_unknown_command(word)).then_some((start, word))
+    }
+}
+
+impl Completer for ZakoHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, candidates) = self.completion.complete(line, pos);
+        self.menu_state
+            .open
+            .store(!candidates.is_empty(), Ordering::Relaxed);
+        self.menu_state
+            .single
+            .store(candidates.len() == 1, Ordering::Relaxed);
+        *self.menu.borrow_mut() = Some(Menu::new(line, start, pos, candidates.clone()));
+        Ok((start, candidates))
+    }
+}
+
+// 提示的位置显示补全菜单或别名展开的预览；预览只用于显示，右方向键不会把它插入输入行
+pub struct ZakoHint {
+    text: String,
+    insert: bool,
+}
+
+impl Hint for ZakoHint {
+    fn display(&self) -> &str {
+        &self.text
+    }
+
+    fn completion(&self) -> Option<&str> {
+        self.insert.then_some(self.text.as_str())
+    }
+}
+
+impl Hinter for ZakoHelper {
+    type Hint = ZakoHint;
+
+    // 光标在别名上时在行尾预览展开的内容；rustyline 只在编辑时重新计算提示，单纯移动光标不会更新
+    fn hint(&self, line: &str, pos: usize, _: &rustyline::Context<'_>) -> Option<ZakoHint> {
+        if let Some(text) = self.paste_warning(line) {
+            return Some(ZakoHint {
+                text,
+                insert: false,
+            });
+        }
+        let selected = match self.menu.borrow().as_ref() {
+            Some(menu) if self.menu_state.open.load(Ordering::Relaxed) && menu.len() > 1 => {
+                menu.selected(line, pos)
+            }
+            _ => None,
+        };
+        let Some(selected) = selected else {
+            self.menu.borrow_mut().take();
+            return self
+                .completion
+                .alias_preview(line, pos)
+                .map(|alias| ZakoHint {
+                    text: format!("  → {}", alias),
+                    insert: false,
+                });
+        };
+        self.selected.set(selected);
+        self.render_menu(false)
+            .map(|text| ZakoHint { text, insert: true })
+    }
+}
+
+impl Highlighter for ZakoHelper {
+    // 按回车之前就把找不到的命令名标出来（默认红色下划线）
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let unknown = self.unknown_command(line, pos);
+        self.underlined.set(unknown.is_some());
+        match unknown {
+            Some((start, word)) => Cow::Owned(format!(
+                "{}{}{}",
+                &line[..start],
+                (self.theme.unknown_command_style)(word.to_string()),
+                &line[start + word.len()..]
+            )),
+            _ => Cow::Borrowed(line),
+        }
+    }
+
+    // 只在需要加上或去掉标记时重画整行，其余按键由 rustyline 直接输出字符
+    fn highlight_char(&self, line: &str, pos: usize, _: CmdKind) -> bool {
+        self.underlined.get() || self.unknown_command(line, pos).is_some()
+    }
+
+    // 保存光标后移到右侧输出，再恢复光标，输入的内容到达右侧时会覆盖它
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        let (plain, colored) = &*self.prompt.borrow();
+        if !default || plain != prompt {
+            return Cow::Borrowed(prompt);
+        }
+        let right = self.right_prompt.borrow();
+        let width = prompt::terminal_width();
+        let right_width = prompt::display_width(&right);
+        // 与左侧之间至少留一个空格，并留出最后一列，放不下时不显示
+        if right.is_empty()
+            || prompt.contains('\n')
+            || prompt::display_width(prompt) + right_width + 2 > width
+        {
+            return Cow::Owned(colored.clone());
+        }
+        Cow::Owned(format!(
+            "{}\x1b7\x1b[{}G{}\x1b8",
+            colored,
+            width - right_width,
+            right
+        ))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        let pending = self.paste.lock().is_ok_and(|guard| guard.pending.is_some());
+        match self.render_menu(true) {
+            _ if pending => Cow::Owned((self.theme.warning_style)(hint.to_string())),
+            Some(menu) => Cow::Owned(menu),
+            None => Cow::Owned((self.theme.alias_preview_style)(hint.to_string())),
+        }
+    }
+}
+
+impl Validator for ZakoHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if parser::is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for ZakoHelper {}
+
+// 补全菜单是否打开，按键处理和 ZakoHelper 共用
+#[derive(Default)]
+struct MenuState {
+    open: AtomicBool,
+    // 只有一个候选项时不显示菜单，再按 Tab 直接确认
+    single: AtomicBool,
+}
+
+// 补全菜单打开时 Tab/Down 选中下一项，Shift-Tab/Up 选中上一项，其他按键关闭菜单
+struct MenuKey {
+    state: Arc<MenuState>,
+    forward: bool,
+    // 菜单没有打开时是否开始补全（Tab）
+    starts: bool,
+}
+
+impl ConditionalEventHandler for MenuKey {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        let open = self.state.open.load(Ordering::Relaxed);
+        if open && self.state.single.load(Ordering::Relaxed) {
+            self.state.open.store(false, Ordering::Relaxed);
+            return self.starts.then_some(Cmd::Noop);
+        }
+        match (open, self.forward) {
+            (true, true) => Some(Cmd::Complete),
+            (true, false) => Some(Cmd::CompleteBackward),
+            (false, _) if self.starts => Some(Cmd::Complete),
+            (false, _) => None,
+        }
+    }
+}
+
+// 没有单独绑定的按键：关闭补全菜单，并清空重做记录；粘贴的内容等待确认时由这个按键决定保留还是撤销
+struct OtherKey {
+    menu: Arc<MenuState>,
+    redo: Arc<Mutex<Vec<String>>>,
+    paste: Arc<Mutex<PasteGuard>>,
+}
+
+impl ConditionalEventHandler for OtherKey {
+    fn handle(&self, evt: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        self.menu.open.store(false, Ordering::Relaxed);
+        if let Ok(mut redo) = self.redo.lock() {
+            redo.clear();
+        }
+        resolve_paste(&self.paste, evt, ctx)
+    }
+}
+
+// 粘贴的内容等待确认时，按键只用来决定保留还是撤销，不再执行它原来的功能；没有等待确认时返回 None
+fn resolve_paste(paste: &Mutex<PasteGuard>, evt: &Event, ctx: &EventContext) -> Option<Cmd> {
+    let pending = paste.lock().ok()?.pending.take()?;
+    let keep = Event::from(KeyEvent(KeyCode::Char('y'), Modifiers::NONE));
+    match pending.verdict {
+        // 重画一次，去掉提示
+        Verdict::Confirm(_) if *evt == keep => Some(Cmd::Repaint),
+        // 粘贴是一次插入，撤销后光标也回到原处；之后又改过输入或粘贴了多次时换回整行
+        _ if pending.single && ctx.line() == pending.after => Some(Cmd::Undo(1)),
+        _ => Some(Cmd::Replace(Movement::WholeBuffer, Some(pending.before))),
+    }
+}
+
+// 单独绑定的按键（Tab、Up、撤销、Ctrl-X Ctrl-E 等）不经过 OtherKey，包上这一层后同样先处理等待确认的粘贴
+struct PasteAware<H> {
+    paste: Arc<Mutex<PasteGuard>>,
+    inner: H,
+}
+
+impl<H: ConditionalEventHandler> ConditionalEventHandler for PasteAware<H> {
+    fn handle(
+        &self,
+        evt: &Event,
+        n: RepeatCount,
+        positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        resolve_paste(&self.paste, evt, ctx).or_else(|| self.inner.handle(evt, n, positive, ctx))
+    }
+}
+
+fn paste_aware<H>(paste: &Arc<Mutex<PasteGuard>>, inner: H) -> EventHandler
+where
+    H: ConditionalEventHandler + 'static,
+{
+    EventHandler::Conditional(Box::new(PasteAware {
+        paste: paste.clone(),
+        inner,
+    }))
+}
+
+// rustyline 只提供撤销：撤销前记下当前输入，重做时换回去，重做本身也可以再撤销
+// vi 模式的 u 和 Ctrl-R 只在命令模式下生效，插入模式下仍然是输入字符和搜索历史
+// 带次数（Alt-2 Alt-_、vi 的 2 Ctrl-R）时一次重做多步，vi 插入模式下按键的次数为 0
+struct UndoKey {
+    redo: Arc<Mutex<Vec<String>>>,
+    vi_only: bool,
+}
+
+impl ConditionalEventHandler for UndoKey {
+    fn handle(&self, _: &Event, n: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        let mut redo = self.redo.lock().ok()?;
+        if self.vi_only && !vi_command_mode(ctx) {
+            redo.clear();
+            return None;
+        }
+        redo.push(ctx.line().to_string());
+        Some(Cmd::Undo(n))
+    }
+}
+
+struct RedoKey {
+    redo: Arc<Mutex<Vec<String>>>,
+    vi_only: bool,
+}
+
+impl ConditionalEventHandler for RedoKey {
+    fn handle(&self, _: &Event, n: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        if self.vi_only && !vi_command_mode(ctx) {
+            return None;
+        }
+        let mut redo = self.redo.lock().ok()?;
+        let keep = redo.len().saturating_sub(n.max(1));
+        let line = redo.drain(keep..).next();
+        match line {
+            Some(line) => Some(Cmd::Replace(Movement::WholeBuffer, Some(line))),
+            None => Some(Cmd::Noop),
+        }
+    }
+}
+
+// Alt-t 交换单词，TransposeWords 不会使用按键的次数，这里自己传入
+struct TransposeWords;
+
+impl ConditionalEventHandler for TransposeWords {
+    fn handle(&self, _: &Event, n: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        Some(Cmd::TransposeWords(n.max(1)))
+    }
+}
+
+fn vi_command_mode(ctx: &EventContext) -> bool {
+    ctx.mode() == EditMode::Vi && ctx.input_mode() == InputMode::Command
+}
+
+// Alt-. 插入上一条命令的最后一个参数，连续按下时换成更早的命令的最后一个参数
+struct LastArgument {
+    history: Arc<Mutex<Vec<String>>>,
+    last: Mutex<Option<Inserted>>,
+}
+
+// 上一次插入后的行、光标位置、插入内容的位置和往前数的条数，用来判断是否连续按下
+struct Inserted {
+    line: String,
+    pos: usize,
+    start: usize,
+    end: usize,
+    back: usize,
+}
+
+impl ConditionalEventHandler for LastArgument {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        let mut last = self.last.lock().ok()?;
+        let previous = last
+            .take()
+            .filter(|last| last.line == ctx.line() && last.pos == ctx.pos());
+        let back = previous.as_ref().map_or(0, |last| last.back + 1);
+        let history = self.history.lock().ok()?;
+        let Some(argument) = history
+            .iter()
+            .rev()
+            .filter_map(|entry| history::split_words(entry).pop())
+            .nth(back)
+        else {
+            *last = previous;
+            return Some(Cmd::Noop);
+        };
+
+        let line = ctx.line();
+        let pos = ctx.pos();
+        let Some(Inserted { start, end, .. }) = previous else {
+            let mut line = line.to_string();
+            line.insert_str(pos, &argument);
+            *last = Some(Inserted {
+                line,
+                pos: pos + argument.len(),
+                start: pos,
+                end: pos + argument.len(),
+                back,
+            });
+            return Some(Cmd::Insert(1, argument));
+        };
+
+        // 替换之后光标停在被删除的范围的开头，移动的次数会被按键的重复次数覆盖，
+        // 所以只用不带次数的移动：光标在参数之后时往回删到参数前面的字符或行首，光标在参数之前时删到行尾再补上
+        let line_end = line[end..].find('\n').map_or(line.len(), |i| end + i);
+        let before = line[..start].chars().next_back();
+        let (cmd, cursor) = match before {
+            Some(c) if pos == end && c != '\n' && !line[start..end].contains(c) => (
+                Cmd::Replace(
+                    Movement::ViCharSearch(1, CharSearch::BackwardAfter(c)),
+                    Some(argument.clone()),
+                ),
+                start,
+            ),
+            _ if pos <= start => {
+                let text = format!("{}{}{}", &line[pos..start], argument, &line[end..line_end]);
+                (Cmd::Replace(Movement::EndOfLine, Some(text)), pos)
+            }
+            _ => {
+                let line_start = line[..start].rfind('\n').map_or(0, |i| i + 1);
+                let text = format!("{}{}", &line[line_start..start], argument);
+                (
+                    Cmd::Replace(Movement::BeginningOfLine, Some(text)),
+                    line_start,
+                )
+            }
+        };
+        let mut line = line.to_string();
+        line.replace_range(start..end, &argument);
+        *last = Some(Inserted {
+            line,
+            pos: cursor,
+            start,
+            end: start + argument.len(),
+            back,
+        });
+        Some(cmd)
+    }
+}
+
+// Ctrl-X Ctrl-E 在 $VISUAL/$EDITOR 中编辑当前输入：记下输入后中断这次读取，由 readline 打开编辑器，
+// 编辑器退出后把编辑的内容放回输入行；编辑器运行时 rustyline 已经恢复了终端设置
+struct EditCommandLine(Arc<Mutex<Option<String>>>);
+
+impl ConditionalEventHandler for EditCommandLine {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        *self.0.lock().ok()? = Some(ctx.line().to_string());
+        Some(Cmd::Interrupt)
+    }
+}
+
+// 编辑器以 sh -c 运行，$EDITOR 可以带参数（如 "code --wait"）；编辑失败时保留原来的输入
+// 临时文件名带随机后缀，只有当前用户可以读写；create_new 在文件已存在时失败，不会跟随别人预先放好的符号链接
+fn create_edit_file(line: &str) -> io::Result<PathBuf> {
+    for _ in 0..8 {
+        let path = env::temp_dir().join(format!(
+            "zako-edit-{}-{:016x}.sh",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path);
+        match file {
+            Ok(mut file) => {
+                file.write_all(format!("{}\n", line).as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "无法创建不重名的临时文件",
+    ))
+}
+
+fn edit_in_editor(line: &str) -> String {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let file = match create_edit_file(line) {
+        Ok(file) => file,
+        Err(err) => {
+            error!("无法创建临时文件: {}", err);
+            return line.to_string();
+        }
+    };
+    // 阻塞子进程信号，否则 SIGCHLD 处理函数会先回收编辑器进程
+    signals::block_child_signals();
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&file)
+        .status();
+    signals::unblock_child_signals();
+    let edited = match status {
+        Ok(status) if status.success() => fs::read_to_string(&file).ok(),
+        Ok(status) => {
+            warn!("编辑器 {} 退出码 {}", editor, status);
+            None
+        }
+        Err(err) => {
+            error!("无法运行编辑器 {}: {}", editor, err);
+            None
+        }
+    };
+    let _ = fs::remove_file(&file);
+    match edited {
+        Some(text) => text.trim_end_matches('\n').to_string(),
+        None => line.to_string(),
+    }
+}
+
+// Alt-W 把当前输入复制到剪贴板，输入保持不变
+struct CopyLine(ClipboardMode);
+
+impl ConditionalEventHandler for CopyLine {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        signals::block_child_signals();
+        if let Err(err) = clipboard::copy(ctx.line(), self.0) {
+            debug!("无法复制到剪贴板: {}", err);
+        }
+        signals::unblock_child_signals();
+        Some(Cmd::Noop)
+    }
+}
+
+// 粘贴的内容由 rustyline 自己读取并插入缓冲区，插入之前拿不到，
+// 所以先记下粘贴前的输入，插入后计算提示时检查，需要确认时由下一个按键决定保留还是换回粘贴前的输入
+#[derive(Default)]
+struct PasteGuard {
+    // 粘贴前的输入和光标位置
+    start: Option<(String, usize)>,
+    pending: Option<PendingPaste>,
+}
+
+// 等待确认的检查结果，以及粘贴前后的输入
+struct PendingPaste {
+    verdict: Verdict,
+    before: String,
+    after: String,
+    // 只有一次粘贴，可以用撤销恢复
+    single: bool,
+}
+
+// 粘贴开始时只做标记，粘贴的内容仍由 rustyline 读取并插入缓冲区
+struct PasteStart {
+    pasted: Arc<AtomicBool>,
+    guard: Arc<Mutex<PasteGuard>>,
+}
+
+impl ConditionalEventHandler for PasteStart {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        self.pasted.store(true, Ordering::Relaxed);
+        if let Ok(mut guard) = self.guard.lock() {
+            guard.start = Some((ctx.line().to_string(), ctx.pos()));
+        }
+        None
+    }
+}
+
+// 去掉每一行开头的 "$ "（前面可以有缩进），其他行保持不变
+fn strip_paste_prompts(input: &str) -> String {
+    input
+        .split('\n')
+        .map(|line| line.trim_start().strip_prefix("$ ").unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
 pub struct ReadlineManager<'a> {
     config: &'a Config,
-    editor: Editor<(), FileHistory>,
+    editor: Editor<ZakoHelper, FileHistory>,
+    // 与编辑器的历史记录相同，供 Alt-. 使用
+    entries: Arc<Mutex<Vec<String>>>,
+    // 下一次读取时预先填入编辑器的内容（histverify）
+    initial: Option<String>,
+    // 按下 Ctrl-X Ctrl-E 时的输入
+    edit_request: Arc<Mutex<Option<String>>>,
+    // 这一次读取的输入中是否有粘贴的内容（paste_strip_prompt）
+    pasted: Arc<AtomicBool>,
 }
 
 impl<'a> ReadlineManager<'a> {
     pub fn new(config: &'a Config) -> Self {
         let rl_config = RLConfig::builder()
             .history_ignore_space(true)
-            .completion_type(CompletionType::List)
+            .completion_type(CompletionType::Circular)
+            .bracketed_paste(config.bracketed_paste)
             .edit_mode(if config.editor_mode == "emacs" {
                 EditMode::Emacs
             } else {
@@ -22,11 +657,123 @@ impl<'a> ReadlineManager<'a> {
             })
             .build();
 
-        let editor = Editor::with_config(rl_config).unwrap_or_else(|err| {
+        let mut editor = Editor::with_config(rl_config).unwrap_or_else(|err| {
             error!("无法初始化 readline: {}", err);
             panic!("无法初始化 readline");
         });
-        Self { config, editor }
+        let menu_state = Arc::new(MenuState::default());
+        let paste = Arc::new(Mutex::new(PasteGuard::default()));
+        editor.set_helper(Some(ZakoHelper {
+            completion: Completion::new(config.completion_match, config.cache_dir.clone()),
+            menu: RefCell::new(None),
+            selected: Cell::new(None),
+            menu_state: menu_state.clone(),
+            theme: Theme::new(config),
+            right_prompt: RefCell::new(String::new()),
+            prompt: RefCell::new((String::new(), String::new())),
+            commands: RefCell::new(None),
+            underlined: Cell::new(false),
+            paste: paste.clone(),
+            paste_rules: PasteRules::new(config),
+        }));
+        let menu_keys = [
+            (KeyCode::Tab, true, true),
+            (KeyCode::Down, true, false),
+            (KeyCode::BackTab, false, false),
+            (KeyCode::Up, false, false),
+        ];
+        let redo = Arc::new(Mutex::new(Vec::new()));
+        for (key, forward, starts) in menu_keys {
+            editor.bind_sequence(
+                KeyEvent(key, Modifiers::NONE),
+                paste_aware(
+                    &paste,
+                    MenuKey {
+                        state: menu_state.clone(),
+                        forward,
+                        starts,
+                    },
+                ),
+            );
+        }
+        editor.bind_sequence(
+            Event::Any,
+            EventHandler::Conditional(Box::new(OtherKey {
+                menu: menu_state,
+                redo: redo.clone(),
+                paste: paste.clone(),
+            })),
+        );
+        // Ctrl-_ 撤销、Alt-_ 重做（vi 命令模式下为 u 和 Ctrl-R），Alt-t 交换单词；
+        // vi 插入模式默认把 Alt 键当作 ESC 加命令，这里绑定后与 emacs 模式相同
+        // Alt-y（Ctrl-Y 之后换成更早删除的内容）只在 emacs 模式下可用：vi 模式粘贴后光标停在最后一个字符上，
+        // rustyline 的 yank-pop 会删错位置
+        let undo_keys = [
+            (KeyEvent::ctrl('_'), false),
+            (KeyEvent(KeyCode::Char('u'), Modifiers::NONE), true),
+        ];
+        for (key, vi_only) in undo_keys {
+            editor.bind_sequence(
+                key,
+                paste_aware(
+                    &paste,
+                    UndoKey {
+                        redo: redo.clone(),
+                        vi_only,
+                    },
+                ),
+            );
+        }
+        let redo_keys = [(KeyEvent::alt('_'), false), (KeyEvent::ctrl('R'), true)];
+        for (key, vi_only) in redo_keys {
+            editor.bind_sequence(
+                key,
+                paste_aware(
+                    &paste,
+                    RedoKey {
+                        redo: redo.clone(),
+                        vi_only,
+                    },
+                ),
+            );
+        }
+        editor.bind_sequence(KeyEvent::alt('t'), paste_aware(&paste, TransposeWords));
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        editor.bind_sequence(
+            KeyEvent::alt('.'),
+            paste_aware(
+                &paste,
+                LastArgument {
+                    history: entries.clone(),
+                    last: Mutex::new(None),
+                },
+            ),
+        );
+        let edit_request = Arc::new(Mutex::new(None));
+        editor.bind_sequence(
+            Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('E')]),
+            paste_aware(&paste, EditCommandLine(edit_request.clone())),
+        );
+        editor.bind_sequence(
+            KeyEvent::alt('w'),
+            paste_aware(&paste, CopyLine(config.clipboard)),
+        );
+        let pasted = Arc::new(AtomicBool::new(false));
+        editor.bind_sequence(
+            KeyEvent(KeyCode::BracketedPasteStart, Modifiers::NONE),
+            EventHandler::Conditional(Box::new(PasteStart {
+                pasted: pasted.clone(),
+                guard: paste,
+            })),
+        );
+        Self {
+            config,
+            editor,
+            entries,
+            initial: None,
+            edit_request,
+            pasted,
+        }
     }
 
     pub fn load_history(&mut self) -> Result<(), ReadlineError> {
@@ -39,15 +786,86 @@ impl<'a> ReadlineManager<'a> {
         } else {
             debug!("历史记录加载成功");
         }
+        self.sync_entries();
         Ok(())
     }
 
+    // rustyline 自己跳过的转义序列有限，交给它的是去掉颜色的提示符，只在显示时换成带颜色的原文，
+    // 光标位置和换行按终端上实际的显示宽度计算
     pub fn readline(&mut self, prompt: &str) -> Result<String, ReadlineError> {
-        self.editor.readline(prompt)
+        let plain = prompt::strip_ansi(prompt);
+        if let Some(helper) = self.editor.helper_mut() {
+            *helper.prompt.borrow_mut() = (plain.clone(), prompt.to_string());
+            helper.commands.borrow_mut().take();
+            helper.underlined.set(false);
+            if let Ok(mut guard) = helper.paste.lock() {
+                *guard = PasteGuard::default();
+            }
+        }
+        let prompt = plain.as_str();
+        self.pasted.store(false, Ordering::Relaxed);
+        loop {
+            let result = match self.initial.take() {
+                Some(initial) => self.editor.readline_with_initial(prompt, (&initial, "")),
+                None => self.editor.readline(prompt),
+            };
+            let request = self.edit_request.lock().ok().and_then(|mut r| r.take());
+            match (result, request) {
+                (Err(ReadlineError::Interrupted), Some(line)) => {
+                    self.initial = Some(edit_in_editor(&line));
+                }
+                (Ok(line), _)
+                    if self.config.paste_strip_prompt && self.pasted.load(Ordering::Relaxed) =>
+                {
+                    return Ok(strip_paste_prompts(&line));
+                }
+                (result, _) => return result,
+            }
+        }
+    }
+
+    // 命令执行期间读取一行回答（select），不使用补全和历史记录
+    // 标准输入不是终端时 rustyline 直接读取一行且不显示提示，这里把提示写到 stderr
+    pub fn read_choice(prompt: &str) -> Result<String, ReadlineError> {
+        let mut editor = rustyline::DefaultEditor::new()?;
+        if !std::io::stdin().is_terminal() {
+            eprint!("{}", prompt);
+            return editor.readline("");
+        }
+        editor.readline(prompt)
+    }
+
+    // 补全使用的作业、内建命令和变量，每次读取输入前更新
+    pub fn completion(&mut self) -> Option<&mut Completion> {
+        self.editor
+            .helper_mut()
+            .map(|helper| &mut helper.completion)
+    }
+
+    pub fn set_right_prompt(&mut self, right: String) {
+        if let Some(helper) = self.editor.helper_mut() {
+            *helper.right_prompt.borrow_mut() = right;
+        }
+    }
+
+    pub fn set_initial(&mut self, text: String) {
+        self.initial = Some(text);
+    }
+
+    pub fn history(&self) -> Vec<String> {
+        self.editor.history().iter().cloned().collect()
     }
 
     pub fn add_history(&mut self, line: String) -> Result<bool, ReadlineError> {
-        self.editor.add_history_entry(line)
+        let added = self.editor.add_history_entry(line)?;
+        self.sync_entries();
+        Ok(added)
+    }
+
+    fn sync_entries(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            *entries = self.history();
+        }
     }
 
     pub fn save_history(&mut self) -> Result<(), ReadlineError> {
@@ -0,0 +1,288 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use nix::fcntl::OFlag;
+
+use crate::shell::parser::ast::Redirection;
+use crate::shell::parser::lexer::RedirectOp;
+use crate::shell::signals;
+use zakosh::i18n;
+
+#[derive(Debug, PartialEq)]
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+// 在子进程中应用重定向：打开目标并 dup2 到对应的 fd，按书写顺序应用，2>&1 > file 与 > file 2>&1 不同
+pub fn apply_redirection(redirection: &Redirection, target: &str, devtcp: bool) -> io::Result<()> {
+    let (fds, file) = open_redirection(redirection, target, devtcp)?;
+    for fd in fds {
+        nix::unistd::dup2(file.as_raw_fd(), fd)?;
+    }
+    Ok(())
+}
+
+// 打开重定向的目标，返回要替换的 fd 和打开的文件；>&N 复制一份 fd N
+fn open_redirection(
+    redirection: &Redirection,
+    target: &str,
+    devtcp: bool,
+) -> io::Result<(Vec<i32>, OwnedFd)> {
+    let mut options = OpenOptions::new();
+    match redirection.operator {
+        RedirectOp::Input => {
+            options.read(true);
+        }
+        RedirectOp::Output | RedirectOp::OutputAll => {
+            options.write(true).create(true).truncate(true);
+        }
+        RedirectOp::Append | RedirectOp::AppendAll => {
+            options.append(true).create(true);
+        }
+        RedirectOp::Duplicate => {
+            let source = target.parse::<i32>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    i18n::tf("redirect.invalid_fd", &[target]),
+                )
+            })?;
+            let copy = nix::fcntl::fcntl(source, nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(0))?;
+            return Ok((redirection.fds(), unsafe { OwnedFd::from_raw_fd(copy) }));
+        }
+        RedirectOp::HereDoc { .. } => return Ok((redirection.fds(), here_document(target)?)),
+        RedirectOp::HereString => return Ok((redirection.fds(), here_string(target)?)),
+    }
+
+    let file: OwnedFd = match open_network(target, devtcp) {
+        Some(stream) => stream?,
+        None => options.open(target)?.into(),
+    };
+    Ok((redirection.fds(), file))
+}
+
+// exec 重定向 shell 自身的 fd 后，保存原来的终端 fd，读取输入时临时换回终端
+#[derive(Default)]
+pub struct ShellFds {
+    // 被重定向前的 fd（复制到 10 以上，exec 时关闭）
+    saved: HashMap<i32, OwnedFd>,
+    // 当前重定向到的文件
+    redirected: HashMap<i32, OwnedFd>,
+}
+
+impl ShellFds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // exec > file：打开目标并持久地替换 shell 的 fd
+    pub fn redirect(
+        &mut self,
+        redirection: &Redirection,
+        target: &str,
+        devtcp: bool,
+    ) -> io::Result<()> {
+        let (fds, file) = open_redirection(redirection, target, devtcp)?;
+        let _ = io::stdout().flush();
+        for fd in fds {
+            if let Entry::Vacant(entry) = self.saved.entry(fd) {
+                let saved = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(10))?;
+                entry.insert(unsafe { OwnedFd::from_raw_fd(saved) });
+            }
+            nix::unistd::dup2(file.as_raw_fd(), fd)?;
+            self.redirected.insert(fd, file.try_clone()?);
+        }
+        signals::set_terminal_fd(self.terminal_fd());
+        Ok(())
+    }
+
+    // 优先使用仍然指向终端的标准 fd，都被重定向时使用保存的终端 fd
+    fn terminal_fd(&self) -> i32 {
+        let standard = [libc::STDOUT_FILENO, libc::STDIN_FILENO, libc::STDERR_FILENO];
+        let saved = self.saved.values().map(|fd| fd.as_raw_fd());
+        standard
+            .into_iter()
+            .chain(saved)
+            .find(|fd| unsafe { libc::isatty(*fd) } == 1)
+            .unwrap_or(libc::STDOUT_FILENO)
+    }
+
+    // 换回原来的终端 fd，让 readline 和终端交互
+    pub fn use_terminal(&self) {
+        let _ = io::stdout().flush();
+        for (fd, saved) in &self.saved {
+            let _ = nix::unistd::dup2(saved.as_raw_fd(), *fd);
+        }
+    }
+
+    // 重新应用 exec 的重定向，之后的命令输出到重定向的目标
+    pub fn use_redirections(&self) {
+        for (fd, file) in &self.redirected {
+            let _ = nix::unistd::dup2(file.as_raw_fd(), *fd);
+        }
+    }
+}
+
+// here document 的内容写入临时文件后从头读取，打开后立即删除文件；内容较多时也不会像管道一样阻塞
+fn here_document(body: &str) -> io::Result<OwnedFd> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "zako-heredoc-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    let _ = std::fs::remove_file(&path);
+    file.write_all(body.as_bytes())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file.into())
+}
+
+// here string 的内容加上换行写入管道，读端作为标准输入；
+// 不超过 PIPE_BUF 时写入空管道不会阻塞，更长的内容没有进程同时读取，改用 here document 的临时文件
+fn here_string(word: &str) -> io::Result<OwnedFd> {
+    let content = format!("{}\n", word);
+    if content.len() > libc::PIPE_BUF {
+        return here_document(&content);
+    }
+    let (read_end, write_end) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+    std::fs::File::from(write_end).write_all(content.as_bytes())?;
+    Ok(read_end)
+}
+
+// 解析 /dev/tcp/host/port 与 /dev/udp/host/port
+fn network_target(target: &str) -> Option<(Protocol, &str, &str)> {
+    let (protocol, rest) = if let Some(rest) = target.strip_prefix("/dev/tcp/") {
+        (Protocol::Tcp, rest)
+    } else if let Some(rest) = target.strip_prefix("/dev/udp/") {
+        (Protocol::Udp, rest)
+    } else {
+        return None;
+    };
+    let (host, port) = rest.rsplit_once('/')?;
+    Some((protocol, host, port))
+}
+
+// 打开网络连接，不是网络路径时返回 None
+fn open_network(target: &str, enabled: bool) -> Option<io::Result<OwnedFd>> {
+    let (protocol, host, port) = network_target(target)?;
+    if !enabled {
+        return Some(Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            i18n::t("redirect.devtcp_off"),
+        )));
+    }
+    let Ok(port) = port.parse::<u16>() else {
+        return Some(Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            i18n::tf("redirect.invalid_port", &[port]),
+        )));
+    };
+
+    Some(match protocol {
+        Protocol::Tcp => TcpStream::connect((host, port)).map(OwnedFd::from),
+        Protocol::Udp => connect_udp(host, port),
+    })
+}
+
+fn connect_udp(host: &str, port: u16) -> io::Result<OwnedFd> {
+    let addr = (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            i18n::tf("redirect.unknown_host", &[host]),
+        )
+    })?;
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(addr)?;
+    Ok(OwnedFd::from(socket))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_target() {
+        assert_eq!(
+            network_target("/dev/tcp/localhost/80"),
+            Some((Protocol::Tcp, "localhost", "80"))
+        );
+        assert_eq!(
+            network_target("/dev/udp/127.0.0.1/53"),
+            Some((Protocol::Udp, "127.0.0.1", "53"))
+        );
+        assert_eq!(network_target("/dev/tcp/localhost"), None);
+        assert_eq!(network_target("/tmp/output.txt"), None);
+    }
+
+    fn redirection(fd: Option<i32>, operator: RedirectOp) -> Redirection {
+        Redirection {
+            fd,
+            operator,
+            filename: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_open_redirection() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("zako-redirect-{}", std::process::id()));
+        let target = path.to_string_lossy();
+        let fds = |fd, operator, target: &str| {
+            open_redirection(&redirection(fd, operator), target, false).map(|(fds, _)| fds)
+        };
+        assert_eq!(fds(None, RedirectOp::Output, &target).ok(), Some(vec![1]));
+        assert_eq!(fds(None, RedirectOp::Input, &target).ok(), Some(vec![0]));
+        assert_eq!(
+            fds(Some(2), RedirectOp::Append, &target).ok(),
+            Some(vec![2])
+        );
+        assert_eq!(
+            fds(None, RedirectOp::OutputAll, &target).ok(),
+            Some(vec![1, 2])
+        );
+        assert_eq!(fds(Some(2), RedirectOp::Duplicate, "1").ok(), Some(vec![2]));
+        assert!(fds(None, RedirectOp::Duplicate, "x").is_err());
+
+        let (fds, file) = open_redirection(
+            &redirection(None, RedirectOp::HereDoc { expand: true }),
+            "line 1\nline 2\n",
+            false,
+        )?;
+        assert_eq!(fds, vec![0]);
+        let mut content = String::new();
+        io::Read::read_to_string(&mut std::fs::File::from(file), &mut content)?;
+        assert_eq!(content, "line 1\nline 2\n");
+
+        for word in ["a b".to_string(), "x".repeat(libc::PIPE_BUF)] {
+            let (fds, file) =
+                open_redirection(&redirection(Some(3), RedirectOp::HereString), &word, false)?;
+            assert_eq!(fds, vec![3]);
+            let mut content = String::new();
+            io::Read::read_to_string(&mut std::fs::File::from(file), &mut content)?;
+            assert_eq!(content, format!("{}\n", word));
+        }
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_devtcp_disabled() {
+        let result = open_network("/dev/tcp/localhost/80", false);
+        assert!(matches!(result, Some(Err(e)) if e.kind() == io::ErrorKind::PermissionDenied));
+        assert!(open_network("/tmp/output.txt", false).is_none());
+    }
+}
@@ -0,0 +1,140 @@
+// rm 安全网（rm_safety_net = true 时启用）：递归删除 /、家目录或 git 仓库的根目录时，
+// 交互模式下要求确认，脚本中拒绝执行；设置了 rm_trash_command 时改为把这些目标移到回收站
+// 在展开别名和变量之后、启动外部命令之前检查，\rm、/bin/rm 和别名都绕不过去
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::shell::hooks::{ExecPolicy, Verdict};
+use crate::utils::config::Config;
+use zakosh::i18n;
+
+pub struct RmSafetyNet {
+    // 回收站命令及其参数，目标追加在后面，例如 trash-put 或 gio trash
+    trash_command: Option<Vec<String>>,
+}
+
+impl RmSafetyNet {
+    pub fn new(config: &Config) -> Option<Self> {
+        config.rm_safety_net.then(|| Self {
+            trash_command: config
+                .rm_trash_command
+                .as_deref()
+                .and_then(|command| shell_words::split(command).ok())
+                .filter(|words| !words.is_empty()),
+        })
+    }
+}
+
+impl ExecPolicy for RmSafetyNet {
+    fn check(&mut self, program: &str, args: &[String]) -> Verdict {
+        if Path::new(program)
+            .file_name()
+            .and_then(|name| name.to_str())
+            != Some("rm")
+        {
+            return Verdict::Allow;
+        }
+        let (recursive, targets) = parse_rm_args(args);
+        if !recursive {
+            return Verdict::Allow;
+        }
+        let home = env::var_os("HOME").map(PathBuf::from);
+        let Some((target, reason)) = targets
+            .iter()
+            .find_map(|target| protected(target, home.as_deref()).map(|reason| (target, reason)))
+        else {
+            return Verdict::Allow;
+        };
+        match &self.trash_command {
+            Some(trash) => {
+                let mut words = trash.iter().cloned();
+                let program = words.next().unwrap_or_default();
+                let args = words.chain(targets.iter().map(|t| t.to_string())).collect();
+                Verdict::Rewrite(program, args)
+            }
+            None => Verdict::Confirm(i18n::tf("safety.confirm", &[target, &i18n::t(reason)])),
+        }
+    }
+}
+
+// 是否有 -r/-R/--recursive，以及要删除的目标；-- 之后的参数都是目标
+fn parse_rm_args(args: &[String]) -> (bool, Vec<&str>) {
+    let mut recursive = false;
+    let mut targets = Vec::new();
+    let mut options_done = false;
+    for arg in args {
+        if options_done || arg == "-" || !arg.starts_with('-') {
+            targets.push(arg.as_str());
+        } else if arg == "--" {
+            options_done = true;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            recursive |= long == "recursive";
+        } else {
+            recursive |= arg.contains(['r', 'R']);
+        }
+    }
+    (recursive, targets)
+}
+
+// 受保护的目录返回说明的消息键；目标不存在时不需要保护
+fn protected(target: &str, home: Option<&Path>) -> Option<&'static str> {
+    // 参数不会展开 ~，但 rm -rf ~ 的意图很明确
+    let expanded = shellexpand::tilde(target);
+    let path = fs::canonicalize(expanded.as_ref()).ok()?;
+    if path == Path::new("/") {
+        Some("safety.root")
+    } else if home.and_then(|home| fs::canonicalize(home).ok()).as_ref() == Some(&path) {
+        Some("safety.home")
+    } else if path.join(".git").exists() {
+        Some("safety.git_root")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rm_safety_net() {
+        assert_eq!(
+            parse_rm_args(&strings(&["-fr", "a", "--", "-b"])),
+            (true, vec!["a", "-b"])
+        );
+        assert_eq!(parse_rm_args(&strings(&["-f", "a"])), (false, vec!["a"]));
+        assert!(parse_rm_args(&strings(&["--recursive", "a"])).0);
+
+        let mut net = RmSafetyNet {
+            trash_command: None,
+        };
+        assert!(matches!(
+            net.check("/bin/rm", &strings(&["-rf", "/"])),
+            Verdict::Confirm(_)
+        ));
+        assert_eq!(net.check("rm", &strings(&["/"])), Verdict::Allow);
+        assert_eq!(net.check("ls", &strings(&["-R", "/"])), Verdict::Allow);
+        let missing = env::temp_dir().join("zako_no_such_dir");
+        let missing = missing.to_string_lossy().to_string();
+        assert_eq!(net.check("rm", &strings(&["-r", &missing])), Verdict::Allow);
+
+        // 仓库的根目录受保护，其中的子目录不受保护
+        let repo = env::temp_dir().join(format!("zako_rm_{}", std::process::id()));
+        let _ = fs::create_dir_all(repo.join(".git"));
+        let _ = fs::create_dir_all(repo.join("src"));
+        let root = repo.to_string_lossy().to_string();
+        let src = repo.join("src").to_string_lossy().to_string();
+        net.trash_command = Some(strings(&["gio", "trash"]));
+        assert_eq!(
+            net.check("rm", &strings(&["-r", &src, &root])),
+            Verdict::Rewrite("gio".to_string(), strings(&["trash", &src, &root]))
+        );
+        assert_eq!(net.check("rm", &strings(&["-r", &src])), Verdict::Allow);
+        let _ = fs::remove_dir_all(&repo);
+    }
+}
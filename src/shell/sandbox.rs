@@ -0,0 +1,110 @@
+// sandbox [-p NAME] cmd ...：在新的用户命名空间中运行命令，按配置断开网络、把家目录等路径挂载为只读，
+// 适合运行 curl | sh 式的安装脚本；只支持 Linux，需要编译时启用 sandbox 功能并且内核允许非特权用户命名空间
+use std::io;
+use std::path::PathBuf;
+
+use crate::utils::config::SandboxProfile;
+
+pub const SUPPORTED: bool = cfg!(all(feature = "sandbox", target_os = "linux"));
+
+// 需要挂载为只读的路径：家目录和配置中的 readonly
+fn readonly_paths(profile: &SandboxProfile) -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .filter(|_| profile.readonly_home)
+        .map(PathBuf::from);
+    home.into_iter().chain(profile.readonly.clone()).collect()
+}
+
+// 在子进程中 exec 之前调用，失败时不能运行命令
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+pub fn enter(profile: &SandboxProfile) -> io::Result<()> {
+    use nix::mount::{mount, MsFlags};
+    use nix::sched::{unshare, CloneFlags};
+    use nix::sys::statvfs::{statvfs, FsFlags};
+    use std::fs;
+
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let readonly = readonly_paths(profile);
+    let mut flags = CloneFlags::CLONE_NEWUSER;
+    if !profile.network {
+        flags |= CloneFlags::CLONE_NEWNET;
+    }
+    if !readonly.is_empty() {
+        flags |= CloneFlags::CLONE_NEWNS;
+    }
+    unshare(flags)?;
+    // 在新的命名空间中保持原来的 uid 和 gid，文件的属主不变
+    fs::write("/proc/self/setgroups", "deny")?;
+    fs::write("/proc/self/uid_map", format!("{} {} 1", uid, uid))?;
+    fs::write("/proc/self/gid_map", format!("{} {} 1", gid, gid))?;
+    if readonly.is_empty() {
+        return Ok(());
+    }
+
+    let none = None::<&str>;
+    // 挂载的修改不传播回 shell 所在的命名空间
+    mount(none, "/", none, MsFlags::MS_REC | MsFlags::MS_PRIVATE, none)?;
+    for path in readonly {
+        let failed = |e: nix::Error| io::Error::other(format!("{}: {}", path.display(), e));
+        mount(
+            Some(&path),
+            &path,
+            none,
+            MsFlags::MS_BIND | MsFlags::MS_REC,
+            none,
+        )
+        .map_err(failed)?;
+        // 原来的挂载的 nosuid/nodev 等标志在用户命名空间中被锁定，重新挂载时必须保留
+        let mounted = statvfs(&path).map_err(failed)?.flags();
+        let mut flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY;
+        for (fs_flag, ms_flag) in [
+            (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+            (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+            (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+            (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+            (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+            (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+        ] {
+            if mounted.contains(fs_flag) {
+                flags |= ms_flag;
+            }
+        }
+        mount(none, &path, none, flags, none).map_err(failed)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(all(feature = "sandbox", target_os = "linux")))]
+pub fn enter(_: &SandboxProfile) -> io::Result<()> {
+    Err(unsupported())
+}
+
+pub fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "sandbox: 这个 zako 编译时没有启用 sandbox 功能（只支持 Linux）",
+    )
+}
+
+// sandbox -l 显示的一行
+pub fn describe(profile: &SandboxProfile) -> String {
+    let network = if profile.network {
+        "网络"
+    } else {
+        "无网络"
+    };
+    let readonly: Vec<String> = readonly_paths(profile)
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    if readonly.is_empty() {
+        format!("{:<12} {}", profile.name, network)
+    } else {
+        format!(
+            "{:<12} {}，只读: {}",
+            profile.name,
+            network,
+            readonly.join(" ")
+        )
+    }
+}
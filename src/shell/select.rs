@@ -0,0 +1,74 @@
+// select NAME in WORD ... do cmd ...：显示编号菜单，读取选择后把 NAME 设为选中的项并运行命令，然后继续显示提示
+// 输入保存在 REPLY 中，编号无效时 NAME 为空；直接回车重新显示菜单，Ctrl-D 或 Ctrl-C 结束
+// WORD 与 for 相同，可以是范围和通配符；提示使用 PS3，没有设置时为 "#? "
+use zakosh::i18n;
+
+pub const USAGE: &str = "用法: select NAME in WORD ... do cmd ...";
+
+pub const DEFAULT_PROMPT: &str = "#? ";
+
+// 菜单每项一行，再多也没法从中选择；范围很大时不要先全部展开
+pub const MAX_ITEMS: usize = 1000;
+
+// 超过 MAX_ITEMS 项时报错，最多只取 MAX_ITEMS + 1 项
+pub fn collect(values: impl Iterator<Item = String>) -> Result<Vec<String>, String> {
+    let items: Vec<String> = values.take(MAX_ITEMS + 1).collect();
+    if items.len() > MAX_ITEMS {
+        return Err(i18n::tf("select.too_many", &[&MAX_ITEMS.to_string()]));
+    }
+    Ok(items)
+}
+
+// 编号右对齐，每项一行
+pub fn menu(items: &[String]) -> String {
+    let width = items.len().to_string().len();
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| format!("{:>width$}) {}\n", index + 1, item, width = width))
+        .collect()
+}
+
+// 编号从 1 开始，允许前后有空格
+pub fn choose<'a>(items: &'a [String], reply: &str) -> Option<&'a str> {
+    let number = reply.trim().parse::<usize>().ok()?;
+    items.get(number.checked_sub(1)?).map(|item| item.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(count: usize) -> Vec<String> {
+        (1..=count).map(|n| format!("item{}", n)).collect()
+    }
+
+    #[test]
+    fn test_menu() {
+        assert_eq!(menu(&items(2)), "1) item1\n2) item2\n");
+        let long = menu(&items(10));
+        assert!(long.starts_with(" 1) item1\n"));
+        assert!(long.ends_with("10) item10\n"));
+        assert_eq!(menu(&[]), "");
+    }
+
+    #[test]
+    fn test_choose() {
+        let items = items(3);
+        assert_eq!(choose(&items, "2"), Some("item2"));
+        assert_eq!(choose(&items, " 3 "), Some("item3"));
+        assert_eq!(choose(&items, "0"), None);
+        assert_eq!(choose(&items, "4"), None);
+        assert_eq!(choose(&items, "item1"), None);
+    }
+
+    #[test]
+    fn test_collect() {
+        assert_eq!(collect(items(3).into_iter()), Ok(items(3)));
+        assert_eq!(
+            collect(items(MAX_ITEMS).into_iter()).map(|items| items.len()),
+            Ok(MAX_ITEMS)
+        );
+        assert!(collect((0..).map(|n: u64| n.to_string())).is_err());
+    }
+}
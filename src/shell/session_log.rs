@@ -0,0 +1,86 @@
+// logoutput on FILE：把子进程的 stdout/stderr 同时写入会话日志，用于保留终端记录
+// 输出到终端时经过伪终端转发，子进程看到的仍然是终端（颜色、分列、行缓冲不变）；否则经过管道转发
+// 子进程的控制终端和标准输入不变，作业控制和 Ctrl-C 照常工作
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use nix::fcntl::{FcntlArg, FdFlag, OFlag};
+use nix::sys::termios::{self, OutputFlags};
+
+#[derive(Clone)]
+pub struct SessionLog {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl SessionLog {
+    // 追加写入，多次开启同一个文件不会覆盖之前的记录
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // 多个转发线程共用日志，持有锁一次性写完
+    pub fn write(&self, parts: &[&[u8]]) {
+        if let Ok(mut file) = self.file.lock() {
+            for part in parts {
+                let _ = file.write_all(part);
+            }
+        }
+    }
+}
+
+// 返回转发的读端和子进程使用的写端，fd 是 shell 自身的 stdout 或 stderr
+pub fn relay_pipe(fd: i32) -> io::Result<(OwnedFd, OwnedFd)> {
+    let target = unsafe { BorrowedFd::borrow_raw(fd) };
+    let Ok(mut modes) = termios::tcgetattr(target) else {
+        return Ok(nix::unistd::pipe2(OFlag::O_CLOEXEC)?);
+    };
+    // 换行等输出处理交给真正的终端，伪终端原样转发，日志中也不会出现多余的 \r
+    modes.output_flags.remove(OutputFlags::OPOST);
+    let size = window_size(fd);
+    let pty = nix::pty::openpty(size.as_ref(), Some(&modes))?;
+    for end in [&pty.master, &pty.slave] {
+        nix::fcntl::fcntl(end.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+    }
+    Ok((pty.master, pty.slave))
+}
+
+// 伪终端使用与终端相同的窗口大小，命令运行期间调整窗口不会同步
+fn window_size(fd: i32) -> Option<nix::pty::Winsize> {
+    unsafe {
+        let mut ws: libc::winsize = std::mem::zeroed();
+        (libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) == 0).then_some(ws)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_session_log_appends() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("zako_session_log_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        SessionLog::open(&path)?.write(&[b"first\n"]);
+        let log = SessionLog::open(&path)?;
+        log.write(&[b"sec", b"ond\n"]);
+        assert_eq!(log.path(), path);
+        let mut content = String::new();
+        File::open(&path)?.read_to_string(&mut content)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(content, "first\nsecond\n");
+        Ok(())
+    }
+}
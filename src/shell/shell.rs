@@ -1,43 +1,132 @@
 use log::{debug, error, warn};
 use std::error::Error;
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{fs, process};
 
+use crate::shell::aliases::AliasKind;
+use crate::shell::bench;
 use crate::shell::executor::Executor;
+use crate::shell::history;
+use crate::shell::hooks::Hooks;
+use crate::shell::identity;
+use crate::shell::import_env;
 use crate::shell::job_manager::JobManager;
-use crate::shell::parser::Parser;
+use crate::shell::local_config::LocalConfig;
+use crate::shell::parser::{self, Lexer, Parser};
 use crate::shell::readline::{ReadlineError, ReadlineManager};
 use crate::shell::signals;
+use crate::shell::terminal::TerminalModes;
+use crate::shell::update::{self, UpdateCheck};
 use crate::utils::config::Config;
+use crate::utils::segment::JobCounts;
 use crate::utils::theme::Theme;
 
+// 收到 SIGTERM 退出时的退出码
+const TERMINATED: i32 = 128 + libc::SIGTERM;
+
 pub struct Shell<'a> {
+    config: &'a Config,
     theme: Theme,
     readline: ReadlineManager<'a>,
     executor: Executor,
+    hooks: Hooks,
+    // 关闭 local_config 时为 None
+    local_config: Option<LocalConfig>,
+    // 关闭 update_check 时为 None
+    update_check: Option<UpdateCheck>,
+    script: Option<PathBuf>,
+    rc_file: PathBuf,
+    dump_tokens: bool,
+    dump_ast: bool,
+    bench_internal: bool,
+    // 交互式 shell 退出时保存历史
+    interactive: bool,
+    // 交互式 shell 启动时的终端模式，退出时恢复
+    terminal_modes: Option<TerminalModes>,
 }
 
 impl<'a> Shell<'a> {
     pub fn new(config: &'a Config) -> Self {
         Self {
-            theme: Theme::new(),
+            config,
+            theme: Theme::new(config),
             readline: ReadlineManager::new(config),
-            executor: Executor::new(JobManager::new()),
+            executor: Executor::new(config, JobManager::new()),
+            hooks: Hooks::new(config),
+            local_config: config
+                .local_config
+                .then(|| LocalConfig::new(config.trust_file())),
+            update_check: UpdateCheck::new(config),
+            script: config.script.clone(),
+            rc_file: config.rc_file.clone(),
+            dump_tokens: config.dump_tokens,
+            dump_ast: config.dump_ast,
+            bench_internal: config.bench_internal,
+            interactive: false,
+            terminal_modes: None,
         }
-        // let theme_file = Theme::get_theme_file(config);
-        // shell.variables.load_theme_variables(&theme_file);
     }
 
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
         debug!("初始化 ZakoShell...");
 
+        if self.config.upgrade {
+            process::exit(update::upgrade_self());
+        }
+
+        // 在设置信号处理之前运行登录 shell，它的子进程不受影响
+        if self.config.import_env {
+            import_env::import_login_env();
+        }
+        identity::export(self.config.login);
+        // 在 rc 文件和脚本运行前导出，通过 zako 启动的子 shell 同样受限
+        if self.executor.restricted() {
+            std::env::set_var("ZAKO_RESTRICTED", "1");
+        }
+
         // 忽略 shell block 信号，如 Ctrl-C, Ctrl-D 等
         signals::disable_signals();
+        // SIGTERM 时完成退出流程后再退出
+        signals::setup_terminate_handler();
         // 设置子进程信号处理，并存起子进程 pid 状态
         signals::setup_sigchld_handler();
         // 阻塞子进程信号，否则会造成子进程信号处理失败
         signals::block_child_signals();
 
+        if self.dump_tokens || self.dump_ast {
+            process::exit(self.dump_parse());
+        }
+
+        if self.bench_internal {
+            process::exit(bench::run(&mut self.executor));
+        }
+
+        if let Some(script) = self.script.take() {
+            signals::honor_quit();
+            let status = self.run_script(&script);
+            self.shutdown(status);
+        }
+
+        self.terminal_modes = TerminalModes::save();
+        // 交互式 shell 启动时先运行 rc 文件，其中的 exit 直接退出
+        if self.rc_file.is_file() {
+            let rc_file = self.rc_file.clone();
+            self.run_script(&rc_file);
+            if let Some(status) = self.executor.exit_requested() {
+                self.shutdown(status);
+            }
+        }
+
         self.readline.load_history()?;
+        self.interactive = true;
+
+        // 暴露种子，方便测试和录屏时复现同样的输出
+        if let Some(seed) = self.theme.message_seed() {
+            debug!("嘲讽消息种子: {}", seed);
+            std::env::set_var("ZAKO_MESSAGE_SEED", seed.to_string());
+        }
 
         println!(
             "{}",
@@ -47,19 +136,52 @@ impl<'a> Shell<'a> {
             "{}",
             (self.theme.warning_style)(self.theme.get_message("help"))
         );
+        if let Some(update_check) = &self.update_check {
+            update_check.start();
+        }
         debug!("ZakoShell 准备就绪...");
 
         self.run_loop()?;
-        self.readline.save_history()?;
+        // EOF 时以上一条命令的退出码退出
+        let status = self.executor.last_status();
+        self.shutdown(status);
+    }
 
+    // 所有退出路径（exit 内建命令、EOF、脚本结束、SIGTERM）都经过这里，按顺序完成退出前的工作：
+    // 运行 EXIT trap，开启 huponexit 时向作业发送 SIGHUP，保存历史，
+    // 清理执行器的资源（临时文件、命令使用次数等），最后恢复终端模式
+    fn shutdown(&mut self, status: i32) -> ! {
         debug!("退出 ZakoShell...");
-        Ok(())
+        let status = self.executor.run_exit_trap(status);
+        self.executor.hangup_jobs();
+        if self.interactive {
+            let _ = self.readline.save_history();
+        }
+        self.executor.cleanup();
+        if let Some(modes) = &self.terminal_modes {
+            modes.restore();
+        }
+        let _ = std::io::stdout().flush();
+        process::exit(status);
     }
 
     fn run_loop(&mut self) -> Result<(), Box<dyn Error>> {
         loop {
             std::io::stdout().flush()?;
-            let prompt = (self.theme.prompt_style)(self.theme.get_message("prompt"));
+            self.hooks.check_directory();
+            self.update_local_config();
+            if let Some(notice) = self.update_check.as_mut().and_then(UpdateCheck::poll) {
+                println!("{}", (self.theme.warning_style)(notice));
+            }
+            let status = self.executor.last_status();
+            self.hooks.precmd(status);
+            let duration = self.executor.last_duration();
+            let (running, stopped) = self.executor.job_counts();
+            let jobs = JobCounts { running, stopped };
+            let vars = self.executor.variables();
+            let prompt = self.theme.get_prompt(status, duration, jobs, vars);
+            let right = self.theme.get_right_prompt(status, duration, jobs, vars);
+            self.readline.set_right_prompt(right);
 
             // FIXME: in `rl.read_line()` below, there is lots of Rust code,
             // which may not be async-signal-safe. see follow links for details:
@@ -67,20 +189,45 @@ impl<'a> Shell<'a> {
             // - https://man7.org/linux/man-pages/man7/signal-safety.7.html
             signals::unblock_child_signals();
 
-            match self.readline.readline(&prompt) {
+            self.executor.use_terminal();
+            if let Some(completion) = self.readline.completion() {
+                completion.jobs = self.executor.jobs();
+                completion.builtins = self.executor.builtin_names();
+                completion.builtins.extend(self.executor.function_names());
+                completion.aliases = self.executor.alias_entries(AliasKind::Command);
+                completion.global_aliases = self.executor.alias_entries(AliasKind::Global);
+                completion.suffix_aliases = self.executor.alias_entries(AliasKind::Suffix);
+                completion.command_counts = self.executor.command_counts();
+                completion.variables = self.executor.variable_names();
+            }
+            // 先标记再检查，SIGTERM 无论在哪一步到达都不会错过
+            signals::set_reading_input(true);
+            let input = (!signals::terminate_requested()).then(|| self.readline.readline(&prompt));
+            signals::set_reading_input(false);
+            let Some(input) = input.filter(|_| !signals::terminate_requested()) else {
+                self.shutdown(TERMINATED);
+            };
+            self.executor.use_redirections();
+            match input.map(|line| self.read_continuation(line)) {
                 Ok(line) => {
                     // 阻塞信号，否则会造成子进程信号处理失败
                     signals::block_child_signals();
 
-                    if line.trim() == "exit" {
-                        debug!("退出 ZakoShell...");
-                        println!(
-                            "{}",
-                            (self.theme.success_style)(self.theme.get_message("exit"))
-                        );
-                        std::process::exit(0);
-                    }
+                    let Some(line) = self.expand_history(line) else {
+                        continue;
+                    };
+
                     self.handle_input(&line)?;
+
+                    // profile NAME：保存历史后以新的 profile 重新启动
+                    if let Some(profile) = self.executor.take_profile_switch() {
+                        self.readline.save_history()?;
+                        let mut args = vec!["--profile".to_string(), profile];
+                        if self.config.login {
+                            args.push("--login".to_string());
+                        }
+                        self.executor.exec_shell(&args);
+                    }
                 }
                 Err(err) => match err {
                     ReadlineError::Eof => {
@@ -97,6 +244,8 @@ impl<'a> Shell<'a> {
                             "\n{}",
                             (self.theme.warning_style)(self.theme.get_message("interrupt_signal"))
                         );
+                        // 行编辑时的 Ctrl-C 由 readline 处理，同样触发 INT trap
+                        self.executor.run_trap(libc::SIGINT);
                     }
                     err => {
                         error!("发生错误: {}", err);
@@ -110,33 +259,257 @@ impl<'a> Shell<'a> {
             }
             // 阻塞信号，否则会造成子进程信号处理失败
             signals::block_child_signals();
+            // 等待后台作业的排队命令
+            self.run_queued_commands();
+            self.executor.run_pending_traps();
+            if signals::terminate_requested() {
+                self.shutdown(TERMINATED);
+            }
+            // 命令或 trap 中的 exit
+            if let Some(status) = self.executor.exit_requested() {
+                println!(
+                    "{}",
+                    (self.theme.success_style)(self.theme.get_message("exit"))
+                );
+                self.shutdown(status);
+            }
         }
         Ok(())
     }
 
+    // 当前目录的 .zako.toml 变化时更新别名并重新加载主题
+    fn update_local_config(&mut self) {
+        let Some(local) = self.local_config.as_mut() else {
+            return;
+        };
+        let Ok(dir) = std::env::current_dir() else {
+            return;
+        };
+        if local.update(&dir) {
+            self.executor.set_aliases(local.aliases());
+            self.theme = Theme::with_local(self.config, local.source());
+        }
+    }
+
+    // 终端上 readline 的校验器会继续读取未结束的输入，标准输入不是终端时在这里读取后续行
+    fn read_continuation(&mut self, mut line: String) -> String {
+        while parser::is_incomplete(&line) {
+            match self.readline.readline("> ") {
+                Ok(next) => {
+                    line.push('\n');
+                    line.push_str(&next);
+                }
+                Err(_) => break,
+            }
+        }
+        line
+    }
+
+    // 历史扩展：出错时不执行；开启 histverify 时把结果放回编辑器，否则先显示扩展后的命令
+    fn expand_history(&mut self, line: String) -> Option<String> {
+        match history::expand(&line, &self.readline.history()) {
+            Ok(None) => Some(line),
+            Ok(Some(expanded)) if self.executor.histverify() => {
+                self.readline.set_initial(expanded);
+                None
+            }
+            Ok(Some(expanded)) => {
+                println!("{}", expanded);
+                Some(expanded)
+            }
+            Err(e) => {
+                eprintln!("zako: {}", e);
+                None
+            }
+        }
+    }
+
     fn handle_input(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
         if line.trim().is_empty() {
             return Ok(());
         }
 
         self.readline.add_history(line.to_string())?;
+        self.hooks.preexec(line);
+        // 粘贴的多行内容作为一条历史记录，逐条执行其中的命令
+        for command in parser::split_commands(line) {
+            self.run_line(&command);
+            if self.executor.exit_requested().is_some() {
+                break;
+            }
+        }
+        self.run_queued_commands();
+        Ok(())
+    }
+
+    // 运行 queue 排队的、所等待作业已结束的命令
+    fn run_queued_commands(&mut self) {
+        loop {
+            let ready = self.executor.take_ready_queue();
+            if ready.is_empty() {
+                break;
+            }
+            for line in ready {
+                debug!("运行排队命令: {}", line);
+                println!("{}", (self.theme.warning_style)(format!("queue: {}", line)));
+                self.run_line(&line);
+            }
+        }
+    }
+
+    // 打印脚本（没有脚本时为标准输入）每一行的词法单元和语法树，不执行
+    fn dump_parse(&self) -> i32 {
+        let content = match &self.script {
+            Some(script) => fs::read_to_string(script),
+            None => std::io::read_to_string(std::io::stdin()),
+        };
+        let content = match content {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("zako: {}", e);
+                return 127;
+            }
+        };
+        let mut status = 0;
+        for (number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            println!("# {}: {}", number + 1, line);
+            if self.dump_tokens {
+                for token in Lexer::new(line).tokens() {
+                    println!("{:?}", token);
+                }
+            }
+            if self.dump_ast {
+                match Parser::new(line).parse_command() {
+                    Ok(node) => println!("{:#?}", node),
+                    Err(e) => {
+                        eprintln!("zako: {}: {}", number + 1, e);
+                        status = 2;
+                    }
+                }
+            }
+        }
+        status
+    }
+
+    // 逐行执行脚本，不输出嘲讽消息，返回最后一条命令的退出码
+    // 有语法错误时退出码为 2；-n 模式下只检查语法和展开，不执行命令
+    // 逐行读取并执行，不把整个脚本读入内存，zako <(generate) 这样的管道输入也一样
+    fn run_script(&mut self, script: &PathBuf) -> i32 {
+        let reader = match fs::File::open(script) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => {
+                eprintln!("zako: {}: {}", script.display(), e);
+                return 127;
+            }
+        };
+        let mut status = 0;
+        let mut syntax_error = false;
+        // 跨多行的命令从第一行开始累积，报错时使用第一行的行号
+        let mut pending = String::new();
+        let mut number = 0;
+        for (index, line) in reader.lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("zako: {}:{}: {}", script.display(), index + 1, e);
+                    return 2;
+                }
+            };
+            let line = line.as_str();
+            if pending.is_empty() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                number = index;
+                pending.push_str(line);
+            } else {
+                pending.push('\n');
+                pending.push_str(line);
+            }
+            if parser::is_incomplete(&pending) {
+                continue;
+            }
+            let line = std::mem::take(&mut pending);
+            let node = match self.executor.parse(&line) {
+                Ok(node) => node,
+                Err(e) => {
+                    eprintln!("zako: {}:{}: {}", script.display(), number + 1, e);
+                    syntax_error = true;
+                    continue;
+                }
+            };
+            let mut condition = false;
+            status = match self.executor.execute(node) {
+                Ok(result) => {
+                    condition = result.condition;
+                    if let Some(report) = result.signal_report() {
+                        eprintln!(
+                            "zako: {}:{}: {}  {}",
+                            script.display(),
+                            number + 1,
+                            report,
+                            line
+                        );
+                    }
+                    result.status
+                }
+                Err(e) => {
+                    eprintln!("zako: {}:{}: {}", script.display(), number + 1, e);
+                    1
+                }
+            };
+            self.run_queued_commands();
+            self.executor.run_pending_traps();
+            if let Some(code) = self.executor.exit_requested() {
+                return code;
+            }
+            if signals::terminate_requested() {
+                return TERMINATED;
+            }
+            if status != 0 && !condition && self.executor.errexit() {
+                break;
+            }
+        }
+        if !pending.is_empty() {
+            eprintln!("zako: {}:{}: 输入意外结束", script.display(), number + 1);
+            syntax_error = true;
+        }
+        if syntax_error {
+            2
+        } else {
+            status
+        }
+    }
+
+    fn run_line(&mut self, line: &str) {
+        // explain 需要原始输入来展示引号的处理结果，不能先经过解析器
+        if let Some(rest) = line.trim_start().strip_prefix("explain ") {
+            match self.executor.explain(rest) {
+                Ok(explanation) => println!("{}", explanation),
+                Err(e) => eprintln!("explain: {}", e),
+            }
+            return;
+        }
+
         // 使用 parser 解析命令
-        let mut parser = Parser::new(line);
-        match parser.parse_command() {
+        match self.executor.parse(line) {
             Ok(node) => match self.executor.execute(node) {
-                Ok(_) => {
-                    println!(
-                        "{} {}",
-                        (self.theme.success_style)(self.theme.get_message("success_symbol")),
-                        (self.theme.success_style)(self.theme.get_message("command_success"))
-                    );
-                }
+                Ok(result) => self.print_command_result(&result, line),
                 Err(e) => {
                     println!("{}", e);
+                    // 受限模式拒绝的命令和没有权限时使用同一类嘲讽
+                    let key = match e.kind() {
+                        std::io::ErrorKind::PermissionDenied => "permission_denied",
+                        _ => "command_error",
+                    };
                     eprintln!(
                         "{} {}",
                         (self.theme.error_style)(self.theme.get_message("error_symbol")),
-                        (self.theme.error_style)(self.theme.get_message("command_error")),
+                        (self.theme.error_style)(self.theme.get_message(key)),
                     );
                 }
             },
@@ -149,7 +522,36 @@ impl<'a> Shell<'a> {
                 );
             }
         }
-        Ok(())
+    }
+
+    fn print_command_result(&self, result: &CommandResult, line: &str) {
+        debug!(
+            "命令结束: 退出码 {}，信号 {:?}，耗时 {:?}",
+            result.status, result.signal, result.duration
+        );
+        // 被 Ctrl-Z 暂停的作业已经打印过作业状态了
+        if result.stopped {
+            return;
+        }
+        // 和 zsh 一样说明进程是怎么死掉的：segmentation fault (core dumped)  ./a.out
+        if let Some(report) = result.signal_report() {
+            eprintln!(
+                "{}",
+                (self.theme.error_style)(format!("zako: {}  {}", report, line.trim()))
+            );
+        }
+        match result.failure_key() {
+            None => println!(
+                "{} {}",
+                (self.theme.success_style)(self.theme.get_message("success_symbol")),
+                (self.theme.success_style)(self.theme.get_message("command_success"))
+            ),
+            Some(key) => eprintln!(
+                "{} {}",
+                (self.theme.error_style)(self.theme.get_message("error_symbol")),
+                (self.theme.error_style)(self.theme.get_message(key)),
+            ),
+        }
     }
 
     // fn handle_variable_assignment(&mut self, args: &[String]) -> Result<(), Box<dyn Error>> {
@@ -179,40 +581,113 @@ impl<'a> Shell<'a> {
     // }
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug, Default)]
 pub struct CommandResult {
-    pub gid: i32,
     pub status: i32,
-    pub stdout: String,
-    pub stderr: String,
+    pub stopped: bool,
+    pub timed_out: bool,
+    // 作业中每个进程的退出码，按启动顺序排列
+    pub statuses: Vec<i32>,
+    // 最后一个进程被信号终止时的信号
+    pub signal: Option<i32>,
+    pub core_dumped: bool,
+    // 从启动到结束（或暂停）的耗时
+    pub duration: Duration,
+    // 退出码被 && 或 || 检查（不是列表中最后一个命令），失败时 set -e 不退出
+    pub condition: bool,
 }
 
 impl CommandResult {
     pub fn new() -> CommandResult {
         CommandResult {
-            gid: 0,
             status: 0,
-            stdout: String::new(),
-            stderr: String::new(),
+            stopped: false,
+            timed_out: false,
+            statuses: Vec::new(),
+            signal: None,
+            core_dumped: false,
+            duration: Duration::ZERO,
+            condition: false,
         }
     }
 
-    pub fn from_status(gid: i32, status: i32) -> CommandResult {
+    pub fn from_status(status: i32) -> CommandResult {
         CommandResult {
-            gid,
             status,
-            stdout: String::new(),
-            stderr: String::new(),
+            stopped: false,
+            timed_out: false,
+            statuses: Vec::new(),
+            signal: None,
+            core_dumped: false,
+            duration: Duration::ZERO,
+            condition: false,
         }
     }
 
-    pub fn error() -> CommandResult {
-        CommandResult {
-            gid: 0,
-            status: 1,
-            stdout: String::new(),
-            stderr: String::new(),
+    // 按退出码区分失败类型，对应不同的嘲讽消息池
+    pub fn failure_key(&self) -> Option<&'static str> {
+        if self.timed_out {
+            return Some("command_timeout");
         }
+        if self.core_dumped {
+            return Some("command_core_dumped");
+        }
+        if self.signal.is_some() {
+            return Some("command_killed");
+        }
+        match self.status {
+            0 => None,
+            127 => Some("command_not_found"),
+            126 => Some("permission_denied"),
+            status if status > 128 => Some("command_killed"),
+            _ => Some("command_error"),
+        }
+    }
+
+    // 被信号终止时的说明，如 "segmentation fault (core dumped)"
+    pub fn signal_report(&self) -> Option<String> {
+        let description = signals::signal_description(self.signal?)?;
+        if self.core_dumped {
+            Some(format!("{} (core dumped)", description))
+        } else {
+            Some(description.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_key() {
+        assert_eq!(CommandResult::new().failure_key(), None);
+        assert_eq!(
+            CommandResult::from_status(127).failure_key(),
+            Some("command_not_found")
+        );
+        assert_eq!(
+            CommandResult::from_status(2).failure_key(),
+            Some("command_error")
+        );
+        let mut killed = CommandResult::from_status(128 + libc::SIGSEGV);
+        killed.signal = Some(libc::SIGSEGV);
+        assert_eq!(killed.failure_key(), Some("command_killed"));
+        assert_eq!(
+            killed.signal_report().as_deref(),
+            Some("segmentation fault")
+        );
+        killed.core_dumped = true;
+        assert_eq!(killed.failure_key(), Some("command_core_dumped"));
+        assert_eq!(
+            killed.signal_report().as_deref(),
+            Some("segmentation fault (core dumped)")
+        );
+        killed.timed_out = true;
+        assert_eq!(killed.failure_key(), Some("command_timeout"));
+
+        let mut interrupted = CommandResult::from_status(128 + libc::SIGINT);
+        interrupted.signal = Some(libc::SIGINT);
+        assert_eq!(interrupted.signal_report(), None);
     }
 }
@@ -4,14 +4,17 @@ use nix::sys::signal;
 use nix::sys::wait::{waitpid, WaitPidFlag as WF, WaitStatus as WS};
 use nix::unistd::Pid;
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use std::{fmt, mem};
 
+// 子进程的状态变化，由 reap_children 和等待前台作业时记录，更新作业时取出
 lazy_static! {
     static ref REAP_MAP: Mutex<HashMap<i32, i32>> = Mutex::new(HashMap::new());
     static ref STOP_MAP: Mutex<HashSet<i32>> = Mutex::new(HashSet::new());
     static ref CONT_MAP: Mutex<HashSet<i32>> = Mutex::new(HashSet::new());
-    static ref KILL_MAP: Mutex<HashMap<i32, i32>> = Mutex::new(HashMap::new());
+    static ref KILL_MAP: Mutex<HashMap<i32, (i32, bool)>> = Mutex::new(HashMap::new());
 }
 
 pub fn disable_signals() {
@@ -33,9 +36,114 @@ pub fn enable_signals() {
         libc::signal(libc::SIGTSTP, libc::SIG_DFL); // Ctrl-Z
         libc::signal(libc::SIGTTOU, libc::SIG_DFL); // 当后台进程尝试写入终端时暂停进程
         libc::signal(libc::SIGTTIN, libc::SIG_DFL); // 当后台进程尝试从终端读取输入时暂停进程
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL); // Rust 运行时忽略了 SIGPIPE，读端关闭后写入的进程应当退出
+        libc::signal(libc::SIGTERM, libc::SIG_DFL); // shell 的 SIGTERM 处理函数不适用于子进程
     }
 }
 
+// 非交互式 shell（运行脚本）不忽略 SIGQUIT，与其他程序一样按默认处理终止
+static HONOR_QUIT: AtomicBool = AtomicBool::new(false);
+
+pub fn honor_quit() {
+    HONOR_QUIT.store(true, Ordering::SeqCst);
+    unsafe {
+        libc::signal(libc::SIGQUIT, libc::SIG_DFL);
+    }
+}
+
+// 收到 SIGTERM 时只做记录，由 shell 在读取输入返回或命令执行完之后完成退出流程
+static TERMINATE: AtomicBool = AtomicBool::new(false);
+// shell 正在 readline 中等待输入
+static READING_INPUT: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_terminate(_: i32) {
+    TERMINATE.store(true, Ordering::SeqCst);
+    if READING_INPUT.load(Ordering::SeqCst) {
+        // readline 遇到 EINTR 时继续等待输入，把标准输入换成 /dev/null 让它读到 EOF 返回
+        // open、dup2 和 close 都是 async-signal-safe 的
+        unsafe {
+            let fd = libc::open(c"/dev/null".as_ptr(), libc::O_RDONLY);
+            if fd >= 0 {
+                libc::dup2(fd, libc::STDIN_FILENO);
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+pub fn setup_terminate_handler() {
+    if let Err(e) = set_handler(libc::SIGTERM, signal::SigHandler::Handler(handle_terminate)) {
+        error!("无法设置 SIGTERM 处理函数: {}", e);
+    }
+}
+
+pub fn set_reading_input(reading: bool) {
+    READING_INPUT.store(reading, Ordering::SeqCst);
+}
+
+pub fn terminate_requested() -> bool {
+    TERMINATE.load(Ordering::SeqCst)
+}
+
+// sleep、waitfor 等内建命令在 shell 进程中等待，期间 Ctrl-C 和 Ctrl-Z 结束等待，而不是和平时一样被忽略
+static WAKE_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn handle_wake(sig: i32) {
+    WAKE_SIGNAL.store(sig, Ordering::SeqCst);
+}
+
+pub enum Wake {
+    Elapsed,
+    // Ctrl-C 或 SIGTERM
+    Interrupted,
+    // Ctrl-Z，带有剩余的时间
+    Suspended(Duration),
+}
+
+pub fn sleep_interruptible(duration: Duration) -> Wake {
+    WAKE_SIGNAL.store(0, Ordering::SeqCst);
+    let sa = signal::SigAction::new(
+        signal::SigHandler::Handler(handle_wake),
+        signal::SaFlags::empty(),
+        signal::SigSet::empty(),
+    );
+    let previous: Vec<_> = [signal::Signal::SIGINT, signal::Signal::SIGTSTP]
+        .into_iter()
+        .filter_map(|sig| Some((sig, unsafe { signal::sigaction(sig, &sa) }.ok()?)))
+        .collect();
+    // 不计算截止时间，很长的时长加到 Instant 上会溢出
+    let started = Instant::now();
+    let wake = loop {
+        let remaining = duration.saturating_sub(started.elapsed());
+        match WAKE_SIGNAL.load(Ordering::SeqCst) {
+            libc::SIGTSTP => break Wake::Suspended(remaining),
+            libc::SIGINT => break Wake::Interrupted,
+            _ if terminate_requested() => break Wake::Interrupted,
+            _ if remaining.is_zero() => break Wake::Elapsed,
+            _ => {}
+        }
+        // 与 std::thread::sleep 不同，nanosleep 被信号打断时返回，不会继续等待
+        let ts = libc::timespec {
+            tv_sec: remaining.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_nsec: remaining.subsec_nanos() as libc::c_long,
+        };
+        unsafe {
+            libc::nanosleep(&ts, std::ptr::null_mut());
+        }
+    };
+    for (sig, action) in previous {
+        let _ = unsafe { signal::sigaction(sig, &action) };
+    }
+    wake
+}
+
+// 控制终端所在的 fd，exec 重定向了 stdout 后改为其他仍然指向终端的 fd
+static TERMINAL_FD: AtomicI32 = AtomicI32::new(libc::STDOUT_FILENO);
+
+pub fn set_terminal_fd(fd: i32) {
+    TERMINAL_FD.store(fd, Ordering::SeqCst);
+}
+
 pub fn give_terminal_to(gid: i32) -> bool {
     unsafe {
         debug!("正在将终端控制权交给进程组: {}", gid);
@@ -52,13 +160,18 @@ pub fn give_terminal_to(gid: i32) -> bool {
         if rcode != 0 {
             error!("failed to call pthread_sigmask");
         }
-        let rcode = libc::tcsetpgrp(1, gid);
+        let rcode = libc::tcsetpgrp(TERMINAL_FD.load(Ordering::SeqCst), gid);
         let given;
         if rcode == -1 {
             given = false;
             let e = errno();
             let code = e.0;
-            error!("error in give_terminal_to() {}: {}", code, e);
+            // 没有控制终端（例如标准输出被重定向）不是错误
+            if code == libc::ENOTTY {
+                debug!("error in give_terminal_to() {}: {}", code, e);
+            } else {
+                error!("error in give_terminal_to() {}: {}", code, e);
+            }
         } else {
             given = true;
         }
@@ -112,9 +225,9 @@ pub fn waitpidx(wpid: i32, block: bool) -> WaitStatus {
             let pid = i32::from(pid);
             WaitStatus::from_continuted(pid)
         }
-        Ok(WS::Signaled(pid, sig, _core_dumped)) => {
+        Ok(WS::Signaled(pid, sig, core_dumped)) => {
             let pid = i32::from(pid);
-            WaitStatus::from_signaled(pid, sig as i32)
+            WaitStatus::from_signaled(pid, sig as i32, core_dumped)
         }
         Ok(WS::StillAlive) => WaitStatus::empty(),
         Ok(_others) => {
@@ -126,9 +239,71 @@ pub fn waitpidx(wpid: i32, block: bool) -> WaitStatus {
     }
 }
 
-// 监听子进程状态
+// SIGCHLD 使用 self-pipe：处理函数只向管道写一个字节（write 和原子操作是异步信号安全的），
+// waitpid 和更新下面的状态表都由 reap_children 在信号处理函数之外完成，不会在持有锁时被信号打断
+static SIGCHLD_READ_FD: AtomicI32 = AtomicI32::new(-1);
+static SIGCHLD_WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
 extern "C" fn handle_sigchld(_sig: i32) {
     let saved_errno = errno();
+    let fd = SIGCHLD_WRITE_FD.load(Ordering::Relaxed);
+    if fd >= 0 {
+        // 管道满了说明已经有未处理的通知，写入失败也没关系
+        unsafe {
+            libc::write(fd, [1u8].as_ptr() as *const libc::c_void, 1);
+        }
+    }
+    set_errno(saved_errno);
+}
+
+pub fn setup_sigchld_handler() {
+    if SIGCHLD_READ_FD.load(Ordering::SeqCst) < 0 {
+        match create_sigchld_pipe() {
+            Ok((read_fd, write_fd)) => {
+                SIGCHLD_READ_FD.store(read_fd, Ordering::SeqCst);
+                SIGCHLD_WRITE_FD.store(write_fd, Ordering::SeqCst);
+            }
+            Err(e) => error!("无法创建 SIGCHLD 管道: {}", e),
+        }
+    }
+    let sigset = signal::SigSet::empty();
+    let handler = signal::SigHandler::Handler(handle_sigchld);
+    // automatically restart system calls interrupted by this signal handler
+    let flags = signal::SaFlags::SA_RESTART;
+    let sa = signal::SigAction::new(handler, flags, sigset);
+    unsafe {
+        match signal::sigaction(signal::SIGCHLD, &sa) {
+            Ok(_) => {}
+            Err(e) => {
+                error!("sigaction error: {:?}", e);
+            }
+        }
+    }
+}
+
+// 两端都是非阻塞的，并且不会被子进程继承
+fn create_sigchld_pipe() -> std::io::Result<(i32, i32)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    for fd in fds {
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+    }
+    Ok((fds[0], fds[1]))
+}
+
+// 读空通知管道，收集所有状态发生变化的子进程，记录到状态表中
+pub fn reap_children() {
+    let fd = SIGCHLD_READ_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } > 0 {}
+    }
     loop {
         let status = waitpidx(-1, false);
 
@@ -141,7 +316,7 @@ extern "C" fn handle_sigchld(_sig: i32) {
             // on Mac (both for signal handler or sync waitpid).
             insert_cont_map(status.get_pid());
         } else if status.is_signaled() {
-            killed_map_insert(status.get_pid(), status.get_signal());
+            killed_map_insert(status.get_pid(), status.get_signal(), status.core_dumped());
         } else if status.is_error() {
             if status.get_errno() != nix::Error::ECHILD {
                 error!("chld waitpid error: {:?}", status.get_errno());
@@ -151,34 +326,172 @@ extern "C" fn handle_sigchld(_sig: i32) {
             break;
         }
     }
+}
 
-    set_errno(saved_errno);
+// 信号名称表，kill/trap 和作业状态显示共用
+const SIGNALS: &[(i32, &str)] = &[
+    (libc::SIGHUP, "HUP"),
+    (libc::SIGINT, "INT"),
+    (libc::SIGQUIT, "QUIT"),
+    (libc::SIGILL, "ILL"),
+    (libc::SIGTRAP, "TRAP"),
+    (libc::SIGABRT, "ABRT"),
+    (libc::SIGBUS, "BUS"),
+    (libc::SIGFPE, "FPE"),
+    (libc::SIGKILL, "KILL"),
+    (libc::SIGUSR1, "USR1"),
+    (libc::SIGSEGV, "SEGV"),
+    (libc::SIGUSR2, "USR2"),
+    (libc::SIGPIPE, "PIPE"),
+    (libc::SIGALRM, "ALRM"),
+    (libc::SIGTERM, "TERM"),
+    (libc::SIGCHLD, "CHLD"),
+    (libc::SIGCONT, "CONT"),
+    (libc::SIGSTOP, "STOP"),
+    (libc::SIGTSTP, "TSTP"),
+    (libc::SIGTTIN, "TTIN"),
+    (libc::SIGTTOU, "TTOU"),
+    (libc::SIGURG, "URG"),
+    (libc::SIGXCPU, "XCPU"),
+    (libc::SIGXFSZ, "XFSZ"),
+    (libc::SIGVTALRM, "VTALRM"),
+    (libc::SIGPROF, "PROF"),
+    (libc::SIGWINCH, "WINCH"),
+    (libc::SIGIO, "IO"),
+    (libc::SIGSYS, "SYS"),
+];
+
+pub fn all_signals() -> &'static [(i32, &'static str)] {
+    SIGNALS
 }
 
-pub fn setup_sigchld_handler() {
-    let sigset = signal::SigSet::empty();
-    let handler = signal::SigHandler::Handler(handle_sigchld);
-    // automatically restart system calls interrupted by this signal handler
-    let flags = signal::SaFlags::SA_RESTART;
-    let sa = signal::SigAction::new(handler, flags, sigset);
-    unsafe {
-        match signal::sigaction(signal::SIGCHLD, &sa) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("sigaction error: {:?}", e);
-            }
-        }
+// 信号编号对应的名称，如 11 -> SIGSEGV
+pub fn signal_name(sig: i32) -> String {
+    match SIGNALS.iter().find(|(num, _)| *num == sig) {
+        Some((_, name)) => format!("SIG{}", name),
+        None => format!("SIG{}", sig),
+    }
+}
+
+// 进程被信号终止时显示的说明，与 zsh 相同；INT 和 PIPE 通常是用户或管道有意为之，不显示
+pub fn signal_description(sig: i32) -> Option<&'static str> {
+    let description = match sig {
+        libc::SIGHUP => "hangup",
+        libc::SIGQUIT => "quit",
+        libc::SIGILL => "illegal hardware instruction",
+        libc::SIGTRAP => "trace trap",
+        libc::SIGABRT => "abort",
+        libc::SIGBUS => "bus error",
+        libc::SIGFPE => "floating point exception",
+        libc::SIGKILL => "killed",
+        libc::SIGUSR1 => "user-defined signal 1",
+        libc::SIGSEGV => "segmentation fault",
+        libc::SIGUSR2 => "user-defined signal 2",
+        libc::SIGALRM => "alarm",
+        libc::SIGTERM => "terminated",
+        libc::SIGXCPU => "cpu limit exceeded",
+        libc::SIGXFSZ => "file size limit exceeded",
+        libc::SIGVTALRM => "virtual time alarm",
+        libc::SIGPROF => "profile signal",
+        libc::SIGSYS => "invalid system call",
+        _ => return None,
+    };
+    Some(description)
+}
+
+// 解析信号：HUP / SIGHUP / sighup / 1
+pub fn parse_signal(input: &str) -> Option<i32> {
+    if let Ok(num) = input.parse::<i32>() {
+        return SIGNALS.iter().any(|(n, _)| *n == num).then_some(num);
+    }
+    let upper = input.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(num, _)| *num)
+}
+
+// EXIT 伪信号：trap 'cmd' EXIT 在 shell 退出时运行，不对应真实的信号
+pub const EXIT: i32 = 0;
+
+// trap 接受的名称：除了信号外还有 EXIT（或 0）
+pub fn parse_trap(input: &str) -> Option<i32> {
+    if input == "0" || input.eq_ignore_ascii_case("EXIT") {
+        return Some(EXIT);
+    }
+    parse_signal(input)
+}
+
+pub fn trap_name(sig: i32) -> String {
+    if sig == EXIT {
+        return "EXIT".to_string();
     }
+    signal_name(sig)
+}
+
+// trap 设置的信号到达时只记录下来，由 shell 在执行完命令或显示提示符前运行对应的命令
+static PENDING_TRAPS: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn handle_trap(sig: i32) {
+    PENDING_TRAPS.fetch_or(1 << sig, Ordering::SeqCst);
+}
+
+// 为信号安装 trap 处理函数，ignore 为 true 时忽略该信号（trap '' SIG）
+pub fn install_trap(sig: i32, ignore: bool) -> nix::Result<()> {
+    let handler = if ignore {
+        signal::SigHandler::SigIgn
+    } else {
+        signal::SigHandler::Handler(handle_trap)
+    };
+    set_handler(sig, handler)
 }
 
-pub fn killed_map_insert(pid: i32, sig: i32) {
-    if let Ok(mut m) = KILL_MAP.try_lock() {
-        m.insert(pid, sig);
+// 恢复 shell 对该信号的默认处理
+pub fn reset_trap(sig: i32) -> nix::Result<()> {
+    if sig == libc::SIGTERM {
+        return set_handler(sig, signal::SigHandler::Handler(handle_terminate));
+    }
+    let ignored = [
+        libc::SIGINT,
+        libc::SIGQUIT,
+        libc::SIGTSTP,
+        libc::SIGTTOU,
+        libc::SIGTTIN,
+    ];
+    let honored = sig == libc::SIGQUIT && HONOR_QUIT.load(Ordering::SeqCst);
+    let handler = if ignored.contains(&sig) && !honored {
+        signal::SigHandler::SigIgn
+    } else {
+        signal::SigHandler::SigDfl
+    };
+    set_handler(sig, handler)
+}
+
+fn set_handler(sig: i32, handler: signal::SigHandler) -> nix::Result<()> {
+    let sig = signal::Signal::try_from(sig)?;
+    let sa = signal::SigAction::new(
+        handler,
+        signal::SaFlags::SA_RESTART,
+        signal::SigSet::empty(),
+    );
+    unsafe { signal::sigaction(sig, &sa) }.map(|_| ())
+}
+
+// 取出所有已到达但还没有处理的 trap 信号
+pub fn take_pending_traps() -> Vec<i32> {
+    let pending = PENDING_TRAPS.swap(0, Ordering::SeqCst);
+    (1..64).filter(|sig| pending & (1 << sig) != 0).collect()
+}
+
+pub fn killed_map_insert(pid: i32, sig: i32, core_dumped: bool) {
+    if let Ok(mut m) = KILL_MAP.lock() {
+        m.insert(pid, (sig, core_dumped));
     }
 }
 
-pub fn killed_map_pop(pid: i32) -> Option<i32> {
-    if let Ok(mut m) = KILL_MAP.try_lock() {
+pub fn killed_map_pop(pid: i32) -> Option<(i32, bool)> {
+    if let Ok(mut m) = KILL_MAP.lock() {
         m.remove(&pid)
     } else {
         None
@@ -186,44 +499,73 @@ pub fn killed_map_pop(pid: i32) -> Option<i32> {
 }
 
 pub fn insert_cont_map(pid: i32) {
-    if let Ok(mut m) = CONT_MAP.try_lock() {
+    if let Ok(mut m) = CONT_MAP.lock() {
         m.insert(pid);
     }
 }
 
 pub fn pop_cont_map(pid: i32) -> bool {
-    match CONT_MAP.try_lock() {
+    match CONT_MAP.lock() {
         Ok(mut m) => m.remove(&pid),
         Err(_) => false,
     }
 }
 
 pub fn insert_stopped_map(pid: i32) {
-    if let Ok(mut m) = STOP_MAP.try_lock() {
+    if let Ok(mut m) = STOP_MAP.lock() {
         m.insert(pid);
     }
 }
 
 pub fn pop_stopped_map(pid: i32) -> bool {
-    match STOP_MAP.try_lock() {
+    match STOP_MAP.lock() {
         Ok(mut m) => m.remove(&pid),
         Err(_) => false,
     }
 }
 
 pub fn insert_reap_map(pid: i32, status: i32) {
-    if let Ok(mut m) = REAP_MAP.try_lock() {
+    if let Ok(mut m) = REAP_MAP.lock() {
         m.insert(pid, status);
     }
 }
 
 pub fn pop_reap_map(pid: i32) -> Option<i32> {
-    match REAP_MAP.try_lock() {
+    match REAP_MAP.lock() {
         Ok(mut m) => m.remove(&pid),
         Err(_) => None,
     }
 }
 
+// 回收所有已经结束的子进程，再丢弃不属于 tracked 中任何进程的状态记录，返回丢弃的数量
+// 管道中的非组长进程、disown 的作业和 double fork 的中间进程没有作业来取出状态，不清理时会一直留在表中
+pub fn reap_orphans(tracked: &[i32]) -> usize {
+    reap_children();
+    retain_pids(&REAP_MAP, tracked)
+        + retain_pids(&KILL_MAP, tracked)
+        + retain_pid_set(&STOP_MAP, tracked)
+        + retain_pid_set(&CONT_MAP, tracked)
+}
+
+// 只保留 tracked 中的进程，返回丢弃的数量
+fn retain_pids<V>(map: &Mutex<HashMap<i32, V>>, tracked: &[i32]) -> usize {
+    let Ok(mut m) = map.lock() else {
+        return 0;
+    };
+    let before = m.len();
+    m.retain(|pid, _| tracked.contains(pid));
+    before - m.len()
+}
+
+fn retain_pid_set(set: &Mutex<HashSet<i32>>, tracked: &[i32]) -> usize {
+    let Ok(mut m) = set.lock() else {
+        return 0;
+    };
+    let before = m.len();
+    m.retain(|pid| tracked.contains(pid));
+    before - m.len()
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct WaitStatus(i32, i32, i32);
 
@@ -232,8 +574,9 @@ impl WaitStatus {
         WaitStatus(pid, 0, status)
     }
 
-    pub fn from_signaled(pid: i32, sig: i32) -> Self {
-        WaitStatus(pid, 1, sig)
+    // 产生了 core dump 时使用单独的状态 4
+    pub fn from_signaled(pid: i32, sig: i32, core_dumped: bool) -> Self {
+        WaitStatus(pid, if core_dumped { 4 } else { 1 }, sig)
     }
 
     pub fn from_stopped(pid: i32, sig: i32) -> Self {
@@ -265,7 +608,11 @@ impl WaitStatus {
     }
 
     pub fn is_signaled(&self) -> bool {
-        self.1 == 1
+        self.1 == 1 || self.1 == 4
+    }
+
+    pub fn core_dumped(&self) -> bool {
+        self.1 == 4
     }
 
     pub fn get_errno(&self) -> nix::Error {
@@ -333,3 +680,37 @@ impl fmt::Debug for WaitStatus {
         formatter.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_names() {
+        assert_eq!(parse_signal("HUP"), Some(libc::SIGHUP));
+        assert_eq!(parse_signal("sigint"), Some(libc::SIGINT));
+        assert_eq!(parse_signal("9"), Some(libc::SIGKILL));
+        assert_eq!(parse_signal("NOPE"), None);
+        assert_eq!(parse_signal("999"), None);
+        assert_eq!(signal_name(libc::SIGSEGV), "SIGSEGV");
+        assert_eq!(parse_trap("exit"), Some(EXIT));
+        assert_eq!(parse_trap("TERM"), Some(libc::SIGTERM));
+        assert_eq!(trap_name(EXIT), "EXIT");
+        assert_eq!(
+            signal_description(libc::SIGSEGV),
+            Some("segmentation fault")
+        );
+        assert_eq!(signal_description(libc::SIGINT), None);
+    }
+
+    #[test]
+    fn test_retain_pids() {
+        // 使用自己的表，不影响其他测试中子进程的记录
+        let reaped = Mutex::new(HashMap::from([(1, 0), (2, 0), (3, 1)]));
+        let stopped = Mutex::new(HashSet::from([2, 3]));
+        assert_eq!(retain_pids(&reaped, &[1]), 2);
+        assert_eq!(retain_pid_set(&stopped, &[1, 2]), 1);
+        assert_eq!(reaped.lock().map(|m| m.len()).ok(), Some(1));
+        assert_eq!(stopped.lock().map(|m| m.contains(&2)).ok(), Some(true));
+    }
+}
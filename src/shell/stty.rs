@@ -0,0 +1,137 @@
+use nix::sys::termios::{
+    self, ControlFlags, InputFlags, LocalFlags, OutputFlags, SetArg, SpecialCharacterIndices,
+    Termios,
+};
+use std::io;
+use std::os::fd::BorrowedFd;
+
+// stty [sane|raw|-raw|echo|-echo|size]，直接操作 shell 的标准输入所在的终端
+pub fn stty(args: &[String]) -> io::Result<()> {
+    // shell 的标准输入在整个会话中保持打开
+    let fd = unsafe { BorrowedFd::borrow_raw(libc::STDIN_FILENO) };
+    let mut attrs = termios::tcgetattr(fd).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("stty: 标准输入不是终端: {}", e),
+        )
+    })?;
+
+    if args.is_empty() {
+        println!("{}", describe(&attrs));
+        return Ok(());
+    }
+
+    for arg in args {
+        match arg.as_str() {
+            "size" => {
+                let (rows, cols) = window_size()?;
+                println!("{} {}", rows, cols);
+                continue;
+            }
+            "sane" | "-raw" | "cooked" => make_sane(&mut attrs),
+            "raw" | "-cooked" => termios::cfmakeraw(&mut attrs),
+            "echo" => attrs.local_flags.insert(LocalFlags::ECHO),
+            "-echo" => attrs.local_flags.remove(LocalFlags::ECHO),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("stty: 无效参数 {}，支持 sane/raw/-raw/echo/-echo/size", arg),
+                ))
+            }
+        }
+        termios::tcsetattr(fd, SetArg::TCSADRAIN, &attrs)?;
+    }
+    Ok(())
+}
+
+// 恢复到新终端的默认行为：行缓冲、回显、信号键和换行转换
+fn make_sane(attrs: &mut Termios) {
+    attrs
+        .input_flags
+        .insert(InputFlags::BRKINT | InputFlags::ICRNL | InputFlags::IXON | InputFlags::IMAXBEL);
+    attrs
+        .input_flags
+        .remove(InputFlags::IGNCR | InputFlags::INLCR | InputFlags::ISTRIP);
+    attrs
+        .output_flags
+        .insert(OutputFlags::OPOST | OutputFlags::ONLCR);
+    attrs.control_flags.insert(ControlFlags::CREAD);
+    attrs.local_flags.insert(
+        LocalFlags::ISIG
+            | LocalFlags::ICANON
+            | LocalFlags::IEXTEN
+            | LocalFlags::ECHO
+            | LocalFlags::ECHOE
+            | LocalFlags::ECHOK
+            | LocalFlags::ECHOCTL
+            | LocalFlags::ECHOKE,
+    );
+    attrs
+        .local_flags
+        .remove(LocalFlags::ECHONL | LocalFlags::NOFLSH | LocalFlags::TOSTOP);
+    attrs.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+    attrs.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+}
+
+fn describe(attrs: &Termios) -> String {
+    let flag = |on: bool, name: &str| {
+        if on {
+            name.to_string()
+        } else {
+            format!("-{}", name)
+        }
+    };
+    let local = attrs.local_flags;
+    let mut parts = vec![
+        flag(local.contains(LocalFlags::ECHO), "echo"),
+        flag(local.contains(LocalFlags::ICANON), "icanon"),
+        flag(local.contains(LocalFlags::ISIG), "isig"),
+        flag(attrs.input_flags.contains(InputFlags::ICRNL), "icrnl"),
+        flag(attrs.output_flags.contains(OutputFlags::OPOST), "opost"),
+    ];
+    if let Ok((rows, cols)) = window_size() {
+        parts.push(format!("rows {}; columns {}", rows, cols));
+    }
+    parts.join(" ")
+}
+
+fn window_size() -> io::Result<(u16, u16)> {
+    unsafe {
+        let mut ws: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, &mut ws) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((ws.ws_row, ws.ws_col))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_make_sane() {
+        // 在新的伪终端上操作，不影响运行测试的终端
+        let pty = nix::pty::openpty(None, None).unwrap();
+        let mut attrs = termios::tcgetattr(&pty.slave).unwrap();
+        termios::cfmakeraw(&mut attrs);
+        assert!(!attrs.local_flags.contains(LocalFlags::ECHO));
+        assert!(describe(&attrs).starts_with("-echo -icanon -isig -icrnl -opost"));
+
+        make_sane(&mut attrs);
+        assert!(attrs
+            .local_flags
+            .contains(LocalFlags::ECHO | LocalFlags::ICANON | LocalFlags::ISIG));
+        assert_eq!(
+            attrs.control_chars[SpecialCharacterIndices::VMIN as usize],
+            1
+        );
+        assert!(describe(&attrs).starts_with("echo icanon isig icrnl opost"));
+        termios::tcsetattr(&pty.slave, SetArg::TCSANOW, &attrs).unwrap();
+        let applied = termios::tcgetattr(&pty.slave).unwrap();
+        assert!(applied
+            .output_flags
+            .contains(OutputFlags::OPOST | OutputFlags::ONLCR));
+    }
+}
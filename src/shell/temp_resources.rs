@@ -0,0 +1,135 @@
+use log::{debug, warn};
+use nix::fcntl::{self, OFlag};
+use nix::sys::stat::Mode;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::shell::parser::lexer::SubstKind;
+
+// 进程替换优先使用 /dev/fd/N，没有 /dev/fd 的平台改用临时命名管道
+pub fn has_dev_fd() -> bool {
+    Path::new("/dev/fd").is_dir()
+}
+
+struct TempFifo {
+    // 所属作业的进程组，0 表示作业还没有启动
+    gid: i32,
+    path: PathBuf,
+    kind: SubstKind,
+}
+
+// 执行命令时创建的临时资源（命名管道），作业结束后清理
+// 后台作业的资源保留到 shell 退出时再清理
+#[derive(Default)]
+pub struct TempResources {
+    dir: Option<PathBuf>,
+    counter: usize,
+    fifos: Vec<TempFifo>,
+}
+
+impl TempResources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 在 shell 的临时目录中创建命名管道，目录只有当前用户可以访问
+    pub fn create_fifo(&mut self, kind: SubstKind) -> io::Result<PathBuf> {
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = std::env::temp_dir().join(format!("zako-{}", std::process::id()));
+                fs::create_dir_all(&dir)?;
+                fs::set_permissions(&dir, std::os::unix::fs::PermissionsExt::from_mode(0o700))?;
+                self.dir = Some(dir.clone());
+                dir
+            }
+        };
+        self.counter += 1;
+        let path = dir.join(format!("subst-{}", self.counter));
+        nix::unistd::mkfifo(&path, Mode::S_IRUSR | Mode::S_IWUSR)?;
+        debug!("创建命名管道: {}", path.display());
+        self.fifos.push(TempFifo {
+            gid: 0,
+            path: path.clone(),
+            kind,
+        });
+        Ok(path)
+    }
+
+    // 作业启动后记录刚创建的资源属于哪个作业
+    pub fn assign(&mut self, gid: i32) {
+        for fifo in self.fifos.iter_mut().filter(|f| f.gid == 0) {
+            fifo.gid = gid;
+        }
+    }
+
+    // 清理作业的资源
+    pub fn release(&mut self, gid: i32) {
+        let (released, kept) = std::mem::take(&mut self.fifos)
+            .into_iter()
+            .partition(|f| f.gid == gid);
+        self.fifos = kept;
+        for fifo in released {
+            remove_fifo(&fifo);
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        for fifo in std::mem::take(&mut self.fifos) {
+            remove_fifo(&fifo);
+        }
+        if let Some(dir) = self.dir.take() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+impl Drop for TempResources {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+// 命令没有打开命名管道时（如命令不存在），替换进程会一直阻塞在 open 上
+// 先以非阻塞方式打开另一端让它继续运行，关闭后它会读到 EOF 或收到 SIGPIPE
+fn remove_fifo(fifo: &TempFifo) {
+    let flags = match fifo.kind {
+        SubstKind::Input => OFlag::O_RDONLY,
+        SubstKind::Output => OFlag::O_WRONLY,
+    };
+    if let Ok(fd) = fcntl::open(
+        &fifo.path,
+        flags | OFlag::O_NONBLOCK | OFlag::O_CLOEXEC,
+        Mode::empty(),
+    ) {
+        let _ = nix::unistd::close(fd);
+    }
+    if let Err(e) = fs::remove_file(&fifo.path) {
+        warn!("删除命名管道失败 {}: {}", fifo.path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_fifo_lifecycle() {
+        let mut resources = TempResources::new();
+        let first = resources.create_fifo(SubstKind::Input).unwrap();
+        resources.assign(100);
+        let second = resources.create_fifo(SubstKind::Output).unwrap();
+        assert!(first.exists() && second.exists());
+
+        resources.release(100);
+        assert!(!first.exists());
+        assert!(second.exists());
+
+        let dir = second.parent().unwrap().to_path_buf();
+        resources.release_all();
+        assert!(!second.exists());
+        assert!(!dir.exists());
+    }
+}
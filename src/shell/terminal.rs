@@ -0,0 +1,107 @@
+// 控制终端的归属：只有 shell 自己（读取输入时）或一个前台作业的进程组可以持有终端
+// 所有 tcsetpgrp 都经过这里，只允许 Shell -> Job 和 Job -> Shell 两种转移，其他转移说明作业控制的逻辑有错误
+use log::{debug, error};
+use nix::sys::termios::{self, SetArg, Termios};
+use std::io;
+use std::os::fd::{AsFd, OwnedFd};
+
+use crate::shell::signals;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Owner {
+    Shell,
+    // 前台作业的进程组 id
+    Job(i32),
+}
+
+pub struct TerminalController {
+    owner: Owner,
+    // 标准输入是终端且 shell 是它的前台进程组时才做作业控制，否则两种转移都不做
+    job_control: bool,
+}
+
+impl TerminalController {
+    pub fn new() -> Self {
+        let job_control = unsafe {
+            libc::isatty(libc::STDIN_FILENO) == 1
+                && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+        };
+        debug!("作业控制: {}", job_control);
+        Self {
+            owner: Owner::Shell,
+            job_control,
+        }
+    }
+
+    // Shell -> Job：前台作业启动或被 fg 恢复时交出终端，失败时（例如没有控制终端）仍由 shell 持有
+    pub fn give_to_job(&mut self, pgid: i32) -> bool {
+        if !self.job_control {
+            return false;
+        }
+        if let Owner::Job(owner) = self.owner {
+            if owner == pgid {
+                return true;
+            }
+            error!("无效的终端转移: Job({}) -> Job({})", owner, pgid);
+            debug_assert!(false, "无效的终端转移: Job({}) -> Job({})", owner, pgid);
+        }
+        let given = signals::give_terminal_to(pgid);
+        if given {
+            debug!("终端转移: {:?} -> Job({})", self.owner, pgid);
+            self.owner = Owner::Job(pgid);
+        }
+        given
+    }
+
+    // Job -> Shell：前台作业结束或被暂停后收回终端
+    // shell 已经持有时同样调用 tcsetpgrp，子进程可能自己修改了前台进程组
+    // 收回失败时终端已经不可用，不再认为它属于作业
+    pub fn reclaim(&mut self) {
+        if !self.job_control {
+            return;
+        }
+        let shell_pgid = unsafe { libc::getpgid(0) };
+        signals::give_terminal_to(shell_pgid);
+        if self.owner != Owner::Shell {
+            debug!("终端转移: {:?} -> Shell", self.owner);
+            self.owner = Owner::Shell;
+        }
+    }
+}
+
+// 交互式 shell 启动时的终端模式，退出时恢复，异常结束的程序留下的 raw 模式等不会影响父进程
+// 保存终端 fd 的副本：收到 SIGTERM 时标准输入会被换成 /dev/null
+pub struct TerminalModes {
+    tty: OwnedFd,
+    modes: Termios,
+}
+
+impl TerminalModes {
+    pub fn save() -> Option<Self> {
+        let tty = io::stdin().as_fd().try_clone_to_owned().ok()?;
+        let modes = termios::tcgetattr(&tty).ok()?;
+        Some(Self { tty, modes })
+    }
+
+    pub fn restore(&self) {
+        if let Err(e) = termios::tcsetattr(&self.tty, SetArg::TCSADRAIN, &self.modes) {
+            debug!("无法恢复终端模式: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_job_control() {
+        let mut terminal = TerminalController {
+            owner: Owner::Shell,
+            job_control: false,
+        };
+        assert!(!terminal.give_to_job(1));
+        terminal.reclaim();
+        assert_eq!(terminal.owner, Owner::Shell);
+    }
+}
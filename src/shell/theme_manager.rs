@@ -0,0 +1,328 @@
+// theme 内建命令：从 git 仓库、压缩包或单个主题文件安装主题到 themes_dir
+//   theme list | theme install URL | theme update [NAME ...] | theme remove NAME ...
+// 安装时校验主题文件，无法解析的文件不安装；每个主题旁边的 <name>.source 记录来源，theme update 据此重新安装
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::utils::style::ColorDepth;
+use crate::utils::toml;
+use crate::utils::zsh_theme;
+
+const THEME_EXTENSIONS: [&str; 2] = ["toml", "zsh-theme"];
+const ARCHIVE_SUFFIXES: [&str; 6] = [".tar.gz", ".tgz", ".tar.xz", ".tar.bz2", ".tar", ".zip"];
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub fn theme(themes_dir: &Path, args: &[String]) -> io::Result<()> {
+    let usage = || {
+        invalid("用法: theme list | install URL | update [NAME ...] | remove NAME ...".to_string())
+    };
+    let rest = args.get(1..).unwrap_or_default();
+    match args.first().map(|s| s.as_str()) {
+        None | Some("list") => list(themes_dir),
+        Some("install") if rest.len() == 1 => {
+            for name in install(themes_dir, &rest[0])? {
+                println!("已安装主题 {}", name);
+            }
+            Ok(())
+        }
+        Some("update") => update(themes_dir, rest),
+        Some("remove") if !rest.is_empty() => {
+            for name in rest {
+                remove(themes_dir, name)?;
+                println!("已删除主题 {}", name);
+            }
+            Ok(())
+        }
+        _ => Err(usage()),
+    }
+}
+
+// 已安装的主题和它们的来源
+fn list(themes_dir: &Path) -> io::Result<()> {
+    for name in installed(themes_dir) {
+        match fs::read_to_string(source_path(themes_dir, &name)) {
+            Ok(source) => println!("{}\t{}", name, source.trim()),
+            Err(_) => println!("{}", name),
+        }
+    }
+    Ok(())
+}
+
+fn installed(themes_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(themes_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| theme_name(&entry.path()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// 主题文件的主题名，不是主题文件时为 None
+fn theme_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    THEME_EXTENSIONS.iter().find_map(|ext| {
+        let name = file_name.strip_suffix(&format!(".{}", ext))?;
+        (!name.is_empty() && !name.starts_with('.')).then(|| name.to_string())
+    })
+}
+
+fn source_path(themes_dir: &Path, name: &str) -> PathBuf {
+    themes_dir.join(format!("{}.source", name))
+}
+
+// 下载到 themes_dir 下的临时目录，校验通过的主题文件复制到 themes_dir，返回安装的主题名
+fn install(themes_dir: &Path, url: &str) -> io::Result<Vec<String>> {
+    fs::create_dir_all(themes_dir)?;
+    let work = themes_dir.join(format!(".install-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&work);
+    fs::create_dir_all(&work)?;
+    let result = fetch(url, &work).and_then(|src| install_from(themes_dir, url, &src));
+    let _ = fs::remove_dir_all(&work);
+    result
+}
+
+fn install_from(themes_dir: &Path, url: &str, src: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    find_theme_files(src, &mut files);
+    files.sort();
+    let mut names = Vec::new();
+    let mut errors = Vec::new();
+    for file in files {
+        let Some(name) = theme_name(&file) else {
+            continue;
+        };
+        if let Err(message) = validate(&file) {
+            errors.push(format!("theme: 跳过 {}: {}", name, message));
+            continue;
+        }
+        let Some(file_name) = file.file_name() else {
+            continue;
+        };
+        fs::copy(&file, themes_dir.join(file_name))?;
+        fs::write(source_path(themes_dir, &name), format!("{}\n", url))?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    if names.is_empty() {
+        return Err(invalid(format!("theme: {} 中没有可用的主题文件", url)));
+    }
+    Ok(names)
+}
+
+// 递归查找主题文件，跳过 .git 等隐藏目录
+fn find_theme_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if path.is_dir() && !hidden {
+            find_theme_files(&path, files);
+        } else if path.is_file() && theme_name(&path).is_some() {
+            files.push(path);
+        }
+    }
+}
+
+// toml 主题不能有语法错误，zsh 主题至少要能导入 PROMPT
+fn validate(path: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        let document = toml::parse(&text);
+        if let Some((line, message)) = document.errors.first() {
+            return Err(format!("第 {} 行: {}", line, message));
+        }
+    } else if zsh_theme::import(&text, ColorDepth::None).left.is_empty() {
+        return Err("没有找到 PROMPT 或 PS1".to_string());
+    }
+    Ok(())
+}
+
+// git 仓库用 git clone，压缩包和单个主题文件用 curl 下载，返回下载内容所在的目录
+fn fetch(url: &str, work: &Path) -> io::Result<PathBuf> {
+    let src = work.join("src");
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    if let Some(suffix) = ARCHIVE_SUFFIXES.iter().find(|s| path.ends_with(*s)) {
+        let archive = work.join(format!("archive{}", suffix));
+        run(Command::new("curl")
+            .args(["-fsSL", "-o"])
+            .arg(&archive)
+            .arg("--")
+            .arg(url))?;
+        fs::create_dir_all(&src)?;
+        if *suffix == ".zip" {
+            run(Command::new("unzip")
+                .arg("-q")
+                .arg(&archive)
+                .arg("-d")
+                .arg(&src))?;
+        } else {
+            run(Command::new("tar")
+                .arg("-xf")
+                .arg(&archive)
+                .arg("-C")
+                .arg(&src))?;
+        }
+    } else if let Some(file_name) = Path::new(path)
+        .file_name()
+        .filter(|_| theme_name(Path::new(path)).is_some())
+    {
+        fs::create_dir_all(&src)?;
+        run(Command::new("curl")
+            .args(["-fsSL", "-o"])
+            .arg(src.join(file_name))
+            .arg("--")
+            .arg(url))?;
+    } else {
+        run(Command::new("git")
+            .args(["clone", "-q", "--depth", "1", "--", url])
+            .arg(&src))?;
+    }
+    Ok(src)
+}
+
+fn run(command: &mut Command) -> io::Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("theme: 无法运行 {}: {}", program, e)))?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "theme: {} 失败（{}）",
+            program, status
+        )));
+    }
+    Ok(())
+}
+
+// 不指定主题时更新所有记录了来源的主题，同一个来源只下载一次
+fn update(themes_dir: &Path, names: &[String]) -> io::Result<()> {
+    let names = if names.is_empty() {
+        installed(themes_dir)
+            .into_iter()
+            .filter(|name| source_path(themes_dir, name).exists())
+            .collect()
+    } else {
+        names.to_vec()
+    };
+    let mut sources: Vec<String> = Vec::new();
+    for name in &names {
+        check_name(name)?;
+        let source = fs::read_to_string(source_path(themes_dir, name))
+            .map_err(|_| invalid(format!("theme: {} 不是通过 theme install 安装的", name)))?;
+        let source = source.trim().to_string();
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    for source in sources {
+        for name in install(themes_dir, &source)? {
+            println!("已更新主题 {}", name);
+        }
+    }
+    Ok(())
+}
+
+// 主题名拼接到 themes_dir 下，不能含有 / 或 ..，否则会操作主题目录以外的文件
+fn check_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name.contains('/') || name.contains("..") {
+        return Err(invalid(format!("theme: 无效的主题名 {}", name)));
+    }
+    Ok(())
+}
+
+fn remove(themes_dir: &Path, name: &str) -> io::Result<()> {
+    check_name(name)?;
+    let mut removed = false;
+    let files = THEME_EXTENSIONS
+        .iter()
+        .map(|ext| themes_dir.join(format!("{}.{}", name, ext)))
+        .chain([source_path(themes_dir, name)]);
+    for file in files {
+        if file.is_file() {
+            fs::remove_file(&file)?;
+            removed = true;
+        }
+    }
+    if !removed {
+        return Err(invalid(format!("theme: 没有安装主题 {}", name)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_update_remove() {
+        let dir = std::env::temp_dir().join(format!("zako_theme_manager_{}", std::process::id()));
+        let themes_dir = dir.join("themes");
+        let repo = dir.join("repo");
+        fs::create_dir_all(repo.join("themes")).unwrap();
+        fs::write(
+            repo.join("themes/pink.toml"),
+            "[styles]\nprompt = \"fg=magenta\"\n",
+        )
+        .unwrap();
+        fs::write(repo.join("broken.toml"), "[styles\n").unwrap();
+        fs::write(repo.join("plain.zsh-theme"), "PROMPT='%n> '\n").unwrap();
+
+        let url = format!("file://{}", repo.join("themes/pink.toml").display());
+        assert_eq!(install(&themes_dir, &url).unwrap(), vec!["pink"]);
+        assert_eq!(
+            fs::read_to_string(themes_dir.join("pink.source")).unwrap(),
+            format!("{}\n", url)
+        );
+
+        let archive = dir.join("themes.tar.gz");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&repo)
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let url = format!("file://{}", archive.display());
+        assert_eq!(install(&themes_dir, &url).unwrap(), vec!["plain", "pink"]);
+        assert!(!themes_dir.join("broken.toml").exists());
+        assert_eq!(installed(&themes_dir), vec!["pink", "plain"]);
+
+        update(&themes_dir, &["plain".to_string()]).unwrap();
+        assert!(update(&themes_dir, &["missing".to_string()]).is_err());
+
+        remove(&themes_dir, "pink").unwrap();
+        assert!(remove(&themes_dir, "pink").is_err());
+
+        // 主题目录以外的文件不会被删除
+        fs::write(dir.join("config.toml"), "").unwrap();
+        assert!(remove(&themes_dir, "../config").is_err());
+        assert!(remove(&themes_dir, "a/b").is_err());
+        assert!(dir.join("config.toml").exists());
+        assert!(update(&themes_dir, &["../config".to_string()]).is_err());
+        assert_eq!(installed(&themes_dir), vec!["plain"]);
+        assert!(install(
+            &themes_dir,
+            &format!("file://{}", repo.join("broken.toml").display())
+        )
+        .is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
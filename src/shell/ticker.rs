@@ -0,0 +1,115 @@
+// 运行时间显示（ticker_after > 0 时启用）：前台作业运行超过 ticker_after 秒后，
+// 每秒在终端标题上显示已经运行的时间；作业结束或被暂停时停止，并恢复原来的标题
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::utils::duration;
+
+pub struct Ticker {
+    // 等待作业的一方设为 true 并唤醒计时线程
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Ticker {
+    // 在等待前台作业之前启动，label 为作业的命令，未知时为空
+    pub fn start(after: Duration, label: &str) -> Self {
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let label = label.to_string();
+        let thread = {
+            let stopped = stopped.clone();
+            thread::spawn(move || tick(&stopped, after, &label))
+        };
+        Self {
+            stopped,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Ticker {
+    // 等计时线程恢复标题之后才返回，避免和随后的提示符交错输出
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.stopped;
+        if let Ok(mut stopped) = lock.lock() {
+            *stopped = true;
+        }
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn tick(stopped: &(Mutex<bool>, Condvar), after: Duration, label: &str) {
+    let (lock, condvar) = stopped;
+    let started = Instant::now();
+    let Ok(mut guard) = lock.lock() else {
+        return;
+    };
+    let mut tty: Option<File> = None;
+    let mut wait = after;
+    loop {
+        guard = match condvar.wait_timeout_while(guard, wait, |stopped| !*stopped) {
+            Ok((guard, _)) => guard,
+            Err(_) => break,
+        };
+        if *guard {
+            break;
+        }
+        // 第一次显示前把原来的标题压入终端的标题栈（xterm 的 CSI 22 t），结束时弹出
+        if tty.is_none() {
+            let Ok(mut opened) = OpenOptions::new().write(true).open("/dev/tty") else {
+                return;
+            };
+            let _ = write!(opened, "\x1b[22;2t");
+            tty = Some(opened);
+        }
+        let elapsed = started.elapsed();
+        if let Some(tty) = tty.as_mut() {
+            let _ = write!(tty, "\x1b]2;{}\x1b\\", title(elapsed, label));
+            let _ = tty.flush();
+        }
+        // 在整秒时刷新，显示的秒数不会跳过或重复
+        wait = Duration::from_secs(elapsed.as_secs() + 1).saturating_sub(elapsed);
+    }
+    if let Some(mut tty) = tty {
+        let _ = write!(tty, "\x1b[23;2t");
+        let _ = tty.flush();
+    }
+}
+
+// 标题为 ⏱ 运行时间 命令，命令只取第一行并去掉控制字符
+fn title(elapsed: Duration, label: &str) -> String {
+    let elapsed = duration::format_duration(Duration::from_secs(elapsed.as_secs()));
+    let label: String = label
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    if label.is_empty() {
+        format!("⏱ {}", elapsed)
+    } else {
+        format!("⏱ {} {}", elapsed, label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticker_title() {
+        assert_eq!(
+            title(Duration::from_millis(65_400), "make\nls"),
+            "⏱ 1m5s make"
+        );
+        assert_eq!(title(Duration::from_secs(3600), ""), "⏱ 1h");
+        assert_eq!(title(Duration::from_secs(3), "a\x07b"), "⏱ 3s ab");
+    }
+}
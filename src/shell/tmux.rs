@@ -0,0 +1,83 @@
+// tmux 集成（tmux_integration = true 且在 tmux 中运行时启用）：
+// 命令运行时窗格标题为命令本身，命令失败后标题标出退出码，成功后恢复为 shell 名和当前目录
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use crate::shell::hooks::CommandHook;
+use crate::utils::config::Config;
+
+// 标题最多显示的字符数
+const TITLE_MAX_CHARS: usize = 40;
+
+pub struct TmuxIntegration {
+    // 正在运行或上一条运行的命令
+    command: String,
+}
+
+impl TmuxIntegration {
+    pub fn new(config: &Config) -> Option<Self> {
+        (config.tmux_integration && env::var_os("TMUX").is_some()).then(|| Self {
+            command: String::new(),
+        })
+    }
+}
+
+impl CommandHook for TmuxIntegration {
+    fn preexec(&mut self, line: &str) {
+        self.command = shorten(line);
+        set_pane_title(&self.command);
+    }
+
+    fn precmd(&mut self, status: i32) {
+        let cwd = env::current_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        set_pane_title(&idle_title(status, &self.command, &cwd));
+    }
+}
+
+// 没有运行命令时的标题：上一条命令失败时为 ✗ 退出码 命令，否则为 zako 当前目录
+fn idle_title(status: i32, command: &str, cwd: &str) -> String {
+    if status != 0 && !command.is_empty() {
+        format!("✗ {} {}", status, command)
+    } else {
+        format!("zako {}", cwd)
+    }
+}
+
+// 只取第一行，超长时截断
+fn shorten(line: &str) -> String {
+    let line = line.trim().lines().next().unwrap_or_default();
+    if line.chars().count() > TITLE_MAX_CHARS {
+        let short: String = line.chars().take(TITLE_MAX_CHARS - 1).collect();
+        format!("{}…", short)
+    } else {
+        line.to_string()
+    }
+}
+
+// tmux 把 OSC 2 作为窗格标题（#{pane_title}），写到终端而不是可能被重定向的 stdout
+fn set_pane_title(title: &str) {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    if let Ok(mut tty) = OpenOptions::new().write(true).open("/dev/tty") {
+        let _ = write!(tty, "\x1b]2;{}\x1b\\", title);
+        let _ = tty.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_titles() {
+        assert_eq!(shorten("  make test\necho done"), "make test");
+        let long = "x".repeat(50);
+        assert_eq!(shorten(&long).chars().count(), TITLE_MAX_CHARS);
+        assert!(shorten(&long).ends_with('…'));
+        assert_eq!(idle_title(0, "make", "/src"), "zako /src");
+        assert_eq!(idle_title(2, "make", "/src"), "✗ 2 make");
+        assert_eq!(idle_title(1, "", "/src"), "zako /src");
+    }
+}
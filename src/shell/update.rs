@@ -0,0 +1,398 @@
+// 新版本检查（update_check = true 时启用）：每周最多一次在后台用 curl 获取 GitHub 上最新的 release，
+// 保存到 cache_dir，显示提示符前发现有新版本时提示一次；zako upgrade --self 下载并替换当前的可执行文件，
+// 只有 release 中发布了 SHA-256 校验和（NAME.sha256 或 SHA256SUMS）并且与下载的文件一致时才替换
+use log::{debug, error};
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use crate::utils::config::Config;
+use crate::utils::digest::sha256_hex;
+use zakosh::i18n;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/JiyuShao/zakosh/releases/latest";
+const CHECK_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+// 更新说明最多显示的字符数
+const SUMMARY_MAX_CHARS: usize = 60;
+
+pub struct UpdateCheck {
+    // 最新 release 的 JSON
+    release_file: PathBuf,
+    // 修改时间为上次检查的时间，检查失败时也不会马上重试
+    stamp_file: PathBuf,
+    // 上次读取的 release_file 的修改时间
+    seen: Option<SystemTime>,
+    notified: bool,
+}
+
+impl UpdateCheck {
+    pub fn new(config: &Config) -> Option<Self> {
+        config.update_check.then(|| Self {
+            release_file: config.cache_dir.join("latest_release.json"),
+            stamp_file: config.cache_dir.join("update_checked"),
+            seen: None,
+            notified: false,
+        })
+    }
+
+    // 距离上次检查超过一周时在后台运行 curl，不等待它结束
+    // curl 放在单独的进程组中，不会收到终端的 Ctrl-C
+    pub fn start(&self) {
+        let checked = fs::metadata(&self.stamp_file).and_then(|meta| meta.modified());
+        let fresh = checked
+            .ok()
+            .and_then(|time| time.elapsed().ok())
+            .is_some_and(|elapsed| elapsed < CHECK_INTERVAL);
+        if fresh {
+            return;
+        }
+        if let Err(e) = self.touch_stamp() {
+            error!("无法写入 {}: {}", self.stamp_file.display(), e);
+            return;
+        }
+        debug!("检查新版本 {}", LATEST_RELEASE_URL);
+        let spawned = Command::new("sh")
+            .args([
+                "-c",
+                "curl -fsSL --max-time 10 -o \"$1.tmp\" \"$2\" && mv \"$1.tmp\" \"$1\"",
+                "sh",
+            ])
+            .arg(&self.release_file)
+            .arg(LATEST_RELEASE_URL)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn();
+        if let Err(e) = spawned {
+            error!("无法检查新版本: {}", e);
+        }
+    }
+
+    fn touch_stamp(&self) -> io::Result<()> {
+        if let Some(parent) = self.stamp_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.stamp_file, "")
+    }
+
+    // 后台检查写入了更新的版本时返回一行提示，每次会话只提示一次
+    pub fn poll(&mut self) -> Option<String> {
+        if self.notified {
+            return None;
+        }
+        let modified = fs::metadata(&self.release_file)
+            .and_then(|meta| meta.modified())
+            .ok()?;
+        if self.seen == Some(modified) {
+            return None;
+        }
+        self.seen = Some(modified);
+        let release = Release::parse(&fs::read_to_string(&self.release_file).ok()?)?;
+        if !is_newer(&release.version, CURRENT_VERSION) {
+            return None;
+        }
+        self.notified = true;
+        Some(release.notice())
+    }
+}
+
+struct Release {
+    version: String,
+    summary: String,
+    assets: Vec<String>,
+}
+
+impl Release {
+    fn parse(json: &str) -> Option<Self> {
+        let version = json_string(json, "tag_name")?;
+        let body = json_string(json, "body").unwrap_or_default();
+        let summary = body
+            .lines()
+            .map(|line| line.trim_start_matches(['#', '-', '*', ' ']).trim())
+            .find(|line| !line.is_empty())
+            .unwrap_or_default();
+        let summary = if summary.chars().count() > SUMMARY_MAX_CHARS {
+            let short: String = summary.chars().take(SUMMARY_MAX_CHARS - 1).collect();
+            format!("{}…", short)
+        } else {
+            summary.to_string()
+        };
+        let mut assets = Vec::new();
+        let mut rest = json;
+        while let Some(pos) = rest.find("\"browser_download_url\"") {
+            rest = &rest[pos..];
+            assets.extend(json_string(rest, "browser_download_url"));
+            rest = &rest[1..];
+        }
+        Some(Self {
+            version: version.trim_start_matches('v').to_string(),
+            summary,
+            assets,
+        })
+    }
+
+    fn notice(&self) -> String {
+        let summary = if self.summary.is_empty() {
+            String::new()
+        } else {
+            i18n::tf("update.summary", &[&self.summary])
+        };
+        i18n::tf("update.notice", &[&self.version, &summary, CURRENT_VERSION])
+    }
+
+    // 文件名中同时含有当前的系统和架构的资源，例如 zako-x86_64-linux
+    fn asset_for_platform(&self) -> Option<&str> {
+        let os = match std::env::consts::OS {
+            "macos" => ["macos", "darwin"],
+            os => [os, os],
+        };
+        self.assets
+            .iter()
+            .find(|url| {
+                let name = asset_name(url).to_lowercase();
+                !name.ends_with(".sha256")
+                    && name.contains(std::env::consts::ARCH)
+                    && os.iter().any(|os| name.contains(os))
+            })
+            .map(|url| url.as_str())
+    }
+
+    // 资源的校验和文件：优先使用同名的 .sha256，其次是列出所有资源的 SHA256SUMS
+    fn checksum_for(&self, url: &str) -> Option<&str> {
+        let single = format!("{}.sha256", url);
+        self.assets
+            .iter()
+            .find(|asset| **asset == single)
+            .or_else(|| {
+                self.assets.iter().find(|asset| {
+                    let name = asset_name(asset).to_lowercase();
+                    name == "sha256sums" || name == "sha256sums.txt"
+                })
+            })
+            .map(|url| url.as_str())
+    }
+}
+
+fn asset_name(url: &str) -> &str {
+    url.rsplit('/').next().unwrap_or_default()
+}
+
+// 从 sha256sum 格式的内容中找出 name 的校验和，只有一行且没有文件名时直接使用这一行
+fn parse_checksum(content: &str, name: &str) -> Option<String> {
+    let is_hex = |hash: &str| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+    let lines: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let found = lines.iter().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let hash = fields.next()?;
+        let file = fields.next()?.trim_start_matches('*');
+        (file == name).then_some(hash)
+    });
+    let hash = match (found, lines.as_slice()) {
+        (Some(hash), _) => hash,
+        (None, [line]) if line.split_whitespace().count() == 1 => line.trim(),
+        _ => return None,
+    };
+    is_hex(hash).then(|| hash.to_lowercase())
+}
+
+// 找到第一个 "key": "..." 并解码字符串中的转义
+fn json_string(json: &str, key: &str) -> Option<String> {
+    let pattern = format!("\"{}\"", key);
+    let rest = &json[json.find(&pattern)? + pattern.len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let mut chars = rest.strip_prefix('"')?.chars();
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).ok()?;
+                    value.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                }
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+// 按 . 分隔的数字比较，无法解析的版本不算更新
+fn is_newer(latest: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Option<Vec<u64>> {
+        version
+            .trim_start_matches('v')
+            .split(['.', '-'])
+            .take(3)
+            .map(|part| part.parse().ok())
+            .collect()
+    };
+    match (parse(latest), parse(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+fn curl(args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--max-time", "120"])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(i18n::tf(
+            "update.curl_failed",
+            &[
+                &output.status.to_string(),
+                String::from_utf8_lossy(&output.stderr).trim(),
+            ],
+        )));
+    }
+    Ok(output.stdout)
+}
+
+// zako upgrade --self，返回退出码
+pub fn upgrade_self() -> i32 {
+    match try_upgrade_self() {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("zako upgrade: {}", e);
+            1
+        }
+    }
+}
+
+fn try_upgrade_self() -> io::Result<()> {
+    let json = curl(&[LATEST_RELEASE_URL])?;
+    let release = Release::parse(&String::from_utf8_lossy(&json))
+        .ok_or_else(|| io::Error::other(i18n::t("update.bad_release")))?;
+    if !is_newer(&release.version, CURRENT_VERSION) {
+        println!("{}", i18n::tf("update.up_to_date", &[CURRENT_VERSION]));
+        return Ok(());
+    }
+    let url = release.asset_for_platform().ok_or_else(|| {
+        io::Error::other(i18n::tf(
+            "update.no_asset",
+            &[
+                &release.version,
+                std::env::consts::ARCH,
+                std::env::consts::OS,
+            ],
+        ))
+    })?;
+    let checksum_url = release
+        .checksum_for(url)
+        .ok_or_else(|| io::Error::other(i18n::tf("update.no_checksums", &[&release.version])))?;
+    let checksums = curl(&[checksum_url])?;
+    let expected = parse_checksum(&String::from_utf8_lossy(&checksums), asset_name(url))
+        .ok_or_else(|| {
+            io::Error::other(i18n::tf("update.checksum_missing", &[checksum_url, url]))
+        })?;
+    let exe = fs::canonicalize(std::env::current_exe()?)?;
+    println!(
+        "{}",
+        i18n::tf("update.downloading", &[&release.version, url])
+    );
+    // 先下载到同一目录，校验通过后再重命名，替换是原子的，正在运行的 zako 不受影响
+    let new_exe = exe.with_file_name(".zako.new");
+    let result = download(url, &new_exe)
+        .and_then(|()| verify(&new_exe, &expected))
+        .and_then(|()| fs::rename(&new_exe, &exe));
+    if result.is_err() {
+        let _ = fs::remove_file(&new_exe);
+    }
+    result?;
+    println!(
+        "{}",
+        i18n::tf(
+            "update.upgraded",
+            &[&release.version, &exe.display().to_string()]
+        )
+    );
+    Ok(())
+}
+
+fn verify(path: &Path, expected: &str) -> io::Result<()> {
+    let actual = sha256_hex(&fs::read(path)?);
+    if actual != expected {
+        return Err(io::Error::other(i18n::tf(
+            "update.checksum_mismatch",
+            &[expected, &actual],
+        )));
+    }
+    Ok(())
+}
+
+fn download(url: &str, path: &Path) -> io::Result<()> {
+    let path_arg = path.to_string_lossy();
+    curl(&["-o", &path_arg, url])?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_release() {
+        let json = r#"{"tag_name": "v0.2.0", "body": "\n## 新增 \"clip\" 命令\n- moreé",
+            "assets": [{"browser_download_url":"https://x/zako-x86_64-linux"},
+                       {"browser_download_url":"https://x/zako-aarch64-macos"}]}"#;
+        let release = Release::parse(json).unwrap_or_else(|| panic!("无法解析"));
+        assert_eq!(release.version, "0.2.0");
+        assert_eq!(release.summary, "新增 \"clip\" 命令");
+        assert_eq!(release.assets.len(), 2);
+        assert!(json_string(r#"{"body": "unterminated"#, "body").is_none());
+
+        assert!(is_newer("v0.2.0", "0.1.9"));
+        assert!(is_newer("0.10.0", "0.9.0"));
+        assert!(!is_newer("0.0.1", "0.0.1"));
+        assert!(!is_newer("nightly", "0.0.1"));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let json = r#"{"tag_name": "v0.2.0", "assets": [
+            {"browser_download_url":"https://x/zako-x86_64-linux"},
+            {"browser_download_url":"https://x/zako-x86_64-linux.sha256"},
+            {"browser_download_url":"https://x/zako-aarch64-macos"},
+            {"browser_download_url":"https://x/SHA256SUMS"}]}"#;
+        let release = Release::parse(json).unwrap_or_else(|| panic!("无法解析"));
+        assert_eq!(
+            release.checksum_for("https://x/zako-x86_64-linux"),
+            Some("https://x/zako-x86_64-linux.sha256")
+        );
+        assert_eq!(
+            release.checksum_for("https://x/zako-aarch64-macos"),
+            Some("https://x/SHA256SUMS")
+        );
+
+        let hash = sha256_hex(b"abc");
+        let sums = format!(
+            "{}  zako-aarch64-macos\n{} *zako-x86_64-linux\n",
+            "0".repeat(64),
+            hash
+        );
+        assert_eq!(
+            parse_checksum(&sums, "zako-x86_64-linux"),
+            Some(hash.clone())
+        );
+        assert_eq!(parse_checksum(&sums, "zako-other"), None);
+        assert_eq!(parse_checksum(&format!("{}\n", hash), "zako"), Some(hash));
+        assert_eq!(parse_checksum("not-a-hash  zako", "zako"), None);
+    }
+}
@@ -20,14 +20,14 @@ impl Variable {
     //             r#"
     //             # 执行主题文件
     //             source {}
-                
+
     //             # 输出环境变量（保持原始格式）
     //             env | while IFS= read -r line || [ -n "$line" ]; do
     //                 printf '%s\n' "$line"
     //             done
 
     //             echo "---ENV_VAR_END---"
-                
+
     //             # 输出所有变量（保持原始格式）
     //             set | while IFS= read -r line || [ -n "$line" ]; do
     //                 printf '%s\n' "$line"
@@ -51,4 +51,8 @@ impl Variable {
     pub fn set(&mut self, name: String, value: String) {
         self.local_vars.insert(name, value);
     }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.local_vars.remove(name)
+    }
 }
@@ -0,0 +1,162 @@
+// waitfor --port [HOST:]PORT | --file PATH [--timeout DURATION]：等待 TCP 端口可以连接或文件出现
+// 例如 waitfor --port 5432 --timeout 30s && psql ...，超时返回 1，Ctrl-C 中断返回 130
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::signals::{self, Wake};
+use crate::utils::duration;
+use zakosh::i18n;
+
+// 两次检查之间的间隔，以及每次连接的超时
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    Port(String, u16),
+    File(PathBuf),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WaitFor {
+    pub target: Target,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Ready,
+    TimedOut,
+    Interrupted,
+}
+
+pub const USAGE: &str = "用法: waitfor --port [HOST:]PORT | --file PATH [--timeout DURATION]";
+
+pub fn parse(args: &[String]) -> Result<WaitFor, String> {
+    let mut target = None;
+    let mut timeout = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| USAGE.to_string());
+        match arg.as_str() {
+            "--port" | "-p" => target = Some(parse_port(value()?)?),
+            "--file" | "-f" => target = Some(Target::File(PathBuf::from(value()?))),
+            "--timeout" | "-t" => timeout = Some(duration::parse_duration(value()?)?),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let target = target.ok_or_else(|| USAGE.to_string())?;
+    Ok(WaitFor { target, timeout })
+}
+
+// 没有指定主机时连接本机
+fn parse_port(value: &str) -> Result<Target, String> {
+    let (host, port) = match value.rsplit_once(':') {
+        Some((host, port)) => (host.trim_matches(['[', ']']), port),
+        None => ("localhost", value),
+    };
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| i18n::tf("waitfor.invalid_port", &[value]))?;
+    Ok(Target::Port(host.to_string(), port))
+}
+
+impl Target {
+    fn ready(&self) -> bool {
+        match self {
+            Target::Port(host, port) => (host.as_str(), *port)
+                .to_socket_addrs()
+                .map(|mut addrs| {
+                    addrs.any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok())
+                })
+                .unwrap_or(false),
+            Target::File(path) => path.exists(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Target::Port(host, port) => format!("{}:{}", host, port),
+            Target::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+impl WaitFor {
+    pub fn wait(&self) -> Outcome {
+        let started = Instant::now();
+        loop {
+            if self.target.ready() {
+                return Outcome::Ready;
+            }
+            let interval = match self.timeout {
+                Some(timeout) => match timeout.checked_sub(started.elapsed()) {
+                    Some(left) if !left.is_zero() => left.min(POLL_INTERVAL),
+                    _ => return Outcome::TimedOut,
+                },
+                None => POLL_INTERVAL,
+            };
+            // 没有可以暂停的子进程，Ctrl-Z 只结束这一次等待
+            if let Wake::Interrupted = signals::sleep_interruptible(interval) {
+                return Outcome::Interrupted;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_waitfor() {
+        assert_eq!(
+            parse(&args("--port 5432 --timeout 30s")),
+            Ok(WaitFor {
+                target: Target::Port("localhost".to_string(), 5432),
+                timeout: Some(Duration::from_secs(30)),
+            })
+        );
+        assert_eq!(
+            parse(&args("--port db:5432")).map(|w| w.target),
+            Ok(Target::Port("db".to_string(), 5432))
+        );
+        assert_eq!(
+            parse(&args("--port [::1]:80")).map(|w| w.target),
+            Ok(Target::Port("::1".to_string(), 80))
+        );
+        assert_eq!(
+            parse(&args("-f /tmp/ready")).map(|w| w.target),
+            Ok(Target::File(PathBuf::from("/tmp/ready")))
+        );
+        assert!(parse(&args("--port abc")).is_err());
+        assert!(parse(&args("--timeout 5s")).is_err());
+        assert!(parse(&args("--port")).is_err());
+    }
+
+    #[test]
+    fn test_wait_for_port() -> std::io::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let wait = WaitFor {
+            target: Target::Port("127.0.0.1".to_string(), port),
+            timeout: Some(Duration::from_secs(1)),
+        };
+        assert_eq!(wait.wait(), Outcome::Ready);
+        drop(listener);
+
+        let missing = std::env::temp_dir().join(format!("zako_waitfor_{}", std::process::id()));
+        let wait = WaitFor {
+            target: Target::File(missing),
+            timeout: Some(Duration::from_millis(300)),
+        };
+        assert_eq!(wait.wait(), Outcome::TimedOut);
+        Ok(())
+    }
+}
@@ -1,9 +1,175 @@
 use dotenv::dotenv;
-use log::error;
+use log::{error, info};
+use regex::Regex;
 use shellexpand;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use super::toml::{self, Document, Entry, Problem};
+use zakosh::i18n::{self, Lang};
+
+// 配置文件 config_dir/config.toml 和 profiles/<name>.toml 中可以使用的设置，
+// 键名与环境变量去掉 ZAKO_ 前缀后相同；history_file 的相对路径相对于 state_dir，rc_file 和 fpath 的相对于 config_dir
+const CONFIG_KEYS: [&str; 29] = [
+    "log_level",
+    "theme",
+    "editor_mode",
+    "bracketed_paste",
+    "paste_strip_prompt",
+    "prompt_max_ratio",
+    "prompt_command",
+    "right_prompt_command",
+    "prompt_command_timeout",
+    "message_mode",
+    "message_seed",
+    "completion_match",
+    "stderr_color",
+    "auto_activate",
+    "local_config",
+    "import_env",
+    "clipboard",
+    "tmux_integration",
+    "language",
+    "accessible",
+    "update_check",
+    "max_nesting",
+    "max_args",
+    "ticker_after",
+    "rm_safety_net",
+    "rm_trash_command",
+    "history_file",
+    "rc_file",
+    "fpath",
+];
+const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+// [sandbox.NAME] 段中可以使用的设置
+const SANDBOX_KEYS: [&str; 3] = ["network", "readonly_home", "readonly"];
+// [policy.NAME] 段中可以使用的设置
+const POLICY_KEYS: [&str; 4] = ["match", "action", "message", "on"];
+// 内置的粘贴检查（on = "paste"），用同名的 [policy.NAME] 段可以修改，action = "allow" 关闭
+const PASTE_RULES: [(&str, &str, &str); 4] = [
+    ("paste_multiline", r"\n", "粘贴的内容有多行"),
+    ("paste_sudo", r"\bsudo\b", "粘贴的内容包含 sudo"),
+    (
+        "paste_rm_rf",
+        r"\brm\s+(-\S+\s+)*-[a-zA-Z]*([rR][a-zA-Z]*f|f[a-zA-Z]*[rR])",
+        "粘贴的内容包含 rm -rf",
+    ),
+    (
+        "paste_curl_sh",
+        r"\b(curl|wget)\b[^|]*\|\s*(sudo\s+)?(ba|z|da)?sh\b",
+        "粘贴的内容把下载的脚本交给 shell 执行",
+    ),
+];
+
+// 不使用任何 profile 时的名字
+pub const DEFAULT_PROFILE: &str = "default";
+
+// 执行策略规则，config.toml 中的 [policy.NAME] 段：match 为正则表达式，
+// 与展开后以空格连接的命令行（命令名和参数）匹配时按 action 要求确认或拒绝执行；
+// on = "paste" 的规则改为检查粘贴到输入行的内容
+#[derive(Debug, Clone)]
+pub struct PolicyRule {
+    pub name: String,
+    pub pattern: Regex,
+    pub action: PolicyAction,
+    // 代替默认提示显示的说明
+    pub message: Option<String>,
+    pub on: PolicyTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolicyAction {
+    // 交互模式下要求确认，脚本中拒绝
+    Confirm,
+    // 总是拒绝
+    Deny,
+    // 不检查，用于关闭同名的规则（例如内置的粘贴检查）
+    Allow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolicyTarget {
+    // 执行的命令
+    Exec,
+    // 粘贴的内容，确认之前可以撤销
+    Paste,
+}
+
+fn default_policy_rules() -> Vec<PolicyRule> {
+    PASTE_RULES
+        .iter()
+        .filter_map(|(name, pattern, message)| {
+            Some(PolicyRule {
+                name: name.to_string(),
+                pattern: Regex::new(pattern).ok()?,
+                action: PolicyAction::Confirm,
+                message: Some(message.to_string()),
+                on: PolicyTarget::Paste,
+            })
+        })
+        .collect()
+}
+
+// 嘲讽消息的选择方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageMode {
+    // 每次随机
+    Random,
+    // 使用固定种子，同一种子每次会话输出相同
+    Seeded(u64),
+    // 总是选择第一条
+    First,
+}
+
+// 补全候选项的匹配方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionMatch {
+    // 前缀完全相同
+    Prefix,
+    // 忽略大小写
+    IgnoreCase,
+    // 忽略大小写，并且不区分 - 和 _
+    IgnoreSeparators,
+    // 依次包含输入的每个字符（忽略大小写）
+    Fuzzy,
+}
+
+// sandbox 内建命令使用的配置，config.toml 中的 [sandbox.NAME] 段
+// 没有设置的项与内置的 default 相同：断开网络，家目录只读
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxProfile {
+    pub name: String,
+    // 为 false 时命令在新的网络命名空间中运行，只有未启用的 lo
+    pub network: bool,
+    pub readonly_home: bool,
+    // 另外挂载为只读的路径
+    pub readonly: Vec<PathBuf>,
+}
+
+impl SandboxProfile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            network: false,
+            readonly_home: true,
+            readonly: Vec::new(),
+        }
+    }
+}
+
+// 复制到剪贴板的方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipboardMode {
+    // 终端支持时使用 OSC 52，否则使用外部命令
+    Auto,
+    // 总是使用 OSC 52
+    Osc52,
+    // 总是使用 pbcopy/wl-copy/xclip/xsel
+    Command,
+    Off,
+}
 
 pub struct Config {
     pub name: String,
@@ -11,32 +177,472 @@ pub struct Config {
     pub logger_dir: PathBuf,
     pub theme: String,
     pub editor_mode: String,
-    // paths
+    // 终端的 bracketed paste：粘贴的多行内容放进编辑缓冲区，按回车后作为一次输入执行，而不是逐行立即执行
+    pub bracketed_paste: bool,
+    // 去掉粘贴的每一行开头的 "$ "，文档中复制的命令可以直接执行
+    pub paste_strip_prompt: bool,
+    // 提示符最多占终端宽度的比例，超出时截断 cwd/git 片段
+    pub prompt_max_ratio: f32,
+    pub message_mode: MessageMode,
+    pub completion_match: CompletionMatch,
+    // 给子进程的 stderr 加颜色（会让子进程的 stderr 不再是 TTY）
+    pub stderr_color: bool,
+    // 进入目录时自动激活项目的 Python 虚拟环境和 .nvmrc 指定的 node 版本
+    pub auto_activate: bool,
+    // 进入受信任的项目目录时加载其中的 .zako.toml
+    pub local_config: bool,
+    // 启动时运行一次用户的登录 shell（$SHELL），导入它导出的环境变量
+    pub import_env: bool,
+    pub clipboard: ClipboardMode,
+    // 在 tmux 中运行时把窗格标题设为正在运行的命令，并标记失败的命令
+    pub tmux_integration: bool,
+    // 提示和错误信息的语言，None 时根据 LC_ALL/LC_MESSAGES/LANG 检测
+    pub language: Option<Lang>,
+    // 无障碍模式：不使用颜色、emoji 和全角符号，提示符和状态行为固定的纯文本，适合读屏软件和 dumb 终端
+    pub accessible: bool,
+    // 每周在后台检查一次 GitHub 上是否有新版本
+    pub update_check: bool,
+    // zako upgrade --self：下载最新版本替换当前的可执行文件后退出
+    pub upgrade: bool,
+    // 命令嵌套执行（repeat、timeout 等运行的命令和其中展开的别名）的最大层数，超过时报错而不是无限递归
+    pub max_nesting: usize,
+    // 外部命令展开后的参数超过这个数量时，交互模式下要求确认，脚本中报错
+    pub max_args: usize,
+    // 前台命令运行超过这么多秒后在终端标题上显示已经运行的时间，结束后恢复原来的标题；0 表示不显示
+    pub ticker_after: u64,
+    // 生成提示符的外部命令，例如 "starship prompt --status=$?"，设置时代替主题的渲染，失败或超时时仍使用主题的提示符
+    pub prompt_command: Option<String>,
+    pub right_prompt_command: Option<String>,
+    // 提示符命令最多运行这么多毫秒，超时后结束它
+    pub prompt_command_timeout: u64,
+    // 递归删除 /、家目录或 git 仓库的根目录前要求确认，设置了 rm_trash_command 时改为移到回收站
+    pub rm_safety_net: bool,
+    pub rm_trash_command: Option<String>,
+    // [sandbox.NAME] 段定义的沙箱配置，profile 中的同名配置覆盖 config.toml 中的
+    pub sandbox_profiles: Vec<SandboxProfile>,
+    // [policy.NAME] 段定义的执行策略规则，按在文件中出现的顺序检查
+    pub policy_rules: Vec<PolicyRule>,
+    // 受限模式：禁止 cd、带 / 的命令和输出重定向
+    pub restricted: bool,
+    // 登录 shell：argv[0] 以 - 开头（login、sshd 启动）或带 --login/-l 参数
+    pub login: bool,
+    // 只解析和展开命令，不执行（zako -n script）
+    pub noexec: bool,
+    // 要执行的脚本，为空时进入交互模式
+    pub script: Option<PathBuf>,
+    // 打印词法单元/语法树而不执行，用于调试解析器
+    pub dump_tokens: bool,
+    pub dump_ast: bool,
+    // 打印热路径上各个操作的耗时中位数后退出
+    pub bench_internal: bool,
+    // paths：配置、状态（历史和日志）和缓存分别遵循 XDG_CONFIG_HOME、XDG_STATE_HOME 和 XDG_CACHE_HOME，
+    // 设置了 ZAKO_CONFIG_DIR 时全部放在这个目录下
     pub config_dir: PathBuf,
+    pub state_dir: PathBuf,
+    pub cache_dir: PathBuf,
     pub history_file: PathBuf,
     pub themes_dir: PathBuf,
+    // 交互式 shell 启动时运行的脚本
+    pub rc_file: PathBuf,
+    // 自动加载函数的目录，其中每个文件是一个以文件名命名的函数，第一次调用时才读取
+    pub fpath: Vec<PathBuf>,
+    // zako --profile NAME 或 ZAKO_PROFILE 选择的配置，在 config.toml 之后加载 profiles/NAME.toml
+    pub profile: Option<String>,
+    // 加载配置文件时发现的问题，日志初始化之后报告
+    pub problems: Vec<Problem>,
 }
 
 impl Config {
-    fn default() -> Self {
-        let config_dir = if let Ok(dir) = env::var("ZAKO_CONFIG_DIR") {
-            if let Some(stripped) = dir.strip_prefix("./") {
+    pub(crate) fn default() -> Self {
+        let (config_dir, state_dir, cache_dir) = if let Ok(dir) = env::var("ZAKO_CONFIG_DIR") {
+            let dir = if let Some(stripped) = dir.strip_prefix("./") {
                 env::current_dir().unwrap_or_default().join(stripped)
             } else {
                 PathBuf::from(shellexpand::tilde(&dir).into_owned())
-            }
+            };
+            (dir.clone(), dir.clone(), dir.join("cache"))
         } else {
-            PathBuf::from(shellexpand::tilde("~/.config/zako").into_owned())
+            (
+                xdg_dir("XDG_CONFIG_HOME", "~/.config"),
+                xdg_dir("XDG_STATE_HOME", "~/.local/state"),
+                xdg_dir("XDG_CACHE_HOME", "~/.cache"),
+            )
         };
         Config {
             name: String::from("zako"),
             logger_level: String::from("info"),
-            logger_dir: config_dir.join("logs"),
+            logger_dir: state_dir.join("logs"),
             theme: String::from("default"),
             editor_mode: String::from("vi"),
+            bracketed_paste: true,
+            paste_strip_prompt: false,
+            prompt_max_ratio: 0.5,
+            message_mode: MessageMode::Random,
+            completion_match: CompletionMatch::Prefix,
+            stderr_color: false,
+            auto_activate: false,
+            local_config: true,
+            import_env: false,
+            clipboard: ClipboardMode::Auto,
+            tmux_integration: false,
+            language: None,
+            accessible: false,
+            update_check: false,
+            upgrade: false,
+            max_nesting: 100,
+            max_args: 10000,
+            ticker_after: 0,
+            prompt_command: None,
+            right_prompt_command: None,
+            prompt_command_timeout: 500,
+            rm_safety_net: false,
+            rm_trash_command: None,
+            sandbox_profiles: Vec::new(),
+            policy_rules: default_policy_rules(),
+            restricted: false,
+            login: false,
+            noexec: false,
+            script: None,
+            dump_tokens: false,
+            dump_ast: false,
+            bench_internal: false,
+            history_file: state_dir.join(".zako_history"),
             config_dir: config_dir.clone(),
-            history_file: config_dir.join(".zako_history"),
+            state_dir,
+            cache_dir,
             themes_dir: config_dir.join("themes"),
+            rc_file: config_dir.join("zakorc"),
+            fpath: vec![config_dir.join("functions")],
+            profile: None,
+            problems: Vec::new(),
+        }
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join("config.toml")
+    }
+
+    // trust 信任过的 .zako.toml
+    pub fn trust_file(&self) -> PathBuf {
+        self.state_dir.join("trusted")
+    }
+
+    pub fn profiles_dir(&self) -> PathBuf {
+        self.config_dir.join("profiles")
+    }
+
+    // 读取配置文件和当前 profile 的配置，返回发现的问题
+    pub fn load_file(&mut self) -> Vec<Problem> {
+        let mut problems = self.load_toml(&self.config_file());
+        if let Some(profile) = self.profile.clone() {
+            let path = self.profiles_dir().join(format!("{}.toml", profile));
+            if path.is_file() {
+                problems.extend(self.load_toml(&path));
+            } else {
+                let message = format!("找不到 profile {}", profile);
+                problems.push(Problem::new(&path, 0, message));
+            }
+        }
+        problems
+    }
+
+    // 无效的设置报告后使用之前的值，文件不存在时什么都不做
+    fn load_toml(&mut self, path: &Path) -> Vec<Problem> {
+        let Ok(text) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        let document = toml::parse(&text);
+        let mut problems: Vec<Problem> = document
+            .errors
+            .iter()
+            .map(|(line, message)| Problem::new(path, *line, message.clone()))
+            .collect();
+        // [sandbox.NAME] 和 [policy.NAME] 段的名字由用户决定
+        let mut named: Vec<&str> = Vec::new();
+        for entry in &document.entries {
+            let section = entry.section.as_str();
+            if (section.starts_with("sandbox.") || section.starts_with("policy."))
+                && !named.contains(&section)
+            {
+                named.push(section);
+            }
+        }
+        let mut schema: Vec<(&str, &[&str])> = vec![("", &CONFIG_KEYS)];
+        schema.extend(named.iter().map(|section| {
+            let keys: &[&str] = if section.starts_with("sandbox.") {
+                &SANDBOX_KEYS
+            } else {
+                &POLICY_KEYS
+            };
+            (*section, keys)
+        }));
+        problems.extend(toml::check_keys(path, &document, &schema));
+        for section in named {
+            if section.starts_with("sandbox.") {
+                problems.extend(self.load_sandbox(path, &document, section));
+            } else {
+                problems.extend(self.load_policy(path, &document, section));
+            }
+        }
+        let mut mode = None;
+        let mut seed = None;
+        for entry in document.section("") {
+            let result = match entry.key.as_str() {
+                "log_level" => expect_str(entry)
+                    .and_then(|level| one_of(level, &LOG_LEVELS))
+                    .map(|level| self.logger_level = level),
+                "theme" => expect_str(entry).map(|theme| self.theme = theme.to_string()),
+                "editor_mode" => expect_str(entry)
+                    .and_then(|editor| one_of(editor, &["vi", "emacs"]))
+                    .map(|editor| self.editor_mode = editor),
+                "prompt_max_ratio" => match entry.value.as_float() {
+                    Some(ratio) if ratio > 0.0 && ratio <= 1.0 => {
+                        self.prompt_max_ratio = ratio as f32;
+                        Ok(())
+                    }
+                    Some(ratio) => Err(format!("{} 不在 (0, 1] 之间", ratio)),
+                    None => Err(type_error(entry, "小数")),
+                },
+                "message_mode" => expect_str(entry)
+                    .and_then(|value| one_of(value, &["random", "seeded", "first"]))
+                    .map(|value| mode = Some(value)),
+                "message_seed" => match entry.value.as_integer() {
+                    Some(value) if value >= 0 => {
+                        seed = Some(value as u64);
+                        Ok(())
+                    }
+                    Some(value) => Err(format!("{} 应为非负整数", value)),
+                    None => Err(type_error(entry, "整数")),
+                },
+                "completion_match" => expect_str(entry).and_then(|value| {
+                    let matching = parse_completion_match(value).ok_or_else(|| {
+                        format!("无效的值 {}，应为 prefix/case/separator/fuzzy", value)
+                    })?;
+                    self.completion_match = matching;
+                    Ok(())
+                }),
+                "bracketed_paste" => expect_bool(entry).map(|flag| self.bracketed_paste = flag),
+                "paste_strip_prompt" => {
+                    expect_bool(entry).map(|flag| self.paste_strip_prompt = flag)
+                }
+                "stderr_color" => expect_bool(entry).map(|flag| self.stderr_color = flag),
+                "auto_activate" => expect_bool(entry).map(|flag| self.auto_activate = flag),
+                "local_config" => expect_bool(entry).map(|flag| self.local_config = flag),
+                "import_env" => expect_bool(entry).map(|flag| self.import_env = flag),
+                "tmux_integration" => expect_bool(entry).map(|flag| self.tmux_integration = flag),
+                "accessible" => expect_bool(entry).map(|flag| self.accessible = flag),
+                "update_check" => expect_bool(entry).map(|flag| self.update_check = flag),
+                "rm_safety_net" => expect_bool(entry).map(|flag| self.rm_safety_net = flag),
+                "rm_trash_command" => expect_str(entry)
+                    .map(|command| self.rm_trash_command = Some(command.to_string())),
+                "prompt_command" => {
+                    expect_str(entry).map(|command| self.prompt_command = non_empty(command))
+                }
+                "right_prompt_command" => {
+                    expect_str(entry).map(|command| self.right_prompt_command = non_empty(command))
+                }
+                "prompt_command_timeout" => match entry.value.as_integer() {
+                    Some(millis) if millis > 0 => {
+                        self.prompt_command_timeout = millis as u64;
+                        Ok(())
+                    }
+                    Some(millis) => Err(format!("{} 应为正整数", millis)),
+                    None => Err(type_error(entry, "整数")),
+                },
+                "max_nesting" => match entry.value.as_integer() {
+                    Some(depth) if depth > 0 => {
+                        self.max_nesting = depth as usize;
+                        Ok(())
+                    }
+                    Some(depth) => Err(format!("{} 应为正整数", depth)),
+                    None => Err(type_error(entry, "整数")),
+                },
+                "max_args" => match entry.value.as_integer() {
+                    Some(count) if count > 0 => {
+                        self.max_args = count as usize;
+                        Ok(())
+                    }
+                    Some(count) => Err(format!("{} 应为正整数", count)),
+                    None => Err(type_error(entry, "整数")),
+                },
+                "ticker_after" => match entry.value.as_integer() {
+                    Some(seconds) if seconds >= 0 => {
+                        self.ticker_after = seconds as u64;
+                        Ok(())
+                    }
+                    Some(seconds) => Err(format!("{} 应为非负整数", seconds)),
+                    None => Err(type_error(entry, "整数")),
+                },
+                "clipboard" => expect_str(entry).and_then(|value| {
+                    self.clipboard = parse_clipboard(value).ok_or_else(|| {
+                        format!("无效的值 {}，应为 auto/osc52/command/off", value)
+                    })?;
+                    Ok(())
+                }),
+                "language" => expect_str(entry).and_then(|value| {
+                    self.language = parse_language(value)
+                        .ok_or_else(|| format!("无效的值 {}，应为 auto/zh/en", value))?;
+                    Ok(())
+                }),
+                "history_file" => expect_str(entry).map(|file| {
+                    self.history_file = resolve_path(&self.state_dir, file);
+                }),
+                "rc_file" => expect_str(entry).map(|file| {
+                    self.rc_file = resolve_path(&self.config_dir, file);
+                }),
+                "fpath" => match entry.value.as_strings() {
+                    Some(dirs) => {
+                        self.fpath = dirs
+                            .iter()
+                            .map(|dir| resolve_path(&self.config_dir, dir))
+                            .collect();
+                        Ok(())
+                    }
+                    None => Err(type_error(entry, "字符串数组")),
+                },
+                _ => Ok(()),
+            };
+            if let Err(message) = result {
+                let message = format!("{}: {}", entry.key, message);
+                problems.push(Problem::new(path, entry.line, message));
+            }
+        }
+        // 只设置了种子时使用 seeded 模式
+        let mode = mode.or_else(|| seed.map(|_| "seeded".to_string()));
+        if let Some(mode) = mode.and_then(|mode| parse_message_mode(&mode, seed)) {
+            self.message_mode = mode;
+        }
+        problems
+    }
+
+    fn load_sandbox(&mut self, path: &Path, document: &Document, section: &str) -> Vec<Problem> {
+        let name = &section["sandbox.".len()..];
+        let mut profile = SandboxProfile::new(name);
+        let mut problems = Vec::new();
+        for entry in document.section(section) {
+            let result = match entry.key.as_str() {
+                "network" => expect_bool(entry).map(|flag| profile.network = flag),
+                "readonly_home" => expect_bool(entry).map(|flag| profile.readonly_home = flag),
+                "readonly" => match entry.value.as_strings() {
+                    Some(paths) => {
+                        profile.readonly = paths
+                            .iter()
+                            .map(|path| PathBuf::from(shellexpand::tilde(path).as_ref()))
+                            .collect();
+                        Ok(())
+                    }
+                    None => Err(type_error(entry, "字符串数组")),
+                },
+                _ => Ok(()),
+            };
+            if let Err(message) = result {
+                let message = format!("{}.{}: {}", section, entry.key, message);
+                problems.push(Problem::new(path, entry.line, message));
+            }
+        }
+        self.sandbox_profiles
+            .retain(|existing| existing.name != name);
+        self.sandbox_profiles.push(profile);
+        problems
+    }
+
+    fn load_policy(&mut self, path: &Path, document: &Document, section: &str) -> Vec<Problem> {
+        let name = &section["policy.".len()..];
+        let mut pattern = None;
+        let mut action = None;
+        let mut message = None;
+        let mut on = None;
+        let mut problems = Vec::new();
+        for entry in document.section(section) {
+            let result = match entry.key.as_str() {
+                "match" => expect_str(entry).and_then(|value| {
+                    pattern = Some(Regex::new(value).map_err(|e| e.to_string())?);
+                    Ok(())
+                }),
+                "action" => expect_str(entry).and_then(|value| {
+                    action = Some(match value {
+                        "confirm" => PolicyAction::Confirm,
+                        "deny" => PolicyAction::Deny,
+                        "allow" => PolicyAction::Allow,
+                        _ => return Err(format!("无效的值 {}，应为 confirm/deny/allow", value)),
+                    });
+                    Ok(())
+                }),
+                "message" => expect_str(entry).map(|value| message = Some(value.to_string())),
+                "on" => expect_str(entry).and_then(|value| {
+                    on = Some(match value {
+                        "exec" => PolicyTarget::Exec,
+                        "paste" => PolicyTarget::Paste,
+                        _ => return Err(format!("无效的值 {}，应为 exec/paste", value)),
+                    });
+                    Ok(())
+                }),
+                _ => Ok(()),
+            };
+            if let Err(message) = result {
+                let message = format!("{}.{}: {}", section, entry.key, message);
+                problems.push(Problem::new(path, entry.line, message));
+            }
+        }
+        // 修改已有的同名规则（例如内置的粘贴检查）时，没有写出的设置保持原样
+        let previous = self
+            .policy_rules
+            .iter()
+            .position(|existing| existing.name == name)
+            .map(|index| self.policy_rules.remove(index));
+        match pattern.or_else(|| previous.as_ref().map(|rule| rule.pattern.clone())) {
+            Some(pattern) => self.policy_rules.push(PolicyRule {
+                name: name.to_string(),
+                pattern,
+                action: action
+                    .or(previous.as_ref().map(|rule| rule.action))
+                    .unwrap_or(PolicyAction::Confirm),
+                message: message
+                    .or_else(|| previous.as_ref().and_then(|rule| rule.message.clone())),
+                on: on
+                    .or(previous.as_ref().map(|rule| rule.on))
+                    .unwrap_or(PolicyTarget::Exec),
+            }),
+            None => {
+                let line = document
+                    .section(section)
+                    .next()
+                    .map_or(0, |entry| entry.line);
+                let message = format!("[{}] 缺少 match", section);
+                problems.push(Problem::new(path, line, message));
+            }
+        }
+        problems
+    }
+
+    // 旧版本把历史、日志和缓存都放在 ~/.config/zako 下，移动到新的位置；目标已存在时保留旧文件
+    fn migrate_from(&self, legacy: &Path) {
+        let mut moves = vec![
+            (
+                legacy.join(".zako_history"),
+                self.state_dir.join(".zako_history"),
+            ),
+            (legacy.join("logs"), self.logger_dir.clone()),
+            (legacy.join("cache"), self.cache_dir.clone()),
+        ];
+        // 设置了 XDG_CONFIG_HOME 时配置文件也要移动
+        if legacy != self.config_dir {
+            for name in ["config.toml", "zakorc", "themes", "profiles"] {
+                moves.push((legacy.join(name), self.config_dir.join(name)));
+            }
+        }
+        for (from, to) in moves {
+            if from == to || !from.exists() || to.exists() {
+                continue;
+            }
+            let result = to
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::rename(&from, &to));
+            match result {
+                Ok(()) => info!("已迁移 {} 到 {}", from.display(), to.display()),
+                Err(e) => error!("无法迁移 {} 到 {}: {}", from.display(), to.display(), e),
+            }
         }
     }
 
@@ -48,8 +654,20 @@ impl Config {
             dotenv().ok();
         }
 
-        // 默认配置
+        // 默认配置，其次是配置文件和 profile，环境变量和命令行参数优先
         let mut config = Config::default();
+        if env::var_os("ZAKO_CONFIG_DIR").is_none() {
+            config.migrate_from(&PathBuf::from(
+                shellexpand::tilde("~/.config/zako").into_owned(),
+            ));
+        }
+        let args = parse_args(env::args());
+        config.profile = args
+            .profile
+            .clone()
+            .or_else(|| env::var("ZAKO_PROFILE").ok())
+            .filter(|profile| !profile.is_empty() && profile != DEFAULT_PROFILE);
+        config.problems = config.load_file();
 
         if let Ok(logger_level) = env::var("ZAKO_LOG") {
             config.logger_level = logger_level;
@@ -63,6 +681,186 @@ impl Config {
             config.editor_mode = editor;
         }
 
+        if let Ok(ratio) = env::var("ZAKO_PROMPT_MAX_RATIO") {
+            match ratio.parse::<f32>() {
+                Ok(ratio) if ratio > 0.0 && ratio <= 1.0 => config.prompt_max_ratio = ratio,
+                _ => error!(
+                    "无效的 ZAKO_PROMPT_MAX_RATIO: {}，应为 (0, 1] 之间的小数",
+                    ratio
+                ),
+            }
+        }
+
+        // ZAKO_MESSAGE_MODE: random | seeded | first，设置了 ZAKO_MESSAGE_SEED 时默认为 seeded
+        let seed = env::var("ZAKO_MESSAGE_SEED").ok();
+        let seed = match seed.as_deref().map(|s| s.parse::<u64>()) {
+            Some(Ok(seed)) => Some(seed),
+            Some(Err(_)) => {
+                error!("无效的 ZAKO_MESSAGE_SEED: {:?}，应为非负整数", seed);
+                None
+            }
+            None => None,
+        };
+        let mode = env::var("ZAKO_MESSAGE_MODE")
+            .ok()
+            .or_else(|| seed.map(|_| "seeded".to_string()));
+        if let Some(mode) = mode {
+            config.message_mode = parse_message_mode(&mode, seed).unwrap_or_else(|| {
+                error!(
+                    "无效的 ZAKO_MESSAGE_MODE: {}，应为 random/seeded/first",
+                    mode
+                );
+                MessageMode::Random
+            });
+        }
+
+        // ZAKO_COMPLETION_MATCH: prefix | case | separator | fuzzy
+        if let Ok(matching) = env::var("ZAKO_COMPLETION_MATCH") {
+            config.completion_match = parse_completion_match(&matching).unwrap_or_else(|| {
+                error!(
+                    "无效的 ZAKO_COMPLETION_MATCH: {}，应为 prefix/case/separator/fuzzy",
+                    matching
+                );
+                CompletionMatch::Prefix
+            });
+        }
+
+        // ZAKO_CLIPBOARD: auto | osc52 | command | off
+        if let Ok(clipboard) = env::var("ZAKO_CLIPBOARD") {
+            match parse_clipboard(&clipboard) {
+                Some(mode) => config.clipboard = mode,
+                None => error!(
+                    "无效的 ZAKO_CLIPBOARD: {}，应为 auto/osc52/command/off",
+                    clipboard
+                ),
+            }
+        }
+
+        // ZAKO_LANG: auto | zh | en
+        if let Ok(language) = env::var("ZAKO_LANG") {
+            match parse_language(&language) {
+                Some(lang) => config.language = lang,
+                None => error!("无效的 ZAKO_LANG: {}，应为 auto/zh/en", language),
+            }
+        }
+
+        if let Some(flag) = env_flag("ZAKO_BRACKETED_PASTE") {
+            config.bracketed_paste = flag;
+        }
+
+        if let Some(flag) = env_flag("ZAKO_PASTE_STRIP_PROMPT") {
+            config.paste_strip_prompt = flag;
+        }
+
+        if let Some(flag) = env_flag("ZAKO_STDERR_COLOR") {
+            config.stderr_color = flag;
+        }
+
+        if let Some(flag) = env_flag("ZAKO_AUTO_ACTIVATE") {
+            config.auto_activate = flag;
+        }
+
+        if let Some(flag) = env_flag("ZAKO_LOCAL_CONFIG") {
+            config.local_config = flag;
+        }
+
+        if let Some(flag) = env_flag("ZAKO_TMUX_INTEGRATION") {
+            config.tmux_integration = flag;
+        }
+
+        if let Some(flag) = env_flag("ZAKO_UPDATE_CHECK") {
+            config.update_check = flag;
+        }
+        config.upgrade = args.upgrade;
+
+        if let Ok(depth) = env::var("ZAKO_MAX_NESTING") {
+            match depth.parse::<usize>() {
+                Ok(depth) if depth > 0 => config.max_nesting = depth,
+                _ => error!("无效的 ZAKO_MAX_NESTING: {}，应为正整数", depth),
+            }
+        }
+
+        if let Some(flag) = env_flag("ZAKO_RM_SAFETY_NET") {
+            config.rm_safety_net = flag;
+        }
+
+        if let Ok(command) = env::var("ZAKO_RM_TRASH_COMMAND") {
+            config.rm_trash_command = Some(command);
+        }
+
+        if let Ok(command) = env::var("ZAKO_PROMPT_COMMAND") {
+            config.prompt_command = non_empty(&command);
+        }
+
+        if let Ok(command) = env::var("ZAKO_RIGHT_PROMPT_COMMAND") {
+            config.right_prompt_command = non_empty(&command);
+        }
+
+        if let Ok(millis) = env::var("ZAKO_PROMPT_COMMAND_TIMEOUT") {
+            match millis.parse::<u64>() {
+                Ok(millis) if millis > 0 => config.prompt_command_timeout = millis,
+                _ => error!("无效的 ZAKO_PROMPT_COMMAND_TIMEOUT: {}，应为正整数", millis),
+            }
+        }
+
+        if let Ok(count) = env::var("ZAKO_MAX_ARGS") {
+            match count.parse::<usize>() {
+                Ok(count) if count > 0 => config.max_args = count,
+                _ => error!("无效的 ZAKO_MAX_ARGS: {}，应为正整数", count),
+            }
+        }
+
+        // 与 PATH 一样以 : 分隔
+        if let Some(fpath) = env::var_os("ZAKO_FPATH") {
+            config.fpath = env::split_paths(&fpath)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect();
+        }
+
+        if let Ok(seconds) = env::var("ZAKO_TICKER_AFTER") {
+            match seconds.parse::<u64>() {
+                Ok(seconds) => config.ticker_after = seconds,
+                _ => error!("无效的 ZAKO_TICKER_AFTER: {}，应为非负整数", seconds),
+            }
+        }
+
+        if let Some(flag) = env_flag("ZAKO_IMPORT_ENV") {
+            config.import_env = flag;
+        }
+        config.import_env |= args.import_env;
+
+        // 无障碍模式下消息固定为第一条，子进程的 stderr 也不加颜色
+        if let Some(flag) = env_flag("ZAKO_ACCESSIBLE") {
+            config.accessible = flag;
+        }
+        config.accessible |= args.accessible;
+        if config.accessible {
+            config.message_mode = MessageMode::First;
+            config.stderr_color = false;
+        }
+
+        // 以 rzako 启动、带 --restricted/-r 参数或设置了 ZAKO_RESTRICTED 时进入受限模式
+        config.restricted = args.restricted || env_flag("ZAKO_RESTRICTED").unwrap_or(false);
+        config.login = args.login;
+        config.noexec = args.noexec;
+        config.script = args.script;
+
+        // ZAKO_DEBUG_PARSE: tokens | ast | all，与 --dump-tokens/--dump-ast 相同
+        config.dump_tokens = args.dump_tokens;
+        config.dump_ast = args.dump_ast;
+        config.bench_internal = args.bench_internal;
+        if let Ok(value) = env::var("ZAKO_DEBUG_PARSE") {
+            match value.to_lowercase().as_str() {
+                "tokens" => config.dump_tokens = true,
+                "ast" => config.dump_ast = true,
+                "all" | "1" => {
+                    config.dump_tokens = true;
+                    config.dump_ast = true;
+                }
+                _ => error!("无效的 ZAKO_DEBUG_PARSE: {}，应为 tokens/ast/all", value),
+            }
+        }
+
         // 确保历史文件目录存在
         if let Some(parent) = config.history_file.parent() {
             if let Err(e) = fs::create_dir_all(parent) {
@@ -73,3 +871,382 @@ impl Config {
         config
     }
 }
+
+// $VAR/zako，没有设置或不是绝对路径时（XDG 规范要求忽略相对路径）使用 fallback/zako
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    match env::var(var) {
+        Ok(dir) if dir.starts_with('/') => PathBuf::from(dir).join("zako"),
+        _ => PathBuf::from(shellexpand::tilde(fallback).into_owned()).join("zako"),
+    }
+}
+
+// 展开 ~，相对路径相对于 base
+fn resolve_path(base: &Path, path: &str) -> PathBuf {
+    base.join(shellexpand::tilde(path).as_ref())
+}
+
+// random | seeded | first，seeded 模式没有种子时随机选择一个
+fn parse_message_mode(mode: &str, seed: Option<u64>) -> Option<MessageMode> {
+    match mode.to_lowercase().as_str() {
+        "first" => Some(MessageMode::First),
+        "seeded" => Some(MessageMode::Seeded(seed.unwrap_or_else(rand::random))),
+        "random" => Some(MessageMode::Random),
+        _ => None,
+    }
+}
+
+// prefix | case | separator | fuzzy
+fn parse_completion_match(matching: &str) -> Option<CompletionMatch> {
+    match matching.to_lowercase().as_str() {
+        "prefix" => Some(CompletionMatch::Prefix),
+        "case" => Some(CompletionMatch::IgnoreCase),
+        "separator" => Some(CompletionMatch::IgnoreSeparators),
+        "fuzzy" => Some(CompletionMatch::Fuzzy),
+        _ => None,
+    }
+}
+
+// auto | osc52 | command | off
+fn parse_clipboard(mode: &str) -> Option<ClipboardMode> {
+    match mode.to_lowercase().as_str() {
+        "auto" => Some(ClipboardMode::Auto),
+        "osc52" => Some(ClipboardMode::Osc52),
+        "command" => Some(ClipboardMode::Command),
+        "off" => Some(ClipboardMode::Off),
+        _ => None,
+    }
+}
+
+// auto | zh | en，auto 为 None
+fn parse_language(language: &str) -> Option<Option<Lang>> {
+    if language.eq_ignore_ascii_case("auto") {
+        return Some(None);
+    }
+    i18n::parse_lang(language).map(Some)
+}
+
+// 空的命令表示不使用
+fn non_empty(command: &str) -> Option<String> {
+    (!command.trim().is_empty()).then(|| command.to_string())
+}
+
+fn type_error(entry: &Entry, expected: &str) -> String {
+    format!("应为{}，而不是{}", expected, entry.value.type_name())
+}
+
+fn expect_str(entry: &Entry) -> Result<&str, String> {
+    entry
+        .value
+        .as_str()
+        .ok_or_else(|| type_error(entry, "字符串"))
+}
+
+fn expect_bool(entry: &Entry) -> Result<bool, String> {
+    entry
+        .value
+        .as_bool()
+        .ok_or_else(|| type_error(entry, "布尔值"))
+}
+
+// 不区分大小写地匹配可选的值，返回小写的值
+fn one_of(value: &str, choices: &[&str]) -> Result<String, String> {
+    let lower = value.to_lowercase();
+    if choices.contains(&lower.as_str()) {
+        Ok(lower)
+    } else {
+        Err(format!("无效的值 {}，应为 {}", value, choices.join("/")))
+    }
+}
+
+// 命令行参数：zako [-r|--restricted] [-l|--login] [-n|--noexec] [--profile NAME] [--import-env] [--accessible] [--dump-tokens] [--dump-ast] [--bench-internal] [script]
+//   zako upgrade --self
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    restricted: bool,
+    login: bool,
+    profile: Option<String>,
+    import_env: bool,
+    accessible: bool,
+    upgrade: bool,
+    noexec: bool,
+    dump_tokens: bool,
+    dump_ast: bool,
+    bench_internal: bool,
+    script: Option<PathBuf>,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Args {
+    let mut args = args.peekable();
+    let arg0 = args.next().unwrap_or_default();
+    let name = PathBuf::from(&arg0)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut result = Args {
+        restricted: name.trim_start_matches('-') == "rzako",
+        login: arg0.starts_with('-'),
+        ..Args::default()
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" | "--restricted" => result.restricted = true,
+            "-l" | "--login" => result.login = true,
+            "--profile" => match args.next() {
+                Some(profile) => result.profile = Some(profile),
+                None => error!("--profile 缺少配置名"),
+            },
+            _ if arg.starts_with("--profile=") => {
+                result.profile = Some(arg["--profile=".len()..].to_string())
+            }
+            "-n" | "--noexec" => result.noexec = true,
+            "--import-env" => result.import_env = true,
+            "--accessible" => result.accessible = true,
+            "--dump-tokens" => result.dump_tokens = true,
+            "--dump-ast" => result.dump_ast = true,
+            "--bench-internal" => result.bench_internal = true,
+            "upgrade" if result.script.is_none() && args.peek().is_some_and(|a| a == "--self") => {
+                args.next();
+                result.upgrade = true;
+            }
+            _ if arg.starts_with('-') => error!("未知参数: {}", arg),
+            _ if result.script.is_none() => result.script = Some(PathBuf::from(arg)),
+            _ => error!("多余的参数: {}", arg),
+        }
+    }
+    result
+}
+
+// 解析布尔类型的环境变量：1/true/on/yes 与 0/false/off/no
+fn env_flag(name: &str) -> Option<bool> {
+    let value = env::var(name).ok()?;
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Some(true),
+        "0" | "false" | "off" | "no" => Some(false),
+        _ => {
+            error!("无效的 {}: {}，应为 on 或 off", name, value);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(items: &[&str]) -> impl Iterator<Item = String> {
+        items
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert!(parse_args(args(&["/usr/local/bin/rzako"])).restricted);
+        assert!(parse_args(args(&["zako", "--restricted"])).restricted);
+        assert!(parse_args(args(&["zako", "-r"])).restricted);
+        assert!(!parse_args(args(&["zako"])).restricted);
+        assert!(!parse_args(args(&["/opt/rzako/zako"])).restricted);
+        // login 启动登录 shell 时 argv[0] 以 - 开头
+        assert!(parse_args(args(&["-zako"])).login);
+        assert!(parse_args(args(&["-rzako"])).restricted);
+        assert!(parse_args(args(&["zako", "--login"])).login);
+        assert!(!parse_args(args(&["zako"])).login);
+        assert_eq!(
+            parse_args(args(&["zako", "-n", "build.zk"])),
+            Args {
+                noexec: true,
+                script: Some(PathBuf::from("build.zk")),
+                ..Args::default()
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["zako", "--profile", "work", "--profile=home"])).profile,
+            Some("home".to_string())
+        );
+        assert!(parse_args(args(&["zako", "--import-env"])).import_env);
+        assert!(parse_args(args(&["zako", "--accessible"])).accessible);
+        assert!(parse_args(args(&["zako", "upgrade", "--self"])).upgrade);
+        assert_eq!(
+            parse_args(args(&["zako", "upgrade"])).script,
+            Some(PathBuf::from("upgrade"))
+        );
+        let dump = parse_args(args(&["zako", "--dump-tokens", "--dump-ast"]));
+        assert!(dump.dump_tokens && dump.dump_ast && dump.script.is_none());
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_load_file() {
+        let dir = env::temp_dir().join(format!("zako_config_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.config_dir = dir.clone();
+        config.state_dir = dir.clone();
+        assert!(config.load_file().is_empty());
+
+        fs::write(
+            config.config_file(),
+            r#"
+theme = "pink"
+editor_mode = "Emacs"
+prompt_max_ratio = 2
+message_seed = 7
+stderr_color = "yes"
+colour = true
+prompt_command = "starship prompt"
+prompt_command_timeout = 200
+[prompt]
+"#,
+        )
+        .unwrap();
+        let problems = config.load_file();
+        assert_eq!(config.theme, "pink");
+        assert_eq!(config.editor_mode, "emacs");
+        assert_eq!(config.prompt_max_ratio, 0.5);
+        assert_eq!(config.message_mode, MessageMode::Seeded(7));
+        assert!(!config.stderr_color);
+        assert_eq!(config.prompt_command.as_deref(), Some("starship prompt"));
+        assert_eq!(config.prompt_command_timeout, 200);
+        let lines: Vec<usize> = problems.iter().map(|p| p.line).collect();
+        assert_eq!(lines, vec![7, 4, 6]);
+        assert!(problems[2].message.contains("应为布尔值"));
+
+        // profile 在 config.toml 之后加载，覆盖其中的设置
+        fs::create_dir_all(config.profiles_dir()).unwrap();
+        fs::write(
+            config.profiles_dir().join("work.toml"),
+            "theme = \"work\"\nhistory_file = \"work_history\"\n",
+        )
+        .unwrap();
+        config.profile = Some("work".to_string());
+        config.load_file();
+        assert_eq!(config.theme, "work");
+        assert_eq!(config.history_file, dir.join("work_history"));
+        config.profile = Some("missing".to_string());
+        let problems = config.load_file();
+        assert!(problems.last().unwrap().message.contains("找不到 profile"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_sandbox_profiles() {
+        let dir = env::temp_dir().join(format!("zako_sandbox_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.config_dir = dir.clone();
+        fs::write(
+            config.config_file(),
+            r#"
+[sandbox.offline]
+readonly_home = false
+[sandbox.installer]
+network = true
+readonly = ["/etc", "~/bin"]
+mount = "x"
+"#,
+        )
+        .unwrap();
+        let problems = config.load_file();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("sandbox.installer.mount"));
+        assert_eq!(
+            config.sandbox_profiles[0],
+            SandboxProfile {
+                readonly_home: false,
+                ..SandboxProfile::new("offline")
+            }
+        );
+        let installer = &config.sandbox_profiles[1];
+        assert!(installer.network && installer.readonly_home);
+        assert_eq!(installer.readonly[0], PathBuf::from("/etc"));
+        assert!(!installer.readonly[1].starts_with("~"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_policy_rules() {
+        let dir = env::temp_dir().join(format!("zako_policy_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.config_dir = dir.clone();
+        fs::write(
+            config.config_file(),
+            r#"
+[policy.kube]
+match = 'kubectl delete.*--all'
+[policy.prod]
+match = "--context prod"
+action = "deny"
+message = "不要在这里操作生产环境"
+[policy.broken]
+match = "("
+action = "ask"
+on = "both"
+[policy.paste_sudo]
+action = "allow"
+"#,
+        )
+        .unwrap();
+        let problems = config.load_file();
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].message.contains("policy.broken.match"));
+        assert!(problems[1].message.contains("policy.broken.action"));
+        assert!(problems[2].message.contains("policy.broken.on"));
+        assert!(problems[3].message.contains("缺少 match"));
+        let sudo = config
+            .policy_rules
+            .iter()
+            .find(|rule| rule.name == "paste_sudo");
+        let sudo = sudo.unwrap();
+        assert_eq!(
+            (sudo.action, sudo.on),
+            (PolicyAction::Allow, PolicyTarget::Paste)
+        );
+        assert!(sudo.pattern.is_match("sudo rm"));
+        config
+            .policy_rules
+            .retain(|rule| rule.on == PolicyTarget::Exec);
+        assert_eq!(config.policy_rules.len(), 2);
+        let kube = &config.policy_rules[0];
+        assert_eq!(
+            (kube.name.as_str(), kube.action),
+            ("kube", PolicyAction::Confirm)
+        );
+        assert!(kube.pattern.is_match("kubectl delete pods --all"));
+        assert_eq!(config.policy_rules[1].action, PolicyAction::Deny);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_migrate_from() {
+        let dir = env::temp_dir().join(format!("zako_migrate_{}", std::process::id()));
+        let legacy = dir.join("legacy");
+        fs::create_dir_all(legacy.join("logs")).unwrap();
+        fs::write(legacy.join(".zako_history"), "ls\n").unwrap();
+        fs::write(legacy.join("logs/zako.log"), "").unwrap();
+        fs::write(legacy.join("config.toml"), "theme = \"pink\"\n").unwrap();
+        let mut config = Config::default();
+        config.config_dir = dir.join("config");
+        config.state_dir = dir.join("state");
+        config.cache_dir = dir.join("cache");
+        config.logger_dir = config.state_dir.join("logs");
+        // 新位置已有配置文件时不覆盖
+        fs::create_dir_all(&config.config_dir).unwrap();
+        fs::write(config.config_dir.join("config.toml"), "").unwrap();
+
+        config.migrate_from(&legacy);
+        assert_eq!(
+            fs::read_to_string(config.state_dir.join(".zako_history")).unwrap(),
+            "ls\n"
+        );
+        assert!(config.state_dir.join("logs/zako.log").exists());
+        assert!(!legacy.join(".zako_history").exists());
+        assert!(legacy.join("config.toml").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+use zakosh::i18n;
+
+// 解析带单位的时长：10 / 10s / 1.5m / 2h / 1d / 500ms，不带单位时按秒计算
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let value = number
+        .parse::<f64>()
+        .map_err(|_| i18n::tf("duration.invalid", &[input]))?;
+    let seconds = match unit {
+        "" | "s" => value,
+        "ms" => value / 1000.0,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        _ => return Err(i18n::tf("duration.invalid_unit", &[unit])),
+    };
+    // 负数、无穷大和超出 Duration 范围的值都是无效的时长
+    Duration::try_from_secs_f64(seconds).map_err(|_| i18n::tf("duration.invalid", &[input]))
+}
+
+// 以最大的合适单位显示时长：90s -> 1m30s
+pub fn format_duration(duration: Duration) -> String {
+    let total = duration.as_secs();
+    if total == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+    let (days, hours, minutes, seconds) = (
+        total / 86400,
+        total % 86400 / 3600,
+        total % 3600 / 60,
+        total % 60,
+    );
+    let mut result = String::new();
+    for (value, unit) in [(days, "d"), (hours, "h"), (minutes, "m"), (seconds, "s")] {
+        if value > 0 {
+            result.push_str(&format!("{}{}", value, unit));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("10s").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+        assert!(parse_duration("9999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m30s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h");
+        assert_eq!(format_duration(Duration::from_millis(250)), "250ms");
+    }
+}
@@ -16,13 +16,17 @@ pub fn init_logger(config: &Config) {
         _ => LevelFilter::Info,
     };
 
-    // 创建日志目录
-    fs::create_dir_all(&config.logger_dir).expect("Failed to create log directory");
+    // 创建日志目录，失败时只输出到 stdout
+    let mut writers: Vec<Box<dyn Write + Send + Sync>> = vec![Box::new(std::io::stdout())];
+    if let Err(e) = fs::create_dir_all(&config.logger_dir) {
+        eprintln!("无法创建日志目录: {}", e);
+    }
     let date = Local::now().format("%Y-%m-%d");
-    let log_file = config
-        .logger_dir
-        .join(format!("zako_{}.log", date.to_string()));
-    let file = File::create(log_file).expect("Failed to create log file");
+    let log_file = config.logger_dir.join(format!("zako_{}.log", date));
+    match File::create(log_file) {
+        Ok(file) => writers.push(Box::new(file)),
+        Err(e) => eprintln!("无法创建日志文件: {}", e),
+    }
 
     Builder::new()
         .format(|buf, record| {
@@ -35,9 +39,7 @@ pub fn init_logger(config: &Config) {
                 record.args()
             )
         })
-        .target(Target::Pipe(Box::new(MultiWriter {
-            writers: vec![Box::new(std::io::stdout()), Box::new(file)],
-        })))
+        .target(Target::Pipe(Box::new(MultiWriter { writers })))
         .filter(Some(&config.name), level)
         .filter(None, LevelFilter::Warn)
         .init();
@@ -1,4 +1,11 @@
 pub mod config;
+pub mod digest;
+pub mod duration;
 pub mod log;
 pub mod path;
+pub mod prompt;
+pub mod segment;
+pub mod style;
 pub mod theme;
+pub mod toml;
+pub mod zsh_theme;
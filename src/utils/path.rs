@@ -1,19 +1,11 @@
-use std::borrow::Cow;
 use std::env;
 use std::fs::read_dir;
 use std::io::ErrorKind;
 use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 
 use log::error;
 
-pub fn basename(path: &str) -> Cow<'_, str> {
-    let mut pieces = path.rsplit('/');
-    match pieces.next() {
-        Some(p) => p.into(),
-        None => path.into(),
-    }
-}
-
 pub fn find_file_in_path(filename: &str, exec: bool) -> String {
     let env_path = match env::var("PATH") {
         Ok(x) => x,
@@ -62,6 +54,22 @@ pub fn find_file_in_path(filename: &str, exec: bool) -> String {
     String::new()
 }
 
+// PATH 中所有名为 filename 的可执行文件，按 PATH 的顺序，第一个是实际执行的
+pub fn find_all_in_path(filename: &str) -> Vec<PathBuf> {
+    let env_path = env::var("PATH").unwrap_or_default();
+    env_path
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(filename))
+        .filter(|path| is_executable(path))
+        .collect()
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
 pub fn current_dir() -> String {
     let _current_dir = match env::current_dir() {
         Ok(x) => x,
@@ -80,3 +88,11 @@ pub fn current_dir() -> String {
 
     current_dir.to_string()
 }
+
+// 从 start 开始逐级向上查找，返回第一个存在的 names 中的文件
+pub fn find_upwards(start: &Path, names: &[&str]) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.exists())
+}
@@ -0,0 +1,347 @@
+use log::error;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::path;
+use super::segment::JobCounts;
+
+const ELLIPSIS: char = '…';
+// 截断后每个片段至少保留的显示宽度（含省略号）
+const MIN_SEGMENT_WIDTH: usize = 6;
+
+// 去掉 ANSI 转义序列（颜色等）
+pub fn strip_ansi(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: ESC [ 参数... 结束字符 (0x40..=0x7e)
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC（窗口标题、超链接等）：ESC ] ... 以 BEL 或 ESC \ 结束
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // 两字符序列
+            _ => {}
+        }
+    }
+    result
+}
+
+// 计算字符串在终端上的显示宽度，全角字符和 emoji 占两列
+// 按字符串计算宽度，❤️（带 VS16）和 👨‍👩‍👧（ZWJ 序列）这样的组合与 rustyline 的计算相同，换行不占宽度
+pub fn display_width(s: &str) -> usize {
+    strip_ansi(s).split('\n').map(|line| line.width()).sum()
+}
+
+// 从左边截断，保留尾部：~/a/b/c -> …b/c
+pub fn truncate_left(s: &str, max_width: usize) -> String {
+    if display_width(s) <= max_width {
+        return s.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    let mut width = ELLIPSIS.width().unwrap_or(1);
+    let mut tail = Vec::new();
+    for c in s.chars().rev() {
+        let w = c.width().unwrap_or(0);
+        if width + w > max_width {
+            break;
+        }
+        width += w;
+        tail.push(c);
+    }
+    std::iter::once(ELLIPSIS)
+        .chain(tail.into_iter().rev())
+        .collect()
+}
+
+// 从右边截断，保留头部：feature/long-name -> featu…
+pub fn truncate_right(s: &str, max_width: usize) -> String {
+    if display_width(s) <= max_width {
+        return s.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    let mut width = ELLIPSIS.width().unwrap_or(1);
+    let mut head = String::new();
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if width + w > max_width {
+            break;
+        }
+        width += w;
+        head.push(c);
+    }
+    head.push(ELLIPSIS);
+    head
+}
+
+// 获取终端列数，失败时依次回退到 $COLUMNS 和 80
+pub fn terminal_width() -> usize {
+    unsafe {
+        let mut ws: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) == 0 && ws.ws_col > 0 {
+            return ws.ws_col as usize;
+        }
+    }
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse::<usize>().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(80)
+}
+
+// 当前目录，家目录缩写为 ~
+pub fn cwd_segment() -> String {
+    let cwd = path::current_dir();
+    match env::var("HOME") {
+        Ok(home) if !home.is_empty() && cwd.starts_with(&home) => {
+            let rest = &cwd[home.len()..];
+            if rest.is_empty() || rest.starts_with('/') {
+                format!("~{}", rest)
+            } else {
+                cwd
+            }
+        }
+        _ => cwd,
+    }
+}
+
+// 当前 git 分支，直接读取 HEAD 文件，避免每次提示符都启动 git 进程
+pub fn git_segment() -> Option<String> {
+    let cwd = env::current_dir().ok()?;
+    let git_dir = find_git_dir(&cwd)?;
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+fn find_git_dir(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        // worktree / submodule: .git 是一个内容为 "gitdir: <path>" 的文件
+        if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            let git_dir = PathBuf::from(content.trim().strip_prefix("gitdir: ")?);
+            return Some(if git_dir.is_absolute() {
+                git_dir
+            } else {
+                dir.join(git_dir)
+            });
+        }
+    }
+    None
+}
+
+// 运行外部命令（例如 starship）生成提示符，命令的标准输出去掉结尾的换行后作为提示符
+// 命令中的 $? 为上一条命令的退出码，另外通过环境变量传递 ZAKO_STATUS、
+// ZAKO_DURATION（上一条命令的耗时，毫秒）和 ZAKO_JOBS（后台作业数）
+// 命令在自己的进程组中运行，超过 timeout 时连同它启动的进程一起结束；失败或超时时返回 None
+pub fn command_prompt(
+    command: &str,
+    timeout: Duration,
+    last_status: i32,
+    duration: Duration,
+    jobs: JobCounts,
+) -> Option<String> {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("(exit {}); {}", last_status & 0xff, command))
+        .env("ZAKO_STATUS", last_status.to_string())
+        .env("ZAKO_DURATION", duration.as_millis().to_string())
+        .env("ZAKO_JOBS", (jobs.running + jobs.stopped).to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .process_group(0)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            error!("无法运行提示符命令 {}: {}", command, e);
+            return None;
+        }
+    };
+
+    // 在线程中读取输出，读完或超时后再等待进程
+    let (sender, receiver) = mpsc::channel();
+    let mut stdout = child.stdout.take()?;
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stdout.read_to_end(&mut output);
+        let _ = sender.send(output);
+    });
+    let output = match receiver.recv_timeout(timeout) {
+        Ok(output) => output,
+        Err(_) => {
+            unsafe {
+                libc::kill(-(child.id() as i32), libc::SIGKILL);
+            }
+            let _ = child.wait();
+            error!("提示符命令超过 {} 毫秒: {}", timeout.as_millis(), command);
+            return None;
+        }
+    };
+    match child.wait() {
+        Ok(status) if status.success() => Some(
+            String::from_utf8_lossy(&output)
+                .trim_end_matches('\n')
+                .to_string(),
+        ),
+        Ok(status) => {
+            error!("提示符命令失败（{}）: {}", status, command);
+            None
+        }
+        Err(e) => {
+            error!("无法运行提示符命令 {}: {}", command, e);
+            None
+        }
+    }
+}
+
+// 按最大宽度收缩 cwd 和 git 片段，fixed_width 为其余部分（提示符号、分隔符）的宽度
+// 优先截断 cwd，其次截断 git 分支
+pub fn fit_segments(
+    cwd: &str,
+    git: Option<&str>,
+    fixed_width: usize,
+    max_width: usize,
+) -> (String, Option<String>) {
+    let git_width = git.map(display_width).unwrap_or(0);
+    let total = display_width(cwd) + git_width + fixed_width;
+    if total <= max_width {
+        return (cwd.to_string(), git.map(|g| g.to_string()));
+    }
+
+    let mut overflow = total - max_width;
+    let cwd_width = display_width(cwd);
+    let cwd_target = cwd_width
+        .saturating_sub(overflow)
+        .max(MIN_SEGMENT_WIDTH.min(cwd_width));
+    let cwd = truncate_left(cwd, cwd_target);
+    overflow = overflow.saturating_sub(cwd_width - display_width(&cwd));
+
+    let git = git.map(|g| {
+        if overflow == 0 {
+            return g.to_string();
+        }
+        let target = git_width
+            .saturating_sub(overflow)
+            .max(MIN_SEGMENT_WIDTH.min(git_width));
+        truncate_right(g, target)
+    });
+    (cwd, git)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("雑魚～> "), 8);
+        assert_eq!(display_width("\x1b[1;35m雑魚\x1b[0m"), 4);
+        assert_eq!(display_width("❤\u{fe0f} 👨\u{200d}👩\u{200d}👧 ⚡"), 8);
+        assert_eq!(
+            display_width("\x1b]0;title\x07ざこ\x1b]8;;https://x\x1b\\ok"),
+            6
+        );
+        assert_eq!(display_width("ざこ\n> "), 6);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[38;2;255;0;0m赤\x1b[0m"), "赤");
+        assert_eq!(strip_ansi("\x1b]2;title\x1b\\a\x1b7b"), "ab");
+        assert_eq!(strip_ansi("\x1b]8;;url\x07link\x1b]8;;\x07"), "link");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate_left("~/a/b/c/d", 5), "…/c/d");
+        assert_eq!(truncate_right("feature-branch", 6), "featu…");
+        assert_eq!(truncate_left("~/雑魚/ざこ", 5), "…ざこ");
+        assert_eq!(truncate_left("short", 10), "short");
+    }
+
+    #[test]
+    fn test_command_prompt() {
+        let jobs = JobCounts {
+            running: 1,
+            stopped: 1,
+        };
+        let duration = Duration::from_millis(1500);
+        let timeout = Duration::from_secs(5);
+        let prompt = command_prompt(
+            "echo \"$? $ZAKO_STATUS $ZAKO_DURATION $ZAKO_JOBS> \"",
+            timeout,
+            3,
+            duration,
+            jobs,
+        );
+        assert_eq!(prompt.as_deref(), Some("3 3 1500 2> "));
+        assert_eq!(command_prompt("exit 1", timeout, 0, duration, jobs), None);
+
+        // 超时后不再等待，后台的子进程也一起结束
+        let started = std::time::Instant::now();
+        let timeout = Duration::from_millis(100);
+        assert_eq!(
+            command_prompt("sleep 10 & wait", timeout, 0, duration, jobs),
+            None
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_fit_segments() {
+        let (cwd, git) = fit_segments("~/projects/zakosh/src", Some("main"), 10, 100);
+        assert_eq!(cwd, "~/projects/zakosh/src");
+        assert_eq!(git.as_deref(), Some("main"));
+
+        let (cwd, git) = fit_segments("~/projects/zakosh/src", Some("main"), 10, 20);
+        assert_eq!(display_width(&cwd), 6);
+        assert_eq!(git.as_deref(), Some("main"));
+
+        let (cwd, git) = fit_segments("~/projects/zakosh", Some("feature-branch"), 4, 16);
+        assert_eq!(cwd, "…akosh");
+        assert_eq!(git.as_deref(), Some("featu…"));
+    }
+}
@@ -0,0 +1,951 @@
+// 提示符片段：主题文件的 [prompt] segments 按名字组合片段，例如
+//   segments = ["status", "root", "ssh", "cwd", "git", "symbol"]
+// cwd、git 和 symbol 由主题自己渲染（需要按终端宽度截断），其余的名字在 SegmentRegistry 中查找，
+// 新的片段实现 PromptSegment 并注册即可，不用修改提示符的渲染代码
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::CStr;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::path;
+use super::style::{ColorDepth, Style};
+
+// 渲染片段时可用的信息，内置的片段不一定都用到
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub struct SegmentContext<'a> {
+    // 当前目录，家目录缩写为 ~
+    pub cwd: &'a str,
+    pub last_status: i32,
+    // 上一条命令的耗时
+    pub duration: Duration,
+    pub jobs: JobCounts,
+    pub vars: &'a HashMap<String, String>,
+    pub styles: &'a Styles,
+    // 片段名后面的参数，例如 time:%H:%M 中的 %H:%M
+    pub arg: Option<&'a str>,
+}
+
+// 运行中和已停止的作业数量
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JobCounts {
+    pub running: usize,
+    pub stopped: usize,
+}
+
+impl SegmentContext<'_> {
+    // shell 变量优先，其次是环境变量，空值视为没有设置
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.vars
+            .get(name)
+            .cloned()
+            .or_else(|| env::var(name).ok())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+// 片段输出的缓存方式
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CachePolicy {
+    // 每次绘制提示符都重新渲染
+    Never,
+    // 当前目录没有变化时使用上一次的结果
+    PerDirectory,
+    // 在给定的时间内使用上一次的结果
+    Ttl(Duration),
+    // 这些文件的修改时间都没有变化时使用上一次的结果
+    Files(Vec<PathBuf>),
+}
+
+pub trait PromptSegment {
+    fn id(&self) -> &str;
+
+    // 返回带样式的文本，None 表示不显示这个片段
+    fn render(&self, ctx: &SegmentContext) -> Option<String>;
+
+    fn cache_policy(&self, _ctx: &SegmentContext) -> CachePolicy {
+        CachePolicy::Never
+    }
+}
+
+// 主题中的样式，片段按名字取用
+pub struct Styles {
+    styles: HashMap<String, Style>,
+    depth: ColorDepth,
+}
+
+impl Styles {
+    pub fn new(styles: HashMap<String, Style>, depth: ColorDepth) -> Self {
+        Self { styles, depth }
+    }
+
+    // 没有这个样式时原样返回
+    pub fn paint(&self, name: &str, text: &str) -> String {
+        match self.styles.get(name) {
+            Some(style) => style.paint(text, self.depth),
+            None => text.to_string(),
+        }
+    }
+
+    pub fn get_fn(&self, name: &str) -> Box<dyn Fn(String) -> String> {
+        self.styles
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+            .into_fn(self.depth)
+    }
+}
+
+struct Cached {
+    cwd: String,
+    time: Instant,
+    // Files 策略下的文件和修改时间，文件不存在时为 None
+    mtimes: Vec<(PathBuf, Option<SystemTime>)>,
+    output: Option<String>,
+}
+
+fn mtimes(policy: &CachePolicy) -> Vec<(PathBuf, Option<SystemTime>)> {
+    match policy {
+        CachePolicy::Files(files) => files
+            .iter()
+            .map(|file| {
+                let mtime = fs::metadata(file).and_then(|meta| meta.modified()).ok();
+                (file.clone(), mtime)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub struct SegmentRegistry {
+    segments: Vec<Box<dyn PromptSegment>>,
+    cache: RefCell<HashMap<String, Cached>>,
+}
+
+impl SegmentRegistry {
+    // 包含内置的片段
+    pub fn new() -> Self {
+        let mut registry = Self {
+            segments: Vec::new(),
+            cache: RefCell::new(HashMap::new()),
+        };
+        registry.register(Box::new(StatusSegment));
+        registry.register(Box::new(RootSegment));
+        registry.register(Box::new(SshSegment));
+        registry.register(Box::new(KubeSegment));
+        registry.register(Box::new(AwsSegment));
+        registry.register(Box::new(PythonSegment));
+        registry.register(Box::new(NodeSegment));
+        registry.register(Box::new(TimeSegment));
+        registry.register(Box::new(BatterySegment));
+        registry.register(Box::new(JobsSegment));
+        registry.register(Box::new(UserSegment));
+        registry.register(Box::new(HostSegment));
+        registry.register(Box::new(DirSegment));
+        registry
+    }
+
+    // 同名的片段替换已有的
+    pub fn register(&mut self, segment: Box<dyn PromptSegment>) {
+        let id = segment.id().to_string();
+        self.segments.retain(|s| s.id() != id);
+        self.cache
+            .borrow_mut()
+            .retain(|spec, _| split_spec(spec).0 != id);
+        self.segments.push(segment);
+    }
+
+    // spec 为片段名，或者片段名:参数
+    pub fn contains(&self, spec: &str) -> bool {
+        let (id, _) = split_spec(spec);
+        self.segments.iter().any(|segment| segment.id() == id)
+    }
+
+    // 缓存按 spec 区分，参数不同的同一个片段分别缓存
+    pub fn render(&self, spec: &str, ctx: &SegmentContext) -> Option<String> {
+        let (id, arg) = split_spec(spec);
+        let segment = self.segments.iter().find(|segment| segment.id() == id)?;
+        let ctx = &SegmentContext { arg, ..*ctx };
+        let policy = segment.cache_policy(ctx);
+        let mtimes = mtimes(&policy);
+        let mut cache = self.cache.borrow_mut();
+        if let Some(cached) = cache.get(spec) {
+            let fresh = match &policy {
+                CachePolicy::Never => false,
+                CachePolicy::PerDirectory => cached.cwd == ctx.cwd,
+                CachePolicy::Ttl(ttl) => cached.time.elapsed() < *ttl,
+                CachePolicy::Files(_) => cached.mtimes == mtimes,
+            };
+            if fresh {
+                return cached.output.clone();
+            }
+        }
+        let output = segment.render(ctx);
+        if policy != CachePolicy::Never {
+            cache.insert(
+                spec.to_string(),
+                Cached {
+                    cwd: ctx.cwd.to_string(),
+                    time: Instant::now(),
+                    mtimes,
+                    output: output.clone(),
+                },
+            );
+        }
+        output
+    }
+}
+
+fn split_spec(spec: &str) -> (&str, Option<&str>) {
+    match spec.split_once(':') {
+        Some((id, arg)) => (id, Some(arg)),
+        None => (spec, None),
+    }
+}
+
+// 右侧提示符等使用的格式字符串，{name} 或 {name:arg} 为片段，其余为原样输出的文字
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPart {
+    Text(String),
+    Segment(String),
+}
+
+pub fn parse_format(text: &str) -> Result<Vec<FormatPart>, String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(FormatPart::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("占位符没有结束: {}", &rest[start..]))?;
+        let spec = rest[start + 1..start + end].trim();
+        if spec.is_empty() {
+            return Err("占位符缺少片段名".to_string());
+        }
+        parts.push(FormatPart::Segment(spec.to_string()));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(FormatPart::Text(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+// 上一条命令失败时显示退出码
+struct StatusSegment;
+
+impl PromptSegment for StatusSegment {
+    fn id(&self) -> &str {
+        "status"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        (ctx.last_status != 0).then(|| {
+            ctx.styles
+                .paint("status", &format!("[{}]", ctx.last_status))
+        })
+    }
+}
+
+// 以 root 身份运行时显示醒目的 ⚡root
+struct RootSegment;
+
+impl PromptSegment for RootSegment {
+    fn id(&self) -> &str {
+        "root"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let euid = unsafe { libc::geteuid() };
+        (euid == 0).then(|| ctx.styles.paint("root", "⚡root"))
+    }
+}
+
+// 通过 SSH 登录时显示 user@host，本地会话不显示
+struct SshSegment;
+
+fn user_name(ctx: &SegmentContext) -> Option<String> {
+    if let Some(user) = ctx.var("USER") {
+        return Some(user);
+    }
+    unsafe {
+        let pw = libc::getpwuid(libc::geteuid());
+        if pw.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr((*pw).pw_name).to_string_lossy().into_owned())
+    }
+}
+
+// 短主机名，去掉域名部分
+fn host_name() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        return None;
+    }
+    let host = CStr::from_bytes_until_nul(&buf).ok()?.to_string_lossy();
+    host.split('.').next().map(|host| host.to_string())
+}
+
+impl PromptSegment for SshSegment {
+    fn id(&self) -> &str {
+        "ssh"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+            .iter()
+            .find_map(|name| ctx.var(name))?;
+        let text = format!("{}@{}", user_name(ctx)?, host_name()?);
+        Some(ctx.styles.paint("ssh", &text))
+    }
+
+    // 用户名和主机名很少变化
+    fn cache_policy(&self, _ctx: &SegmentContext) -> CachePolicy {
+        CachePolicy::Ttl(Duration::from_secs(60))
+    }
+}
+
+// 用户名、主机名和当前目录的最后一级，对应 zsh 的 %n、%m 和 %c，导入的 zsh 主题会用到
+struct UserSegment;
+
+impl PromptSegment for UserSegment {
+    fn id(&self) -> &str {
+        "user"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        Some(ctx.styles.paint("user", &user_name(ctx)?))
+    }
+}
+
+struct HostSegment;
+
+impl PromptSegment for HostSegment {
+    fn id(&self) -> &str {
+        "host"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        Some(ctx.styles.paint("host", &host_name()?))
+    }
+
+    fn cache_policy(&self, _ctx: &SegmentContext) -> CachePolicy {
+        CachePolicy::Ttl(Duration::from_secs(60))
+    }
+}
+
+struct DirSegment;
+
+impl PromptSegment for DirSegment {
+    fn id(&self) -> &str {
+        "dir"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let dir = match ctx.cwd.rsplit_once('/') {
+            Some((_, "")) => "/",
+            Some((_, name)) => name,
+            None => ctx.cwd,
+        };
+        Some(ctx.styles.paint("dir", dir))
+    }
+}
+
+// 后台作业：jobs:(1 running, 2 stopped)，数量为 0 的部分不显示，都没有时整个片段不显示
+// 有停止的作业时使用 jobs_stopped 样式，提醒还有被 Ctrl-Z 挂起的编辑器等
+struct JobsSegment;
+
+impl PromptSegment for JobsSegment {
+    fn id(&self) -> &str {
+        "jobs"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let JobCounts { running, stopped } = ctx.jobs;
+        let parts: Vec<String> = [(running, "running"), (stopped, "stopped")]
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, state)| format!("{} {}", count, state))
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        let style = if stopped > 0 { "jobs_stopped" } else { "jobs" };
+        Some(
+            ctx.styles
+                .paint(style, &format!("jobs:({})", parts.join(", "))),
+        )
+    }
+}
+
+// 当前时间，参数为 strftime 格式，默认为 %H:%M
+struct TimeSegment;
+
+impl PromptSegment for TimeSegment {
+    fn id(&self) -> &str {
+        "time"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let format = ctx.arg.unwrap_or("%H:%M");
+        let mut text = String::new();
+        // 无效的格式会让 chrono 返回错误，这时不显示
+        write!(text, "{}", chrono::Local::now().format(format)).ok()?;
+        Some(ctx.styles.paint("time", &text))
+    }
+}
+
+// 电池电量：Linux 读取 /sys/class/power_supply，macOS 读取 pmset -g batt 的输出
+// 充电时显示 ⚡，电量低于 20% 且没有充电时使用 battery_low 样式，没有电池时不显示
+struct BatterySegment;
+
+struct Battery {
+    percent: u32,
+    charging: bool,
+}
+
+fn read_battery() -> Option<Battery> {
+    if let Ok(entries) = fs::read_dir("/sys/class/power_supply") {
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let kind = fs::read_to_string(dir.join("type")).unwrap_or_default();
+            if kind.trim() != "Battery" {
+                continue;
+            }
+            let Some(percent) = fs::read_to_string(dir.join("capacity"))
+                .ok()
+                .and_then(|capacity| capacity.trim().parse().ok())
+            else {
+                continue;
+            };
+            let status = fs::read_to_string(dir.join("status")).unwrap_or_default();
+            return Some(Battery {
+                percent,
+                charging: matches!(status.trim(), "Charging" | "Full"),
+            });
+        }
+        return None;
+    }
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()?;
+    parse_pmset(&String::from_utf8_lossy(&output.stdout))
+}
+
+// -InternalBattery-0 (id=1234)	85%; charging; 1:02 remaining present: true
+fn parse_pmset(output: &str) -> Option<Battery> {
+    let line = output
+        .lines()
+        .find(|line| line.contains("InternalBattery"))?;
+    let (before, after) = line.split_once('%')?;
+    let percent = before
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()?;
+    let state = after.split(';').nth(1).unwrap_or_default().trim();
+    Some(Battery {
+        percent,
+        charging: matches!(state, "charging" | "charged" | "finishing charge"),
+    })
+}
+
+impl PromptSegment for BatterySegment {
+    fn id(&self) -> &str {
+        "battery"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let battery = read_battery()?;
+        let (symbol, style) = match battery {
+            Battery { charging: true, .. } => ("⚡", "battery"),
+            Battery { percent, .. } if percent < 20 => ("🪫", "battery_low"),
+            _ => ("🔋", "battery"),
+        };
+        Some(
+            ctx.styles
+                .paint(style, &format!("{}{}%", symbol, battery.percent)),
+        )
+    }
+
+    // 电量变化很慢，macOS 上还需要启动 pmset，半分钟内使用上一次的结果
+    fn cache_policy(&self, _ctx: &SegmentContext) -> CachePolicy {
+        CachePolicy::Ttl(Duration::from_secs(30))
+    }
+}
+
+// kubectl 当前的 context 和 namespace：k8s:(context/namespace)
+// 读取 $KUBECONFIG（冒号分隔的多个文件）或 ~/.kube/config，文件没有变化时使用缓存
+struct KubeSegment;
+
+impl KubeSegment {
+    fn config_files(ctx: &SegmentContext) -> Vec<PathBuf> {
+        match ctx.var("KUBECONFIG") {
+            Some(files) => files
+                .split(':')
+                .filter(|file| !file.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+            None => ctx
+                .var("HOME")
+                .map(|home| vec![PathBuf::from(home).join(".kube/config")])
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl PromptSegment for KubeSegment {
+    fn id(&self) -> &str {
+        "kube"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let configs: Vec<String> = Self::config_files(ctx)
+            .iter()
+            .filter_map(|file| fs::read_to_string(file).ok())
+            .collect();
+        // 与 kubectl 相同，取第一个设置了 current-context 的文件
+        let context = configs
+            .iter()
+            .find_map(|config| kube_current_context(config))?;
+        let namespace = configs
+            .iter()
+            .find_map(|config| kube_namespace(config, &context))
+            .unwrap_or_else(|| "default".to_string());
+        Some(
+            ctx.styles
+                .paint("kube", &format!("k8s:({}/{})", context, namespace)),
+        )
+    }
+
+    fn cache_policy(&self, ctx: &SegmentContext) -> CachePolicy {
+        CachePolicy::Files(Self::config_files(ctx))
+    }
+}
+
+// 去掉 YAML 值两边的空白和引号
+fn yaml_value(value: &str) -> String {
+    value
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_string()
+}
+
+fn kube_current_context(config: &str) -> Option<String> {
+    config
+        .lines()
+        .find_map(|line| line.strip_prefix("current-context:"))
+        .map(yaml_value)
+        .filter(|context| !context.is_empty())
+}
+
+// 在顶层的 contexts 列表中找到名为 context 的项，返回它的 namespace
+fn kube_namespace(config: &str, context: &str) -> Option<String> {
+    let mut in_contexts = false;
+    let mut name = None;
+    let mut namespace = None;
+    for line in config.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // 顶层的键，"- " 开头的列表项可以不缩进
+        if !line.starts_with(' ') && !line.starts_with('-') {
+            in_contexts = line.trim_end() == "contexts:";
+            continue;
+        }
+        if !in_contexts {
+            continue;
+        }
+        // 新的列表项开始前检查上一项
+        if let Some(rest) = trimmed.strip_prefix("- ") {
+            if name.as_deref() == Some(context) {
+                return namespace;
+            }
+            name = None;
+            namespace = None;
+            if let Some(value) = rest.strip_prefix("name:") {
+                name = Some(yaml_value(value));
+            }
+            continue;
+        }
+        if let Some(value) = trimmed.strip_prefix("name:") {
+            name = Some(yaml_value(value));
+        } else if let Some(value) = trimmed.strip_prefix("namespace:") {
+            namespace = Some(yaml_value(value));
+        }
+    }
+    if name.as_deref() == Some(context) {
+        namespace
+    } else {
+        None
+    }
+}
+
+// 当前的 AWS profile 和 region：aws:(profile@region)
+struct AwsSegment;
+
+impl PromptSegment for AwsSegment {
+    fn id(&self) -> &str {
+        "aws"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let profile = ctx.var("AWS_PROFILE")?;
+        let text = match ctx
+            .var("AWS_REGION")
+            .or_else(|| ctx.var("AWS_DEFAULT_REGION"))
+        {
+            Some(region) => format!("aws:({}@{})", profile, region),
+            None => format!("aws:({})", profile),
+        };
+        Some(ctx.styles.paint("aws", &text))
+    }
+}
+
+// 激活的 Python 虚拟环境，没有激活时显示 .python-version 指定的版本：py:(name)
+struct PythonSegment;
+
+// 虚拟环境目录叫 .venv、venv 等通用名字时显示项目目录的名字
+fn venv_name(venv: &str) -> String {
+    let venv = Path::new(venv);
+    let name = venv
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match (
+        name.as_str(),
+        venv.parent().and_then(|parent| parent.file_name()),
+    ) {
+        (".venv" | "venv" | ".env" | "env", Some(project)) => project.to_string_lossy().to_string(),
+        _ => name,
+    }
+}
+
+// 版本文件的第一行
+fn read_version(start: &Path, names: &[&str]) -> Option<String> {
+    let file = path::find_upwards(start, names)?;
+    let content = fs::read_to_string(file).ok()?;
+    content
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+}
+
+impl PromptSegment for PythonSegment {
+    fn id(&self) -> &str {
+        "python"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let name = match ctx.var("VIRTUAL_ENV") {
+            Some(venv) => venv_name(&venv),
+            None => read_version(&env::current_dir().ok()?, &[".python-version"])?,
+        };
+        Some(ctx.styles.paint("python", &format!("py:({})", name)))
+    }
+}
+
+// .nvmrc 或 .node-version 指定的 node 版本：node:(version)
+struct NodeSegment;
+
+impl PromptSegment for NodeSegment {
+    fn id(&self) -> &str {
+        "node"
+    }
+
+    fn render(&self, ctx: &SegmentContext) -> Option<String> {
+        let version = read_version(&env::current_dir().ok()?, &[".nvmrc", ".node-version"])?;
+        Some(ctx.styles.paint("node", &format!("node:({})", version)))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Counter {
+        id: &'static str,
+        policy: CachePolicy,
+        renders: Rc<Cell<usize>>,
+    }
+
+    impl PromptSegment for Counter {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn render(&self, ctx: &SegmentContext) -> Option<String> {
+            self.renders.set(self.renders.get() + 1);
+            Some(format!("{}:{}", ctx.cwd, self.renders.get()))
+        }
+
+        fn cache_policy(&self, _ctx: &SegmentContext) -> CachePolicy {
+            self.policy.clone()
+        }
+    }
+
+    fn context<'a>(
+        cwd: &'a str,
+        status: i32,
+        vars: &'a HashMap<String, String>,
+        styles: &'a Styles,
+    ) -> SegmentContext<'a> {
+        SegmentContext {
+            cwd,
+            last_status: status,
+            duration: Duration::ZERO,
+            jobs: JobCounts::default(),
+            vars,
+            styles,
+            arg: None,
+        }
+    }
+
+    #[test]
+    fn test_segment_cache_policy() {
+        let vars = HashMap::new();
+        let styles = Styles::new(HashMap::new(), ColorDepth::None);
+        let mut registry = SegmentRegistry::new();
+        let renders = Rc::new(Cell::new(0));
+        registry.register(Box::new(Counter {
+            id: "dir",
+            policy: CachePolicy::PerDirectory,
+            renders: renders.clone(),
+        }));
+
+        let ctx = context("~/a", 0, &vars, &styles);
+        assert_eq!(registry.render("dir", &ctx).as_deref(), Some("~/a:1"));
+        assert_eq!(registry.render("dir", &ctx).as_deref(), Some("~/a:1"));
+        let ctx = context("~/b", 0, &vars, &styles);
+        assert_eq!(registry.render("dir", &ctx).as_deref(), Some("~/b:2"));
+
+        // 重新注册后丢弃旧的缓存
+        registry.register(Box::new(Counter {
+            id: "dir",
+            policy: CachePolicy::Never,
+            renders: renders.clone(),
+        }));
+        assert_eq!(registry.render("dir", &ctx).as_deref(), Some("~/b:3"));
+        assert_eq!(registry.render("dir", &ctx).as_deref(), Some("~/b:4"));
+        assert_eq!(registry.render("missing", &ctx), None);
+    }
+
+    #[test]
+    fn test_status_segment() {
+        let vars = HashMap::new();
+        let styles = Styles::new(HashMap::new(), ColorDepth::None);
+        let registry = SegmentRegistry::new();
+        assert_eq!(
+            registry.render("status", &context("~", 0, &vars, &styles)),
+            None
+        );
+        assert_eq!(
+            registry
+                .render("status", &context("~", 127, &vars, &styles))
+                .as_deref(),
+            Some("[127]")
+        );
+    }
+
+    const KUBECONFIG: &str = r#"
+apiVersion: v1
+clusters:
+- cluster:
+    server: https://example.com
+  name: prod
+contexts:
+- context:
+    cluster: staging
+    user: admin
+  name: staging
+- name: "prod-admin"
+  context:
+    cluster: prod
+    namespace: payments
+    user: admin
+current-context: prod-admin
+users:
+- name: admin
+  user:
+    namespace: wrong
+"#;
+
+    #[test]
+    fn test_kube_context() {
+        assert_eq!(
+            kube_current_context(KUBECONFIG).as_deref(),
+            Some("prod-admin")
+        );
+        assert_eq!(
+            kube_namespace(KUBECONFIG, "prod-admin").as_deref(),
+            Some("payments")
+        );
+        assert_eq!(kube_namespace(KUBECONFIG, "staging"), None);
+        assert_eq!(kube_namespace(KUBECONFIG, "admin"), None);
+        assert_eq!(kube_current_context("current-context: \"\"\n"), None);
+    }
+
+    #[test]
+    fn test_kube_and_aws_segments() {
+        let dir = env::temp_dir().join(format!("zako_kube_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config");
+        fs::write(&config, KUBECONFIG).unwrap();
+
+        let mut vars = HashMap::new();
+        vars.insert("KUBECONFIG".to_string(), config.display().to_string());
+        vars.insert("AWS_PROFILE".to_string(), "dev".to_string());
+        vars.insert("AWS_REGION".to_string(), "eu-west-1".to_string());
+        let styles = Styles::new(HashMap::new(), ColorDepth::None);
+        let registry = SegmentRegistry::new();
+        let ctx = context("~", 0, &vars, &styles);
+        assert_eq!(
+            registry.render("kube", &ctx).as_deref(),
+            Some("k8s:(prod-admin/payments)")
+        );
+        assert_eq!(
+            registry.render("aws", &ctx).as_deref(),
+            Some("aws:(dev@eu-west-1)")
+        );
+
+        // 文件修改后重新读取
+        fs::write(&config, "current-context: staging\n").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::open(&config)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            registry.render("kube", &ctx).as_deref(),
+            Some("k8s:(staging/default)")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ssh_segment() {
+        let mut vars = HashMap::new();
+        vars.insert("USER".to_string(), "zako".to_string());
+        let styles = Styles::new(HashMap::new(), ColorDepth::None);
+        let registry = SegmentRegistry::new();
+        let ssh = |vars: &HashMap<String, String>| {
+            let ctx = context("~", 0, vars, &styles);
+            SshSegment.render(&ctx)
+        };
+        if env::var_os("SSH_CONNECTION").is_none()
+            && env::var_os("SSH_CLIENT").is_none()
+            && env::var_os("SSH_TTY").is_none()
+        {
+            assert_eq!(ssh(&vars), None);
+        }
+        vars.insert(
+            "SSH_CONNECTION".to_string(),
+            "10.0.0.1 22 10.0.0.2 22".to_string(),
+        );
+        let host = host_name().unwrap();
+        assert_eq!(ssh(&vars), Some(format!("zako@{}", host)));
+        assert!(!host.contains('.'));
+        assert!(registry.contains("root"));
+
+        let dir = |cwd: &str| DirSegment.render(&context(cwd, 0, &vars, &styles));
+        assert_eq!(dir("~/src/zakosh"), Some("zakosh".to_string()));
+        assert_eq!(dir("~"), Some("~".to_string()));
+        assert_eq!(dir("/"), Some("/".to_string()));
+        assert_eq!(
+            registry.render("user", &context("~", 0, &vars, &styles)),
+            Some("zako".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_and_arguments() {
+        assert_eq!(
+            parse_format("{battery} | {time:%H:%M}"),
+            Ok(vec![
+                FormatPart::Segment("battery".to_string()),
+                FormatPart::Text(" | ".to_string()),
+                FormatPart::Segment("time:%H:%M".to_string()),
+            ])
+        );
+        assert!(parse_format("{time").is_err());
+        assert!(parse_format("{}").is_err());
+
+        let vars = HashMap::new();
+        let styles = Styles::new(HashMap::new(), ColorDepth::None);
+        let registry = SegmentRegistry::new();
+        let ctx = context("~", 0, &vars, &styles);
+        assert!(registry.contains("time:%Y"));
+        let year = registry.render("time:%Y", &ctx).unwrap();
+        assert_eq!(year, chrono::Local::now().format("%Y").to_string());
+        assert_eq!(registry.render("time", &ctx).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_parse_pmset() {
+        let output = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=4653155)\t15%; discharging; 0:42 remaining present: true\n";
+        let battery = parse_pmset(output).unwrap();
+        assert_eq!((battery.percent, battery.charging), (15, false));
+        let output = " -InternalBattery-0 (id=1)\t100%; charged; 0:00 remaining present: true";
+        let battery = parse_pmset(output).unwrap();
+        assert_eq!((battery.percent, battery.charging), (100, true));
+        assert!(parse_pmset("Now drawing from 'AC Power'").is_none());
+    }
+
+    #[test]
+    fn test_jobs_segment() {
+        let vars = HashMap::new();
+        let styles = Styles::new(HashMap::new(), ColorDepth::None);
+        let registry = SegmentRegistry::new();
+        let mut ctx = context("~", 0, &vars, &styles);
+        assert_eq!(registry.render("jobs", &ctx), None);
+        ctx.jobs = JobCounts {
+            running: 0,
+            stopped: 1,
+        };
+        assert_eq!(
+            registry.render("jobs", &ctx).as_deref(),
+            Some("jobs:(1 stopped)")
+        );
+        ctx.jobs.running = 2;
+        assert_eq!(
+            registry.render("jobs", &ctx).as_deref(),
+            Some("jobs:(2 running, 1 stopped)")
+        );
+    }
+
+    #[test]
+    fn test_venv_name() {
+        assert_eq!(venv_name("/home/zako/projects/api/.venv"), "api");
+        assert_eq!(venv_name("/home/zako/.virtualenvs/tools"), "tools");
+    }
+
+    #[test]
+    fn test_read_version() {
+        let dir = env::temp_dir().join(format!("zako_nvmrc_{}", std::process::id()));
+        let nested = dir.join("src/lib");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.join(".nvmrc"), "v18\n# comment\n").unwrap();
+        assert_eq!(
+            read_version(&nested, &[".nvmrc", ".node-version"]).as_deref(),
+            Some("v18")
+        );
+        assert_eq!(read_version(&nested, &[".python-version"]), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
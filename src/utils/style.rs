@@ -0,0 +1,282 @@
+// 主题样式描述：`fg=#ff79c6 bg=black bold underline`
+// fg/bg 可以是颜色名（red、bright_blue 等）、0-255 的调色板编号或 #rrggbb，
+// 其余的词为文字属性。终端不支持真彩色时自动降级为 256 色或 16 色
+use std::env;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    // 16 色中的编号，8-15 为亮色
+    Ansi(u8),
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+// 终端支持的颜色数量
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum ColorDepth {
+    None,
+    Ansi16,
+    Indexed256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    // NO_COLOR/CLICOLOR 与 colored 的判断一致，COLORTERM 和 TERM 决定支持的颜色数量
+    pub fn detect() -> Self {
+        if !colored::control::SHOULD_COLORIZE.should_colorize() {
+            return ColorDepth::None;
+        }
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term == "dumb" {
+            ColorDepth::None
+        } else if term.contains("256color") {
+            ColorDepth::Indexed256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+// 16 色在常见终端配色下的近似值，用于把真彩色降级为 16 色
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+impl Color {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        if let Some(hex) = spec.strip_prefix('#') {
+            let value = (hex.len() == 6)
+                .then(|| u32::from_str_radix(hex, 16).ok())
+                .flatten()
+                .ok_or_else(|| format!("无效的颜色: {}，应为 #rrggbb", spec))?;
+            return Ok(Color::Rgb(
+                (value >> 16) as u8,
+                (value >> 8) as u8,
+                value as u8,
+            ));
+        }
+        if let Ok(index) = spec.parse::<u8>() {
+            return Ok(Color::Indexed(index));
+        }
+        let lower = spec.to_lowercase();
+        let (bright, name) = match lower.strip_prefix("bright_") {
+            Some(name) => (true, name),
+            None => (false, lower.as_str()),
+        };
+        // purple 与 colored 中的叫法相同
+        let name = if name == "purple" { "magenta" } else { name };
+        COLOR_NAMES
+            .iter()
+            .position(|n| *n == name)
+            .map(|index| Color::Ansi(index as u8 + if bright { 8 } else { 0 }))
+            .ok_or_else(|| format!("未知的颜色: {}", spec))
+    }
+
+    // 按终端支持的颜色数量降级
+    fn downgrade(self, depth: ColorDepth) -> Self {
+        match (self, depth) {
+            (Color::Rgb(r, g, b), ColorDepth::Indexed256) => Color::Indexed(rgb_to_256(r, g, b)),
+            (Color::Rgb(r, g, b), ColorDepth::Ansi16) => Color::Ansi(rgb_to_16(r, g, b)),
+            (Color::Indexed(index), ColorDepth::Ansi16) => {
+                let (r, g, b) = indexed_to_rgb(index);
+                Color::Ansi(rgb_to_16(r, g, b))
+            }
+            (color, _) => color,
+        }
+    }
+
+    // SGR 参数，background 为背景色
+    fn sgr(self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+        match self {
+            Color::Ansi(index) if index < 8 => format!("{}", base + index as u32),
+            Color::Ansi(index) => format!("{}", base + 60 + (index - 8) as u32),
+            Color::Indexed(index) => format!("{};5;{}", base + 8, index),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+// 6x6x6 颜色立方体或 24 级灰阶中最接近的颜色
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            248..=255 => 231,
+            _ => 232 + ((r as u16 - 8) * 24 / 247) as u8,
+        };
+    }
+    let level = |v: u8| {
+        if v < 48 {
+            0
+        } else {
+            ((v as u16 - 35) / 40) as u8
+        }
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn rgb_to_16(r: u8, g: u8, b: u8) -> u8 {
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, cr) + d(g, cg) + d(b, cb)
+    };
+    (0..16u8)
+        .min_by_key(|&index| distance(ANSI_RGB[index as usize]))
+        .unwrap_or(7)
+}
+
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_RGB[index as usize],
+        16..=231 => {
+            let value = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let i = index - 16;
+            (value(i / 36), value(i / 6 % 6), value(i % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+// 文字属性与对应的 SGR 参数
+const ATTRIBUTES: [(&str, u8); 7] = [
+    ("bold", 1),
+    ("dim", 2),
+    ("italic", 3),
+    ("underline", 4),
+    ("blink", 5),
+    ("reverse", 7),
+    ("strikethrough", 9),
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    // ATTRIBUTES 中的 SGR 参数
+    pub attributes: Vec<u8>,
+}
+
+impl Style {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut style = Style::default();
+        for word in spec.split_whitespace() {
+            if let Some(color) = word.strip_prefix("fg=") {
+                style.fg = Some(Color::parse(color)?);
+            } else if let Some(color) = word.strip_prefix("bg=") {
+                style.bg = Some(Color::parse(color)?);
+            } else if let Some((_, code)) = ATTRIBUTES.iter().find(|(name, _)| *name == word) {
+                style.attributes.push(*code);
+            } else if word == "none" {
+                continue;
+            } else {
+                return Err(format!("未知的样式: {}", word));
+            }
+        }
+        Ok(style)
+    }
+
+    // 开启这个样式的转义序列，没有任何样式或终端不支持颜色时为 None
+    pub fn sgr(&self, depth: ColorDepth) -> Option<String> {
+        if depth == ColorDepth::None {
+            return None;
+        }
+        let mut params: Vec<String> = self.attributes.iter().map(|a| a.to_string()).collect();
+        if let Some(fg) = self.fg {
+            params.push(fg.downgrade(depth).sgr(false));
+        }
+        if let Some(bg) = self.bg {
+            params.push(bg.downgrade(depth).sgr(true));
+        }
+        if params.is_empty() {
+            return None;
+        }
+        Some(format!("\x1b[{}m", params.join(";")))
+    }
+
+    pub fn paint(&self, text: &str, depth: ColorDepth) -> String {
+        match self.sgr(depth) {
+            Some(sgr) if !text.is_empty() => {
+                let mut painted = sgr;
+                let _ = write!(painted, "{}\x1b[0m", text);
+                painted
+            }
+            _ => text.to_string(),
+        }
+    }
+
+    // 转换成主题使用的渲染函数
+    pub fn into_fn(self, depth: ColorDepth) -> Box<dyn Fn(String) -> String> {
+        Box::new(move |text| self.paint(&text, depth))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_style() {
+        let style = Style::parse("fg=#ff79c6 bg=black bold underline").unwrap();
+        assert_eq!(style.fg, Some(Color::Rgb(0xff, 0x79, 0xc6)));
+        assert_eq!(style.bg, Some(Color::Ansi(0)));
+        assert_eq!(style.attributes, vec![1, 4]);
+        assert_eq!(
+            Style::parse("fg=bright_blue").unwrap().fg,
+            Some(Color::Ansi(12))
+        );
+        assert_eq!(
+            Style::parse("fg=208").unwrap().fg,
+            Some(Color::Indexed(208))
+        );
+        assert!(Style::parse("fg=#12345").is_err());
+        assert!(Style::parse("fg=pink").is_err());
+        assert!(Style::parse("blinky").is_err());
+    }
+
+    #[test]
+    fn test_paint_and_downgrade() {
+        let style = Style::parse("fg=#ff79c6 bold").unwrap();
+        assert_eq!(
+            style.paint("hi", ColorDepth::TrueColor),
+            "\x1b[1;38;2;255;121;198mhi\x1b[0m"
+        );
+        assert_eq!(
+            style.paint("hi", ColorDepth::Indexed256),
+            "\x1b[1;38;5;212mhi\x1b[0m"
+        );
+        let red = Style::parse("fg=#ff5555").unwrap();
+        assert_eq!(red.paint("hi", ColorDepth::Ansi16), "\x1b[91mhi\x1b[0m");
+        assert_eq!(style.paint("hi", ColorDepth::None), "hi");
+        let style = Style::parse("fg=black bg=bright_magenta").unwrap();
+        assert_eq!(style.paint("x", ColorDepth::Ansi16), "\x1b[30;105mx\x1b[0m");
+        assert_eq!(rgb_to_256(128, 128, 128), 243);
+    }
+}
@@ -1,35 +1,604 @@
-use colored::Colorize;
+use log::error;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
 use std::{collections::HashMap, path::PathBuf};
 
-use super::config::Config;
+use super::config::{Config, MessageMode};
+use super::prompt;
+use super::segment::{self, FormatPart, JobCounts, SegmentContext, SegmentRegistry, Styles};
+use super::style::{ColorDepth, Style};
+use super::toml::{self, Document, Problem};
+use super::zsh_theme;
+
+// 内置的样式，主题文件 themes_dir/<theme>.toml 的 [styles] 段可以覆盖
+const DEFAULT_STYLES: [(&str, &str); 22] = [
+    ("prompt", "fg=bright_magenta bold"),
+    ("path", "fg=cyan bold"),
+    ("git", "fg=bright_blue"),
+    ("success", "fg=bright_magenta"),
+    ("warning", "fg=yellow"),
+    ("error", "fg=bright_red"),
+    ("menu", "fg=magenta"),
+    ("menu_selected", "fg=black bg=bright_magenta"),
+    ("unknown_command", "fg=red underline"),
+    ("alias_preview", "fg=bright_black"),
+    ("status", "fg=bright_red"),
+    ("root", "fg=bright_white bg=red bold"),
+    ("ssh", "fg=bright_yellow"),
+    ("kube", "fg=bright_cyan"),
+    ("aws", "fg=yellow"),
+    ("python", "fg=green"),
+    ("node", "fg=bright_green"),
+    ("time", "fg=bright_black"),
+    ("battery", "fg=green"),
+    ("battery_low", "fg=bright_red bold"),
+    ("jobs", "fg=bright_blue"),
+    ("jobs_stopped", "fg=yellow bold"),
+];
+
+// 由主题自己渲染的片段，其余的片段在 SegmentRegistry 中查找
+const CORE_SEGMENTS: [&str; 3] = ["cwd", "git", "symbol"];
+
+// 主题文件中可以使用的段和键，[styles] 和 [messages] 的键名在加载时检查
+const THEME_SCHEMA: [(&str, &[&str]); 4] = [
+    ("", &["inherit"]),
+    ("prompt", &["segments", "right"]),
+    ("styles", &[]),
+    ("messages", &[]),
+];
+
+// 继承链的最大长度，避免主题互相继承时无限加载
+const MAX_INHERIT_DEPTH: usize = 8;
+
+// 默认的提示符，root 和 ssh 只在以 root 运行或通过 SSH 登录时显示
+const DEFAULT_SEGMENTS: [&str; 5] = ["root", "ssh", "cwd", "git", "symbol"];
+// 无障碍模式的提示符，不含 emoji
+const ACCESSIBLE_SEGMENTS: [&str; 3] = ["cwd", "git", "symbol"];
+
+// 一个主题文件，主题文件开头的 inherit = "<theme>" 继承另一个主题，只需要写出要覆盖的设置
+struct ThemeFile {
+    path: PathBuf,
+    document: Document,
+}
 
 pub struct Theme {
     pub prompt_style: Box<dyn Fn(String) -> String>,
+    pub path_style: Box<dyn Fn(String) -> String>,
+    pub git_style: Box<dyn Fn(String) -> String>,
     pub success_style: Box<dyn Fn(String) -> String>,
     pub warning_style: Box<dyn Fn(String) -> String>,
     pub error_style: Box<dyn Fn(String) -> String>,
+    // 补全菜单的候选项和选中的候选项
+    pub menu_style: Box<dyn Fn(String) -> String>,
+    pub menu_selected_style: Box<dyn Fn(String) -> String>,
+    // 输入时找不到的命令名
+    pub unknown_command_style: Box<dyn Fn(String) -> String>,
+    // 行尾显示的别名展开预览
+    pub alias_preview_style: Box<dyn Fn(String) -> String>,
+    styles: Styles,
+    // 提示符由这些片段按顺序组成，主题文件的 [prompt] segments 可以覆盖
+    segments: Vec<String>,
+    // 从 zsh 主题导入的提示符，设置时代替 segments
+    left: Option<Vec<FormatPart>>,
+    // 右侧提示符，[prompt] right 的格式字符串，例如 "{battery} {time:%H:%M}"
+    right: Vec<FormatPart>,
+    // 配置中的 prompt_command 和 right_prompt_command，设置时代替主题的渲染，命令失败或超时时仍使用主题的提示符
+    command: Option<String>,
+    right_command: Option<String>,
+    command_timeout: Duration,
+    registry: SegmentRegistry,
     messages: HashMap<String, Vec<String>>,
+    message_mode: MessageMode,
+    rng: RefCell<StdRng>,
+    prompt_max_ratio: f32,
 }
 
 impl Theme {
-    pub fn new() -> Self {
-        Theme {
-            prompt_style: Box::new(|s| s.bright_purple().bold().to_string()),
-            success_style: Box::new(|s| s.bright_magenta().to_string()),
-            warning_style: Box::new(|s| s.yellow().to_string()),
-            error_style: Box::new(|s| s.bright_red().to_string()),
-            messages: Self::init_messages(),
+    // 主题文件中的问题报告后使用继承的或内置的设置
+    pub fn new(config: &Config) -> Self {
+        Self::with_local(config, None)
+    }
+
+    // 叠加项目目录下 .zako.toml 的 [prompt] 和 [styles]，它的其他段由 LocalConfig 处理
+    // local 为文件路径和通过信任检查的内容，不重新读取文件
+    pub fn with_local(config: &Config, local: Option<(&Path, &str)>) -> Self {
+        let (theme, problems) = Self::load(config, local);
+        for problem in problems {
+            error!("{}", problem);
         }
+        theme
+    }
+
+    // 加载当前主题，只返回发现的问题，用于 config check
+    pub fn check(config: &Config) -> Vec<Problem> {
+        Self::load(config, None).1
+    }
+
+    // 无障碍模式不读取主题文件，只使用内置的片段和纯文本消息
+    fn load(config: &Config, local: Option<(&Path, &str)>) -> (Self, Vec<Problem>) {
+        let registry = SegmentRegistry::new();
+        let mut problems = Vec::new();
+        let mut files = if config.accessible {
+            vec![ThemeFile {
+                path: config.themes_dir.join(format!("{}.toml", config.theme)),
+                document: Document::default(),
+            }]
+        } else {
+            Self::load_theme_files(config, &mut problems)
+        };
+        let depth = if config.accessible {
+            ColorDepth::None
+        } else {
+            ColorDepth::detect()
+        };
+        // 没有 <theme>.toml 时尝试导入同名的 zsh 主题
+        let imported = if config.accessible || files.iter().any(|file| file.path.exists()) {
+            None
+        } else {
+            Self::load_zsh_theme(&Self::get_theme_file(config), depth, &mut problems)
+        };
+        if let Some((path, text)) = local.filter(|_| !config.accessible) {
+            files.push(ThemeFile {
+                path: path.to_path_buf(),
+                document: toml::parse(text),
+            });
+        }
+        let prompt_file = |key: &str| {
+            let file = Self::prompt_file(&files, key);
+            (file.path.as_path(), &file.document)
+        };
+        let (left, right) = match imported {
+            Some(imported) => (Some(imported.left), imported.right),
+            None => {
+                let (path, document) = prompt_file("right");
+                (
+                    None,
+                    Self::load_right_prompt(path, document, &registry, &mut problems),
+                )
+            }
+        };
+        let (path, document) = prompt_file("segments");
+        let segments = if config.accessible {
+            ACCESSIBLE_SEGMENTS
+                .iter()
+                .map(|name| name.to_string())
+                .collect()
+        } else {
+            Self::load_segments(path, document, &registry, &mut problems)
+        };
+        let styles = Styles::new(Self::load_styles(&files, &registry, &mut problems), depth);
+        let messages = if config.accessible {
+            Self::accessible_messages()
+        } else {
+            Self::load_messages(&files, &mut problems)
+        };
+        let theme = Theme {
+            prompt_style: styles.get_fn("prompt"),
+            path_style: styles.get_fn("path"),
+            git_style: styles.get_fn("git"),
+            success_style: styles.get_fn("success"),
+            warning_style: styles.get_fn("warning"),
+            error_style: styles.get_fn("error"),
+            menu_style: styles.get_fn("menu"),
+            menu_selected_style: styles.get_fn("menu_selected"),
+            unknown_command_style: styles.get_fn("unknown_command"),
+            alias_preview_style: styles.get_fn("alias_preview"),
+            styles,
+            segments,
+            left,
+            right,
+            command: config.prompt_command.clone(),
+            right_command: config.right_prompt_command.clone(),
+            command_timeout: Duration::from_millis(config.prompt_command_timeout),
+            registry,
+            messages,
+            message_mode: config.message_mode,
+            rng: RefCell::new(match config.message_mode {
+                MessageMode::Seeded(seed) => StdRng::seed_from_u64(seed),
+                _ => StdRng::from_entropy(),
+            }),
+            prompt_max_ratio: config.prompt_max_ratio,
+        };
+        (theme, problems)
+    }
+
+    // 主题文件不存在时使用空的文档，即全部使用内置的设置
+    fn load_theme_file(path: &Path, problems: &mut Vec<Problem>) -> Document {
+        let Ok(text) = fs::read_to_string(path) else {
+            return Document::default();
+        };
+        let document = toml::parse(&text);
+        for (line, message) in &document.errors {
+            problems.push(Problem::new(path, *line, message.clone()));
+        }
+        problems.extend(toml::check_keys(path, &document, &THEME_SCHEMA));
+        document
+    }
+
+    // 当前主题和它继承的主题，继承的主题在前
+    // 继承 default 而没有 default.toml 时只使用内置的设置，找不到的主题和循环继承报告后停止继承
+    fn load_theme_files(config: &Config, problems: &mut Vec<Problem>) -> Vec<ThemeFile> {
+        let theme_path = |name: &str| config.themes_dir.join(format!("{}.toml", name));
+        let mut names = vec![config.theme.clone()];
+        let mut files = Vec::new();
+        let mut path = theme_path(&config.theme);
+        loop {
+            let document = Self::load_theme_file(&path, problems);
+            let inherit = Self::load_inherit(&path, &document, problems);
+            let child = path;
+            files.push(ThemeFile {
+                path: child.clone(),
+                document,
+            });
+            let Some((parent, line)) = inherit else {
+                break;
+            };
+            path = theme_path(&parent);
+            if names.contains(&parent) {
+                names.push(parent);
+                let message = format!("主题循环继承: {}", names.join(" -> "));
+                problems.push(Problem::new(&child, line, message));
+                break;
+            }
+            if names.len() >= MAX_INHERIT_DEPTH {
+                let message = format!("主题继承的层数超过 {}", MAX_INHERIT_DEPTH);
+                problems.push(Problem::new(&child, line, message));
+                break;
+            }
+            if !path.exists() {
+                if parent != "default" {
+                    let message = format!("找不到继承的主题 {}", path.display());
+                    problems.push(Problem::new(&child, line, message));
+                }
+                break;
+            }
+            names.push(parent);
+        }
+        files.reverse();
+        files
+    }
+
+    // 继承的主题名和所在的行
+    fn load_inherit(
+        path: &Path,
+        document: &Document,
+        problems: &mut Vec<Problem>,
+    ) -> Option<(String, usize)> {
+        let entry = document.section("").filter(|e| e.key == "inherit").last()?;
+        match entry.value.as_str() {
+            Some(parent) => Some((parent.to_string(), entry.line)),
+            None => {
+                let message = format!("inherit 应为字符串，而不是{}", entry.value.type_name());
+                problems.push(Problem::new(path, entry.line, message));
+                None
+            }
+        }
+    }
+
+    // 最后一个设置了 [prompt] key 的主题文件，都没有设置时为当前主题的文件
+    fn prompt_file<'a>(files: &'a [ThemeFile], key: &str) -> &'a ThemeFile {
+        let defines = |file: &&ThemeFile| file.document.section("prompt").any(|e| e.key == key);
+        match files.iter().rev().find(defines) {
+            Some(file) => file,
+            None => &files[files.len() - 1],
+        }
+    }
+
+    // 不支持的写法报告后跳过，没有找到提示符时使用内置的提示符
+    fn load_zsh_theme(
+        path: &Path,
+        depth: ColorDepth,
+        problems: &mut Vec<Problem>,
+    ) -> Option<zsh_theme::Imported> {
+        let text = fs::read_to_string(path).ok()?;
+        let imported = zsh_theme::import(&text, depth);
+        for (line, message) in &imported.warnings {
+            problems.push(Problem::new(path, *line, message.clone()));
+        }
+        (!imported.left.is_empty()).then_some(imported)
+    }
+
+    // 内置样式加上主题文件中的覆盖，无效的样式报告后使用继承的或内置的
+    // 除了内置的样式名，还可以为注册的片段定义同名的样式
+    fn load_styles(
+        files: &[ThemeFile],
+        registry: &SegmentRegistry,
+        problems: &mut Vec<Problem>,
+    ) -> HashMap<String, Style> {
+        let mut styles: HashMap<String, Style> = DEFAULT_STYLES
+            .iter()
+            .filter_map(|(name, spec)| Some((name.to_string(), Style::parse(spec).ok()?)))
+            .collect();
+        let entries = files.iter().flat_map(|file| {
+            file.document
+                .section("styles")
+                .map(move |entry| (&file.path, entry))
+        });
+        for (path, entry) in entries {
+            let known = styles.contains_key(&entry.key) || registry.contains(&entry.key);
+            let parsed = match (known, entry.value.as_str()) {
+                (false, _) => Err(format!("未知的样式名 {}", entry.key)),
+                (true, None) => Err(format!(
+                    "{} 应为字符串，而不是{}",
+                    entry.key,
+                    entry.value.type_name()
+                )),
+                (true, Some(spec)) => Style::parse(spec),
+            };
+            match parsed {
+                Ok(style) => {
+                    styles.insert(entry.key.clone(), style);
+                }
+                Err(message) => problems.push(Problem::new(path, entry.line, message)),
+            }
+        }
+        styles
+    }
+
+    // 未知的片段名报告后跳过
+    fn load_segments(
+        path: &Path,
+        document: &Document,
+        registry: &SegmentRegistry,
+        problems: &mut Vec<Problem>,
+    ) -> Vec<String> {
+        let default = DEFAULT_SEGMENTS
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let Some(entry) = document
+            .section("prompt")
+            .filter(|e| e.key == "segments")
+            .last()
+        else {
+            return default;
+        };
+        let Some(names) = entry.value.as_strings() else {
+            let message = format!("segments 应为字符串数组，而不是{}", entry.value.type_name());
+            problems.push(Problem::new(path, entry.line, message));
+            return default;
+        };
+        names
+            .into_iter()
+            .filter(|name| {
+                let known = CORE_SEGMENTS.contains(&name.as_str()) || registry.contains(name);
+                if !known {
+                    let message = format!("未知的提示符片段 {}", name);
+                    problems.push(Problem::new(path, entry.line, message));
+                }
+                known
+            })
+            .collect()
+    }
+
+    // 格式错误或含有未知片段时报告后不显示右侧提示符
+    fn load_right_prompt(
+        path: &Path,
+        document: &Document,
+        registry: &SegmentRegistry,
+        problems: &mut Vec<Problem>,
+    ) -> Vec<FormatPart> {
+        let Some(entry) = document
+            .section("prompt")
+            .filter(|e| e.key == "right")
+            .last()
+        else {
+            return Vec::new();
+        };
+        let parsed = match entry.value.as_str() {
+            Some(format) => segment::parse_format(format),
+            None => Err(format!(
+                "right 应为字符串，而不是{}",
+                entry.value.type_name()
+            )),
+        };
+        let unknown = parsed.as_ref().ok().and_then(|parts| {
+            parts.iter().find_map(|part| match part {
+                FormatPart::Segment(spec) if !registry.contains(spec) => Some(spec.clone()),
+                _ => None,
+            })
+        });
+        match (parsed, unknown) {
+            (Ok(parts), None) => parts,
+            (Ok(_), Some(spec)) => {
+                let message = format!("未知的提示符片段 {}", spec);
+                problems.push(Problem::new(path, entry.line, message));
+                Vec::new()
+            }
+            (Err(message), _) => {
+                problems.push(Problem::new(path, entry.line, message));
+                Vec::new()
+            }
+        }
+    }
+
+    // 当前会话使用的随机种子，只有 seeded 模式下才有
+    pub fn message_seed(&self) -> Option<u64> {
+        match self.message_mode {
+            MessageMode::Seeded(seed) => Some(seed),
+            _ => None,
+        }
+    }
+
+    // 渲染提示符，默认为：<cwd> git:(<branch>) 雑魚～>
+    // 片段之间用空格分隔，没有内容的片段不显示
+    // 总宽度超过终端宽度的 prompt_max_ratio 时截断 cwd 和 git 片段
+    pub fn get_prompt(
+        &self,
+        last_status: i32,
+        duration: Duration,
+        jobs: JobCounts,
+        vars: &HashMap<String, String>,
+    ) -> String {
+        if let Some(command) = &self.command {
+            if let Some(prompt) =
+                prompt::command_prompt(command, self.command_timeout, last_status, duration, jobs)
+            {
+                return prompt;
+            }
+        }
+        let cwd = prompt::cwd_segment();
+        let ctx = SegmentContext {
+            cwd: &cwd,
+            last_status,
+            duration,
+            jobs,
+            vars,
+            styles: &self.styles,
+            arg: None,
+        };
+        if let Some(left) = &self.left {
+            return self.render_parts(left, &ctx);
+        }
+        let has = |name: &str| self.segments.iter().any(|s| s == name);
+        let symbol = has("symbol").then(|| self.get_message("prompt"));
+        let git = if has("git") {
+            prompt::git_segment()
+        } else {
+            None
+        };
+        // 先渲染其余的片段，它们的宽度计入固定部分
+        let others: Vec<Option<String>> = self
+            .segments
+            .iter()
+            .map(|name| match name.as_str() {
+                "cwd" | "git" | "symbol" => None,
+                _ => self.registry.render(name, &ctx),
+            })
+            .collect();
+
+        let visible = self
+            .segments
+            .iter()
+            .zip(&others)
+            .filter(|(name, other)| match name.as_str() {
+                "cwd" | "symbol" => true,
+                "git" => git.is_some(),
+                _ => other.is_some(),
+            })
+            .count();
+        let max_width = (prompt::terminal_width() as f32 * self.prompt_max_ratio) as usize;
+        // 分隔空格、"git:()"、提示符号以及其余片段
+        let fixed_width = visible.saturating_sub(1)
+            + git.as_ref().map(|_| "git:()".len()).unwrap_or(0)
+            + symbol.as_deref().map(prompt::display_width).unwrap_or(0)
+            + others
+                .iter()
+                .flatten()
+                .map(|other| prompt::display_width(other))
+                .sum::<usize>();
+        let cwd = if has("cwd") { cwd.as_str() } else { "" };
+        let (cwd, git) = prompt::fit_segments(cwd, git.as_deref(), fixed_width, max_width);
+
+        let mut parts = Vec::new();
+        for (name, other) in self.segments.iter().zip(others) {
+            match name.as_str() {
+                "cwd" => parts.push((self.path_style)(cwd.clone())),
+                "git" => {
+                    if let Some(branch) = &git {
+                        parts.push((self.git_style)(format!("git:({})", branch)));
+                    }
+                }
+                "symbol" => parts.push((self.prompt_style)(symbol.clone().unwrap_or_default())),
+                _ => parts.extend(other),
+            }
+        }
+        parts.join(" ")
+    }
+
+    // 渲染右侧提示符，没有内容的片段为空字符串
+    pub fn get_right_prompt(
+        &self,
+        last_status: i32,
+        duration: Duration,
+        jobs: JobCounts,
+        vars: &HashMap<String, String>,
+    ) -> String {
+        if let Some(command) = &self.right_command {
+            if let Some(prompt) =
+                prompt::command_prompt(command, self.command_timeout, last_status, duration, jobs)
+            {
+                return prompt.trim().to_string();
+            }
+        }
+        if self.right.is_empty() {
+            return String::new();
+        }
+        let cwd = prompt::cwd_segment();
+        let ctx = SegmentContext {
+            cwd: &cwd,
+            last_status,
+            duration,
+            jobs,
+            vars,
+            styles: &self.styles,
+            arg: None,
+        };
+        self.render_parts(&self.right, &ctx).trim().to_string()
+    }
+
+    // 按格式字符串渲染，导入的 zsh 主题还会用到 cwd 和 git，它们不做截断
+    fn render_parts(&self, parts: &[FormatPart], ctx: &SegmentContext) -> String {
+        parts
+            .iter()
+            .map(|part| match part {
+                FormatPart::Text(text) => text.clone(),
+                FormatPart::Segment(spec) if spec == "cwd" => ctx.cwd.to_string(),
+                FormatPart::Segment(spec) if spec == "git" => prompt::git_segment()
+                    .map(|branch| (self.git_style)(format!("git:({})", branch)))
+                    .unwrap_or_default(),
+                FormatPart::Segment(spec) => self.registry.render(spec, ctx).unwrap_or_default(),
+            })
+            .collect()
     }
 
     pub fn get_message(&self, key: &str) -> String {
-        self.messages
+        let msgs = self
+            .messages
             .get(key)
-            .or_else(|| self.messages.get("error"))
-            .and_then(|msgs| msgs.choose(&mut rand::thread_rng()))
-            .cloned()
-            .unwrap_or_default()
+            .or_else(|| self.messages.get("error"));
+        let msg = match self.message_mode {
+            MessageMode::First => msgs.and_then(|msgs| msgs.first()),
+            _ => msgs.and_then(|msgs| msgs.choose(&mut *self.rng.borrow_mut())),
+        };
+        msg.cloned().unwrap_or_default()
+    }
+
+    // 内置的消息加上主题文件 [messages] 段的覆盖，每个键的消息整体替换，例如
+    //   prompt = ["ざこ> ", "ざこ❥ "]
+    fn load_messages(
+        files: &[ThemeFile],
+        problems: &mut Vec<Problem>,
+    ) -> HashMap<String, Vec<String>> {
+        let mut messages = Self::init_messages();
+        for file in files {
+            for entry in file.document.section("messages") {
+                let parsed = match entry.value.as_strings() {
+                    _ if !messages.contains_key(&entry.key) => {
+                        Err(format!("未知的消息名 {}", entry.key))
+                    }
+                    Some(pool) if !pool.is_empty() => Ok(pool),
+                    Some(_) => Err(format!("{} 不能为空数组", entry.key)),
+                    None => Err(format!(
+                        "{} 应为字符串或字符串数组，而不是{}",
+                        entry.key,
+                        entry.value.type_name()
+                    )),
+                };
+                match parsed {
+                    Ok(pool) => {
+                        messages.insert(entry.key.clone(), pool);
+                    }
+                    Err(message) => problems.push(Problem::new(&file.path, entry.line, message)),
+                }
+            }
+        }
+        messages
     }
 
     fn init_messages() -> HashMap<String, Vec<String>> {
@@ -117,6 +686,49 @@ impl Theme {
                 "哎呀呀～这种程度就犯错，看来你的极限就这样了呢～".to_string(),
             ],
         );
+        messages.insert(
+            "command_not_found".to_string(),
+            vec![
+                "啊啦～根本没有这个命令哦，连名字都拼不对吗？".to_string(),
+                "哼～这种命令人家可不认识呢，是笨蛋君自己编的吧？".to_string(),
+                "呵～找不到命令呢，要不要先去查查字典啊，废物君～".to_string(),
+                "真是的～连命令都记不住，脑袋里装的都是什么呀～".to_string(),
+            ],
+        );
+        messages.insert(
+            "permission_denied".to_string(),
+            vec![
+                "哼！没有权限还想硬来？真是不自量力呢～".to_string(),
+                "啊啦～被拒绝了呢，杂鱼就是杂鱼，权限都没有～".to_string(),
+                "呵～这种地方可不是废物君能碰的哦～".to_string(),
+                "真可怜呢～连执行权限都没有，要去求求 root 吗？".to_string(),
+            ],
+        );
+        messages.insert(
+            "command_killed".to_string(),
+            vec![
+                "哎呀呀～进程被干掉了呢，跟你一样脆弱～".to_string(),
+                "呵～被信号打断了吗？真是一点都不顽强呢～".to_string(),
+                "啊啦～死掉了呢，是不是你又做了什么坏事？".to_string(),
+                "哼～连进程都撑不下去，果然是废物君的命令呢～".to_string(),
+            ],
+        );
+        messages.insert(
+            "command_core_dumped".to_string(),
+            vec![
+                "啊啦～连 core 都吐出来了呢，废物君的程序真是狼狈～".to_string(),
+                "呵～崩溃得这么彻底，还留下 core 文件给人家收拾吗？".to_string(),
+                "哼～core dumped 了哦，好好去调试吧，笨蛋～".to_string(),
+            ],
+        );
+        messages.insert(
+            "command_timeout".to_string(),
+            vec![
+                "哼～慢吞吞的，时间到了哦，人家可不会一直等你～".to_string(),
+                "啊啦～超时了呢，就这点速度还好意思让人家等？".to_string(),
+                "呵～磨磨蹭蹭的，直接被掐掉了呢，活该～".to_string(),
+            ],
+        );
         messages.insert(
             "error".to_string(),
             vec![
@@ -131,12 +743,155 @@ impl Theme {
         messages
     }
 
-    pub fn get_theme_file(config: &Config) -> String {
-        let themes_dir = config.themes_dir.clone();
-        PathBuf::from(&themes_dir)
+    // 无障碍模式的消息：只有 ASCII，每个键只有一条，读屏软件每次读到的都相同
+    fn accessible_messages() -> HashMap<String, Vec<String>> {
+        [
+            ("prompt", "zako> "),
+            ("success_symbol", "[ok]"),
+            ("error_symbol", "[error]"),
+            ("welcome", "zako shell"),
+            ("help", "Type exit to quit."),
+            ("exit", "Goodbye."),
+            ("interrupt_signal", "Interrupted (Ctrl+C)."),
+            ("eof_signal", "End of input (Ctrl+D)."),
+            ("command_success", "Command succeeded."),
+            ("command_error", "Command failed."),
+            ("command_not_found", "Command not found."),
+            ("permission_denied", "Permission denied."),
+            ("command_killed", "Command killed by signal."),
+            ("command_core_dumped", "Command crashed (core dumped)."),
+            ("command_timeout", "Command timed out."),
+            ("error", "Error."),
+        ]
+        .into_iter()
+        .map(|(key, message)| (key.to_string(), vec![message.to_string()]))
+        .collect()
+    }
+
+    pub fn get_theme_file(config: &Config) -> PathBuf {
+        config
+            .themes_dir
             .join(format!("{}.zsh-theme", config.theme))
-            .to_str()
-            .unwrap_or_default()
-            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme_with_mode(mode: MessageMode) -> Theme {
+        let mut config = Config::default();
+        config.message_mode = mode;
+        Theme::new(&config)
+    }
+
+    #[test]
+    fn test_seeded_messages_are_reproducible() {
+        let a = theme_with_mode(MessageMode::Seeded(42));
+        let b = theme_with_mode(MessageMode::Seeded(42));
+        for key in [
+            "welcome",
+            "help",
+            "command_success",
+            "command_error",
+            "exit",
+        ] {
+            assert_eq!(a.get_message(key), b.get_message(key));
+        }
+        assert_eq!(a.message_seed(), Some(42));
+    }
+
+    #[test]
+    fn test_first_message_mode() {
+        let theme = theme_with_mode(MessageMode::First);
+        assert_eq!(theme.get_message("prompt"), "雑魚～> ");
+        assert_eq!(theme.get_message("prompt"), "雑魚～> ");
+        assert_eq!(theme.message_seed(), None);
+    }
+
+    #[test]
+    fn test_accessible_theme() {
+        let mut config = Config::default();
+        config.accessible = true;
+        config.message_mode = MessageMode::First;
+        let theme = Theme::new(&config);
+        // 内置消息的每个键都有纯文本的版本
+        for key in Theme::init_messages().keys() {
+            let message = theme.get_message(key);
+            assert!(!message.is_empty() && message.is_ascii(), "{}", key);
+        }
+        assert_eq!((theme.prompt_style)("zako> ".to_string()), "zako> ");
+        let vars = HashMap::new();
+        let prompt = theme.get_prompt(1, Duration::ZERO, JobCounts::default(), &vars);
+        assert!(prompt.ends_with("zako> ") && !prompt.contains('\x1b'));
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_import_zsh_theme() {
+        let dir = std::env::temp_dir().join(format!("zako_zsh_theme_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("plain.zsh-theme"),
+            "PROMPT='%n> '\nRPROMPT='[%j]'\n",
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.themes_dir = dir.clone();
+        config.theme = "plain".to_string();
+        let theme = Theme::new(&config);
+        let mut vars = HashMap::new();
+        vars.insert("USER".to_string(), "zako".to_string());
+        let jobs = JobCounts {
+            running: 2,
+            stopped: 0,
+        };
+        assert_eq!(theme.get_prompt(0, Duration::ZERO, jobs, &vars), "zako> ");
+        assert!(theme
+            .get_right_prompt(0, Duration::ZERO, jobs, &vars)
+            .contains("2 running"));
+
+        // 同名的 toml 主题优先
+        fs::write(dir.join("plain.toml"), "").unwrap();
+        let theme = Theme::new(&config);
+        assert_ne!(theme.get_prompt(0, Duration::ZERO, jobs, &vars), "zako> ");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_theme_inheritance() {
+        let dir = std::env::temp_dir().join(format!("zako_inherit_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("base.toml"),
+            "inherit = \"default\"\n[prompt]\nsegments = [\"symbol\"]\n[messages]\nprompt = \"base> \"\nexit = \"bye\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("child.toml"),
+            "inherit = \"base\"\n[messages]\nprompt = [\"child> \"]\n[colors]\nprompt = 1\n",
+        )
+        .unwrap();
+        fs::write(dir.join("loop.toml"), "inherit = \"loop\"\n").unwrap();
+        let mut config = Config::default();
+        config.themes_dir = dir.clone();
+        config.message_mode = MessageMode::First;
+        config.theme = "child".to_string();
+        let theme = Theme::new(&config);
+        assert_eq!(theme.segments, vec!["symbol"]);
+        assert_eq!(theme.get_message("prompt"), "child> ");
+        assert_eq!(theme.get_message("exit"), "bye");
+        // 没有覆盖的消息仍使用内置的
+        assert_eq!(theme.get_message("success_symbol"), "♡");
+        let problems = Theme::check(&config);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, 5);
+        assert!(problems[0].message.contains("[colors]"));
+
+        config.theme = "loop".to_string();
+        assert_eq!(Theme::load_theme_files(&config, &mut Vec::new()).len(), 1);
+        assert!(Theme::check(&config)[0].message.contains("循环继承"));
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,414 @@
+// 主题和配置文件使用的 TOML 子集：[section]、key = value 和 # 注释
+// 值可以是字符串（"..." 或 '...'）、整数、小数、true/false 以及由它们组成的数组，数组可以跨行
+// 解析出错的行会被跳过并记录行号，其余内容照常使用
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    // 整数也可以当作小数使用
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(f) => Some(*f),
+            Value::Integer(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    // 字符串数组，单个字符串也当作只有一项的数组
+    pub fn as_strings(&self) -> Option<Vec<String>> {
+        match self {
+            Value::String(s) => Some(vec![s.clone()]),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "字符串",
+            Value::Integer(_) => "整数",
+            Value::Float(_) => "小数",
+            Value::Boolean(_) => "布尔值",
+            Value::Array(_) => "数组",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    // 所在的 [section]，文件开头的键为空字符串
+    pub section: String,
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Document {
+    pub entries: Vec<Entry>,
+    // 出错的行号和原因
+    pub errors: Vec<(usize, String)>,
+}
+
+impl Document {
+    pub fn section<'a>(&'a self, section: &'a str) -> impl Iterator<Item = &'a Entry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.section == section)
+    }
+}
+
+// 配置或主题文件中的一个问题，显示为 文件:行号: 说明，行号为 0 时表示整个文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn new(path: &Path, line: usize, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}: {}", self.path.display(), self.message),
+            line => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+        }
+    }
+}
+
+// 检查文件中的段名和键名，schema 为每个段允许的键，允许的键为空时该段可以使用任意键
+// 文件开头的键属于名字为空字符串的段
+pub fn check_keys(path: &Path, document: &Document, schema: &[(&str, &[&str])]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut reported_sections: Vec<&str> = Vec::new();
+    for entry in &document.entries {
+        match schema.iter().find(|(section, _)| *section == entry.section) {
+            None if entry.section.is_empty() => problems.push(Problem::new(
+                path,
+                entry.line,
+                format!("未知的设置 {}", entry.key),
+            )),
+            None if !reported_sections.contains(&entry.section.as_str()) => {
+                reported_sections.push(&entry.section);
+                problems.push(Problem::new(
+                    path,
+                    entry.line,
+                    format!("未知的段 [{}]", entry.section),
+                ));
+            }
+            Some((section, keys)) if !keys.is_empty() && !keys.contains(&entry.key.as_str()) => {
+                let name = match *section {
+                    "" => entry.key.clone(),
+                    section => format!("{}.{}", section, entry.key),
+                };
+                let message = format!("未知的设置 {}，可用的设置: {}", name, keys.join(", "));
+                problems.push(Problem::new(path, entry.line, message));
+            }
+            _ => {}
+        }
+    }
+    problems
+}
+
+pub fn parse(text: &str) -> Document {
+    let mut document = Document::default();
+    let mut section = String::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line_no = index + 1;
+        let mut statement = strip_comment(line).trim().to_string();
+        if statement.is_empty() {
+            continue;
+        }
+        if let Some(name) = statement.strip_prefix('[') {
+            match name.strip_suffix(']') {
+                Some(name) if !name.trim().is_empty() => section = name.trim().to_string(),
+                _ => document
+                    .errors
+                    .push((line_no, format!("无效的段名: {}", statement))),
+            }
+            continue;
+        }
+        // 数组没有闭合时继续读取下一行
+        while bracket_depth(&statement) > 0 {
+            match lines.next() {
+                Some((_, next)) => {
+                    statement.push(' ');
+                    statement.push_str(strip_comment(next).trim());
+                }
+                None => break,
+            }
+        }
+        let Some((key, value)) = statement.split_once('=') else {
+            document
+                .errors
+                .push((line_no, format!("应为 key = value: {}", statement)));
+            continue;
+        };
+        let key = key.trim().trim_matches('"').to_string();
+        match parse_value(value.trim()) {
+            Ok(value) if !key.is_empty() => document.entries.push(Entry {
+                section: section.clone(),
+                key,
+                value,
+                line: line_no,
+            }),
+            Ok(_) => document.errors.push((line_no, "缺少键名".to_string())),
+            Err(message) => document.errors.push((line_no, message)),
+        }
+    }
+    document
+}
+
+// 去掉引号外的 # 注释
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+// 引号外未闭合的 [ 的数量
+fn bracket_depth(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let (value, rest) = parse_item(text)?;
+    if !rest.trim().is_empty() {
+        return Err(format!("值后面有多余的内容: {}", rest.trim()));
+    }
+    Ok(value)
+}
+
+// 解析一个值，返回剩余的内容
+fn parse_item(text: &str) -> Result<(Value, &str), String> {
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('e') => value.push('\x1b'),
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("无效的转义: \\u{}", hex))?;
+                        value.push(c);
+                    }
+                    other => {
+                        return Err(format!("无效的转义: \\{}", other.unwrap_or(' ')));
+                    }
+                },
+                _ => value.push(c),
+            }
+        }
+        return Err("字符串没有结束".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        let end = rest
+            .find('\'')
+            .ok_or_else(|| "字符串没有结束".to_string())?;
+        return Ok((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+    }
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_item(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("数组元素之间应有逗号".to_string());
+            }
+        }
+    }
+    let end = text
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    let value = match word {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => {
+            let number = word.replace('_', "");
+            if let Ok(n) = number.parse::<i64>() {
+                Value::Integer(n)
+            } else if let Ok(f) = number.parse::<f64>() {
+                Value::Float(f)
+            } else if word.is_empty() {
+                return Err("缺少值".to_string());
+            } else {
+                return Err(format!("无法识别的值: {}（字符串需要加引号）", word));
+            }
+        }
+    };
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get<'a>(document: &'a Document, section: &'a str, key: &str) -> Option<&'a Entry> {
+        document.section(section).filter(|e| e.key == key).last()
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let document = parse(
+            r#"
+# 主题
+inherit = "default"  # 注释
+ratio = 0.5
+[styles]
+prompt = 'fg=#ff79c6 bold'
+[messages]
+prompt = [
+    "雑魚～> ",  # 第一条
+    "a\"b♥",
+]
+bad line
+count = 3
+"#,
+        );
+        assert_eq!(
+            get(&document, "", "inherit").map(|e| &e.value),
+            Some(&Value::String("default".to_string()))
+        );
+        assert_eq!(
+            get(&document, "", "ratio").map(|e| &e.value),
+            Some(&Value::Float(0.5))
+        );
+        assert_eq!(get(&document, "styles", "prompt").map(|e| e.line), Some(6));
+        assert_eq!(
+            get(&document, "messages", "prompt").map(|e| &e.value),
+            Some(&Value::Array(vec![
+                Value::String("雑魚～> ".to_string()),
+                Value::String("a\"b♥".to_string())
+            ]))
+        );
+        assert_eq!(
+            get(&document, "messages", "count").map(|e| &e.value),
+            Some(&Value::Integer(3))
+        );
+        assert_eq!(document.errors.len(), 1);
+        assert_eq!(document.errors[0].0, 12);
+
+        let path = Path::new("theme.toml");
+        let problems = check_keys(
+            path,
+            &document,
+            &[
+                ("", &["inherit"]),
+                ("styles", &["prompt"]),
+                ("messages", &[]),
+            ],
+        );
+        let lines: Vec<usize> = problems.iter().map(|p| p.line).collect();
+        assert_eq!(lines, vec![4]);
+        assert_eq!(
+            problems[0].to_string(),
+            "theme.toml:4: 未知的设置 ratio，可用的设置: inherit"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_value("\"open").is_err());
+        assert!(parse_value("bare").is_err());
+        assert!(parse_value("[1 2]").is_err());
+        assert!(parse_value("\"a\" b").is_err());
+        assert_eq!(
+            parse_value("[true, 1_000]"),
+            Ok(Value::Array(vec![
+                Value::Boolean(true),
+                Value::Integer(1000)
+            ]))
+        );
+    }
+}
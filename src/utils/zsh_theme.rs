@@ -0,0 +1,564 @@
+// zsh 主题（themes_dir/<theme>.zsh-theme）的导入，尽量把 PROMPT/RPROMPT 转换成 zako 的提示符格式
+// 支持常见的 %~ %c %n %m 等转义、%F{..}/%B 等样式、$fg[..]/$reset_color 颜色变量、
+// 主题中定义的变量以及 $(git_prompt_info) 等常见函数，其余的写法跳过并报告
+// 颜色在导入时直接转换成转义序列，片段自己的样式结束后恢复当前的颜色
+use std::collections::HashMap;
+
+use super::segment::FormatPart;
+use super::style::{Color, ColorDepth, Style};
+
+// 变量展开的最大层数，避免变量互相引用时无限展开
+const MAX_EXPANSION: usize = 8;
+
+pub struct Imported {
+    pub left: Vec<FormatPart>,
+    pub right: Vec<FormatPart>,
+    // 不支持的写法所在的行号和说明
+    pub warnings: Vec<(usize, String)>,
+}
+
+struct Assignment {
+    value: String,
+    line: usize,
+}
+
+pub fn import(text: &str, depth: ColorDepth) -> Imported {
+    let vars = parse_assignments(text);
+    let mut warnings = Vec::new();
+    let mut convert = |names: &[&str]| {
+        let Some(assignment) = names.iter().find_map(|name| vars.get(*name)) else {
+            return Vec::new();
+        };
+        let mut converter = Converter {
+            vars: &vars,
+            depth,
+            line: assignment.line,
+            parts: Vec::new(),
+            style: Style::default(),
+            warnings: &mut warnings,
+        };
+        converter.convert(&assignment.value, 0);
+        converter.finish()
+    };
+    let left = convert(&["PROMPT", "PS1"]);
+    let right = convert(&["RPROMPT", "RPS1"]);
+    if left.is_empty() {
+        warnings.push((1, "没有找到 PROMPT 或 PS1".to_string()));
+    }
+    Imported {
+        left,
+        right,
+        warnings,
+    }
+}
+
+// 读取所有 NAME=value 和 NAME+=value 形式的赋值，去掉引号
+// 函数定义等其他语句中的单词会被忽略
+fn parse_assignments(text: &str) -> HashMap<String, Assignment> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut vars: HashMap<String, Assignment> = HashMap::new();
+    let mut i = 0;
+    let mut line = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\n' => {
+                line += 1;
+                i += 1;
+                continue;
+            }
+            c if c.is_whitespace() || c == ';' => {
+                i += 1;
+                continue;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let start_line = line;
+        let (word, next) = read_word(&chars, i);
+        line += chars[i..next].iter().filter(|c| **c == '\n').count();
+        i = next;
+        let Some((name, value)) = word.split_once('=') else {
+            continue;
+        };
+        let (name, append) = match name.strip_suffix('+') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            continue;
+        }
+        match vars.get_mut(name) {
+            Some(existing) if append => existing.value.push_str(value),
+            _ => {
+                vars.insert(
+                    name.to_string(),
+                    Assignment {
+                        value: value.to_string(),
+                        line: start_line,
+                    },
+                );
+            }
+        }
+    }
+    vars
+}
+
+// 读取一个单词并去掉引号，返回单词和结束的位置
+fn read_word(chars: &[char], mut i: usize) -> (String, usize) {
+    let mut word = String::new();
+    while i < chars.len() {
+        match chars[i] {
+            c if c.is_whitespace() || c == ';' => break,
+            '\'' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '\'' {
+                    word.push(chars[i]);
+                    i += 1;
+                }
+            }
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && matches!(chars.get(i + 1), Some('"' | '\\' | '`')) {
+                        i += 1;
+                    }
+                    word.push(chars[i]);
+                    i += 1;
+                }
+            }
+            '$' if chars.get(i + 1) == Some(&'\'') => {
+                i += 2;
+                while i < chars.len() && chars[i] != '\'' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                        match chars[i] {
+                            'n' => word.push('\n'),
+                            't' => word.push('\t'),
+                            'e' | 'E' => word.push('\x1b'),
+                            c => word.push(c),
+                        }
+                    } else {
+                        word.push(chars[i]);
+                    }
+                    i += 1;
+                }
+            }
+            '\\' => {
+                i += 1;
+                // 行尾的反斜杠表示续行
+                if let Some(&c) = chars.get(i).filter(|c| **c != '\n') {
+                    word.push(c);
+                }
+            }
+            c => word.push(c),
+        }
+        i += 1;
+    }
+    (word, i)
+}
+
+// zsh 的颜色名，default 表示恢复默认颜色
+fn zsh_color(name: &str) -> Option<Option<Color>> {
+    match name.trim() {
+        "default" | "" => Some(None),
+        name => Color::parse(name).ok().map(Some),
+    }
+}
+
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+struct Converter<'a> {
+    vars: &'a HashMap<String, Assignment>,
+    depth: ColorDepth,
+    line: usize,
+    parts: Vec<FormatPart>,
+    style: Style,
+    warnings: &'a mut Vec<(usize, String)>,
+}
+
+impl Converter<'_> {
+    fn text(&mut self, text: &str) {
+        if let Some(FormatPart::Text(last)) = self.parts.last_mut() {
+            last.push_str(text);
+        } else if !text.is_empty() {
+            self.parts.push(FormatPart::Text(text.to_string()));
+        }
+    }
+
+    // 带样式的片段输出后会重置颜色，所以片段之后重新设置当前的样式
+    fn segment(&mut self, spec: &str) {
+        self.parts.push(FormatPart::Segment(spec.to_string()));
+        if let Some(sgr) = self.style.sgr(self.depth) {
+            self.text(&sgr);
+        }
+    }
+
+    fn set_style(&mut self, style: Style) {
+        if style == self.style {
+            return;
+        }
+        if self.depth != ColorDepth::None {
+            self.text("\x1b[0m");
+        }
+        self.style = style;
+        if let Some(sgr) = self.style.sgr(self.depth) {
+            self.text(&sgr);
+        }
+    }
+
+    fn set_attribute(&mut self, code: u8, on: bool) {
+        let mut style = self.style.clone();
+        style.attributes.retain(|a| *a != code);
+        if on {
+            style.attributes.push(code);
+        }
+        self.set_style(style);
+    }
+
+    fn unsupported(&mut self, what: &str) {
+        self.warnings
+            .push((self.line, format!("不支持的 zsh 写法，已跳过: {}", what)));
+    }
+
+    fn finish(mut self) -> Vec<FormatPart> {
+        self.set_style(Style::default());
+        self.parts
+    }
+
+    fn convert(&mut self, value: &str, level: usize) {
+        let chars: Vec<char> = value.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '%' => i = self.percent(&chars, i + 1, level),
+                '$' => i = self.dollar(&chars, i + 1, level),
+                c => {
+                    self.text(&c.to_string());
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    // 处理 % 之后的转义，返回下一个位置
+    fn percent(&mut self, chars: &[char], mut i: usize, level: usize) -> usize {
+        let Some(&c) = chars.get(i) else {
+            self.text("%");
+            return i;
+        };
+        // %1~ %2c 等带数字的写法都当作只显示最后一级目录，%1{ 是宽度提示，和 %{ 一样忽略
+        let start = i;
+        while chars.get(i).is_some_and(|c| c.is_ascii_digit()) {
+            i += 1;
+        }
+        if i > start {
+            match chars.get(i) {
+                Some('{') => return i + 1,
+                Some('~' | 'c' | 'C' | '/' | 'd' | '.') => {
+                    self.segment("dir");
+                    return i + 1;
+                }
+                _ => {
+                    let number: String = chars[start..i].iter().collect();
+                    self.unsupported(&format!("%{}", number));
+                    return i;
+                }
+            }
+        }
+        i += 1;
+        match c {
+            '{' | '}' => {}
+            '%' => self.text("%"),
+            ')' => self.text(")"),
+            '~' | '/' | 'd' => self.segment("cwd"),
+            'c' | 'C' | '.' => self.segment("dir"),
+            'n' => self.segment("user"),
+            'm' | 'M' => self.segment("host"),
+            '#' => self.text(if is_root() { "#" } else { "%" }),
+            '?' => self.segment("status"),
+            'j' => self.segment("jobs"),
+            'T' => self.segment("time:%H:%M"),
+            '*' => self.segment("time:%H:%M:%S"),
+            't' | '@' => self.segment("time:%l:%M%p"),
+            'D' if chars.get(i) == Some(&'{') => {
+                let (format, next) = read_until(chars, i + 1, '}');
+                self.segment(&format!("time:{}", format));
+                i = next;
+            }
+            'D' => self.segment("time:%y-%m-%d"),
+            'B' => self.set_attribute(1, true),
+            'b' => self.set_attribute(1, false),
+            'U' => self.set_attribute(4, true),
+            'u' => self.set_attribute(4, false),
+            'F' | 'K' => {
+                let (name, next) = if chars.get(i) == Some(&'{') {
+                    read_until(chars, i + 1, '}')
+                } else {
+                    // %F 后面可以直接跟颜色编号
+                    let end = (i..chars.len())
+                        .find(|&j| !chars[j].is_ascii_digit())
+                        .unwrap_or(chars.len());
+                    (chars[i..end].iter().collect(), end)
+                };
+                i = next;
+                match zsh_color(&name) {
+                    Some(color) => {
+                        let mut style = self.style.clone();
+                        if c == 'F' {
+                            style.fg = color;
+                        } else {
+                            style.bg = color;
+                        }
+                        self.set_style(style);
+                    }
+                    None => self.unsupported(&format!("%{}{{{}}}", c, name)),
+                }
+            }
+            'f' => {
+                let style = Style {
+                    fg: None,
+                    ..self.style.clone()
+                };
+                self.set_style(style);
+            }
+            'k' => {
+                let style = Style {
+                    bg: None,
+                    ..self.style.clone()
+                };
+                self.set_style(style);
+            }
+            '(' => i = self.conditional(chars, i, level),
+            c => self.unsupported(&format!("%{}", c)),
+        }
+        i
+    }
+
+    // %(cond.true.false)：root 相关的条件在导入时判断，其余的条件总是取成立的分支
+    fn conditional(&mut self, chars: &[char], mut i: usize, level: usize) -> usize {
+        let start = i;
+        while chars.get(i).is_some_and(|c| c.is_ascii_digit()) {
+            i += 1;
+        }
+        let Some(&condition) = chars.get(i) else {
+            self.unsupported("%(");
+            return i;
+        };
+        let Some(&delimiter) = chars.get(i + 1) else {
+            self.unsupported("%(");
+            return i + 1;
+        };
+        let (if_true, next) = read_until(chars, i + 2, delimiter);
+        // 假分支到匹配的 ) 为止，中间可能有 $(...) 等括号
+        let mut depth = 0;
+        let mut end = next;
+        while end < chars.len() {
+            match chars[end] {
+                '(' => depth += 1,
+                ')' if depth == 0 => break,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            end += 1;
+        }
+        let if_false: String = chars[next..end.min(chars.len())].iter().collect();
+        let branch = match condition {
+            '!' | '#' if is_root() => if_true,
+            '!' | '#' => if_false,
+            _ => {
+                let cond: String = chars[start..=i].iter().collect();
+                self.unsupported(&format!("%({}...)，总是按条件成立显示", cond));
+                if_true
+            }
+        };
+        self.convert(&branch, level + 1);
+        end + 1
+    }
+
+    // 处理 $ 之后的变量、颜色和命令替换，返回下一个位置
+    fn dollar(&mut self, chars: &[char], i: usize, level: usize) -> usize {
+        match chars.get(i) {
+            Some('(') => {
+                let (command, next) = read_until(chars, i + 1, ')');
+                let name = command.split_whitespace().next().unwrap_or_default();
+                match name {
+                    "git_prompt_info" => self.segment("git"),
+                    "virtualenv_prompt_info" => self.segment("python"),
+                    "kube_ps1" => self.segment("kube"),
+                    "nvm_prompt_info" => self.segment("node"),
+                    "battery_pct_prompt" => self.segment("battery"),
+                    _ => self.unsupported(&format!("$({})", command)),
+                }
+                next
+            }
+            Some('{') => {
+                let (inner, next) = read_until(chars, i + 1, '}');
+                let (name, key) = match inner.split_once('[') {
+                    Some((name, key)) => (name.to_string(), Some(key.trim_end_matches(']'))),
+                    None => (inner.clone(), None),
+                };
+                self.variable(&name, key, level);
+                next
+            }
+            Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                let end = (i..chars.len())
+                    .find(|&j| !(chars[j].is_ascii_alphanumeric() || chars[j] == '_'))
+                    .unwrap_or(chars.len());
+                let name: String = chars[i..end].iter().collect();
+                if chars.get(end) == Some(&'[') {
+                    let (key, next) = read_until(chars, end + 1, ']');
+                    self.variable(&name, Some(&key), level);
+                    next
+                } else {
+                    self.variable(&name, None, level);
+                    end
+                }
+            }
+            _ => {
+                self.text("$");
+                i
+            }
+        }
+    }
+
+    fn variable(&mut self, name: &str, key: Option<&str>, level: usize) {
+        let mut style = self.style.clone();
+        match (name, key) {
+            ("fg" | "FG" | "fg_no_bold", Some(key)) => {
+                let Some(color) = zsh_color(key) else {
+                    return self.unsupported(&format!("${}[{}]", name, key));
+                };
+                style.fg = color;
+                if name == "fg_no_bold" {
+                    style.attributes.retain(|a| *a != 1);
+                }
+            }
+            ("fg_bold", Some(key)) => {
+                let Some(color) = zsh_color(key) else {
+                    return self.unsupported(&format!("${}[{}]", name, key));
+                };
+                style.fg = color;
+                style.attributes.retain(|a| *a != 1);
+                style.attributes.push(1);
+            }
+            ("bg" | "BG", Some(key)) => {
+                let Some(color) = zsh_color(key) else {
+                    return self.unsupported(&format!("${}[{}]", name, key));
+                };
+                style.bg = color;
+            }
+            ("reset_color", None) => style = Style::default(),
+            ("vcs_info_msg_0_", None) => return self.segment("git"),
+            (name, None) if self.vars.contains_key(name) => {
+                if level >= MAX_EXPANSION {
+                    return self.unsupported(&format!("${}（展开层数过多）", name));
+                }
+                let value = self.vars[name].value.clone();
+                return self.convert(&value, level + 1);
+            }
+            (name, Some(key)) => return self.unsupported(&format!("${}[{}]", name, key)),
+            (name, None) => return self.unsupported(&format!("${}", name)),
+        }
+        self.set_style(style);
+    }
+}
+
+// 读取到 end 为止（不含 end），返回内容和 end 之后的位置
+fn read_until(chars: &[char], start: usize, end: char) -> (String, usize) {
+    let stop = (start..chars.len())
+        .find(|&i| chars[i] == end)
+        .unwrap_or(chars.len());
+    (
+        chars[start.min(chars.len())..stop].iter().collect(),
+        stop + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> FormatPart {
+        FormatPart::Text(s.to_string())
+    }
+
+    fn segment(s: &str) -> FormatPart {
+        FormatPart::Segment(s.to_string())
+    }
+
+    #[test]
+    fn test_import_robbyrussell() {
+        let theme = r#"
+# robbyrussell
+PROMPT="%(?:%{$fg_bold[green]%}%1{➜%} :%{$fg_bold[red]%}%1{➜%} ) %{$fg[cyan]%}%c%{$reset_color%}"
+PROMPT+=' $(git_prompt_info)'
+
+ZSH_THEME_GIT_PROMPT_PREFIX="%{$fg_bold[blue]%}git:(%{$fg[red]%}"
+"#;
+        let imported = import(theme, ColorDepth::None);
+        assert_eq!(
+            imported.left,
+            vec![text("➜  "), segment("dir"), text(" "), segment("git")]
+        );
+        assert!(imported.right.is_empty());
+        assert_eq!(imported.warnings.len(), 1);
+        assert_eq!(imported.warnings[0].0, 3);
+
+        let imported = import(theme, ColorDepth::Ansi16);
+        // 和 zsh 一样，$fg[..] 只改变颜色，之前的粗体保留
+        assert_eq!(
+            imported.left[0],
+            text("\x1b[0m\x1b[1;32m➜  \x1b[0m\x1b[1;36m")
+        );
+        assert_eq!(imported.left[1], segment("dir"));
+        assert_eq!(imported.left[2], text("\x1b[1;36m\x1b[0m "));
+    }
+
+    #[test]
+    fn test_import_variables_and_escapes() {
+        let theme = r#"
+local user_host='%F{208}%n@%m%f'
+local ret="%B%?%b"
+PS1="${user_host} %~ ${ret} %D{%Y} %# "
+RPS1=$'%*\t%j'
+PROMPT_EXTRA="$(custom_func) %x"
+"#;
+        let imported = import(theme, ColorDepth::None);
+        assert_eq!(
+            imported.left,
+            vec![
+                segment("user"),
+                text("@"),
+                segment("host"),
+                text(" "),
+                segment("cwd"),
+                text(" "),
+                segment("status"),
+                text(" "),
+                segment("time:%Y"),
+                text(if is_root() { " # " } else { " % " }),
+            ]
+        );
+        assert_eq!(
+            imported.right,
+            vec![segment("time:%H:%M:%S"), text("\t"), segment("jobs")]
+        );
+        assert!(imported.warnings.is_empty());
+
+        let imported = import("PROMPT='$(custom_func) %x $UNKNOWN> '", ColorDepth::None);
+        assert_eq!(imported.left, vec![text("  > ")]);
+        assert_eq!(imported.warnings.len(), 3);
+        assert!(import("echo hi", ColorDepth::None).warnings[0]
+            .1
+            .contains("PROMPT"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::pty::openpty;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// 等待输出的默认时限
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+// 在伪终端上运行的 zako，用于测试提示符、作业控制和信号处理
+pub struct PtyShell {
+    master: File,
+    child: Child,
+    // 已读取但还没被 expect 消费的输出（去掉了颜色和 \r）
+    buffer: String,
+    config_dir: PathBuf,
+}
+
+impl PtyShell {
+    pub fn spawn() -> Result<Self, String> {
+        Self::spawn_with_env(&[])
+    }
+
+    // 额外设置环境变量，例如测试 Ctrl-X Ctrl-E 时的 EDITOR
+    pub fn spawn_with_env(envs: &[(&str, &str)]) -> Result<Self, String> {
+        let pty = openpty(None, None).map_err(|e| format!("openpty: {}", e))?;
+        let slave: OwnedFd = pty.slave;
+        let stdio = || -> Result<Stdio, String> {
+            Ok(Stdio::from(
+                slave.try_clone().map_err(|e| format!("dup: {}", e))?,
+            ))
+        };
+
+        // 每个测试使用独立的配置目录，避免写入仓库里的历史和日志
+        let config_dir = std::env::temp_dir().join(format!(
+            "zako-pty-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut command = Command::new(env!("CARGO_BIN_EXE_zako"));
+        command
+            .env("ZAKO_CONFIG_DIR", &config_dir)
+            .env("ZAKO_LOG", "error")
+            .env("ZAKO_MESSAGE_MODE", "first")
+            .env("COLUMNS", "100")
+            .envs(envs.iter().copied())
+            .stdin(stdio()?)
+            .stdout(stdio()?)
+            .stderr(stdio()?);
+        // 新会话并把伪终端设为控制终端，shell 才能使用 tcsetpgrp
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn().map_err(|e| format!("spawn: {}", e))?;
+        drop(slave);
+
+        Ok(Self {
+            master: File::from(pty.master),
+            child,
+            buffer: String::new(),
+            config_dir,
+        })
+    }
+
+    // 输入一行命令（终端里回车是 \r）
+    pub fn send_line(&mut self, line: &str) -> Result<(), String> {
+        self.send(&format!("{}\r", line))
+    }
+
+    // 发送控制字符，如 ctrl('c')
+    pub fn send_ctrl(&mut self, c: char) -> Result<(), String> {
+        let byte = (c.to_ascii_uppercase() as u8) & 0x1f;
+        self.send(&(byte as char).to_string())
+    }
+
+    pub fn send(&mut self, input: &str) -> Result<(), String> {
+        self.master
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("write: {}", e))
+    }
+
+    // 等待输出中出现 pattern，返回 pattern 之前（含 pattern）的输出
+    pub fn expect(&mut self, pattern: &str) -> Result<String, String> {
+        self.expect_within(pattern, TIMEOUT)
+    }
+
+    pub fn expect_within(&mut self, pattern: &str, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(index) = self.buffer.find(pattern) {
+                let rest = self.buffer.split_off(index + pattern.len());
+                return Ok(std::mem::replace(&mut self.buffer, rest));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !self.read_some(remaining)? {
+                return Err(format!(
+                    "等待 {:?} 超时，当前输出:\n{}",
+                    pattern, self.buffer
+                ));
+            }
+        }
+    }
+
+    // 等待下一个提示符
+    pub fn expect_prompt(&mut self) -> Result<String, String> {
+        self.expect("雑魚～> ")
+    }
+
+    // 向 shell 进程发送信号
+    pub fn signal(&self, sig: i32) -> Result<(), String> {
+        if unsafe { libc::kill(self.child.id() as i32, sig) } != 0 {
+            return Err(format!("kill: {}", std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    // 等待 shell 退出
+    pub fn wait_exit(&mut self, timeout: Duration) -> Result<ExitStatus, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.child.try_wait().map_err(|e| e.to_string())? {
+                return Ok(status);
+            }
+            if Instant::now() >= deadline {
+                return Err("shell 没有退出".to_string());
+            }
+            // 继续读取输出，避免 shell 阻塞在写终端上
+            let _ = self.read_some(Duration::from_millis(50));
+        }
+    }
+
+    // 读取一次输出，超时或终端关闭时返回 false
+    fn read_some(&mut self, timeout: Duration) -> Result<bool, String> {
+        let millis = timeout.as_millis().min(u16::MAX as u128) as u16;
+        let mut fds = [PollFd::new(self.master.as_fd(), PollFlags::POLLIN)];
+        let ready =
+            poll(&mut fds, PollTimeout::from(millis)).map_err(|e| format!("poll: {}", e))?;
+        if ready == 0 {
+            return Ok(false);
+        }
+        let mut chunk = [0u8; 4096];
+        match self.master.read(&mut chunk) {
+            Ok(0) | Err(_) => Ok(false),
+            Ok(n) => {
+                let text = String::from_utf8_lossy(&chunk[..n]);
+                self.buffer.push_str(&strip_ansi(&text).replace('\r', ""));
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl Drop for PtyShell {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.config_dir);
+    }
+}
+
+// 去掉颜色等 ANSI 转义序列
+fn strip_ansi(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            chars.next();
+        }
+    }
+    result
+}
@@ -0,0 +1,576 @@
+// 在伪终端上驱动 zako 的集成测试，覆盖 fork/tcsetpgrp 相关的作业控制路径
+// 运行：cargo test --features pty-tests
+#![cfg(feature = "pty-tests")]
+
+mod harness;
+
+use harness::PtyShell;
+use std::time::Duration;
+
+#[test]
+fn test_prompt_and_command() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("echo hello-pty")?;
+    let output = shell.expect_prompt()?;
+    assert!(output.contains("\nhello-pty\n"), "{}", output);
+    assert!(output.contains("勉强算你做对了"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_ctrl_c_kills_foreground_job() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("sleep 30")?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send_ctrl('c')?;
+    // SIGINT 只发给前台作业，shell 本身收回终端后继续运行
+    let output = shell.expect_within("雑魚～> ", Duration::from_secs(2))?;
+    assert!(output.contains("进程被干掉了"), "{}", output);
+    shell.send_line("echo still-alive")?;
+    shell.expect("still-alive")?;
+    Ok(())
+}
+
+#[test]
+fn test_ctrl_z_stops_job() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("sleep 30")?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send_ctrl('z')?;
+    shell.expect("stopped sleep 30")?;
+    shell.expect_prompt()?;
+    shell.send_line("jobs")?;
+    let output = shell.expect_prompt()?;
+    assert!(output.contains("[1] + "), "{}", output);
+    assert!(output.contains("stopped sleep 30"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_background_job_keeps_terminal() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("sleep 30 &")?;
+    shell.expect("[1] ")?;
+    shell.expect_prompt()?;
+    // 后台作业运行时 shell 仍然可以读取输入
+    shell.send_line("echo foreground")?;
+    shell.expect("\nforeground\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_exit() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("exit")?;
+    let status = shell.wait_exit(Duration::from_secs(2))?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[test]
+fn test_exit_trap_runs_on_every_exit() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("trap 'echo exit-trap $?' EXIT")?;
+    shell.expect_prompt()?;
+    shell.send_line("exit 5")?;
+    shell.expect("exit-trap 5")?;
+    let status = shell.wait_exit(Duration::from_secs(2))?;
+    assert_eq!(status.code(), Some(5));
+
+    // Ctrl-D 退出时同样运行
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("trap 'echo eof-trap' EXIT")?;
+    shell.expect_prompt()?;
+    shell.send_ctrl('d')?;
+    shell.expect("eof-trap")?;
+    let status = shell.wait_exit(Duration::from_secs(2))?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[test]
+fn test_sigterm_shuts_down_gracefully() -> Result<(), String> {
+    let marker = std::env::temp_dir().join(format!("zako-hup-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("trap 'echo term-trap' EXIT")?;
+    shell.expect_prompt()?;
+    shell.send_line("set -o huponexit")?;
+    shell.expect_prompt()?;
+    // 后台作业收到 SIGHUP 时留下标记
+    shell.send_line(&format!(
+        "sh -c 'trap \"touch {}; exit\" HUP; sleep 30 & wait' &",
+        marker.display()
+    ))?;
+    shell.expect_prompt()?;
+    std::thread::sleep(Duration::from_millis(300));
+
+    // 在提示符处等待输入时收到 SIGTERM
+    shell.signal(libc::SIGTERM)?;
+    shell.expect("term-trap")?;
+    let status = shell.wait_exit(Duration::from_secs(2))?;
+    assert_eq!(status.code(), Some(128 + libc::SIGTERM));
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    while !marker.exists() && std::time::Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(marker.exists(), "后台作业没有收到 SIGHUP");
+    let _ = std::fs::remove_file(&marker);
+    Ok(())
+}
+
+#[test]
+fn test_logoutput_keeps_terminal() -> Result<(), String> {
+    let log = std::env::temp_dir().join(format!("zako-logoutput-{}", std::process::id()));
+    let _ = std::fs::remove_file(&log);
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line(&format!("logoutput on {}", log.display()))?;
+    shell.expect_prompt()?;
+    // 子进程的 stdout/stderr 仍然是终端
+    shell.send_line("sh -c '[ -t 1 ] && echo out-tty'")?;
+    shell.expect("\nout-tty")?;
+    shell.expect_prompt()?;
+    shell.send_line("sh -c '[ -t 2 ] && echo err-tty >&2'")?;
+    shell.expect("\nerr-tty")?;
+    shell.expect_prompt()?;
+    shell.send_line("logoutput off")?;
+    shell.expect_prompt()?;
+    shell.send_line("sh -c 'echo not-logged'")?;
+    shell.expect_prompt()?;
+    let content = std::fs::read_to_string(&log).map_err(|e| e.to_string())?;
+    let _ = std::fs::remove_file(&log);
+    assert!(content.contains("out-tty\n"), "{}", content);
+    assert!(content.contains("err-tty\n"), "{}", content);
+    assert!(!content.contains("not-logged"), "{}", content);
+    Ok(())
+}
+
+#[test]
+fn test_builtin_sleep_is_interruptible() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("trap 'echo int-trap' INT")?;
+    shell.expect_prompt()?;
+    // 内建的 sleep 在 shell 进程中等待，Ctrl-C 仍然中断它并运行 INT trap
+    shell.send_line("sleep 30")?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send_ctrl('c')?;
+    shell.expect_within("int-trap", Duration::from_secs(2))?;
+    shell.expect_prompt()?;
+    shell.send_line("echo status=$?")?;
+    shell.expect("status=130")?;
+    Ok(())
+}
+
+#[test]
+fn test_select_menu_reads_choices() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("select fruit in apple banana do echo got-$fruit-$REPLY")?;
+    shell.expect("2) banana")?;
+    shell.expect("#? ")?;
+    shell.send_line("2")?;
+    shell.expect("got-banana-2")?;
+    shell.expect("#? ")?;
+    // 编号无效时变量为空，Ctrl-D 结束菜单回到提示符
+    shell.send_line("7")?;
+    shell.expect("got--7")?;
+    shell.expect("#? ")?;
+    shell.send_ctrl('d')?;
+    shell.expect_prompt()?;
+    Ok(())
+}
+
+#[test]
+fn test_coproc_round_trip() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("coproc up { tr a-z A-Z; }")?;
+    shell.expect_prompt()?;
+    shell.send_line("echo hello > /dev/fd/${up[1]}")?;
+    shell.expect_prompt()?;
+    // 关闭写入端后 tr 读到 EOF，输出全部内容后退出
+    shell.send_line("coproc -c up")?;
+    shell.expect_prompt()?;
+    shell.send_line("cat < /dev/fd/${up[0]}")?;
+    shell.expect("HELLO")?;
+    shell.expect_prompt()?;
+    Ok(())
+}
+
+#[test]
+fn test_bracketed_paste_runs_on_enter() -> Result<(), String> {
+    let mut shell = PtyShell::spawn_with_env(&[("ZAKO_PASTE_STRIP_PROMPT", "on")])?;
+    shell.expect_prompt()?;
+    // 粘贴的内容只放进缓冲区，按回车后才逐行执行，行首的 "$ " 被去掉；多行粘贴需要先按 y 确认
+    shell.send("\x1b[200~$ echo pasted-one\n$ echo pasted-two\x1b[201~")?;
+    shell.expect("按 y 保留")?;
+    shell.send("y")?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send("\r")?;
+    shell.expect("\npasted-one")?;
+    shell.expect("\npasted-two")?;
+    shell.expect_prompt()?;
+    Ok(())
+}
+
+#[test]
+fn test_pipeline_and_pipestatus() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("echo piped | tr a-z A-Z")?;
+    let output = shell.expect_prompt()?;
+    assert!(output.contains("\nPIPED\n"), "{}", output);
+    shell.send_line("false | true")?;
+    shell.expect_prompt()?;
+    shell.send_line("echo status=$PIPESTATUS first=${PIPESTATUS[0]}")?;
+    shell.expect("status=1 0 first=1")?;
+    Ok(())
+}
+
+#[test]
+fn test_edit_command_line() -> Result<(), String> {
+    let mut shell = PtyShell::spawn_with_env(&[("EDITOR", "sed -i s/before/after/")])?;
+    shell.expect_prompt()?;
+    shell.send("echo before-edit")?;
+    shell.send_ctrl('x')?;
+    shell.send_ctrl('e')?;
+    // 编辑后的内容放回输入行，回车后才执行
+    shell.expect("echo after-edit")?;
+    shell.send("\r")?;
+    shell.expect("\nafter-edit\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_edit_command_line_private_file() -> Result<(), String> {
+    // 编辑器把输入行换成显示临时文件权限的命令
+    let editor = "f() { echo \"echo mode-$(stat -c %a \"$1\")\" > \"$1\"; }; f";
+    let mut shell = PtyShell::spawn_with_env(&[("EDITOR", editor)])?;
+    shell.expect_prompt()?;
+    shell.send("echo x")?;
+    shell.send_ctrl('x')?;
+    shell.send_ctrl('e')?;
+    shell.expect("echo mode-600")?;
+    shell.send("\r")?;
+    shell.expect("\nmode-600\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_capture_last_output() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("set -o captureoutput")?;
+    shell.expect_prompt()?;
+    shell.send_line("echo /tmp/some/path")?;
+    shell.expect_prompt()?;
+    // lastout 是内建命令，不会覆盖保存的输出
+    for _ in 0..2 {
+        shell.send_line("lastout")?;
+        shell.expect("\n/tmp/some/path\n")?;
+        shell.expect_prompt()?;
+    }
+    shell.send_line("echo got=$ZAKO_LAST_OUTPUT")?;
+    shell.expect("\ngot=/tmp/some/path\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_finished_background_jobs_are_reaped() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    for _ in 0..3 {
+        shell.send_line("sleep 0.1 &")?;
+        shell.expect_prompt()?;
+    }
+    std::thread::sleep(Duration::from_millis(500));
+    // SIGCHLD 只通知主循环，作业状态在下一次执行命令前收集
+    shell.send_line("echo collected")?;
+    let output = shell.expect_prompt()?;
+    assert!(output.contains("done"), "{}", output);
+    shell.send_line("jobs")?;
+    let output = shell.expect_prompt()?;
+    assert!(!output.contains("sleep 0.1"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_fg_waits_for_resumed_job() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("sleep 30")?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send_ctrl('z')?;
+    shell.expect("stopped sleep 30")?;
+    shell.expect_prompt()?;
+    // fg 等待作业，作业拿到终端后 Ctrl-C 只终止它
+    shell.send_line("fg")?;
+    shell.expect("sleep 30")?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send_ctrl('c')?;
+    let output = shell.expect_within("雑魚～> ", Duration::from_secs(2))?;
+    assert!(output.contains("进程被干掉了"), "{}", output);
+    shell.send_line("jobs")?;
+    let output = shell.expect_prompt()?;
+    assert!(!output.contains("sleep 30"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_fg_picks_among_jobs() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("sleep 30 &")?;
+    shell.expect_prompt()?;
+    shell.send_line("sleep 31 &")?;
+    shell.expect_prompt()?;
+    // 有多个作业时 fg 打开选择器，默认选中当前作业，↑ 选中上一个
+    shell.send_line("fg")?;
+    shell.expect("x 结束")?;
+    shell.send("\x1b[A")?;
+    std::thread::sleep(Duration::from_millis(100));
+    shell.send("x")?;
+    shell.expect_prompt()?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send_line("echo reaped")?;
+    shell.expect_prompt()?;
+    shell.send_line("jobs")?;
+    let output = shell.expect_prompt()?;
+    assert!(!output.contains("continued sleep 30"), "{}", output);
+    assert!(output.contains("continued sleep 31"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_confirm_many_arguments() -> Result<(), String> {
+    let mut shell = PtyShell::spawn_with_env(&[("ZAKO_MAX_ARGS", "2")])?;
+    shell.expect_prompt()?;
+    shell.send_line("echo a b c")?;
+    shell.expect("[y/N] ")?;
+    shell.send_line("n")?;
+    let output = shell.expect_prompt()?;
+    assert!(output.contains("已取消"), "{}", output);
+    shell.send_line("echo x y z")?;
+    shell.expect("[y/N] ")?;
+    shell.send_line("y")?;
+    shell.expect("\nx y z\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_and_or_short_circuit() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("false && echo skipped || echo or-$?")?;
+    let output = shell.expect_prompt()?;
+    assert!(output.contains("or-1"), "{}", output);
+    assert!(!output.contains("\nskipped"), "{}", output);
+    shell.send_line("sleep 5 && echo after-sleep")?;
+    std::thread::sleep(Duration::from_millis(300));
+    shell.send_ctrl('c')?;
+    let output = shell.expect_prompt()?;
+    assert!(!output.contains("\nafter-sleep"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_semicolon_list_runs_every_command() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("cd /tmp; pwd; false; echo last-$?")?;
+    let output = shell.expect_prompt()?;
+    assert!(output.contains("\n/tmp"), "{}", output);
+    assert!(output.contains("last-1"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_undo_redo_and_yank_ring() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    // 每个按键单独发送；编辑时会重绘提示符，只检查命令的输出
+    let keys = |shell: &mut PtyShell, keys: &[&str]| -> Result<(), String> {
+        for key in keys {
+            shell.send(key)?;
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        shell.send_line("")
+    };
+    // Ctrl-W 删除 drop，Ctrl-_ 撤销，Alt-_ 重做
+    keys(&mut shell, &["echo keep drop", "\x17", "\x1f", "\x1b_"])?;
+    shell.expect("\nkeep\n")?;
+
+    keys(&mut shell, &["echo b a", "\x1bt"])?;
+    shell.expect("\na b\n")?;
+    // vi 命令模式下的 u 和 Ctrl-R，插入模式下 u 仍然是普通字符
+    keys(&mut shell, &["echo uu drop", "\x17", "\x1b", "u", "\x12"])?;
+    shell.expect("\nuu\n")?;
+
+    // emacs 模式：先后删除 one 和 two，Ctrl-Y 粘贴 two，Alt-y 换成 one
+    let mut shell = PtyShell::spawn_with_env(&[("ZAKO_EDITOR_MODE", "emacs")])?;
+    shell.expect_prompt()?;
+    keys(
+        &mut shell,
+        &["echo one", "\x17", "two", "\x17", "\x19", "\x1by"],
+    )?;
+    shell.expect("\none\n")?;
+    keys(&mut shell, &["echo keep drop", "\x17", "\x1f", "\x1b_"])?;
+    shell.expect("\nkeep\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_numeric_arguments() -> Result<(), String> {
+    let keys = |shell: &mut PtyShell, keys: &[&str]| -> Result<(), String> {
+        for key in keys {
+            shell.send(key)?;
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        shell.send_line("")
+    };
+    let mut shell = PtyShell::spawn_with_env(&[("ZAKO_EDITOR_MODE", "emacs")])?;
+    shell.expect_prompt()?;
+    // Alt-2 Ctrl-W 删除两个单词
+    keys(&mut shell, &["echo a b c d", "\x1b2", "\x17"])?;
+    shell.expect("\na b\n")?;
+    // 撤销两次之后 Alt-2 Alt-_ 一次重做两步
+    keys(
+        &mut shell,
+        &[
+            "echo x y z",
+            "\x17",
+            "\x17",
+            "\x1f",
+            "\x1f",
+            "\x1b2",
+            "\x1b_",
+        ],
+    )?;
+    shell.expect("\nx\n")?;
+
+    // vi 插入模式下 Alt-3 进入命令模式并带上次数，3X 删除光标前的三个字符
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    keys(&mut shell, &["echo abcdef", "\x1b3", "X"])?;
+    shell.expect("\nabc\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_stderr_redirections() -> Result<(), String> {
+    let path = std::env::temp_dir().join(format!("zako-stderr-{}", std::process::id()));
+    let file = path.display().to_string();
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("ls /nonexistent 2>&1 | tr a-z A-Z")?;
+    shell.expect("NO SUCH FILE")?;
+    shell.expect_prompt()?;
+    shell.send_line(&format!("ls /nonexistent 2> {}", file))?;
+    let output = shell.expect_prompt()?;
+    assert!(!output.contains("No such file"), "{}", output);
+    shell.send_line(&format!("echo out &>> {}; cat {}", file, file))?;
+    shell.expect("No such file or directory\nout\n")?;
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[test]
+fn test_heredoc() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("cat <<EOF | tr a-z A-Z")?;
+    shell.send_line("first $?")?;
+    shell.send_line("second")?;
+    shell.send_line("EOF")?;
+    shell.expect("FIRST 0\nSECOND\n")?;
+    shell.expect_prompt()?;
+    shell.send_line("cat <<'END'")?;
+    shell.send_line("$? kept")?;
+    shell.send_line("END")?;
+    shell.expect("\n$? kept\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_here_string() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("tr a-z A-Z <<< \"status $?\" | cat -A")?;
+    shell.expect("STATUS 0$\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_alias_preview() -> Result<(), String> {
+    let mut shell = PtyShell::spawn()?;
+    shell.expect_prompt()?;
+    shell.send_line("alias \"ll=ls -la\"")?;
+    shell.expect_prompt()?;
+    shell.send("ll")?;
+    shell.expect("ll  → ls -la")?;
+    shell.send(" /")?;
+    let output = shell.expect("ll /")?;
+    assert!(!output.contains("→"), "{}", output);
+    shell.send_ctrl('u')?;
+    shell.send_line("echo done")?;
+    shell.expect("\ndone\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_safe_paste_confirmation() -> Result<(), String> {
+    let mut shell = PtyShell::spawn_with_env(&[("ZAKO_EDITOR_MODE", "emacs")])?;
+    shell.expect_prompt()?;
+    shell.send("echo \x1b[200~kept; sudo -n true\x1b[201~")?;
+    shell.expect("粘贴的内容包含 sudo，按 y 保留粘贴的内容，其他键撤销")?;
+    shell.send("x")?;
+    std::thread::sleep(Duration::from_millis(100));
+    shell.send_line("reverted")?;
+    shell.expect("\nreverted\n")?;
+    shell.expect_prompt()?;
+
+    shell.send("\x1b[200~echo one\necho two\x1b[201~")?;
+    shell.expect("粘贴的内容有多行")?;
+    shell.send("y")?;
+    std::thread::sleep(Duration::from_millis(100));
+    shell.send_line(" three")?;
+    shell.expect("\none\n")?;
+    shell.expect("\ntwo three\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_safe_paste_bound_keys() -> Result<(), String> {
+    let mut shell = PtyShell::spawn_with_env(&[("ZAKO_EDITOR_MODE", "emacs")])?;
+    shell.expect_prompt()?;
+    shell.send_line("echo first")?;
+    shell.expect("\nfirst\n")?;
+    shell.expect_prompt()?;
+
+    // Up 有自己的绑定，也只用来撤销粘贴，不翻历史
+    shell.send("echo \x1b[200~pasted; sudo -n true\x1b[201~")?;
+    shell.expect("粘贴的内容包含 sudo")?;
+    shell.send("\x1b[A")?;
+    std::thread::sleep(Duration::from_millis(100));
+    shell.send_line("second")?;
+    shell.expect("\nsecond\n")?;
+    shell.expect_prompt()?;
+
+    shell.send("echo \x1b[200~pasted; sudo -n true\x1b[201~")?;
+    shell.expect("粘贴的内容包含 sudo")?;
+    shell.send("\t")?;
+    std::thread::sleep(Duration::from_millis(100));
+    shell.send_line("third")?;
+    shell.expect("\nthird\n")?;
+    Ok(())
+}